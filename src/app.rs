@@ -0,0 +1,3243 @@
+use crate::audio::{self, AudioInput};
+use crate::bloom::{Bloom, BloomSettings};
+use crate::camera::{self, Camera};
+use crate::capture::Capture;
+use crate::checkerboard::Checkerboard;
+use crate::clusters::{self, ClusteredLights};
+use crate::color_grade::{ColorGrade, Grade};
+use crate::culling::Culling;
+use crate::fullscreen::{self, FullscreenMode};
+use crate::error::Error;
+use crate::console::{Command, Console};
+use crate::debug_draw::DebugLines;
+use crate::decals::{DecalKind, DecalList, DecalRenderer};
+use crate::deferred::Deferred;
+use crate::debug_ui::{DebugUi, PanelValues, UiRenderer};
+use crate::debug_view::DebugView;
+use crate::device::create_device;
+use cgmath::{
+    Deg, EuclideanSpace, InnerSpace, Matrix, Matrix4, MetricSpace, Point3, SquareMatrix, Transform, Vector3, Vector4,
+};
+use crate::measure::Measurement;
+use crate::mipmaps::MipGeneration;
+use crate::motion_blur::{DrawId, MotionBlur, MotionBlurSettings, MotionDraw, MotionHistory};
+use crate::objects::ObjectList;
+use crate::frustum::Frustum;
+use crate::god_rays::{GodRaySettings, GodRays};
+use crate::hiz::HiZ;
+use crate::lens_flare::LensFlare;
+use crate::lighting::Light;
+use crate::minimap::{self, Minimap, MinimapSettings};
+use crate::portal::{self, PortalSettings, Portals};
+use crate::msaa::Multisampling;
+use crate::panic_hook::FrameSnapshot;
+use crate::plot::{Plot, PlotRenderer};
+use crate::frame_log::{FrameCounters, FrameLog, FrameRow};
+use crate::input::Input;
+use crate::instancing::InstancedQuads;
+use crate::compute::ParticleSystem;
+use crate::point_cloud::PointCloud;
+use crate::post::{self, Calibration, PostProcess};
+use crate::post_effects::{Effect, EffectChain};
+use crate::primitives::Primitive;
+use crate::projection::Projection;
+use crate::renderer::{FramesInFlight, ScenePipelines};
+use crate::ray::{Aabb, Plane, Ray};
+use crate::refresh::{RefreshRate, RefreshRateChanged};
+use log::{error, info, warn};
+use crate::descriptors::DescriptorCache;
+use crate::parallel::{self, SceneDraw};
+use crate::scene::{Emission, Material, MeshHandle, Renderable, Scene};
+use crate::scenes::{Animation, SceneId, Showcase, CAMERA_DISTANCE, DEMO_SCENES};
+use crate::section::{Section, SECTION_STEP};
+use crate::shader::ShaderStage;
+use crate::shader_reload::{ReloadedShaders, ShaderWatcher, SHADER_DIRECTORY};
+use crate::settings::{Settings, SettingsFile, RESTART_KEYS};
+use crate::state::AppState;
+use crate::gpu_timer::{GpuTimer, Mark};
+use crate::queries::{self, DrawQueries};
+use crate::stats::{FrameStats, StatsDisplay};
+use crate::sky::Sky;
+use crate::skybox::{self, Skybox};
+use crate::staging::{Staging, Upload};
+use crate::subgroups::{self, LuminanceReduction, SubgroupSupport};
+use crate::vertex::Vertex;
+use crate::terrain::{self, Terrain};
+use crate::vegetation::{self, Vegetation};
+use crate::world::World;
+use egui::ClippedMesh;
+use std::{cmp::Ordering, future, iter::Inspect, net::SocketAddr, ops::Bound, path::{Path, PathBuf}, process, sync::{atomic::{AtomicUsize, Ordering as AtomicOrdering}, Arc}, time::{Duration, Instant}};
+use crate::swapchain::{self, create_swap_chain};
+use crate::texture::Texture;
+use crate::spline::{PathFollower, SplinePath};
+use crate::timeline::{Parameter, Timeline};
+use crate::turntable::Turntable;
+use crate::uniforms::{UniformBufferObject, UniformBuffers};
+use crate::{capture, compute, pipeline_cache, debug_draw, debug_utils, device, instancing, logging, mesh, panic_hook, point_cloud, remote, renderer::{self, vertex_shader}, sky, state, uniforms, validation, vertex, vertex_capture};
+use vulkano::{app_info_from_cargo_toml, buffer::{BufferUsage, CpuAccessibleBuffer}, command_buffer::{
+        AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, PrimaryCommandBuffer, SubpassContents,
+    }, descriptor::DescriptorSet, device::{Device, Queue, QueuesIter}, format::ClearValue, image::{view::ImageView, AttachmentImage, ImmutableImage, SwapchainImage}, instance::{
+        debug::DebugCallback,
+        ApplicationInfo, Instance, InstanceExtensions, Version,
+    }, pipeline::{cache::PipelineCache, GraphicsPipeline, GraphicsPipelineAbstract, GraphicsPipelineBuilder, vertex::{BufferlessDefinition, BufferlessVertices, SingleBufferDefinition}}, query::QueriesRange, render_pass::{FramebufferAbstract, RenderPass, Subpass}, swapchain::{acquire_next_image, Surface, Swapchain}, sync::{self, GpuFuture}};
+use vulkano_win::{required_extensions, VkSurfaceBuild};
+use winit::{
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{Event, MouseButton, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    platform::run_return::EventLoopExtRunReturn,
+    window::{Window, WindowBuilder},
+};
+
+/// The frame statistics are appended to it with `stats title`
+const WINDOW_TITLE: &str = "Vulkan";
+
+/// Width of decals placed on the scene, in world units
+const DECAL_SIZE: f32 = 0.2;
+
+/// How long bullet holes placed with the B key stay
+const BULLET_HOLE_LIFETIME: Duration = Duration::from_secs(10);
+
+/// Longest time between frames in low power mode when nothing happens, animations keep running
+/// at this rate
+const LOW_POWER_IDLE_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct GraphicsApplication {
+    instance: Arc<Instance>,
+    debug_callback: Option<DebugCallback>,
+    device: Arc<Device>,
+    graphics_queue: Arc<Queue>,
+    presentation_queue: Arc<Queue>,
+    /// The transfer only queue, or the graphics queue on devices without one
+    upload_queue: Arc<Queue>,
+    /// The async compute queue, or the graphics queue on devices without one
+    compute_queue: Arc<Queue>,
+    event_loop: Option<EventLoop<()>>,
+    surface: Arc<Surface<Window>>,
+    swap_chain: Arc<Swapchain<Window>>,
+    swap_chain_images: Vec<Arc<SwapchainImage<Window>>>,
+    render_pass: Arc<RenderPass>,
+    /// The scene pipeline variants, which only differ in a shader or the culling
+    scene_pipelines: ScenePipelines,
+    /// Notices edits to the shader sources, the scene pipeline is rebuilt from them
+    shader_watcher: ShaderWatcher,
+    reloaded_shaders: ReloadedShaders,
+    graphics_pipeline: Arc<GraphicsPipelineAbstract + Send + Sync>,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    frames_in_flight: FramesInFlight,
+    recreate_swap_chain: bool,
+    /// The demo scene's or loaded mesh
+    scene_mesh: MeshHandle,
+    console: Console,
+    debug_view: DebugView,
+    scene: SceneId,
+    start_time: Instant,
+    validation_errors: Arc<AtomicUsize>,
+    frame_index: u64,
+    frames_rendered: u64,
+    smoke_test_frames: Option<u64>,
+    frame_snapshot: Arc<FrameSnapshot>,
+    projection: Projection,
+    camera: Camera,
+    /// Keyboard and mouse input since the last frame
+    input: Input,
+    scene_bounds: Option<Aabb>,
+    /// The scene mesh in world space, for picking surfaces under the cursor
+    scene_triangles: Vec<[Point3<f32>; 3]>,
+    objects: ObjectList,
+    /// The marker quad every spawned object is drawn with
+    object_mesh: MeshHandle,
+    point_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    instanced_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    /// Where the metrics of every frame are written, from `--frame-log` or `framelog`
+    frame_log: Option<FrameLog>,
+    /// GPU times of every frame for the stats and the frame log, `None` where the graphics queue
+    /// can't write timestamps
+    gpu_timer: Option<GpuTimer>,
+    /// Occlusion and pipeline statistics queries around the scene's draws, `Some` after `queries on`
+    draw_queries: Option<DrawQueries>,
+    /// The instancing demo, a grid of copies of the object quad
+    instanced_quads: Option<InstancedQuads>,
+    particle_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    /// The compute shader demo, a particle fountain
+    particles: Option<ParticleSystem>,
+    /// Threads recording the renderables into secondary command buffers, inline recording if unset
+    recording_threads: Option<usize>,
+    culling: Culling,
+    /// Starts as the settings say, Alt+Enter toggles between windowed and their fullscreen mode
+    fullscreen_mode: FullscreenMode,
+    point_cloud: Option<PointCloud>,
+    /// Copies submitted while streaming, the next frame waits for them on the GPU
+    uploads: Vec<Upload>,
+    /// Chunks streamed around the camera, `Some` after `world on`
+    world: Option<World>,
+    /// Draws the world's chunks with their detail textures while it splats them
+    terrain_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    terrain: Terrain,
+    /// Draws the grass on the world's chunks
+    grass_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    vegetation: Vegetation,
+    minimap_settings: MinimapSettings,
+    /// `Some` while `minimap_settings` has it enabled, recreated with the swap chain
+    minimap: Option<Minimap>,
+    /// Model matrix of the mesh shown with the `load` or `primitive` command, which replaces the demo
+    /// scene while set
+    imported_model: Option<Matrix4<f32>>,
+    section: Option<Section>,
+    /// Set while measure mode is on, left clicks then pick its ends instead of reporting hits
+    measurement: Option<Measurement>,
+    line_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    /// Where the next frame is saved, set by the `screenshot` command
+    screenshot: Option<PathBuf>,
+    /// Replaces the camera while a turntable capture is running
+    turntable: Option<Turntable>,
+    /// Moves the camera, objects and post effect parameters while it is loaded
+    timeline: Option<Timeline>,
+    /// Moves the camera or a spawned object and is drawn while it is loaded
+    spline_path: Option<SplinePath>,
+    sky: Sky,
+    sky_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    /// Loaded with the `skybox` command
+    skybox: Option<Skybox>,
+    skybox_pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    portal_settings: PortalSettings,
+    /// `Some` while `portal_settings` has a kind, recreated with the swap chain
+    portals: Option<Portals>,
+    /// Draws the portal's surface in the scene pass
+    portal_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    /// Kept after the scene pass so the lens flare can test the sun against it
+    depth_buffer: Arc<ImageView<Arc<AttachmentImage>>>,
+    /// Multisampled scene targets, `None` when the settings ask for a single sample
+    multisampling: Option<Multisampling>,
+    /// Adds blue noise when writing the scene to the swap chain, toggled with the `dither` command
+    dithering: bool,
+    /// Multiplies the scene in the post pass, set with the `tint` command
+    tint: [f32; 3],
+    post: PostProcess,
+    /// Set by the `grade` command
+    grade: Option<Grade>,
+    /// `Some` while grading, rebuilt with the swap chain
+    color_grade: Option<ColorGrade>,
+    /// Shades half the pixels per frame, toggled with the `checkerboard` command
+    checkerboard_rendering: bool,
+    /// `Some` while checkerboard rendering, rebuilt with the swap chain
+    checkerboard: Option<Checkerboard>,
+    /// Draws the scene into a G-buffer and lights it in place of the scene pass, `Some` when the
+    /// `deferred` setting was on at startup, rebuilt with the swap chain
+    deferred: Option<Deferred>,
+    /// Set by the `subgroups` command, the next frame sums the scene's luminance with it
+    luminance_request: Option<SubgroupSupport>,
+    /// The luminance summed by the frame in the slot, reported when the slot comes round again
+    /// and that frame has finished
+    pending_luminance: Option<(usize, LuminanceReduction)>,
+    blue_noise: Arc<ImageView<Arc<ImmutableImage>>>,
+    lens_flare: LensFlare,
+    god_rays: GodRays,
+    /// Changed with the `godrays` command
+    god_ray_settings: GodRaySettings,
+    bloom: Bloom,
+    /// Changed with the `bloom` command
+    bloom_settings: BloomSettings,
+    post_effects: EffectChain,
+    /// Applied by `post_effects` in this order, set with the `postfx` command
+    effects: Vec<Effect>,
+    /// Built from the visible depth after every scene pass, `None` when the graphics queue can't
+    /// run compute shaders
+    hiz: Option<HiZ>,
+    /// Shades the `lit` view, changed with the `light` command
+    light: Light,
+    /// The `lit` view's other lights, set with the `lights` command
+    clustered_lights: ClusteredLights,
+    /// Level of the Hi-Z pyramid shown in place of the scene, set with the `hiz` command
+    hiz_view: Option<usize>,
+    motion_blur: MotionBlur,
+    /// Changed with the `motionblur` command
+    motion_blur_settings: MotionBlurSettings,
+    /// Transforms of the last frame, for the velocity buffer
+    motion_history: MotionHistory,
+    decals: DecalList,
+    decal_renderer: DecalRenderer,
+    uniform_buffers: UniformBuffers,
+    /// Texture sets of the scene pipeline, dropped with the pipelines or textures they bind
+    descriptors: DescriptorCache,
+    /// Sampled by the scene's fragment shader, set with the `texture` command
+    texture: Option<Arc<Texture>>,
+    /// Light the demo scene or loaded mesh gives off, set with the `emissive` command
+    emission: Emission,
+    /// Multiplies the emission per texel, set with `emissive texture`
+    emissive_map: Option<Arc<Texture>>,
+    /// Bound in place of a texture for spawned objects and scenes without one
+    white_texture: Arc<Texture>,
+    /// Samples the texture's mip chain, turned off with the `mipmaps` command to compare
+    mipmaps: bool,
+    /// How loaded textures get their mip levels, `None` picks the fastest the device supports
+    mip_generation: Option<MipGeneration>,
+    /// Frame time graph, shown while set
+    plot: Option<Plot>,
+    frame_stats: FrameStats,
+    stats_display: StatsDisplay,
+    plot_renderer: PlotRenderer,
+    /// egui panel toggled with F1 or the `ui` command
+    debug_ui: DebugUi,
+    ui_renderer: UiRenderer,
+    /// Radians per second of scenes that rotate, set in the debug panel
+    rotation_speed: f32,
+    rotation_angle: f32,
+    /// WAV file the scene shaders react to, set with the `audio` command
+    audio: Option<AudioInput>,
+    audio_bands: [f32; audio::BANDS],
+    last_frame_start: Instant,
+    /// Input or a window change arrived since the last frame, low power mode draws one then
+    redraw_pending: bool,
+    /// Refresh rate of the window's monitor, the default frame rate cap and simulation rate
+    refresh_rate: RefreshRate,
+    /// Values from the settings file as last read, changes to it are applied live
+    settings: Settings,
+    settings_file: SettingsFile,
+    /// Where the window and view state is restored from and saved to, `None` for smoke tests
+    state_path: Option<PathBuf>,
+}
+
+impl GraphicsApplication {
+    /// Fails when no GPU can run the app or its swap chain, scene pipeline or buffers can't be
+    /// created
+    pub fn new(smoke_test_frames: Option<u64>, mut settings_file: SettingsFile) -> Result<Self, Error> {
+        let settings = Self::load_settings(&mut settings_file);
+        let instance = Self::create_vk_instance(settings.validation, required_extensions());
+        let validation_errors = Arc::new(AtomicUsize::new(0));
+        let debug_callback = Self::create_debug_callback(&instance, &validation_errors, &settings);
+        let (event_loop, surface) = Self::create_surface(&instance, &settings);
+        let frame_snapshot = Arc::new(FrameSnapshot::new(validation_errors.clone()));
+        panic_hook::install(surface.clone(), frame_snapshot.clone());
+        let (device, queues) = create_device(&surface, &instance, settings.gpu.as_deref(), settings.low_power)?;
+        let (graphics_queue, presentation_queue) = (queues.graphics.clone(), queues.present.clone());
+        let upload_queue = queues.uploads();
+        let compute_queue = queues.compute_work();
+        let mut staging = Staging::new(&upload_queue);
+        let (swap_chain, swap_chain_images) = create_swap_chain(
+            &instance,
+            &surface,
+            device.physical_device().index(),
+            &device,
+            &graphics_queue,
+            &presentation_queue,
+            None,
+            settings.vsync,
+            settings.present_mode,
+            settings.swapchain_usage,
+        )?;
+
+        let scene_size = renderer::scene_size(swap_chain.dimensions(), settings.render_scale);
+        let samples = if settings.deferred {
+            if settings.msaa > 1 {
+                warn!("Deferred shading draws one sample per pixel, msaa = {} is ignored", settings.msaa);
+            }
+            1
+        } else {
+            renderer::sample_count(&device, settings.msaa, scene_size)
+        };
+        let blue_noise = post::blue_noise_texture(&mut staging)?;
+        let post = PostProcess::new(&device, &swap_chain_images, swap_chain.format(), scene_size, &blue_noise)?;
+        let render_pass = renderer::create_render_pass(&device, post::INTERMEDIATE_FORMAT, samples);
+        let debug_view = DebugView::default();
+        let reloaded_shaders = ReloadedShaders::new(&device);
+        let cache = match &settings.pipeline_cache {
+            Some(path) => pipeline_cache::load(&device, path),
+            None => PipelineCache::empty(device.clone()).unwrap(),
+        };
+        let mut scene_pipelines = ScenePipelines::new(&device, &render_pass, cache);
+        scene_pipelines.warm_up(&reloaded_shaders)?;
+        let graphics_pipeline = scene_pipelines.get(debug_view, false, &reloaded_shaders)?;
+        let point_pipeline = point_cloud::create_pipeline(&device, &render_pass)?;
+        let instanced_pipeline = instancing::create_pipeline(&device, &render_pass)?;
+        let terrain_pipeline = terrain::create_pipeline(&device, &render_pass)?;
+        let grass_pipeline = vegetation::create_pipeline(&device, &render_pass)?;
+        let particle_pipeline = compute::create_pipeline(&device, &render_pass)?;
+        let line_pipeline = debug_draw::create_pipeline(&device, &render_pass)?;
+        let sky_pipeline = sky::create_pipeline(&device, &render_pass)?;
+        let skybox_pipeline = skybox::create_pipeline(&device, &render_pass)?;
+        let portal_pipeline = portal::create_pipeline(&device, &render_pass)?;
+        let depth_buffer = renderer::create_depth_buffer(&device, scene_size);
+        let multisampling = (samples > 1)
+            .then(|| Multisampling::new(&device, samples, post::INTERMEDIATE_FORMAT, &depth_buffer))
+            .transpose()?;
+        let framebuffer =
+            renderer::create_framebuffer(&render_pass, post.target.view(), &depth_buffer, multisampling.as_ref());
+        let deferred = settings
+            .deferred
+            .then(|| Deferred::new(&device, post.target.view(), &depth_buffer))
+            .transpose()?;
+        let lens_flare = LensFlare::new(&device, &swap_chain_images, swap_chain.format(), &depth_buffer)?;
+        let god_rays = GodRays::new(&device, post.target.view(), &depth_buffer);
+        let bloom = Bloom::new(&device, &post.target);
+        let post_effects = EffectChain::new(&device, &post.target);
+        let hiz = graphics_queue
+            .family()
+            .supports_compute()
+            .then(|| HiZ::new(&graphics_queue, post.target.view(), &depth_buffer));
+        let clustered_lights = ClusteredLights::new(&graphics_queue);
+        let motion_blur = MotionBlur::new(&device, post.target.view(), &depth_buffer);
+        let decal_renderer =
+            DecalRenderer::new(&device, &upload_queue, &swap_chain_images, swap_chain.format(), &depth_buffer)?;
+        let uniform_buffers = UniformBuffers::new(&device);
+        let white_texture = Arc::new(Texture::white(&mut staging)?);
+        let terrain = Terrain::new(&mut staging)?;
+        let vegetation = Vegetation::new(&mut staging)?;
+        let plot_renderer = PlotRenderer::new(&device, &swap_chain_images, swap_chain.format())?;
+        let debug_ui = DebugUi::new(surface.window());
+        let ui_renderer = UiRenderer::new(&device, &swap_chain_images, swap_chain.format());
+
+        let culling = Culling::new(device.physical_device());
+        if culling.conditional_rendering() {
+            info!("The device supports conditional rendering, draws are still culled on the CPU");
+        }
+
+        let mut sky = Sky::default();
+        sky.enabled = settings.sky;
+        sky.set_day_length(settings.day_length);
+        sky.turbidity = settings.turbidity;
+
+        let scene = SceneId::default();
+        let (vertices, indices) = scene.scene().mesh();
+        let scene_bounds = Self::scene_bounds(&vertices, scene.scene().model());
+        let scene_triangles = Self::scene_triangles(&vertices, &indices, scene.scene().model());
+        let scene_mesh = MeshHandle::new(
+            renderer::create_vertex_buffer(&mut staging, &vertices)?,
+            renderer::create_index_buffer(&mut staging, &indices)?,
+        );
+        let object_mesh = MeshHandle::new(
+            renderer::create_vertex_buffer(&mut staging, &vertex::vertecies())?,
+            renderer::create_index_buffer(&mut staging, &vertex::indices())?,
+        );
+        staging.submit_and_wait()?;
+
+        let frames_in_flight = FramesInFlight::new(&device);
+        let gpu_timer = GpuTimer::new(&graphics_queue);
+        if gpu_timer.is_none() {
+            info!("The graphics queue can't write timestamps, there are no GPU times");
+        }
+
+        Ok(Self {
+            instance,
+            debug_callback,
+            device,
+            graphics_queue,
+            presentation_queue,
+            upload_queue,
+            compute_queue,
+            event_loop: Some(event_loop),
+            refresh_rate: RefreshRate::new(surface.window()),
+            surface,
+            swap_chain,
+            swap_chain_images,
+            render_pass,
+            scene_pipelines,
+            shader_watcher: ShaderWatcher::new(Path::new(SHADER_DIRECTORY)),
+            reloaded_shaders,
+            graphics_pipeline,
+            framebuffer,
+            frames_in_flight,
+            recreate_swap_chain: false,
+            scene_mesh,
+            console: Console::spawn(),
+            debug_view,
+            scene,
+            start_time: Instant::now(),
+            validation_errors,
+            frame_index: 0,
+            frames_rendered: 0,
+            smoke_test_frames,
+            frame_snapshot,
+            projection: Projection {
+                fov_y: settings.fov_y,
+                near: settings.near,
+                far: settings.far,
+                ..Projection::default()
+            },
+            camera: Camera::default(),
+            input: Input::default(),
+            scene_bounds,
+            scene_triangles,
+            objects: ObjectList::default(),
+            object_mesh,
+            point_pipeline,
+            instanced_pipeline,
+            instanced_quads: None,
+            particle_pipeline,
+            particles: None,
+            recording_threads: None,
+            culling,
+            fullscreen_mode: settings.fullscreen,
+            frame_log: None,
+            gpu_timer,
+            draw_queries: None,
+            point_cloud: None,
+            uploads: vec![],
+            world: None,
+            terrain_pipeline,
+            terrain,
+            grass_pipeline,
+            vegetation,
+            minimap_settings: MinimapSettings::default(),
+            minimap: None,
+            imported_model: None,
+            section: None,
+            measurement: None,
+            line_pipeline,
+            screenshot: None,
+            turntable: None,
+            timeline: None,
+            spline_path: None,
+            sky,
+            sky_pipeline,
+            skybox: None,
+            skybox_pipeline,
+            portal_settings: PortalSettings::default(),
+            portals: None,
+            portal_pipeline,
+            depth_buffer,
+            multisampling,
+            dithering: true,
+            tint: [1.0; 3],
+            post,
+            grade: None,
+            color_grade: None,
+            checkerboard_rendering: false,
+            checkerboard: None,
+            deferred,
+            luminance_request: None,
+            pending_luminance: None,
+            blue_noise,
+            lens_flare,
+            god_rays,
+            god_ray_settings: GodRaySettings::default(),
+            bloom,
+            bloom_settings: BloomSettings::default(),
+            post_effects,
+            effects: Vec::new(),
+            hiz,
+            light: Light::default(),
+            clustered_lights,
+            hiz_view: None,
+            motion_blur,
+            motion_blur_settings: MotionBlurSettings::default(),
+            motion_history: MotionHistory::default(),
+            decals: DecalList::default(),
+            decal_renderer,
+            uniform_buffers,
+            descriptors: DescriptorCache::default(),
+            texture: None,
+            emission: Emission::default(),
+            emissive_map: None,
+            white_texture,
+            mipmaps: true,
+            mip_generation: None,
+            plot: None,
+            frame_stats: FrameStats::default(),
+            stats_display: StatsDisplay::default(),
+            plot_renderer,
+            debug_ui,
+            ui_renderer,
+            rotation_speed: 1.0,
+            rotation_angle: 0.0,
+            audio: None,
+            audio_bands: [0.0; audio::BANDS],
+            last_frame_start: Instant::now(),
+            redraw_pending: true,
+            settings,
+            settings_file,
+            // smoke tests start from the same state every time
+            state_path: smoke_test_frames.is_none().then(|| PathBuf::from(state::DEFAULT_PATH)),
+        })
+    }
+
+    /// Runs until the window is closed. Console commands are handled once all pending events are
+    /// processed, then a redraw is requested, so frames are drawn back to back. Keyboard and mouse
+    /// events are gathered into `input`, which each frame acts on before it is drawn.
+    pub fn main_loop(mut self) {
+        let mut event_loop = self.event_loop.take().unwrap();
+        let our_window_id = self.surface.window().id();
+
+        event_loop.run_return(|event, _, control_flow| {
+            if let Event::WindowEvent { .. } = &event {
+                self.redraw_pending = true;
+            }
+            *control_flow = match self.next_frame_time() {
+                Some(time) => ControlFlow::WaitUntil(time),
+                None => ControlFlow::Poll,
+            };
+
+            // the debug panel sees input first, clicks and keys it uses don't reach the scene
+            if let Event::WindowEvent { event, window_id } = &event {
+                if *window_id == our_window_id {
+                    if self.debug_ui.on_event(event) {
+                        return;
+                    }
+                    self.input.on_event(event);
+                }
+            }
+
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id,
+                } if window_id == our_window_id => *control_flow = ControlFlow::Exit,
+                // a new scale factor comes with a new size, the swap chain follows the window
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. },
+                    window_id,
+                } if window_id == our_window_id => {
+                    self.recreate_swap_chain = true;
+                    self.check_refresh_rate();
+                }
+                // the window may be on another monitor now
+                Event::WindowEvent {
+                    event: WindowEvent::Moved(_),
+                    window_id,
+                } if window_id == our_window_id => self.check_refresh_rate(),
+                Event::MainEventsCleared => {
+                    self.reload_settings();
+                    self.reload_shaders();
+                    self.handle_console_commands();
+                    if self.next_frame_time().is_none_or(|time| Instant::now() >= time) {
+                        self.surface.window().request_redraw();
+                    }
+                }
+                Event::RedrawRequested(window_id) if window_id == our_window_id => {
+                    self.redraw_pending = false;
+                    self.handle_input();
+                    self.draw_frame();
+                    self.input.end_frame();
+
+                    if let Some(frames) = self.smoke_test_frames {
+                        if self.frames_rendered >= frames {
+                            self.finish_smoke_test();
+                        }
+                    }
+                }
+                _ => (),
+            }
+        });
+
+        self.wait_for_last_frame();
+        self.stop_frame_log();
+        self.save_state();
+        self.save_pipeline_cache();
+    }
+
+    /// When the next frame is due, `None` to draw one as soon as the last is done. Low power mode
+    /// waits for input or the idle interval, the frame rate cap for its interval.
+    fn next_frame_time(&self) -> Option<Instant> {
+        if self.settings.low_power && !self.redraw_pending {
+            return Some(self.last_frame_start + LOW_POWER_IDLE_INTERVAL);
+        }
+        let fps_cap = self.settings.fps_cap?.hz(self.refresh_rate.rate());
+        Some(self.last_frame_start + Duration::from_secs_f64(1.0 / fps_cap as f64))
+    }
+
+    /// Seconds of one step of the particle simulation
+    fn simulation_step(&self) -> f32 {
+        1.0 / self.settings.simulation_rate.hz(self.refresh_rate.rate()) as f32
+    }
+
+    fn apply_simulation_step(&mut self) {
+        let step = self.simulation_step();
+        if let Some(particles) = &mut self.particles {
+            particles.set_step(step);
+        }
+    }
+
+    /// Looks up the refresh rate of the window's monitor, passing a change on
+    fn check_refresh_rate(&mut self) {
+        if let Some(changed) = self.refresh_rate.update(self.surface.window()) {
+            self.on_refresh_rate_changed(changed);
+        }
+    }
+
+    /// Updates what follows the monitor's refresh rate. The frame rate cap reads it for every
+    /// frame, the particle simulation keeps its step.
+    fn on_refresh_rate_changed(&mut self, changed: RefreshRateChanged) {
+        info!(
+            "The window moved to {} at {} Hz, from {} Hz",
+            changed.monitor.as_deref().unwrap_or("a monitor"),
+            changed.rate,
+            changed.previous
+        );
+        self.apply_simulation_step();
+    }
+
+    fn wait_for_last_frame(&mut self) {
+        self.frames_in_flight
+            .wait_idle()
+            .expect("Failed to wait for the last frame");
+    }
+
+    /// Puts the window back where it was and switches to the scene, debug view and toggles that
+    /// were active when the application last closed
+    pub fn restore_state(&mut self) {
+        let path = match &self.state_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let state = match AppState::load(path) {
+            Ok(Some(state)) => state,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Failed to restore the previous session: {}", e);
+                return;
+            }
+        };
+        info!("Restoring the previous session from {}", path.display());
+
+        // a size or fullscreen given on the command line wins over the last session's window
+        let window = self.surface.window();
+        if !self.settings.fullscreen.is_fullscreen() && !self.settings_file.is_overridden("window_size") {
+            if let Some([width, height]) = state.window_size {
+                window.set_inner_size(PhysicalSize::new(width, height));
+            }
+            if let Some([x, y]) = state.window_position {
+                window.set_outer_position(PhysicalPosition::new(x, y));
+            }
+        }
+
+        self.set_scene(state.scene);
+        self.set_debug_view(state.debug_view);
+        if state.plot {
+            self.set_plot(true);
+        }
+        self.mipmaps = state.mipmaps;
+        self.dithering = state.dither;
+        self.camera.set_pose(
+            state.camera_target.into(),
+            state.camera_distance,
+            Deg(state.camera_yaw).into(),
+            Deg(state.camera_pitch).into(),
+        );
+    }
+
+    /// To the file the cache was loaded from at startup, `pipeline_cache` isn't reloaded
+    fn save_pipeline_cache(&self) {
+        if let Some(path) = &self.settings.pipeline_cache {
+            if let Err(e) = pipeline_cache::save(self.scene_pipelines.cache(), path) {
+                error!("Failed to save the pipeline cache: {}", e);
+            }
+        }
+    }
+
+    fn save_state(&self) {
+        let path = match &self.state_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let window = self.surface.window();
+        // the window in fullscreen covers the monitor, which isn't the size to restore
+        let windowed = !self.fullscreen_mode.is_fullscreen();
+        let size = window.inner_size();
+        let state = AppState {
+            window_size: Some([size.width, size.height]).filter(|_| windowed),
+            // not every platform lets windows know where they are
+            window_position: window
+                .outer_position()
+                .ok()
+                .filter(|_| windowed)
+                .map(|position| [position.x, position.y]),
+            scene: self.scene,
+            debug_view: self.debug_view,
+            plot: self.plot.is_some(),
+            mipmaps: self.mipmaps,
+            dither: self.dithering,
+            camera_target: self.camera.target.into(),
+            camera_distance: self.camera.distance,
+            camera_yaw: Deg::from(self.camera.yaw).0,
+            camera_pitch: Deg::from(self.camera.pitch).0,
+        };
+
+        if let Err(e) = state.save(path) {
+            error!("Failed to save the session: {}", e);
+        }
+    }
+
+    /// Prints every physical device and its queue families for `--print-device-info`, then the
+    /// monitors the `monitor` setting picks from. Only the instance and the window are created,
+    /// so it works on the machines where `new` finds no GPU to run on.
+    pub fn print_device_info(mut settings_file: SettingsFile) {
+        let settings = Self::load_settings(&mut settings_file);
+        let instance = Self::create_vk_instance(settings.validation, required_extensions());
+        let (_event_loop, surface) = Self::create_surface(&instance, &settings);
+        print!("{}", device::device_report(&surface, &instance));
+        print!("{}", fullscreen::monitor_report(surface.window().available_monitors()));
+    }
+
+    /// The settings file's settings, the defaults when it is missing or can't be read
+    fn load_settings(settings_file: &mut SettingsFile) -> Settings {
+        match settings_file.load() {
+            Ok(Some(settings)) => {
+                info!("Loaded settings from {}", settings_file.path.display());
+                settings
+            }
+            Ok(None) => Settings::default(),
+            Err(e) => {
+                error!("Failed to load settings, using the defaults: {}", e);
+                Settings::default()
+            }
+        }
+    }
+
+    /// Takes console commands and answers stats requests over TCP, see `remote::listen`
+    pub fn listen(&self, address: SocketAddr) {
+        if let Err(e) = remote::listen(address, self.console.sender(), self.frame_snapshot.clone()) {
+            error!("Failed to listen on {}: {}", address, e);
+        }
+    }
+
+    /// Starts writing the metrics of every frame to `path`, replacing a log that is already open
+    pub fn start_frame_log(&mut self, path: &Path) {
+        self.stop_frame_log();
+        match FrameLog::create(path) {
+            Ok(frame_log) => {
+                println!("framelog: writing to {}", path.display());
+                self.frame_log = Some(frame_log);
+            }
+            Err(e) => error!("Failed to create the frame log: {}", e),
+        }
+    }
+
+    /// Waits for the frames in flight, so their rows are complete, and closes the log
+    fn stop_frame_log(&mut self) {
+        if let Some(frame_log) = self.frame_log.take() {
+            self.wait_for_last_frame();
+            let path = frame_log.path.clone();
+            match frame_log.finish(self.gpu_timer.as_mut()) {
+                Ok(rows) => println!("framelog: wrote {} frames to {}", rows, path.display()),
+                Err(e) => error!("Failed to write the frame log: {}", e),
+            }
+        }
+    }
+
+    /// Waits for the GPU, reports the outcome of `--smoke-test` and exits with a matching status code
+    fn finish_smoke_test(&mut self) -> ! {
+        self.wait_for_last_frame();
+        self.stop_frame_log();
+
+        let errors = self.validation_errors.load(AtomicOrdering::SeqCst);
+
+        if self.debug_callback.is_none() {
+            println!("smoke test: validation layers are not active, only rendering was checked");
+        }
+
+        if let Some(summary) = self.frame_stats.summary() {
+            println!("smoke test: {}", summary);
+        }
+
+        if errors == 0 {
+            println!("smoke test: rendered {} frames without validation errors", self.frames_rendered);
+            process::exit(0);
+        } else {
+            println!("smoke test: {} validation errors in {} frames", errors, self.frames_rendered);
+            process::exit(1);
+        }
+    }
+
+    /// Applies what changed in the settings file since it was last read. A file that fails to
+    /// parse or was deleted leaves everything as it is.
+    fn reload_settings(&mut self) {
+        let mut settings = match self.settings_file.poll() {
+            Some(Ok(Some(settings))) => settings,
+            Some(Ok(None)) | None => return,
+            Some(Err(e)) => {
+                error!("Failed to reload settings: {}", e);
+                return;
+            }
+        };
+
+        let changed = self.settings.changed_keys(&settings);
+        if changed.is_empty() {
+            return;
+        }
+        info!("Settings changed: {}", changed.join(", "));
+
+        for key in &changed {
+            match *key {
+                "vsync" | "present_mode" | "msaa" | "render_scale" | "swapchain_usage" => {
+                    self.recreate_swap_chain = true
+                }
+                "fullscreen" => self.set_fullscreen(settings.fullscreen, settings.monitor.as_deref()),
+                "monitor" if self.fullscreen_mode.is_fullscreen() => {
+                    self.set_fullscreen(self.fullscreen_mode, settings.monitor.as_deref())
+                }
+                "fov" => {
+                    if let Err(e) = self.projection.set_fov_y(settings.fov_y) {
+                        error!("Ignoring the field of view from the settings: {}", e);
+                    }
+                }
+                "near" | "far" => {
+                    if let Err(e) = self.projection.set_planes(settings.near, settings.far) {
+                        error!("Ignoring the clip planes from the settings: {}", e);
+                    }
+                }
+                "sky" => self.sky.enabled = settings.sky,
+                "day_length" => self.sky.set_day_length(settings.day_length),
+                "turbidity" => self.sky.turbidity = settings.turbidity.clamp(1.7, 10.0),
+                key if RESTART_KEYS.contains(&key) => info!("Setting '{}' takes effect after a restart", key),
+                // read every frame
+                _ => (),
+            }
+        }
+
+        // recreate_swap_chain goes on reading `deferred` and the others as they were at startup
+        settings.keep_restart_values(&self.settings);
+        self.settings = settings;
+        if changed.contains(&"simulation_rate") {
+            self.apply_simulation_step();
+        }
+    }
+
+    /// Compiles the scene pipeline's shaders saved since the last check and rebuilds the pipeline
+    /// with them. A shader that fails to compile is reported and the pipeline stays as it is.
+    fn reload_shaders(&mut self) {
+        let mut rebuild = false;
+        for path in self.shader_watcher.poll() {
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            if !renderer::is_scene_shader(&file_name) {
+                info!("{} changed, only the scene pipeline's shaders reload, restart to use it", file_name);
+                continue;
+            }
+
+            match self.reloaded_shaders.reload(&path) {
+                Ok(()) => {
+                    info!("Reloaded {}", file_name);
+                    rebuild = true;
+                }
+                Err(e) => error!("Failed to reload {}: {}", file_name, e),
+            }
+        }
+
+        if rebuild {
+            self.scene_pipelines.clear();
+            self.rebuild_graphics_pipeline();
+            self.rebuild_minimap();
+            self.rebuild_portals();
+        }
+    }
+
+    fn handle_console_commands(&mut self) {
+        for command in self.console.poll() {
+            match command {
+                Command::SetDebugView(debug_view) => self.set_debug_view(debug_view),
+                Command::SetScene(scene) => self.set_scene(scene),
+                Command::ListScenes => {
+                    for (number, scene) in DEMO_SCENES.iter().enumerate() {
+                        println!("{}. {}", number + 1, scene);
+                    }
+                }
+                Command::SetFov(fov_y) => self.set_fov(fov_y),
+                Command::SetNear(near) => self.set_clip_planes(near, self.projection.far),
+                Command::SetFar(far) => self.set_clip_planes(self.projection.near, far),
+                Command::SetAspect(aspect) => {
+                    self.projection.aspect = aspect;
+                    println!("projection: {}", self.projection);
+                }
+                Command::ShowProjection => println!("projection: {}", self.projection),
+                Command::Spawn(position) => {
+                    let object = self.objects.spawn(position);
+                    println!("spawned object {}", object);
+                }
+                Command::Despawn(Some(id)) => match self.objects.despawn(id) {
+                    Some(object) => println!("removed object {}", object),
+                    None => println!("console: there is no object #{}", id),
+                },
+                Command::Despawn(None) => {
+                    self.objects.clear();
+                    println!("removed all objects");
+                }
+                Command::ListObjects if self.objects.is_empty() => println!("no objects spawned"),
+                Command::ListObjects => {
+                    for object in self.objects.iter() {
+                        println!("{}", object);
+                    }
+                }
+                Command::SetDebugUi(enabled) => self.set_debug_ui(enabled),
+                Command::SetStats(display) => self.set_stats(display),
+                Command::ShowStats => match self.frame_stats.summary() {
+                    Some(summary) => println!("stats: {}", summary),
+                    None => println!("stats: no frames rendered yet"),
+                },
+                Command::SetPlot(enabled) => self.set_plot(enabled),
+                Command::ShowPlot => match &self.plot {
+                    Some(plot) => {
+                        let [frame, cpu] = [&plot.series[0], &plot.series[1]];
+                        println!(
+                            "plot: frame {:.2} ms (average {:.2}), cpu {:.2} ms (average {:.2})",
+                            frame.latest().unwrap_or(0.0),
+                            frame.average().unwrap_or(0.0),
+                            cpu.latest().unwrap_or(0.0),
+                            cpu.average().unwrap_or(0.0)
+                        );
+                    }
+                    None => println!("plot: off"),
+                },
+                Command::PlaceDecal(kind, lifetime) => self.place_decal(kind, lifetime),
+                Command::ClearDecals => {
+                    self.decals.clear();
+                    println!("removed all decals");
+                }
+                Command::ListDecals if self.decals.is_empty() => println!("no decals placed"),
+                Command::ListDecals => {
+                    for decal in self.decals.iter() {
+                        println!("{}", decal);
+                    }
+                }
+                Command::SetSection(Some((normal, distance))) => {
+                    let cap = self.section.map(|section| section.cap).unwrap_or(true);
+                    self.set_section(Some(Section { cap, ..Section::new(normal, distance) }));
+                }
+                Command::SetSection(None) => self.set_section(None),
+                Command::SetSectionCap(cap) => self.move_section(|section| section.cap = cap),
+                Command::ShowSection => match &self.section {
+                    Some(section) => println!("section: {}", section),
+                    None => println!("section: off"),
+                },
+                Command::SetMeasureMode(enabled) => self.set_measure_mode(enabled),
+                Command::Screenshot(path) => {
+                    self.screenshot = Some(path.unwrap_or_else(|| capture::default_screenshot_path(self.frame_index + 1)));
+                }
+                Command::Turntable(Some(settings)) => {
+                    let turntable = Turntable::new(
+                        settings.frames,
+                        settings.radius.unwrap_or(CAMERA_DISTANCE),
+                        settings.elevation.unwrap_or(Deg(20.0)),
+                        settings.output.unwrap_or_else(|| PathBuf::from("turntable")),
+                    );
+                    println!("turntable: saving {} frames to {}", turntable.frames, turntable.output.display());
+                    self.turntable = Some(turntable);
+                }
+                Command::Turntable(None) => self.turntable = None,
+                Command::SetSky(enabled) => self.sky.enabled = enabled,
+                Command::SetSkyHour(hour) => self.sky.set_hour(hour),
+                Command::SetDayLength(seconds) => self.sky.set_day_length(seconds),
+                Command::SetTurbidity(turbidity) => self.sky.turbidity = turbidity.clamp(1.7, 10.0),
+                Command::SetGodRays(enabled) => self.god_ray_settings.enabled = enabled,
+                Command::SetGodRayDensity(density) => self.god_ray_settings.density = density.clamp(0.0, 1.0),
+                Command::SetGodRaySteps(steps) => self.god_ray_settings.steps = steps,
+                Command::ShowGodRays => println!("godrays: {}", self.god_ray_settings),
+                Command::SetMotionBlur(enabled) => self.motion_blur_settings.enabled = enabled,
+                Command::SetMotionBlurSamples(samples) => self.motion_blur_settings.samples = samples,
+                Command::SetShutter(shutter) => self.motion_blur_settings.shutter = shutter.clamp(0.0, 1.0),
+                Command::ShowMotionBlur => println!("motionblur: {}", self.motion_blur_settings),
+                Command::SetBloom(enabled) => self.bloom_settings.enabled = enabled,
+                Command::SetBloomThreshold(threshold) => self.bloom_settings.threshold = threshold.max(0.0),
+                Command::SetBloomIntensity(intensity) => self.bloom_settings.intensity = intensity.max(0.0),
+                Command::ShowBloom => println!("bloom: {}", self.bloom_settings),
+                Command::SetPostEffects(effects) => {
+                    self.effects = effects;
+                    println!("postfx: {}", self.describe_effects());
+                }
+                Command::ShowPostEffects => println!("postfx: {}", self.describe_effects()),
+                Command::ShowSky => {
+                    let sun = self.sky.sun_direction();
+                    println!(
+                        "sky: {}, {:.1}h, day length {}s, turbidity {:.1}, sun ({:.2}, {:.2}, {:.2})",
+                        if self.sky.enabled { "on" } else { "off" },
+                        self.sky.hour(),
+                        self.sky.day_length,
+                        self.sky.turbidity,
+                        sun.x,
+                        sun.y,
+                        sun.z
+                    );
+                }
+                Command::LoadSkybox(Some(paths)) => match Skybox::load(&self.upload_queue, &paths) {
+                    Ok(skybox) => {
+                        println!("skybox: {}", skybox.description);
+                        self.skybox = Some(skybox);
+                        // the cached set would keep the old cubemap alive
+                        self.descriptors.clear();
+                    }
+                    Err(e) => println!("console: {}", e),
+                },
+                Command::LoadSkybox(None) => {
+                    self.skybox = None;
+                    self.descriptors.clear();
+                }
+                Command::ShowSkybox => match &self.skybox {
+                    Some(skybox) => println!("skybox: {}", skybox.description),
+                    None => println!("skybox: off"),
+                },
+                Command::LoadMesh(path) => self.load_mesh(&path),
+                Command::LoadPrimitive(primitive) => self.load_primitive(primitive),
+                Command::LoadTexture(Some(path)) => match self.load_texture(&path) {
+                    Ok((texture, description)) => {
+                        println!("texture: {}", description);
+                        self.texture = Some(Arc::new(texture));
+                        self.descriptors.clear();
+                    }
+                    Err(e) => println!("console: {}", e),
+                },
+                Command::LoadTexture(None) => {
+                    self.texture = None;
+                    self.descriptors.clear();
+                    println!("texture: off");
+                }
+                Command::SetEmissiveColor(color) => {
+                    self.emission.color = color.map(|channel| channel.max(0.0));
+                    println!("emissive: {}", self.emission);
+                }
+                Command::SetEmissiveIntensity(intensity) => {
+                    self.emission.intensity = intensity.max(0.0);
+                    println!("emissive: {}", self.emission);
+                }
+                Command::LoadEmissiveMap(Some(path)) => match self.load_texture(&path) {
+                    Ok((texture, description)) => {
+                        println!("emissive: texture {}", description);
+                        self.emissive_map = Some(Arc::new(texture));
+                        self.descriptors.clear();
+                    }
+                    Err(e) => println!("console: {}", e),
+                },
+                Command::LoadEmissiveMap(None) => {
+                    self.emissive_map = None;
+                    self.descriptors.clear();
+                    println!("emissive: texture off");
+                }
+                Command::ShowEmission => println!("emissive: {}", self.emission),
+                Command::SetMipmaps(enabled) => {
+                    self.mipmaps = enabled;
+                    println!("mipmaps: {}", if enabled { "on" } else { "off" });
+                }
+                Command::SetMipGeneration(generation) => {
+                    self.mip_generation = generation;
+                    match generation {
+                        Some(generation) => println!("mipgen: {}", generation),
+                        None => println!("mipgen: auto ({})", MipGeneration::pick(&self.graphics_queue)),
+                    }
+                }
+                Command::SetDither(enabled) => {
+                    self.dithering = enabled;
+                    println!("dither: {}", if enabled { "on" } else { "off" });
+                }
+                Command::SetTint(tint) => {
+                    self.tint = tint;
+                    println!("tint: {:.2} {:.2} {:.2}", tint[0], tint[1], tint[2]);
+                }
+                Command::ShowTint => println!("tint: {:.2} {:.2} {:.2}", self.tint[0], self.tint[1], self.tint[2]),
+                Command::SetCheckerboard(enabled) => {
+                    if enabled && self.deferred.is_some() {
+                        println!("checkerboard: not available with deferred shading");
+                    } else {
+                        self.checkerboard_rendering = enabled;
+                        // the scene framebuffer and the passes reading depth change targets
+                        self.recreate_swap_chain = true;
+                        println!("checkerboard: {}", if enabled { "on" } else { "off" });
+                    }
+                }
+                Command::Subgroups { scalar } => {
+                    let support = if scalar {
+                        SubgroupSupport::default()
+                    } else {
+                        SubgroupSupport::query(self.device.physical_device())
+                    };
+                    println!("subgroups: {}", support);
+                    let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+                    match subgroups::cull_demo(&self.compute_queue, view_projection, support) {
+                        Ok(report) => println!("cull: {}", report),
+                        Err(e) => println!("console: {}", e),
+                    }
+                    self.luminance_request = Some(support);
+                }
+                Command::LoadShader(Some((stage, path))) => {
+                    // a fragment shader stands in for the one of the current debug view
+                    let file_name = match stage {
+                        ShaderStage::Vertex => renderer::VERTEX_SHADER_FILE,
+                        _ => self.debug_view.shader_file(),
+                    };
+                    match self.reloaded_shaders.replace(file_name, &path, stage) {
+                        Ok(()) => {
+                            self.scene_pipelines.clear();
+                            self.rebuild_graphics_pipeline();
+                            self.rebuild_minimap();
+                            self.rebuild_portals();
+                            println!("shader: {} shader from {}", stage, path.display());
+                        }
+                        Err(e) => println!("console: {}", e),
+                    }
+                }
+                Command::LoadShader(None) => {
+                    self.reloaded_shaders.reset();
+                    self.scene_pipelines.clear();
+                    self.rebuild_graphics_pipeline();
+                    self.rebuild_minimap();
+                    self.rebuild_portals();
+                    println!("shader: built-in");
+                }
+                Command::LoadAudio(Some(path)) => match AudioInput::load(&path) {
+                    Ok(audio) => {
+                        println!("audio: {}", audio);
+                        self.audio = Some(audio);
+                    }
+                    Err(e) => println!("console: {}: {}", path.display(), e),
+                },
+                Command::LoadAudio(None) => {
+                    self.audio = None;
+                    println!("audio: off");
+                }
+                Command::ShowAudio => match &self.audio {
+                    Some(audio) => println!("audio: {}", audio),
+                    None => println!("audio: off"),
+                },
+                Command::LoadTimeline(Some(path)) => match Timeline::load(&path) {
+                    Ok(timeline) => {
+                        println!("timeline: {}", timeline);
+                        self.timeline = Some(timeline);
+                    }
+                    Err(e) => println!("console: {}: {}", path.display(), e),
+                },
+                Command::LoadTimeline(None) => {
+                    self.timeline = None;
+                    println!("timeline: off");
+                }
+                Command::PlayTimeline(playing) => self.play_timeline(playing),
+                Command::SeekTimeline(time) => match &mut self.timeline {
+                    Some(timeline) => {
+                        timeline.seek(time);
+                        println!("timeline: {}", timeline);
+                    }
+                    None => println!("console: no timeline loaded"),
+                },
+                Command::ShowTimeline => match &self.timeline {
+                    Some(timeline) => println!("timeline: {}", timeline),
+                    None => println!("timeline: off"),
+                },
+                Command::LoadPath(Some((file, follower))) => match SplinePath::load(&file, follower) {
+                    Ok(path) => {
+                        println!("path: {}", path);
+                        self.spline_path = Some(path);
+                    }
+                    Err(e) => println!("console: {}: {}", file.display(), e),
+                },
+                Command::LoadPath(None) => {
+                    self.spline_path = None;
+                    println!("path: off");
+                }
+                Command::SetPathSpeed(speed) => match &mut self.spline_path {
+                    Some(path) => path.speed = speed,
+                    None => println!("console: no path loaded"),
+                },
+                Command::ShowPath => match &self.spline_path {
+                    Some(path) => println!("path: {}", path),
+                    None => println!("path: off"),
+                },
+                Command::FrameLog(Some(path)) => self.start_frame_log(&path),
+                Command::FrameLog(None) => self.stop_frame_log(),
+                Command::ShowFrameLog => match &self.frame_log {
+                    Some(frame_log) => println!("framelog: writing to {}", frame_log.path.display()),
+                    None => println!("framelog: off"),
+                },
+                Command::LoadPoints(Some(path)) => {
+                    println!("loading point cloud {}", path.display());
+                    self.point_cloud = Some(PointCloud::load(&path));
+                }
+                Command::LoadPoints(None) => self.point_cloud = None,
+                Command::SetWorld(true) => {
+                    if self.world.is_none() {
+                        self.world = Some(World::start());
+                    }
+                    println!("world: on");
+                }
+                Command::SetWorld(false) => {
+                    self.world = None;
+                    println!("world: off");
+                }
+                Command::SetWorldSpeed(speed) => match &mut self.world {
+                    Some(world) => world.speed = speed.max(0.0),
+                    None => println!("world: not on"),
+                },
+                Command::SetWorldSplatting(splatting) => match &mut self.world {
+                    Some(world) => {
+                        world.splatting = splatting;
+                        println!("world: splatting {}", if splatting { "on" } else { "off" });
+                    }
+                    None => println!("world: not on"),
+                },
+                Command::SetWorldTriplanar(triplanar) => match &mut self.world {
+                    Some(world) => {
+                        world.triplanar = triplanar;
+                        println!("world: triplanar {}", if triplanar { "on" } else { "off" });
+                    }
+                    None => println!("world: not on"),
+                },
+                Command::SetWorldGrass(grass) => match &mut self.world {
+                    Some(world) => {
+                        world.grass = grass;
+                        println!("world: grass {}", if grass { "on" } else { "off" });
+                    }
+                    None => println!("world: not on"),
+                },
+                Command::ShowWorld => match &self.world {
+                    Some(world) => println!("world: {}", world.status()),
+                    None => println!("world: off"),
+                },
+                Command::SetMinimap(enabled) => {
+                    self.minimap_settings.enabled = enabled;
+                    self.rebuild_minimap();
+                    println!("minimap: {}", self.minimap_settings);
+                }
+                Command::SetMinimapExtent(extent) => self.minimap_settings.extent = extent,
+                Command::SetMinimapInterval(interval) => self.minimap_settings.interval = interval,
+                Command::ShowMinimap => println!("minimap: {}", self.minimap_settings),
+                Command::SetQueries(enabled) => self.set_queries(enabled),
+                Command::SetPortal(kind) => {
+                    self.portal_settings.kind = kind;
+                    self.rebuild_portals();
+                    println!("portal: {}", self.portal_settings);
+                }
+                Command::SetPortalDepth(depth) => {
+                    self.portal_settings.depth = depth;
+                    self.rebuild_portals();
+                    println!("portal: {}", self.portal_settings);
+                }
+                Command::ShowPortal => println!("portal: {}", self.portal_settings),
+                Command::ShowQueries => match self.draw_queries.as_ref().map(DrawQueries::latest) {
+                    Some(Some(report)) => {
+                        println!("queries: {}", report);
+                        for (index, draw) in report.draws.iter().enumerate() {
+                            match draw.statistics {
+                                Some(statistics) => println!(
+                                    "queries: draw {}: {} samples, {} vertices, {} primitives, {} fragment invocations",
+                                    index,
+                                    draw.samples,
+                                    statistics.vertices,
+                                    statistics.primitives,
+                                    statistics.fragment_invocations
+                                ),
+                                None => println!("queries: draw {}: {} samples", index, draw.samples),
+                            }
+                        }
+                    }
+                    Some(None) => println!("queries: no results yet"),
+                    None => println!("queries: off"),
+                },
+                Command::ShowPoints => match &self.point_cloud {
+                    Some(point_cloud) => println!("{}", point_cloud.status()),
+                    None => println!("no point cloud loaded"),
+                },
+                Command::SetInstances(Some(count)) => self.set_instances(count),
+                Command::SetInstances(None) => {
+                    self.instanced_quads = None;
+                    println!("instances: off");
+                }
+                Command::ShowInstances => match &self.instanced_quads {
+                    Some(quads) => println!("instances: {} quads in one draw call", quads.count),
+                    None => println!("instances: off"),
+                },
+                Command::SetParticles(Some(count)) => self.set_particles(count),
+                Command::SetParticles(None) => {
+                    self.particles = None;
+                    println!("particles: off");
+                }
+                Command::ShowParticles => match &self.particles {
+                    Some(particles) => println!("particles: {} simulated in a compute shader", particles.count),
+                    None => println!("particles: off"),
+                },
+                Command::SetRecordingThreads(threads) => {
+                    self.recording_threads = threads;
+                    self.print_recording_threads();
+                }
+                Command::ShowRecordingThreads => self.print_recording_threads(),
+                Command::SetCulling(enabled) => {
+                    self.culling.enabled = enabled;
+                    println!("cull: {}", self.culling);
+                }
+                Command::ShowCulling => println!("cull: {}", self.culling),
+                Command::SetVsync(vsync) => {
+                    self.settings.vsync = vsync;
+                    self.recreate_swap_chain = true;
+                    println!("vsync: {}", if vsync { "on" } else { "off" });
+                }
+                Command::SetPresentMode(mode) => {
+                    self.settings.present_mode = mode;
+                    self.recreate_swap_chain = true;
+                    match mode {
+                        Some(mode) => println!("present: {:?} requested", mode),
+                        None => println!("present: auto, vsync {}", if self.settings.vsync { "on" } else { "off" }),
+                    }
+                }
+                Command::ShowPresentMode => self.print_present_mode(),
+                Command::CaptureVertices(path) => match self.capture_vertices(&path) {
+                    Ok(report) => println!("vertices: {}", report),
+                    Err(e) => println!("console: {}", e),
+                },
+                Command::SetGrade(grade) => {
+                    self.grade = grade;
+                    self.color_grade = match (grade, self.color_grade.take()) {
+                        (Some(_), Some(color_grade)) => Some(color_grade),
+                        (Some(_), None) => {
+                            match ColorGrade::new(&self.device, &self.swap_chain_images, self.swap_chain.format()) {
+                                Ok(color_grade) => Some(color_grade),
+                                Err(e) => {
+                                    println!("console: {}", e);
+                                    self.grade = None;
+                                    None
+                                }
+                            }
+                        }
+                        (None, _) => None,
+                    };
+                    self.print_grade();
+                }
+                Command::ShowGrade => self.print_grade(),
+                Command::SetHiZView(level) => {
+                    self.hiz_view = level;
+                    self.print_hiz();
+                }
+                Command::ShowHiZ => self.print_hiz(),
+                Command::SetLightPosition(position) => {
+                    self.light.position = position;
+                    println!("light: {}", self.light);
+                }
+                Command::SetLightColor(color) => {
+                    self.light.color = color.map(|channel| channel.max(0.0));
+                    println!("light: {}", self.light);
+                }
+                Command::SetAmbient(ambient) => {
+                    self.light.ambient = ambient.clamp(0.0, 1.0);
+                    println!("light: {}", self.light);
+                }
+                Command::SetShininess(shininess) => {
+                    self.light.shininess = shininess;
+                    println!("light: {}", self.light);
+                }
+                Command::ShowLight => println!("light: {}", self.light),
+                Command::SetLightCount(count) => {
+                    self.clustered_lights.count = count;
+                    self.print_light_count();
+                }
+                Command::ShowLightCount => self.print_light_count(),
+                Command::SetLightHeatmap(heatmap) => {
+                    self.clustered_lights.heatmap = heatmap;
+                    self.print_light_count();
+                }
+                Command::SetPointSize(size) => match &mut self.point_cloud {
+                    // a NaN size would pass the shader's clamp, and gl_PointSize needs to be positive
+                    Some(_) if !size.is_finite() => println!("console: the point size needs to be a finite number"),
+                    Some(point_cloud) => {
+                        point_cloud.point_size = size.clamp(1.0, point_cloud::max_point_size(&self.device));
+                        println!("point-size: {}", point_cloud.point_size);
+                    }
+                    None => println!("console: no point cloud loaded"),
+                },
+            }
+        }
+    }
+
+    fn print_recording_threads(&self) {
+        match self.recording_threads {
+            Some(threads) => println!("parallel: renderables recorded on {} threads", threads),
+            None => println!("parallel: off, the scene pass is recorded inline"),
+        }
+    }
+
+    /// Captures the scene mesh as the vertex stage transforms it this frame into `path`
+    fn capture_vertices(&self, path: &Path) -> Result<String, String> {
+        let scene = self.render_scene();
+        let renderable = scene
+            .iter()
+            .find(|renderable| renderable.id == DrawId::Scene)
+            .ok_or_else(|| "this scene draws no mesh".to_string())?;
+        let transform = vertex_capture::Transform {
+            model: renderable.transform,
+            view_projection: self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix(),
+            time: self.start_time.elapsed().as_secs_f32(),
+            animation: renderable.material.animation,
+        };
+        let vertices = vertex_capture::capture(&self.compute_queue, &renderable.mesh.vertex_buffer, &transform)?;
+        vertex_capture::write_csv(&vertices, path)?;
+        let bounds = match vertex_capture::bounds(&vertices) {
+            Some(bounds) => format!(", world bounds {:?} to {:?}", bounds.min, bounds.max),
+            None => String::new(),
+        };
+        Ok(format!("{} captured into {}{}", vertices.len(), path.display(), bounds))
+    }
+
+    fn print_present_mode(&self) {
+        let supported = match self.surface.capabilities(self.device.physical_device()) {
+            Ok(capabilities) => swapchain::supported_present_modes(capabilities.present_modes),
+            Err(e) => {
+                println!("console: {}", e);
+                return;
+            }
+        };
+        println!(
+            "present: {:?}, the surface supports {:?}",
+            self.swap_chain.present_mode(),
+            supported
+        );
+    }
+
+    fn print_light_count(&self) {
+        let count = self.clustered_lights.count;
+        if count == 0 {
+            println!("lights: off, the lit view has only the main light");
+        } else {
+            println!(
+                "lights: {} orbiting the scene, assigned to {}x{}x{} froxels, shown in the lit view",
+                count,
+                clusters::GRID[0],
+                clusters::GRID[1],
+                clusters::GRID[2]
+            );
+        }
+        if self.clustered_lights.heatmap {
+            println!(
+                "lights: heatmap on, froxels with no lights are black, then blue, green, yellow and red at {}",
+                clusters::MAX_LIGHTS_PER_CLUSTER
+            );
+        }
+    }
+
+    fn print_hiz(&self) {
+        let hiz = match &self.hiz {
+            Some(hiz) => hiz,
+            None => {
+                println!("hiz: not built, the graphics queue can't run compute shaders");
+                return;
+            }
+        };
+        let levels = hiz
+            .levels()
+            .iter()
+            .map(|level| {
+                let [width, height] = level.image().dimensions().width_height();
+                format!("{}x{}", width, height)
+            })
+            .collect::<Vec<_>>();
+        let view = match self.hiz_view {
+            Some(level) => format!("showing level {}", level.min(levels.len() - 1)),
+            None => "not shown".to_string(),
+        };
+        println!("hiz: {} levels ({}), {}", levels.len(), levels.join(", "), view);
+    }
+
+    fn print_grade(&self) {
+        match self.grade {
+            Some(grade) => println!("grade: {}", grade),
+            None => println!("grade: off"),
+        }
+    }
+
+    /// Puts the window in `mode` on the monitor `monitor` names. The window gets a new size,
+    /// which the swap chain is recreated for.
+    fn set_fullscreen(&mut self, mode: FullscreenMode, monitor: Option<&str>) {
+        let window = self.surface.window();
+        let monitor = fullscreen::pick_monitor(window.available_monitors(), window.current_monitor(), monitor);
+        window.set_fullscreen(fullscreen::fullscreen(mode, monitor));
+        info!("Fullscreen: {}", mode);
+        self.fullscreen_mode = mode;
+        self.recreate_swap_chain = true;
+    }
+
+    fn set_fov(&mut self, fov_y: Deg<f32>) {
+        match self.projection.set_fov_y(fov_y) {
+            Ok(()) => println!("projection: {}", self.projection),
+            Err(e) => println!("console: {}", e),
+        }
+    }
+
+    fn set_clip_planes(&mut self, near: f32, far: f32) {
+        match self.projection.set_planes(near, far) {
+            Ok(()) => println!("projection: {}", self.projection),
+            Err(e) => println!("console: {}", e),
+        }
+    }
+
+    /// Acts on the keys and clicks since the last frame, the camera follows its own keys and the
+    /// middle mouse button when it updates
+    fn handle_input(&mut self) {
+        for key in self.input.pressed_keys().to_vec() {
+            if !camera::KEYS.contains(&key) {
+                self.handle_key_press(key);
+            }
+        }
+        if self.input.button_pressed(MouseButton::Left) {
+            match self.measurement {
+                Some(_) => self.measure_at_cursor(),
+                None => self.report_cursor_hit(),
+            }
+        }
+        if self.input.button_pressed(MouseButton::Right) {
+            self.spawn_at_cursor();
+        }
+    }
+
+    fn handle_key_press(&mut self, key: VirtualKeyCode) {
+        if key == VirtualKeyCode::Return && self.input.modifiers().alt() {
+            let mode = match self.fullscreen_mode {
+                FullscreenMode::Windowed if self.settings.fullscreen.is_fullscreen() => self.settings.fullscreen,
+                FullscreenMode::Windowed => FullscreenMode::Borderless,
+                _ => FullscreenMode::Windowed,
+            };
+            let monitor = self.settings.monitor.clone();
+            self.set_fullscreen(mode, monitor.as_deref());
+            return;
+        }
+        let scene_keys = [
+            VirtualKeyCode::Key1,
+            VirtualKeyCode::Key2,
+            VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4,
+            VirtualKeyCode::Key5,
+            VirtualKeyCode::Key6,
+            VirtualKeyCode::Key7,
+            VirtualKeyCode::Key8,
+            VirtualKeyCode::Key9,
+        ];
+
+        if let Some(index) = scene_keys.iter().position(|scene_key| *scene_key == key) {
+            if index < DEMO_SCENES.len() {
+                self.set_scene(SceneId(index));
+            }
+            return;
+        }
+
+        match key {
+            VirtualKeyCode::LBracket => self.set_fov(self.projection.fov_y - Deg(5.0)),
+            VirtualKeyCode::RBracket => self.set_fov(self.projection.fov_y + Deg(5.0)),
+            VirtualKeyCode::Comma => self.set_clip_planes(self.projection.near / 2.0, self.projection.far),
+            VirtualKeyCode::Period => self.set_clip_planes(self.projection.near * 2.0, self.projection.far),
+            VirtualKeyCode::PageUp => self.move_section(|section| section.translate(SECTION_STEP)),
+            VirtualKeyCode::PageDown => self.move_section(|section| section.translate(-SECTION_STEP)),
+            VirtualKeyCode::Home => self.move_section(Section::flip),
+            VirtualKeyCode::M => self.set_measure_mode(self.measurement.is_none()),
+            VirtualKeyCode::B => self.place_decal(DecalKind::BulletHole, Some(BULLET_HOLE_LIFETIME)),
+            VirtualKeyCode::P => self.set_plot(self.plot.is_none()),
+            VirtualKeyCode::F1 => self.set_debug_ui(!self.debug_ui.enabled),
+            VirtualKeyCode::C => self.camera.reset(),
+            VirtualKeyCode::Space if self.timeline.is_some() => {
+                self.play_timeline(!self.timeline.as_ref().unwrap().playing)
+            }
+            _ => (),
+        }
+    }
+
+    fn set_scene(&mut self, scene: SceneId) {
+        if self.scene == scene && self.imported_model.is_none() {
+            return;
+        }
+
+        info!("Switching to scene {}", scene.scene());
+        let (vertices, indices) = scene.scene().mesh();
+        if let Err(e) = self.upload_scene_mesh(&vertices, &indices) {
+            error!("Failed to upload scene {}: {}", scene.scene(), e);
+            return;
+        }
+        self.scene_bounds = Self::scene_bounds(&vertices, scene.scene().model());
+        self.scene_triangles = Self::scene_triangles(&vertices, &indices, scene.scene().model());
+        self.switch_showcase(self.scene.scene().showcase, scene.scene().showcase);
+        self.scene = scene;
+        self.imported_model = None;
+        self.start_time = Instant::now();
+        self.rotation_angle = 0.0;
+    }
+
+    /// Turns off what the previous scene showed off and turns on what the next one does
+    fn switch_showcase(&mut self, previous: Option<Showcase>, next: Option<Showcase>) {
+        if previous == next {
+            return;
+        }
+        match previous {
+            Some(Showcase::Lighting) if self.debug_view == DebugView::Lit => self.set_debug_view(DebugView::default()),
+            Some(Showcase::Particles) => self.particles = None,
+            Some(Showcase::Terrain) => self.world = None,
+            _ => (),
+        }
+        match next {
+            Some(Showcase::Lighting) => self.set_debug_view(DebugView::Lit),
+            Some(Showcase::Particles) if self.particles.is_none() => self.set_particles(compute::DEFAULT_PARTICLES),
+            Some(Showcase::Terrain) if self.world.is_none() => self.world = Some(World::start()),
+            _ => (),
+        }
+    }
+
+    /// Replaces the demo scene with a mesh file, scaled to fit the view
+    fn load_mesh(&mut self, path: &Path) {
+        let mesh = match mesh::load(path) {
+            Ok(mesh) => mesh,
+            Err(e) => {
+                error!("Failed to load mesh {}", e);
+                return;
+            }
+        };
+
+        let bounds = match mesh.bounds() {
+            Some(bounds) if !mesh.indices.is_empty() => bounds,
+            _ => {
+                error!("{} has no faces, use 'points' to show it as a point cloud", path.display());
+                return;
+            }
+        };
+
+        info!(
+            "Loaded {} with {} vertices and {} triangles",
+            path.display(),
+            mesh.positions.len(),
+            mesh.indices.len() / 3
+        );
+        let vertices = mesh.vertices();
+        if let Err(e) = self.upload_scene_mesh(&vertices, &mesh.indices) {
+            error!("Failed to upload {}: {}", path.display(), e);
+            return;
+        }
+        let model = bounds.fit_into(mesh::FIT_SIZE);
+        self.scene_bounds = Self::scene_bounds(&vertices, model);
+        self.scene_triangles = Self::scene_triangles(&vertices, &mesh.indices, model);
+        self.imported_model = Some(model);
+    }
+
+    /// Replaces the demo scene with a generated mesh, like a loaded one but at its own size
+    fn load_primitive(&mut self, primitive: Primitive) {
+        let (vertices, indices) = primitive.mesh();
+        if let Err(e) = self.upload_scene_mesh(&vertices, &indices) {
+            error!("Failed to upload the {}: {}", primitive, e);
+            return;
+        }
+        info!("Showing a {} with {} vertices and {} triangles", primitive, vertices.len(), indices.len() / 3);
+        let model = primitive.model();
+        self.scene_bounds = Self::scene_bounds(&vertices, model);
+        self.scene_triangles = Self::scene_triangles(&vertices, &indices, model);
+        self.imported_model = Some(model);
+    }
+
+    /// Replaces the scene's buffers, the old ones are kept when uploading fails
+    fn upload_scene_mesh(&mut self, vertices: &[Vertex], indices: &[u32]) -> Result<(), Error> {
+        let mut staging = Staging::new(&self.upload_queue);
+        let vertex_buffer = renderer::create_vertex_buffer(&mut staging, vertices)?;
+        let index_buffer = renderer::create_index_buffer(&mut staging, indices)?;
+        staging.submit_and_wait()?;
+        self.scene_mesh = MeshHandle::new(vertex_buffer, index_buffer);
+        Ok(())
+    }
+
+    fn scene_bounds(vertices: &[Vertex], model: Matrix4<f32>) -> Option<Aabb> {
+        let points = vertices.iter().map(|vertex| Point3::from(vertex.pos()));
+        Aabb::from_transformed_points(points, model)
+    }
+
+    fn scene_triangles(vertices: &[Vertex], indices: &[u32], model: Matrix4<f32>) -> Vec<[Point3<f32>; 3]> {
+        let world: Vec<Point3<f32>> = vertices
+            .iter()
+            .map(|vertex| model.transform_point(Point3::from(vertex.pos())))
+            .collect();
+
+        indices
+            .chunks_exact(3)
+            .map(|triangle| [world[triangle[0] as usize], world[triangle[1] as usize], world[triangle[2] as usize]])
+            .collect()
+    }
+
+    /// Closest point on the scene mesh under the cursor, ignoring what the section plane cuts away,
+    /// with the normal of the triangle it is on turned towards the camera
+    fn pick_surface(&self) -> Option<(Point3<f32>, Vector3<f32>)> {
+        let ray = self.cursor_ray()?;
+        let visible = |point: Point3<f32>| match &self.section {
+            Some(section) => section.plane.normal.dot(point.to_vec()) >= section.plane.distance,
+            None => true,
+        };
+
+        let (point, triangle) = self
+            .scene_triangles
+            .iter()
+            .filter_map(|triangle| ray.intersect_triangle(triangle).map(|distance| (ray.at(distance), triangle)))
+            .filter(|(point, _)| visible(*point))
+            .min_by(|(a, _), (b, _)| ray.origin.distance2(*a).total_cmp(&ray.origin.distance2(*b)))?;
+
+        let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]).normalize();
+        let normal = if normal.dot(ray.direction) > 0.0 { -normal } else { normal };
+        Some((point, normal))
+    }
+
+    fn measure_at_cursor(&mut self) {
+        let point = match self.pick_surface() {
+            Some((point, _)) => point,
+            None => {
+                println!("measure: no surface under the cursor");
+                return;
+            }
+        };
+
+        if let Some(measurement) = &mut self.measurement {
+            measurement.add_point(point);
+            match measurement.distance() {
+                Some(distance) => println!("measure: distance {:.4}", distance),
+                None => println!("measure: start at ({:.3}, {:.3}, {:.3})", point.x, point.y, point.z),
+            }
+        }
+    }
+
+    /// Lays out the debug panel for this frame and applies what was changed in it, the meshes are
+    /// drawn at the end of the frame
+    fn run_debug_ui(&mut self) -> Vec<ClippedMesh> {
+        if !self.debug_ui.enabled {
+            return Vec::new();
+        }
+
+        let physical_device = self.device.physical_device();
+        let properties = physical_device.properties();
+        let [width, height] = self.swap_chain.dimensions();
+        let summary = self.frame_stats.summary();
+        let fps = match summary {
+            Some(summary) => format!("{:.1} ({:.2} ms)", summary.fps, summary.average.as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        let gpu_time = match summary.and_then(|summary| summary.gpu) {
+            Some(gpu) => format!("{:.2} ms", gpu.total().as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        let queried = self.draw_queries.as_ref().and_then(DrawQueries::latest);
+        let statistics = queried.and_then(|report| report.statistics());
+        let report = [
+            ("Device", properties.device_name.as_deref().unwrap_or("unknown device").to_string()),
+            ("Type", format!("{:?}", properties.device_type.unwrap())),
+            ("Vulkan", physical_device.api_version().to_string()),
+            ("Swap chain", format!("{}x{} {:?}", width, height, self.swap_chain.format())),
+            ("Present mode", format!("{:?}", self.swap_chain.present_mode())),
+            ("FPS", fps),
+            ("GPU time", gpu_time),
+            (
+                "Queried draws",
+                queried.map_or("-".to_string(), |report| {
+                    format!("{} ({} occluded)", report.draws.len(), report.occluded())
+                }),
+            ),
+            ("Samples passed", queried.map_or("-".to_string(), |report| report.samples().to_string())),
+            ("Vertices", statistics.map_or("-".to_string(), |statistics| statistics.vertices.to_string())),
+            ("Primitives", statistics.map_or("-".to_string(), |statistics| statistics.primitives.to_string())),
+            (
+                "Fragment invocations",
+                statistics.map_or("-".to_string(), |statistics| statistics.fragment_invocations.to_string()),
+            ),
+            ("Refresh rate", format!("{} Hz", self.refresh_rate.rate())),
+        ];
+
+        let mut values = PanelValues {
+            clear_color: self.settings.clear_color,
+            rotation_speed: self.rotation_speed,
+            timeline: self.timeline.as_ref().map(|timeline| (timeline.time(), timeline.duration())),
+        };
+        let meshes = self.debug_ui.run(self.surface.window(), &report, &mut values);
+        self.settings.clear_color = values.clear_color;
+        self.rotation_speed = values.rotation_speed;
+        if let (Some(timeline), Some((time, _))) = (&mut self.timeline, values.timeline) {
+            if time != timeline.time() {
+                timeline.seek(time);
+            }
+        }
+
+        self.ui_renderer.update_font(&self.upload_queue, &self.debug_ui.font());
+        meshes
+    }
+
+    fn play_timeline(&mut self, playing: bool) {
+        match &mut self.timeline {
+            Some(timeline) => {
+                if playing {
+                    timeline.play();
+                } else {
+                    timeline.playing = false;
+                }
+                println!("timeline: {}", timeline);
+            }
+            None => println!("console: no timeline loaded"),
+        }
+    }
+
+    /// Advances the timeline and applies its values, over what the camera controls and the
+    /// console set
+    fn apply_timeline(&mut self, frame_interval: Duration) {
+        let timeline = match &mut self.timeline {
+            Some(timeline) => timeline,
+            None => return,
+        };
+
+        timeline.advance(frame_interval.as_secs_f32());
+        let values = timeline.values();
+        if let Some(pose) = values.camera {
+            pose.apply(&mut self.camera);
+        }
+        for (id, position) in values.objects {
+            // objects the timeline names have to be spawned first, the others are left out
+            if let Some(object) = self.objects.get_mut(id) {
+                object.position = position;
+            }
+        }
+        for (parameter, value) in values.parameters {
+            match parameter {
+                Parameter::Gamma => self.settings.gamma = value,
+                Parameter::Brightness => self.settings.brightness = value,
+                Parameter::Contrast => self.settings.contrast = value,
+                Parameter::GodRayDensity => self.god_ray_settings.density = value.clamp(0.0, 1.0),
+                Parameter::Shutter => self.motion_blur_settings.shutter = value.clamp(0.0, 1.0),
+            }
+        }
+    }
+
+    /// Moves the camera or the object following the spline path
+    fn follow_spline_path(&mut self, frame_interval: Duration) {
+        let path = match &mut self.spline_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        path.advance(frame_interval.as_secs_f32());
+        match path.follower {
+            PathFollower::Camera => self.camera.look_from(path.position(), path.view_target()),
+            PathFollower::Object(id) => {
+                if let Some(object) = self.objects.get_mut(id) {
+                    object.position = path.position();
+                }
+            }
+        }
+    }
+
+    fn set_instances(&mut self, count: u32) {
+        let mut staging = Staging::new(&self.upload_queue);
+        let quads = InstancedQuads::new(&mut staging, count).and_then(|quads| {
+            staging.submit_and_wait()?;
+            Ok(quads)
+        });
+        match quads {
+            Ok(quads) => {
+                self.instanced_quads = Some(quads);
+                println!("instances: {} quads in one draw call", count);
+            }
+            Err(e) => println!("console: {}", e),
+        }
+    }
+
+    fn set_particles(&mut self, count: u32) {
+        // the update is recorded into the frame's command buffer, on the graphics queue
+        if !self.graphics_queue.family().supports_compute() {
+            println!("console: the graphics queue can't run compute shaders");
+            return;
+        }
+
+        let mut staging = Staging::new(&self.upload_queue);
+        let particles = ParticleSystem::new(&mut staging, count, self.simulation_step()).and_then(|particles| {
+            staging.submit_and_wait()?;
+            Ok(particles)
+        });
+        match particles {
+            Ok(particles) => {
+                self.particles = Some(particles);
+                println!("particles: {} simulated in a compute shader", count);
+            }
+            Err(e) => println!("console: {}", e),
+        }
+    }
+
+    fn set_debug_ui(&mut self, enabled: bool) {
+        self.debug_ui.enabled = enabled;
+        println!("ui: {}", if enabled { "on" } else { "off" });
+    }
+
+    /// Shows the frame statistics where the `stats` command asked for every `REPORT_INTERVAL`
+    fn report_stats(&mut self) {
+        if self.stats_display == StatsDisplay::Off {
+            return;
+        }
+
+        if let Some(summary) = self.frame_stats.poll_report() {
+            match self.stats_display {
+                StatsDisplay::Off => (),
+                StatsDisplay::Log => info!("Frame stats: {}", summary),
+                StatsDisplay::Title => self.surface.window().set_title(&format!("{} - {}", WINDOW_TITLE, summary)),
+            }
+        }
+    }
+
+    fn set_stats(&mut self, display: StatsDisplay) {
+        if self.stats_display == StatsDisplay::Title && display != StatsDisplay::Title {
+            self.surface.window().set_title(WINDOW_TITLE);
+        }
+        self.stats_display = display;
+        println!("stats: {}", display);
+    }
+
+    fn set_plot(&mut self, enabled: bool) {
+        self.plot = if enabled { Some(Plot::frame_times()) } else { None };
+        println!("plot: {}", if enabled { "on" } else { "off" });
+    }
+
+    /// Projects a decal onto the scene surface under the cursor
+    fn place_decal(&mut self, kind: DecalKind, lifetime: Option<Duration>) {
+        match self.pick_surface() {
+            Some((point, normal)) => {
+                let decal = self.decals.spawn(kind, point, normal, DECAL_SIZE, lifetime);
+                println!("placed decal {}", decal);
+            }
+            None => println!("decal: no surface under the cursor"),
+        }
+    }
+
+    fn set_measure_mode(&mut self, enabled: bool) {
+        self.measurement = if enabled { Some(Measurement::default()) } else { None };
+        println!(
+            "measure: {}",
+            if enabled { "on, click two points on the scene" } else { "off" }
+        );
+    }
+
+    /// Ray through the pixel under the mouse cursor
+    fn cursor_ray(&self) -> Option<Ray> {
+        let extent = self.swap_chain.dimensions();
+        Ray::from_cursor(
+            self.input.cursor(),
+            extent,
+            self.view_matrix(),
+            self.projection.matrix(extent),
+        )
+    }
+
+    fn report_cursor_hit(&self) {
+        let ray = match self.cursor_ray() {
+            Some(ray) => ray,
+            None => return,
+        };
+
+        let scene_hit = self
+            .scene_bounds
+            .and_then(|bounds| ray.intersect_aabb(&bounds))
+            .map(|distance| ray.at(distance));
+
+        match (scene_hit, ray.intersect_plane(&Plane::ground(0.0))) {
+            (Some(point), _) => info!("Cursor hit the scene at {:?}", point),
+            (None, Some(point)) => info!("Cursor hit the ground plane at {:?}", point),
+            (None, None) => info!("Cursor ray {:?} hit nothing", ray.direction),
+        }
+    }
+
+    /// Places an object where the cursor ray hits the scene or the ground plane
+    fn spawn_at_cursor(&mut self) {
+        let ray = match self.cursor_ray() {
+            Some(ray) => ray,
+            None => return,
+        };
+
+        let hit = self
+            .scene_bounds
+            .and_then(|bounds| ray.intersect_aabb(&bounds))
+            .map(|distance| ray.at(distance))
+            .or_else(|| ray.intersect_plane(&Plane::ground(0.0)));
+
+        match hit {
+            Some(position) => {
+                let object = self.objects.spawn(position);
+                info!("Spawned object {}", object);
+            }
+            None => info!("Nothing under the cursor to place an object on"),
+        }
+    }
+
+    fn set_debug_view(&mut self, debug_view: DebugView) {
+        if self.debug_view == debug_view {
+            return;
+        }
+
+        info!("Switching debug view to {}", debug_view);
+        self.debug_view = debug_view;
+        self.rebuild_graphics_pipeline();
+    }
+
+    /// Loads an image for the scene's fragment shader, its mip levels made the way the `mipgen`
+    /// command says, and describes it for the console
+    fn load_texture(&self, path: &Path) -> Result<(Texture, String), String> {
+        let generation = self.mip_generation.unwrap_or_else(|| MipGeneration::pick(&self.graphics_queue));
+        // blits need the graphics queue, compute generation can run beside the frames
+        let queue = match generation {
+            MipGeneration::Blit => &self.graphics_queue,
+            _ => &self.compute_queue,
+        };
+        let texture = Texture::load(queue, path, generation)?;
+        let time = match texture.generation_time {
+            Some(time) => format!(" in {:.3} ms", time.as_secs_f64() * 1000.0),
+            None => String::new(),
+        };
+        let description = format!(
+            "{} ({}x{}, {} mip levels by {}{})",
+            path.display(),
+            texture.dimensions[0],
+            texture.dimensions[1],
+            texture.mip_levels,
+            generation,
+            time
+        );
+        Ok((texture, description))
+    }
+
+    /// The post effects in the order they are applied, for the console
+    fn describe_effects(&self) -> String {
+        if self.effects.is_empty() {
+            return "off".to_string();
+        }
+        self.effects.iter().map(|effect| effect.to_string()).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Keeps the old pipeline when building the new one fails
+    fn rebuild_graphics_pipeline(&mut self) {
+        self.descriptors.clear();
+        let result = self
+            .scene_pipelines
+            .get(self.debug_view, self.section.is_some(), &self.reloaded_shaders);
+        match result {
+            Ok(pipeline) => self.graphics_pipeline = pipeline,
+            Err(e) => error!("Failed to rebuild the scene pipeline: {}", e),
+        }
+    }
+
+    /// Makes the portal's views again for the current scene size and shaders while there is one
+    fn rebuild_portals(&mut self) {
+        self.portals = None;
+        // the cached sets would keep the old views alive
+        self.descriptors.clear();
+        let kind = match self.portal_settings.kind {
+            Some(kind) => kind,
+            None => return,
+        };
+
+        let portals = Portals::new(
+            &self.device,
+            (kind, self.portal_settings.depth),
+            self.scene_size(),
+            self.scene_pipelines.cache(),
+            &self.reloaded_shaders,
+        );
+        match portals {
+            Ok(portals) => self.portals = Some(portals),
+            Err(e) => error!("Failed to create the portal: {}", e),
+        }
+    }
+
+    fn set_queries(&mut self, enabled: bool) {
+        if !enabled {
+            self.draw_queries = None;
+            println!("queries: off");
+            return;
+        }
+
+        if self.draw_queries.is_none() {
+            match DrawQueries::new(&self.device) {
+                Ok(queries) => self.draw_queries = Some(queries),
+                Err(e) => {
+                    println!("console: {}", e);
+                    return;
+                }
+            }
+        }
+        println!("queries: on");
+        if self.draw_queries.as_ref().is_some_and(|queries| !queries.has_statistics()) {
+            println!("queries: the device has no pipeline statistics, only occlusion is queried");
+        }
+        if self.recording_threads.is_some() || self.deferred.is_some() {
+            println!("queries: only the forward scene pass recording on the main thread is queried");
+        }
+    }
+
+    /// Makes the minimap again for the current swap chain and shaders while it is enabled
+    fn rebuild_minimap(&mut self) {
+        self.minimap = None;
+        if !self.minimap_settings.enabled {
+            return;
+        }
+
+        let minimap = Minimap::new(
+            &self.device,
+            &self.swap_chain_images,
+            self.swap_chain.format(),
+            self.scene_pipelines.cache(),
+            &self.reloaded_shaders,
+            self.frame_index,
+        );
+        match minimap {
+            Ok(minimap) => self.minimap = Some(minimap),
+            Err(e) => error!("Failed to create the minimap: {}", e),
+        }
+    }
+
+    fn set_section(&mut self, section: Option<Section>) {
+        let rebuild = self.section.is_some() != section.is_some();
+        self.section = section;
+        if rebuild {
+            self.rebuild_graphics_pipeline();
+        }
+
+        match &self.section {
+            Some(section) => println!("section: {}", section),
+            None => println!("section: off"),
+        }
+    }
+
+    fn move_section(&mut self, change: impl FnOnce(&mut Section)) {
+        match &mut self.section {
+            Some(section) => {
+                change(section);
+                println!("section: {}", section);
+            }
+            None => println!("console: no section plane, add one with 'section <x|y|z>'"),
+        }
+    }
+
+    /// Returns false while the window is minimized, the frame is skipped then. When recreating
+    /// fails, like for a usage from the settings the surface doesn't support, the old swap chain
+    /// is kept and recreating is tried again once it is out of date.
+    fn recreate_swap_chain(&mut self) -> bool {
+        if self.recreate_swap_chain {
+            let size = self.surface.window().inner_size();
+            if size.width == 0 || size.height == 0 {
+                return false;
+            }
+
+            info!("Recreating swap chain");
+            let result = create_swap_chain(
+                &self.instance,
+                &self.surface,
+                self.device.physical_device().index(),
+                &self.device,
+                &self.graphics_queue,
+                &self.presentation_queue,
+                Some(&self.swap_chain),
+                self.settings.vsync,
+                self.settings.present_mode,
+                self.settings.swapchain_usage,
+            );
+            let (swap_chain, swap_chain_images) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to recreate the swap chain: {}", e);
+                    self.recreate_swap_chain = false;
+                    return true;
+                }
+            };
+
+            self.swap_chain = swap_chain;
+            self.swap_chain_images = swap_chain_images;
+            // a partly rebuilt frame can't be drawn, it is skipped and recreating tried again
+            if let Err(e) = self.recreate_targets() {
+                error!("Failed to recreate the render targets: {}", e);
+                return false;
+            }
+
+            self.recreate_swap_chain = false;
+        }
+
+        true
+    }
+
+    /// Everything sized like the swap chain or the scene, and the scene pass pipelines when the
+    /// sample count changed
+    fn recreate_targets(&mut self) -> Result<(), Error> {
+        let scene_size = self.scene_size();
+        let samples = if self.settings.deferred {
+            1
+        } else {
+            renderer::sample_count(&self.device, self.settings.msaa, scene_size)
+        };
+        self.post = PostProcess::new(
+            &self.device,
+            &self.swap_chain_images,
+            self.swap_chain.format(),
+            scene_size,
+            &self.blue_noise,
+        )?;
+        self.color_grade = self
+            .grade
+            .map(|_| ColorGrade::new(&self.device, &self.swap_chain_images, self.swap_chain.format()))
+            .transpose()?;
+        // the scene pass pipelines take the viewport when drawing, a resize only needs new
+        // framebuffers and they are rebuilt when the sample count changes
+        if self.render_pass.desc().attachments()[0].samples as u32 != samples {
+            self.render_pass = renderer::create_render_pass(&self.device, post::INTERMEDIATE_FORMAT, samples);
+            self.scene_pipelines.set_render_pass(&self.render_pass);
+            if let Err(e) = self.scene_pipelines.warm_up(&self.reloaded_shaders) {
+                error!("Failed to warm up the scene pipelines: {}", e);
+            }
+            self.rebuild_graphics_pipeline();
+            self.point_pipeline = point_cloud::create_pipeline(&self.device, &self.render_pass)?;
+            self.instanced_pipeline = instancing::create_pipeline(&self.device, &self.render_pass)?;
+            self.terrain_pipeline = terrain::create_pipeline(&self.device, &self.render_pass)?;
+            self.grass_pipeline = vegetation::create_pipeline(&self.device, &self.render_pass)?;
+            self.particle_pipeline = compute::create_pipeline(&self.device, &self.render_pass)?;
+            self.line_pipeline = debug_draw::create_pipeline(&self.device, &self.render_pass)?;
+            self.sky_pipeline = sky::create_pipeline(&self.device, &self.render_pass)?;
+            self.skybox_pipeline = skybox::create_pipeline(&self.device, &self.render_pass)?;
+            self.portal_pipeline = portal::create_pipeline(&self.device, &self.render_pass)?;
+        }
+        self.depth_buffer = renderer::create_depth_buffer(&self.device, scene_size);
+        self.multisampling = (samples > 1)
+            .then(|| Multisampling::new(&self.device, samples, post::INTERMEDIATE_FORMAT, &self.depth_buffer))
+            .transpose()?;
+        self.checkerboard = self.checkerboard_rendering.then(|| {
+            Checkerboard::new(&self.device, &self.render_pass, &self.depth_buffer, self.post.target.view())
+        });
+        self.deferred = self
+            .settings
+            .deferred
+            .then(|| Deferred::new(&self.device, self.post.target.view(), &self.depth_buffer))
+            .transpose()?;
+        // the scene only fills half the pixels then, the passes after the resolve see all of them
+        let (scene_target, visible_depth) = match &self.checkerboard {
+            Some(checkerboard) => (&checkerboard.scene, &checkerboard.depth),
+            None => (self.post.target.view(), &self.depth_buffer),
+        };
+        self.framebuffer = renderer::create_framebuffer(
+            &self.render_pass,
+            scene_target,
+            &self.depth_buffer,
+            self.multisampling.as_ref(),
+        );
+        self.lens_flare = LensFlare::new(&self.device, &self.swap_chain_images, self.swap_chain.format(), visible_depth)?;
+        self.god_rays = GodRays::new(&self.device, self.post.target.view(), visible_depth);
+        self.bloom = Bloom::new(&self.device, &self.post.target);
+        self.post_effects = EffectChain::new(&self.device, &self.post.target);
+        self.hiz = self
+            .graphics_queue
+            .family()
+            .supports_compute()
+            .then(|| HiZ::new(&self.graphics_queue, self.post.target.view(), visible_depth));
+        self.motion_blur = MotionBlur::new(&self.device, self.post.target.view(), visible_depth);
+        self.plot_renderer = PlotRenderer::new(&self.device, &self.swap_chain_images, self.swap_chain.format())?;
+        self.ui_renderer = UiRenderer::new(&self.device, &self.swap_chain_images, self.swap_chain.format());
+        self.decal_renderer = DecalRenderer::new(
+            &self.device,
+            &self.upload_queue,
+            &self.swap_chain_images,
+            self.swap_chain.format(),
+            visible_depth,
+        )?;
+        self.rebuild_minimap();
+        self.rebuild_portals();
+        Ok(())
+    }
+
+    fn draw_frame(&mut self) {
+        let frame_start = Instant::now();
+        let frame_interval = frame_start.duration_since(self.last_frame_start);
+        self.last_frame_start = frame_start;
+        self.frame_index += 1;
+        logging::set_frame_index(self.frame_index);
+
+        if let Some(point_cloud) = &mut self.point_cloud {
+            match point_cloud.update(&self.upload_queue) {
+                Ok(upload) => self.uploads.extend(upload),
+                Err(e) => {
+                    error!("Failed to load point cloud: {}", e);
+                    self.point_cloud = None;
+                }
+            }
+        }
+        self.decals.remove_expired();
+        self.camera.update(&self.input, frame_interval);
+        if let Some(world) = &mut self.world {
+            world.travel(&mut self.camera, frame_interval);
+            match world.update(&self.upload_queue, self.camera.target) {
+                Ok(upload) => self.uploads.extend(upload),
+                Err(e) => {
+                    error!("Failed to stream the world: {}", e);
+                    self.world = None;
+                }
+            }
+        }
+        self.apply_timeline(frame_interval);
+        self.follow_spline_path(frame_interval);
+        if let Some(particles) = &mut self.particles {
+            particles.advance(frame_interval.as_secs_f32());
+        }
+        self.rotation_angle += frame_interval.as_secs_f32() * self.rotation_speed;
+        self.audio_bands = self.audio.as_mut().map_or([0.0; audio::BANDS], AudioInput::update);
+
+        let previous_frame = match self.frames_in_flight.begin_frame() {
+            Ok(previous_frame) => previous_frame,
+            Err(e) => {
+                error!("Failed to wait for an earlier frame: {:?}", e);
+                self.frames_in_flight.end_frame(None);
+                return;
+            }
+        };
+        // the frame that used this slot before has finished, its GPU times can be read
+        let slot = self.frames_in_flight.current();
+        let gpu_times = self.gpu_timer.as_mut().and_then(|timer| timer.read(slot));
+        if let Some(queries) = &mut self.draw_queries {
+            queries.read(slot);
+        }
+        if let Some(gpu_times) = gpu_times {
+            self.frame_stats.push_gpu(gpu_times);
+            self.frame_snapshot.record_gpu(gpu_times);
+        }
+        if let Some(frame_log) = &mut self.frame_log {
+            if let Err(e) = frame_log.complete(slot, gpu_times) {
+                error!("Failed to write the frame log, closing it: {}", e);
+                self.frame_log = None;
+            }
+        }
+        if self.pending_luminance.as_ref().is_some_and(|(pending, _)| *pending == slot) {
+            if let Some((_, luminance)) = self.pending_luminance.take() {
+                match luminance.report() {
+                    Ok(report) => println!("luminance: {}", report),
+                    Err(e) => println!("console: {}", e),
+                }
+            }
+        }
+
+        if !self.recreate_swap_chain() {
+            self.frames_in_flight.end_frame(None);
+            return;
+        }
+
+        let (image_index, _, acquire_future) = match acquire_next_image(self.swap_chain.clone(), None) {
+            Ok(result) => result,
+
+            Err(vulkano::swapchain::AcquireError::OutOfDate) => {
+                self.recreate_swap_chain = true;
+                self.frames_in_flight.end_frame(None);
+                return;
+            }
+
+            Err(e) => panic!("{:?}", e)
+
+        };
+        let mut capture = self.take_capture_request();
+        let mut luminance = self.luminance_request.take().map(LuminanceReduction::new);
+        let motion_draws = if self.motion_blur_settings.enabled { self.motion_draws() } else { Vec::new() };
+        let ui_meshes = self.run_debug_ui();
+        let mut counters = FrameCounters::default();
+        let command_buffer =
+            self.create_command_buffer(image_index, &mut capture, &mut luminance, &motion_draws, &ui_meshes, &mut counters);
+
+        // skipped frames keep the uploads for the next one, which may draw what they fill
+        let previous_frame = self
+            .uploads
+            .drain(..)
+            .fold(previous_frame, |previous, upload| Box::new(previous.join(upload)));
+        let future = previous_frame
+            .join(acquire_future)
+            .then_execute(self.graphics_queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(self.presentation_queue.clone(), self.swap_chain.clone(), image_index);
+        let future = (Box::new(future) as Box<dyn GpuFuture + Send + Sync>).then_signal_fence_and_flush();
+
+
+        match future {
+            Ok(future) => {
+                if let Some(capture) = capture {
+                    // only capture frames wait for the GPU, the copy has to be done before reading it.
+                    // A failed capture is dropped, the turntable tries the same frame again.
+                    match future.wait(None) {
+                        Ok(()) => {
+                            capture.save();
+                            self.advance_turntable();
+                        }
+                        Err(e) => error!("Failed to capture the frame, dropping it: {:?}", e),
+                    }
+                }
+                if let Some(luminance) = luminance {
+                    self.pending_luminance = Some((slot, luminance));
+                }
+
+                self.frames_in_flight.end_frame(Some(Arc::new(future)));
+                if let Some(timer) = &mut self.gpu_timer {
+                    timer.submitted(slot);
+                }
+                if let Some(queries) = &mut self.draw_queries {
+                    queries.submitted(slot, counters.queried_draws);
+                }
+                let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+                if let Some(checkerboard) = &mut self.checkerboard {
+                    checkerboard.end_frame(view_projection);
+                }
+                self.motion_history.end_frame(view_projection, &motion_draws);
+                self.frames_rendered += 1;
+                let cpu_time = frame_start.elapsed();
+                if let Some(plot) = &mut self.plot {
+                    plot.push(&[frame_interval.as_secs_f32() * 1000.0, cpu_time.as_secs_f32() * 1000.0]);
+                }
+                self.frame_stats.push(frame_interval, cpu_time);
+                if let Some(frame_log) = &mut self.frame_log {
+                    frame_log.record(
+                        slot,
+                        FrameRow {
+                            frame: self.frame_index,
+                            interval: frame_interval,
+                            cpu_time,
+                            counters,
+                        },
+                    );
+                }
+                self.report_stats();
+                self.frame_snapshot.record(
+                    self.frame_index,
+                    image_index,
+                    frame_start.elapsed().as_micros() as u64,
+                );
+            }
+            Err(sync::FlushError::OutOfDate) => {
+                self.recreate_swap_chain = true;
+                self.frames_in_flight.end_frame(None);
+            }
+            Err(e) => {
+                error!("Failed to flush frame: {:?}", e);
+                self.frames_in_flight.end_frame(None);
+            }
+        }
+    }
+
+    /// Path of the frame to save this frame, from a screenshot request or the running turntable
+    fn take_capture_request(&mut self) -> Option<Capture> {
+        let path = match self.screenshot.take() {
+            Some(path) => path,
+            None => self.turntable.as_ref()?.frame_path(),
+        };
+
+        match Capture::new(&self.device, self.swap_chain.dimensions(), self.swap_chain.format(), path) {
+            Ok(capture) => Some(capture),
+            Err(e) => {
+                error!("Can't capture the frame: {}", e);
+                self.turntable = None;
+                None
+            }
+        }
+    }
+
+    fn advance_turntable(&mut self) {
+        if let Some(turntable) = &mut self.turntable {
+            if !turntable.advance() {
+                info!("Turntable finished, {} frames in {}", turntable.frames, turntable.output.display());
+                self.turntable = None;
+            }
+        }
+    }
+
+    fn create_surface(instance: &Arc<Instance>, settings: &Settings) -> (EventLoop<()>, Arc<Surface<Window>>) {
+        let event_loop = EventLoop::new();
+        let [width, height] = settings.window_size;
+        let monitor = fullscreen::pick_monitor(
+            event_loop.available_monitors(),
+            event_loop.primary_monitor(),
+            settings.monitor.as_deref(),
+        );
+        let surface = WindowBuilder::new()
+            .with_title(WINDOW_TITLE)
+            .with_inner_size(LogicalSize::new(width, height))
+            .with_fullscreen(fullscreen::fullscreen(settings.fullscreen, monitor))
+            .build_vk_surface(&event_loop, instance.clone())
+            .unwrap();
+
+        (event_loop, surface)
+    }
+
+    /// `extensions` are the ones presenting to a window needs, or none to render without one
+    pub(crate) fn create_vk_instance(enable_validation: bool, extensions: InstanceExtensions) -> Arc<Instance> {
+        let supported_extensions =
+            InstanceExtensions::supported_by_core().expect("Failed to get supported extensions");
+
+        info!("Supported extensions: {:?}", supported_extensions);
+
+        let app_info = app_info_from_cargo_toml!();
+        let required_extensions = InstanceExtensions {
+            ext_debug_utils: enable_validation,
+            ..extensions
+        };
+
+        let layers = if enable_validation { validation::pick_layers() } else { &[] };
+        Instance::new(Some(&app_info), Version::V1_1, &required_extensions, layers.iter().cloned())
+            .expect("failed to create Vulkan instance")
+    }
+
+    /// Routes validation messages to the log while the returned callback lives, `None` when
+    /// the settings turn validation off
+    pub(crate) fn create_debug_callback(
+        instance: &Arc<Instance>,
+        validation_errors: &Arc<AtomicUsize>,
+        settings: &Settings,
+    ) -> Option<DebugCallback> {
+        if !settings.validation {
+            return None;
+        }
+        validation::create_callback(instance, settings.validation_messages, validation_errors)
+    }
+
+    /// Size of the scene pass's targets, the swap chain's scaled by the render scale
+    fn scene_size(&self) -> [u32; 2] {
+        renderer::scene_size(self.swap_chain.dimensions(), self.settings.render_scale)
+    }
+
+    fn view_matrix(&self) -> Matrix4<f32> {
+        match &self.turntable {
+            Some(turntable) => turntable.view_matrix(),
+            None => self.camera.view_matrix(),
+        }
+    }
+
+    /// Whether the forward scene pass draws the world's chunks with the terrain pipeline
+    fn splatted_terrain(&self) -> bool {
+        self.world.as_ref().is_some_and(|world| world.splatting)
+    }
+
+    /// The main camera's view and projection matrices
+    fn camera_matrices(&self) -> (Matrix4<f32>, Matrix4<f32>) {
+        (self.view_matrix(), self.projection.matrix(self.swap_chain.dimensions()))
+    }
+
+    /// Matrices, material and, for the `lit` view, the light clusters for one draw of `pipeline`,
+    /// the graphics pipeline, the G-buffer one or the minimap's, seen with `view` and `projection`
+    fn descriptor_sets(
+        &self,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        (view, projection): (Matrix4<f32>, Matrix4<f32>),
+        model: Matrix4<f32>,
+        material: &Material,
+        light_set: Option<&Arc<dyn DescriptorSet + Send + Sync>>,
+    ) -> Vec<Arc<dyn DescriptorSet + Send + Sync>> {
+        let uniforms = UniformBufferObject::new(
+            model,
+            view,
+            projection,
+            self.audio_bands,
+            &self.light,
+            material.emission,
+            self.frame_index,
+        );
+        let uniform_set = self.uniform_buffers.descriptor_set(pipeline, uniforms);
+
+        let texture_set =
+            material.texture.descriptor_set(&material.emissive_map, &self.descriptors, pipeline, 1, self.mipmaps);
+        match texture_set {
+            Some(texture_set) => {
+                let mut sets = vec![uniform_set, texture_set];
+                sets.extend(light_set.cloned());
+                sets
+            }
+            None => vec![uniform_set],
+        }
+    }
+
+    /// `clip_plane` is in world space and replaces the section plane, cutting without a cap
+    fn push_constants(
+        &self,
+        model: Matrix4<f32>,
+        animation: Animation,
+        clip_plane: Option<Vector4<f32>>,
+    ) -> vertex_shader::ty::PushConstants {
+        let (clip_plane, section) = match clip_plane {
+            Some(plane) => ((model.transpose() * plane).into(), 1),
+            None => (
+                self.section.map_or([0.0; 4], |section| section.object_space_plane(model)),
+                Section::mode(self.section.as_ref()),
+            ),
+        };
+        vertex_shader::ty::PushConstants {
+            clip_plane,
+            depth_range: [self.projection.near, self.projection.far],
+            time: self.start_time.elapsed().as_secs_f32(),
+            animation: animation as u32,
+            frame: self.frame_index as u32,
+            section,
+            interpolation: self.particles.as_ref().map_or(0.0, ParticleSystem::interpolation),
+        }
+    }
+
+    /// Draws `lines` in the scene pass, which has to be recording
+    fn draw_lines<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        lines: &DebugLines,
+        view_projection: Matrix4<f32>,
+    ) {
+        // vertices that change with every click or window size, not worth a device local buffer
+        let line_buffer = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::vertex_buffer(),
+            false,
+            lines.vertices().iter().cloned(),
+        )
+        .unwrap();
+
+        builder
+            .draw(
+                self.line_pipeline.clone(),
+                &renderer::dynamic_state(self.scene_size()),
+                vec![line_buffer],
+                (),
+                debug_draw::vertex_shader::ty::PushConstants {
+                    view_projection: view_projection.into(),
+                },
+                vec![],
+            )
+            .unwrap();
+    }
+
+    /// Model matrix of the demo scene or loaded mesh this frame, and how it is animated
+    fn scene_model(&self) -> (Matrix4<f32>, Animation) {
+        let scene = self.scene.scene();
+        let (model, animation) = match self.imported_model {
+            Some(model) => (model, Animation::None),
+            None => (scene.model(), scene.animation),
+        };
+        let model = match animation {
+            Animation::Rotate => model * uniforms::rotation(self.rotation_angle),
+            _ => model,
+        };
+        (model, animation)
+    }
+
+    /// The meshes drawn this frame, for the velocity buffer. Patterns, lines and point clouds only
+    /// get the camera's motion.
+    fn motion_draws(&self) -> Vec<MotionDraw> {
+        self.render_scene()
+            .iter()
+            .map(|renderable| MotionDraw {
+                id: renderable.id,
+                model: renderable.transform,
+                vertex_buffer: renderable.mesh.vertex_buffer.clone(),
+                index_buffer: renderable.mesh.index_buffer.clone(),
+            })
+            .collect()
+    }
+
+    /// What the scene pipeline draws this frame: the demo scene or loaded mesh, unless the demo
+    /// scene is a pattern, then the spawned objects and the streamed world's chunks
+    fn render_scene(&self) -> Scene {
+        let mut scene = Scene::default();
+        if self.imported_model.is_some() || self.scene.scene().pattern.is_none() {
+            let (model, animation) = self.scene_model();
+            scene.add(Renderable {
+                id: DrawId::Scene,
+                mesh: self.scene_mesh.clone(),
+                transform: model,
+                material: Material {
+                    texture: self.texture.clone().unwrap_or_else(|| self.white_texture.clone()),
+                    emission: self.emission,
+                    emissive_map: self.emissive_map.clone().unwrap_or_else(|| self.white_texture.clone()),
+                    animation,
+                },
+            });
+        }
+        for object in self.objects.iter() {
+            scene.add(Renderable {
+                id: DrawId::Object(object.id),
+                mesh: self.object_mesh.clone(),
+                transform: object.model(),
+                material: Material {
+                    texture: self.white_texture.clone(),
+                    emission: Emission::default(),
+                    emissive_map: self.white_texture.clone(),
+                    animation: Animation::None,
+                },
+            });
+        }
+        for (coord, mesh) in self.world.iter().flat_map(World::chunks) {
+            scene.add(Renderable {
+                id: DrawId::Chunk(coord),
+                mesh: mesh.clone(),
+                transform: Matrix4::identity(),
+                material: Material {
+                    texture: self.white_texture.clone(),
+                    emission: Emission::default(),
+                    emissive_map: self.white_texture.clone(),
+                    animation: Animation::None,
+                },
+            });
+        }
+        scene
+    }
+
+    /// Records what the scene pass draws before the renderables: the checkerboard mask, the sky
+    /// and the demo scene's pattern
+    fn record_scene_background<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        dynamic_state: &DynamicState,
+        counters: &mut FrameCounters,
+    ) {
+        let scene = self.scene.scene();
+        if let Some(checkerboard) = &self.checkerboard {
+            checkerboard.draw_mask(builder);
+        }
+
+        if self.sky.enabled {
+            let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+            builder
+                .draw(
+                    self.sky_pipeline.clone(),
+                    dynamic_state,
+                    BufferlessVertices { vertices: 3, instances: 1 },
+                    (),
+                    self.sky.push_constants(view_projection),
+                    vec![],
+                )
+                .unwrap();
+            counters.draw(1);
+        }
+
+        // a loaded mesh replaces the demo scene, pattern or not
+        let pattern = scene.pattern.filter(|_| self.imported_model.is_none());
+        if let Some(draw_pattern) = pattern {
+            let mut pattern_lines = DebugLines::default();
+            draw_pattern(&mut pattern_lines, self.scene_size());
+            // drawn in normalized device coordinates, without the camera
+            self.draw_lines(builder, &pattern_lines, Matrix4::identity());
+            counters.draw(0);
+        }
+    }
+
+    /// The renderables of this frame as draws of `pipeline`, seen with the `camera` view and
+    /// projection matrices. Spawned objects inside its frustum are counted, those outside it and
+    /// world chunks outside it are left out while culling. `clip_plane` cuts away what is behind
+    /// it in place of the section plane. The chunks are left out altogether with `splatted_terrain`,
+    /// the terrain pipeline draws them among the overlays.
+    fn scene_draws(
+        &self,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        camera: (Matrix4<f32>, Matrix4<f32>),
+        clip_plane: Option<Vector4<f32>>,
+        counters: &mut FrameCounters,
+        light_set: Option<&Arc<dyn DescriptorSet + Send + Sync>>,
+        splatted_terrain: bool,
+    ) -> Vec<SceneDraw> {
+        let frustum = Frustum::from_matrix(camera.1 * camera.0);
+        let object_corners = vertex::vertecies();
+        self.render_scene()
+            .iter()
+            .filter_map(|renderable| {
+                match renderable.id {
+                    DrawId::Object(_) => {
+                        let corners = object_corners.iter().map(|vertex| Point3::from(vertex.pos()));
+                        let bounds = Aabb::from_transformed_points(corners, renderable.transform);
+                        if bounds.is_some_and(|bounds| frustum.intersects_aabb(&bounds)) {
+                            counters.visible_objects += 1;
+                        } else if self.culling.enabled {
+                            return None;
+                        }
+                    }
+                    DrawId::Chunk(_) if splatted_terrain => return None,
+                    DrawId::Chunk(coord) => {
+                        let bounds = self.world.as_ref().and_then(|world| world.bounds(coord));
+                        if self.culling.enabled && !bounds.is_some_and(|bounds| frustum.intersects_aabb(&bounds)) {
+                            return None;
+                        }
+                    }
+                    DrawId::Scene => {}
+                }
+                counters.draw(renderable.mesh.triangles());
+                Some(SceneDraw {
+                    vertex_buffer: renderable.mesh.vertex_buffer.clone(),
+                    index_buffer: renderable.mesh.index_buffer.clone(),
+                    sets: self.descriptor_sets(pipeline, camera, renderable.transform, &renderable.material, light_set),
+                    push_constants: self.push_constants(
+                        renderable.transform,
+                        renderable.material.animation,
+                        clip_plane,
+                    ),
+                })
+            })
+            .collect()
+    }
+
+    /// Records what the scene pass draws after the renderables: the splatted terrain, the world's
+    /// grass, the skybox, instanced quads, particles, the point cloud and debug lines
+    fn record_scene_overlays<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        dynamic_state: &DynamicState,
+        counters: &mut FrameCounters,
+    ) {
+        if let Some(portals) = &self.portals {
+            let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+            portals.draw_surface(builder, &self.portal_pipeline, dynamic_state, &self.descriptors, 0, view_projection);
+            counters.draw(2);
+        }
+
+        if let Some(world) = self.world.as_ref().filter(|_| self.splatted_terrain()) {
+            let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+            let frustum = Frustum::from_matrix(view_projection);
+            self.terrain.draw(
+                builder,
+                &self.terrain_pipeline,
+                dynamic_state,
+                &self.descriptors,
+                world,
+                self.culling.enabled.then_some(&frustum),
+                view_projection,
+                self.sky.sun_direction(),
+                counters,
+            );
+        }
+
+        if let Some(world) = self.world.as_ref().filter(|world| world.grass) {
+            let (view, projection) = self.camera_matrices();
+            let frustum = Frustum::from_matrix(projection * view);
+            self.vegetation.draw(
+                builder,
+                &self.grass_pipeline,
+                dynamic_state,
+                world,
+                self.culling.enabled.then_some(&frustum),
+                view,
+                projection,
+                self.sky.sun_direction(),
+                self.start_time.elapsed().as_secs_f32(),
+                counters,
+            );
+        }
+
+        // before anything blended, which would be painted over where it doesn't write depth
+        if let Some(skybox) = &self.skybox {
+            skybox.draw(
+                builder,
+                &self.skybox_pipeline,
+                dynamic_state,
+                &self.descriptors,
+                self.view_matrix(),
+                self.projection.matrix(self.swap_chain.dimensions()),
+            );
+            counters.draw(12);
+        }
+
+        if let Some(quads) = &self.instanced_quads {
+            quads.draw(
+                builder,
+                &self.instanced_pipeline,
+                dynamic_state,
+                &self.object_mesh.vertex_buffer,
+                &self.object_mesh.index_buffer,
+                self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix(),
+                self.start_time.elapsed().as_secs_f32(),
+            );
+            counters.draw(self.object_mesh.triangles() * quads.count as u64);
+        }
+
+        if let Some(particles) = &self.particles {
+            particles.draw(
+                builder,
+                &self.particle_pipeline,
+                dynamic_state,
+                self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix(),
+                point_cloud::max_point_size(&self.device),
+            );
+            counters.draw(0);
+        }
+
+        if let Some(point_cloud) = &self.point_cloud {
+            let projection = self.projection.matrix(self.swap_chain.dimensions());
+            let mvp = projection * self.view_matrix() * point_cloud.model;
+            let frustum = Frustum::from_matrix(mvp);
+            let push_constants = point_cloud::vertex_shader::ty::PushConstants {
+                mvp: mvp.into(),
+                point_size: point_cloud.point_size,
+                max_point_size: point_cloud::max_point_size(&self.device),
+            };
+
+            for chunk in point_cloud.visible_chunks(&frustum) {
+                builder
+                    .draw(
+                        self.point_pipeline.clone(),
+                        dynamic_state,
+                        vec![chunk],
+                        (),
+                        push_constants,
+                        vec![],
+                    )
+                    .unwrap();
+                counters.draw(0);
+            }
+        }
+
+        let mut debug_lines = DebugLines::default();
+        if let Some(measurement) = &self.measurement {
+            // the columns of the inverse view matrix are the camera axes in world space
+            let camera = self.view_matrix().invert().unwrap();
+            measurement.draw(&mut debug_lines, camera.x.truncate(), camera.y.truncate());
+        }
+        if let Some(path) = &self.spline_path {
+            path.draw(&mut debug_lines);
+        }
+
+        if !debug_lines.is_empty() {
+            let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+            self.draw_lines(builder, &debug_lines, view_projection);
+            counters.draw(0);
+        }
+    }
+
+    /// Records the draws for one frame; recorded every frame because the uniforms, push constants
+    /// and the list of spawned objects change. A capture gets a copy of the finished image, or is dropped
+    /// if the swap chain can't be copied from. A luminance reduction is recorded after the post pass.
+    fn create_command_buffer(
+        &self,
+        image_index: usize,
+        capture: &mut Option<Capture>,
+        luminance: &mut Option<LuminanceReduction>,
+        motion_draws: &[MotionDraw],
+        ui_meshes: &[ClippedMesh],
+        counters: &mut FrameCounters,
+    ) -> Arc<PrimaryAutoCommandBuffer> {
+        let clear_color = self.settings.clear_color;
+        let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
+            self.device.clone(),
+            self.graphics_queue.family(),
+            vulkano::command_buffer::CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+
+        let mut clear_values = vec![[clear_color[0], clear_color[1], clear_color[2], 1.0].into(), 1.0.into()];
+        if self.multisampling.is_some() {
+            // the resolve target is overwritten
+            clear_values.push(ClearValue::None);
+        }
+
+        let timer = self.gpu_timer.as_ref();
+        let slot = self.frames_in_flight.current();
+        if let Some(timer) = timer {
+            timer.mark(&mut command_buffer_builder, slot, Mark::Start);
+        }
+        // dispatches can't be recorded inside the render pass that draws the particles
+        if let Some(particles) = &self.particles {
+            particles.update(&mut command_buffer_builder);
+        }
+        // innermost first, each view shows the one before it through the surface
+        if let Some(portals) = &self.portals {
+            let (view, projection) = self.camera_matrices();
+            for level in (0..portals.depth()).rev() {
+                let camera = (portals.level_view(view, level), projection);
+                let clip_plane = Some(portals.clip_plane());
+                let draws =
+                    self.scene_draws(portals.pipeline(), camera, clip_plane, &mut FrameCounters::default(), None, false);
+                portals.draw_level(
+                    &mut command_buffer_builder,
+                    level,
+                    &draws,
+                    clear_color,
+                    &self.descriptors,
+                    view,
+                    projection,
+                );
+            }
+        }
+        if let Some(deferred) = &self.deferred {
+            // only the meshes, without the background and overlays of the scene pass
+            let double_sided = self.section.is_some();
+            let scene_draws =
+                self.scene_draws(deferred.pipeline(double_sided), self.camera_matrices(), None, counters, None, false);
+            deferred.draw(
+                &mut command_buffer_builder,
+                &scene_draws,
+                double_sided,
+                clear_color,
+                self.projection.matrix(self.swap_chain.dimensions()),
+                self.view_matrix(),
+                &self.light,
+            );
+        } else {
+            let light_set = self.clustered_lights.record(
+                &mut command_buffer_builder,
+                &self.graphics_pipeline,
+                self.projection.matrix(self.swap_chain.dimensions()),
+                self.view_matrix(),
+                self.scene_size(),
+                [self.projection.near, self.projection.far],
+                self.start_time.elapsed().as_secs_f32(),
+            );
+
+            // draws recorded into secondary command buffers aren't queried
+            let queries = self.draw_queries.as_ref().filter(|_| self.recording_threads.is_none());
+            if let Some(queries) = queries {
+                queries.reset(&mut command_buffer_builder, slot);
+            }
+
+            let contents = match self.recording_threads {
+                Some(_) => SubpassContents::SecondaryCommandBuffers,
+                None => SubpassContents::Inline,
+            };
+            command_buffer_builder
+                .begin_render_pass(self.framebuffer.clone(), contents, clear_values)
+                .unwrap();
+            let dynamic_state = renderer::dynamic_state(self.scene_size());
+            let scene_draws = self.scene_draws(
+                &self.graphics_pipeline,
+                self.camera_matrices(),
+                None,
+                counters,
+                light_set.as_ref(),
+                self.splatted_terrain(),
+            );
+            match self.recording_threads {
+                None => {
+                    self.record_scene_background(&mut command_buffer_builder, &dynamic_state, counters);
+                    for (index, draw) in scene_draws.iter().enumerate() {
+                        let query = queries.filter(|_| index < queries::MAX_QUERIED_DRAWS as usize);
+                        if let Some(queries) = query {
+                            queries.begin(&mut command_buffer_builder, slot, index as u32);
+                        }
+                        draw.record(&mut command_buffer_builder, &self.graphics_pipeline, &dynamic_state);
+                        if let Some(queries) = query {
+                            queries.end(&mut command_buffer_builder, slot, index as u32);
+                            counters.queried_draws += 1;
+                        }
+                    }
+                    self.record_scene_overlays(&mut command_buffer_builder, &dynamic_state, counters);
+                }
+                Some(threads) => {
+                    // a subpass with secondary contents takes every command from secondary command
+                    // buffers, the background and overlays are recorded here while the threads record
+                    // the renderables
+                    let subpass = Subpass::from(self.render_pass.clone(), 0).unwrap();
+                    let mut background = parallel::secondary_builder(&self.graphics_queue, &subpass);
+                    self.record_scene_background(&mut background, &dynamic_state, counters);
+                    let mut overlays = parallel::secondary_builder(&self.graphics_queue, &subpass);
+                    self.record_scene_overlays(&mut overlays, &dynamic_state, counters);
+
+                    let mut secondaries = vec![background.build().unwrap()];
+                    secondaries.extend(parallel::record(
+                        &self.graphics_queue,
+                        &subpass,
+                        &self.graphics_pipeline,
+                        &dynamic_state,
+                        &scene_draws,
+                        threads,
+                    ));
+                    secondaries.push(overlays.build().unwrap());
+                    command_buffer_builder.execute_commands_from_vec(secondaries).unwrap();
+                }
+            }
+
+            command_buffer_builder.end_render_pass().unwrap();
+        }
+        let minimap = self
+            .minimap
+            .as_ref()
+            .filter(|minimap| minimap.due(self.frame_index, self.minimap_settings.interval));
+        if let Some(minimap) = minimap {
+            // its draws aren't counted, the counters are the main camera's
+            let camera = minimap::camera(self.camera.target, self.minimap_settings.extent);
+            let draws = self.scene_draws(minimap.pipeline(), camera, None, &mut FrameCounters::default(), None, false);
+            minimap.draw(&mut command_buffer_builder, &draws, clear_color);
+        }
+        if let Some(timer) = timer {
+            timer.mark(&mut command_buffer_builder, slot, Mark::SceneDone);
+        }
+
+        if let Some(multisampling) = &self.multisampling {
+            multisampling.resolve_depth(&mut command_buffer_builder);
+        }
+        if let Some(checkerboard) = &self.checkerboard {
+            let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+            checkerboard.resolve(&mut command_buffer_builder, view_projection);
+        }
+        if let Some(hiz) = &self.hiz {
+            hiz.record(&mut command_buffer_builder);
+        }
+        if self.sky.enabled {
+            let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+            self.god_rays.draw(
+                &mut command_buffer_builder,
+                self.god_ray_settings,
+                view_projection,
+                self.sky.sun_direction(),
+            );
+        }
+        if self.motion_blur_settings.enabled {
+            let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+            self.motion_blur.draw(
+                &mut command_buffer_builder,
+                self.motion_blur_settings,
+                &self.motion_history,
+                view_projection,
+                motion_draws,
+            );
+        }
+        self.bloom.draw(&mut command_buffer_builder, self.bloom_settings);
+        self.post_effects.draw(&mut command_buffer_builder, &self.post.target, &self.effects);
+        if let (Some(hiz), Some(level)) = (&self.hiz, self.hiz_view) {
+            hiz.draw_debug(&mut command_buffer_builder, level, [self.projection.near, self.projection.far]);
+        }
+        if let Some(timer) = timer {
+            timer.mark(&mut command_buffer_builder, slot, Mark::EffectsDone);
+        }
+
+        let calibration = Calibration {
+            gamma: self.settings.gamma,
+            brightness: self.settings.brightness,
+            contrast: self.settings.contrast,
+        };
+        match (&self.color_grade, self.grade) {
+            (Some(color_grade), Some(grade)) => color_grade.draw(
+                &mut command_buffer_builder,
+                image_index,
+                &self.post,
+                calibration,
+                self.tint,
+                self.dithering,
+                grade,
+            ),
+            _ => self.post.draw(
+                &mut command_buffer_builder,
+                image_index,
+                calibration,
+                self.tint,
+                self.dithering,
+            ),
+        }
+        if let Some(timer) = timer {
+            timer.mark(&mut command_buffer_builder, slot, Mark::PostDone);
+        }
+        if let Some(luminance) = luminance {
+            luminance.record(&self.device, &mut command_buffer_builder, self.post.target.view());
+        }
+
+        let view_projection = self.projection.matrix(self.swap_chain.dimensions()) * self.view_matrix();
+        self.decal_renderer
+            .draw(&mut command_buffer_builder, image_index, view_projection, &self.decals);
+
+        if self.sky.enabled {
+            self.lens_flare.draw(
+                &mut command_buffer_builder,
+                image_index,
+                view_projection,
+                self.sky.sun_direction(),
+            );
+        }
+
+        if let Some(minimap) = &self.minimap {
+            minimap.composite(&mut command_buffer_builder, image_index, self.settings.gamma);
+        }
+
+        if let Some(plot) = &self.plot {
+            let extent = self.swap_chain.dimensions();
+            let mut plot_lines = DebugLines::default();
+            plot.draw(&mut plot_lines, extent[0] as f32 / extent[1].max(1) as f32);
+            self.plot_renderer.draw(&mut command_buffer_builder, image_index, &plot_lines);
+        }
+
+        if let Some(frame) = capture.as_ref() {
+            let image = self.swap_chain_images[image_index].clone();
+            if let Err(e) = frame.record(&mut command_buffer_builder, image) {
+                error!("Can't capture the frame: {}", e);
+                *capture = None;
+            }
+        }
+
+        // after the capture, screenshots show the scene without the panel
+        self.ui_renderer
+            .draw(&mut command_buffer_builder, image_index, ui_meshes, self.debug_ui.pixels_per_point());
+        if let Some(timer) = timer {
+            timer.mark(&mut command_buffer_builder, slot, Mark::End);
+        }
+
+        let command_buffer = command_buffer_builder.build().unwrap();
+        debug_utils::set_object_name(
+            &self.device,
+            command_buffer.inner(),
+            &format!("frame {} command buffer", self.frame_index),
+        );
+
+        Arc::new(command_buffer)
+    }
+}
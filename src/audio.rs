@@ -0,0 +1,224 @@
+//! Audio file analysis for shaders that react to music. A WAV file is followed along the wall
+//! clock, looping, and the spectrum around the current position is split into bands.
+
+use std::{
+    convert::TryInto,
+    f32::consts::PI,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// Number of frequency bands, the scene's uniform buffer holds them as two vec4s
+pub const BANDS: usize = 8;
+/// Samples per spectrum, a power of two around 20 ms at common sample rates
+const FFT_SIZE: usize = 1024;
+/// Lowest frequency the first band starts at, in Hz
+const LOWEST_FREQUENCY: f32 = 40.0;
+/// Levels from this many decibels below full scale up to full scale map to 0 to 1
+const DYNAMIC_RANGE: f32 = 60.0;
+/// Share of a band's level lost per second once the sound gets quieter, so the bands don't flicker
+const DECAY: f32 = 4.0;
+
+/// Samples of a WAV file mixed down to mono, and the band levels at the playback position
+pub struct AudioInput {
+    pub path: PathBuf,
+    samples: Vec<f32>,
+    sample_rate: u32,
+    start: Instant,
+    last_update: Instant,
+    bands: [f32; BANDS],
+}
+
+impl AudioInput {
+    /// Reads 8, 16, 24 or 32 bit PCM or 32 bit float WAV files
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("failed to read file: {}", e))?;
+        let (samples, sample_rate) = parse_wav(&data)?;
+        if samples.len() < FFT_SIZE {
+            return Err("the file is too short to analyse".to_string());
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            samples,
+            sample_rate,
+            start: Instant::now(),
+            last_update: Instant::now(),
+            bands: [0.0; BANDS],
+        })
+    }
+
+    /// Analyses the samples at the current position and returns the band levels, lowest
+    /// frequencies first, from 0 for silence to 1 for full scale
+    pub fn update(&mut self) -> [f32; BANDS] {
+        let elapsed = self.last_update.elapsed().as_secs_f32();
+        self.last_update = Instant::now();
+
+        let spectrum = spectrum(&self.window());
+        let levels = band_levels(&spectrum, self.sample_rate);
+        let decay = (-DECAY * elapsed).exp();
+        for (band, level) in self.bands.iter_mut().zip(levels.iter()) {
+            *band = level.max(*band * decay);
+        }
+        self.bands
+    }
+
+    /// Playback position in samples, the file loops
+    fn position(&self) -> usize {
+        (self.start.elapsed().as_secs_f64() * self.sample_rate as f64) as usize % self.samples.len()
+    }
+
+    /// The `FFT_SIZE` samples up to the playback position, wrapping around at the start
+    fn window(&self) -> Vec<f32> {
+        let end = self.position() + self.samples.len();
+        (end - FFT_SIZE..end)
+            .map(|i| self.samples[i % self.samples.len()])
+            .collect()
+    }
+
+    fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+}
+
+impl fmt::Display for AudioInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {:.1} of {:.1} s, {} Hz, bands",
+            self.path.display(),
+            self.position() as f32 / self.sample_rate as f32,
+            self.duration(),
+            self.sample_rate
+        )?;
+        for band in self.bands.iter() {
+            write!(f, " {:.2}", band)?;
+        }
+        Ok(())
+    }
+}
+
+/// The mono samples in -1 to 1 and the sample rate
+fn parse_wav(data: &[u8]) -> Result<(Vec<f32>, u32), String> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("not a WAV file".to_string());
+    }
+
+    let u16_at = |bytes: &[u8], i: usize| u16::from_le_bytes(bytes[i..i + 2].try_into().unwrap());
+    let u32_at = |bytes: &[u8], i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+
+    let mut format = None;
+    let mut samples = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = u32_at(data, offset + 4) as usize;
+        let body = &data[offset + 8..(offset + 8 + size).min(data.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let mut encoding = u16_at(body, 0);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real encoding at the start of the sub format
+                if encoding == 0xfffe && body.len() >= 26 {
+                    encoding = u16_at(body, 24);
+                }
+                format = Some((encoding, u16_at(body, 2), u32_at(body, 4), u16_at(body, 14)));
+            }
+            b"data" => samples = Some(body),
+            _ => (),
+        }
+        // chunks are padded to an even size
+        offset += 8 + size + size % 2;
+    }
+
+    let (encoding, channels, sample_rate, bits) = format.ok_or("the fmt chunk is missing")?;
+    let samples = samples.ok_or("the data chunk is missing")?;
+    if channels == 0 || sample_rate == 0 {
+        return Err("the fmt chunk has no channels or no sample rate".to_string());
+    }
+
+    let decode: fn(&[u8]) -> f32 = match (encoding, bits) {
+        (1, 8) => |bytes| (bytes[0] as f32 - 128.0) / 128.0,
+        (1, 16) => |bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+        (1, 24) => |bytes| i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2_147_483_648.0,
+        (1, 32) => |bytes| i32::from_le_bytes(bytes.try_into().unwrap()) as f32 / 2_147_483_648.0,
+        (3, 32) => |bytes| f32::from_le_bytes(bytes.try_into().unwrap()),
+        (encoding, bits) => return Err(format!("unsupported encoding {} with {} bits per sample", encoding, bits)),
+    };
+
+    let sample_size = bits as usize / 8;
+    let frame_size = sample_size * channels as usize;
+    let mono = samples
+        .chunks_exact(frame_size)
+        .map(|frame| frame.chunks_exact(sample_size).map(decode).sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, sample_rate))
+}
+
+/// Magnitudes of the first half of the spectrum of `samples`, whose length is a power of two
+fn spectrum(samples: &[f32]) -> Vec<f32> {
+    let n = samples.len();
+    // a Hann window keeps the loud low frequencies from leaking into every band
+    let mut re: Vec<_> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| sample * (0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos()))
+        .collect();
+    let mut im = vec![0.0; n];
+    fft(&mut re, &mut im);
+
+    re.iter()
+        .zip(im.iter())
+        .take(n / 2)
+        .map(|(re, im)| (re * re + im * im).sqrt() * 4.0 / n as f32)
+        .collect()
+}
+
+/// In place iterative radix-2 FFT
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let angle = -2.0 * PI / length as f32;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + length / 2);
+                let odd_re = re[b] * cos - im[b] * sin;
+                let odd_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - odd_re;
+                im[b] = im[a] - odd_im;
+                re[a] += odd_re;
+                im[a] += odd_im;
+            }
+        }
+        length *= 2;
+    }
+}
+
+/// The loudest bin of each band, the bands are spaced evenly in octaves from `LOWEST_FREQUENCY`
+/// up to half the sample rate
+fn band_levels(spectrum: &[f32], sample_rate: u32) -> [f32; BANDS] {
+    let nyquist = sample_rate as f32 / 2.0;
+    let bin_width = nyquist / spectrum.len() as f32;
+    let edge = |band: usize| LOWEST_FREQUENCY * (nyquist / LOWEST_FREQUENCY).powf(band as f32 / BANDS as f32);
+
+    let mut levels = [0.0; BANDS];
+    for (band, level) in levels.iter_mut().enumerate() {
+        let first = ((edge(band) / bin_width) as usize).min(spectrum.len() - 1);
+        let last = ((edge(band + 1) / bin_width) as usize).clamp(first + 1, spectrum.len());
+        let peak = spectrum[first..last].iter().copied().fold(0.0, f32::max);
+        let decibels = 20.0 * peak.max(1e-6).log10();
+        *level = (1.0 + decibels / DYNAMIC_RANGE).clamp(0.0, 1.0);
+    }
+    levels
+}
@@ -0,0 +1,278 @@
+use std::{fmt, sync::Arc};
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::DescriptorSet,
+    device::Device,
+    format::ClearValue,
+    image::ImageUsage,
+    pipeline::{
+        blend::{AttachmentBlend, BlendFactor, BlendOp},
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, Subpass},
+};
+
+use crate::{descriptors, post, render_target::RenderTarget};
+
+/// Horizontal and vertical blurs run this many times each, every pair widens the glow
+const BLUR_PASSES: usize = 2;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod bright_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/bloom_bright.frag"
+    }
+}
+
+mod blur_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/bloom_blur.frag"
+    }
+}
+
+mod composite_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/bloom_composite.frag"
+    }
+}
+
+/// Controls of the `bloom` command, kept while the passes are rebuilt with the swap chain
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    /// Brightness above which the scene glows. The scene target holds values above 1, which
+    /// only emissive materials and strong highlights reach, so 1 leaves ordinary surfaces alone.
+    pub threshold: f32,
+    /// Scales the glow added back onto the scene
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 1.0,
+            intensity: 0.8,
+        }
+    }
+}
+
+impl fmt::Display for BloomSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, threshold {:.2}, intensity {:.2}",
+            if self.enabled { "on" } else { "off" },
+            self.threshold,
+            self.intensity
+        )
+    }
+}
+
+/// Glow around the brightest parts of the scene. A bright pass at half resolution keeps what
+/// is above the threshold, separable Gaussian blurs spread it back and forth between two half
+/// resolution images, and a last pass adds it onto the scene target before the post pass
+/// calibrates it.
+pub struct Bloom {
+    bright_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    blur_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    composite_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    /// Written by the bright pass and every vertical blur, read by the composite
+    bright: RenderTarget,
+    /// Written by every horizontal blur
+    blurred: RenderTarget,
+    /// Keeps the scene and adds the glow onto it
+    composite_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    scene_set: Arc<dyn DescriptorSet + Send + Sync>,
+    bright_set: Arc<dyn DescriptorSet + Send + Sync>,
+    blurred_set: Arc<dyn DescriptorSet + Send + Sync>,
+    /// The bright image again, for the composite pipeline
+    composite_set: Arc<dyn DescriptorSet + Send + Sync>,
+    scene_inverse_size: [f32; 2],
+}
+
+impl Bloom {
+    /// Recreated with the swap chain. `scene` is the post pass target.
+    pub fn new(device: &Arc<Device>, scene: &RenderTarget) -> Self {
+        let scene_size = scene.size();
+        let bloom_size = [(scene_size[0] / 2).max(1), (scene_size[1] / 2).max(1)];
+        // the blur's offsets fall between texels, the targets' linear sampler blends each pair
+        let bright = RenderTarget::new(device, bloom_size, post::INTERMEDIATE_FORMAT, ImageUsage::none());
+        let blurred = RenderTarget::new(device, bloom_size, post::INTERMEDIATE_FORMAT, ImageUsage::none());
+
+        let composite_render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: post::INTERMEDIATE_FORMAT,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+
+        let vert_shader_module =
+            vertex_shader::Shader::load(device.clone()).expect("Failed to create bloom vertex shader module");
+        let bright_shader_module =
+            bright_shader::Shader::load(device.clone()).expect("Failed to create bloom bright pass shader module");
+        let blur_shader_module =
+            blur_shader::Shader::load(device.clone()).expect("Failed to create bloom blur shader module");
+        let composite_shader_module = composite_shader::Shader::load(device.clone())
+            .expect("Failed to create bloom composite shader module");
+
+        let additive = AttachmentBlend {
+            enabled: true,
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::One,
+            color_destination: BlendFactor::One,
+            alpha_op: BlendOp::Add,
+            alpha_source: BlendFactor::Zero,
+            alpha_destination: BlendFactor::One,
+            mask_red: true,
+            mask_green: true,
+            mask_blue: true,
+            mask_alpha: true,
+        };
+
+        let bright_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![bright.viewport()])
+                .fragment_shader(bright_shader_module.main_entry_point(), ())
+                .render_pass(bright.subpass())
+                .build(device.clone())
+                .unwrap(),
+        );
+        let blur_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![bright.viewport()])
+                .fragment_shader(blur_shader_module.main_entry_point(), ())
+                .render_pass(bright.subpass())
+                .build(device.clone())
+                .unwrap(),
+        );
+        let composite_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![scene.viewport()])
+                .fragment_shader(composite_shader_module.main_entry_point(), ())
+                .blend_collective(additive)
+                .render_pass(Subpass::from(composite_render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let composite_framebuffer = Arc::new(
+            Framebuffer::start(composite_render_pass)
+                .add(scene.view().clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        ) as Arc<dyn FramebufferAbstract + Send + Sync>;
+
+        let set = |pipeline: &Arc<GraphicsPipeline<BufferlessDefinition>>, target: &RenderTarget| {
+            let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+            descriptors::build(layout, &[target.sampled()])
+        };
+        let scene_set = set(&bright_pipeline, scene);
+        let bright_set = set(&blur_pipeline, &bright);
+        let blurred_set = set(&blur_pipeline, &blurred);
+        let composite_set = set(&composite_pipeline, &bright);
+
+        Self {
+            bright_pipeline,
+            blur_pipeline,
+            composite_pipeline,
+            bright,
+            blurred,
+            composite_framebuffer,
+            scene_set,
+            bright_set,
+            blurred_set,
+            composite_set,
+            scene_inverse_size: scene.inverse_size(),
+        }
+    }
+
+    /// Records the passes after the scene and before the post pass, nothing when `settings`
+    /// turn it off
+    pub fn draw(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, settings: BloomSettings) {
+        if !settings.enabled || settings.intensity <= 0.0 {
+            return;
+        }
+
+        let bright_push_constants = bright_shader::ty::PushConstants {
+            inverseSize: self.bright.inverse_size(),
+            threshold: settings.threshold.max(0.0),
+        };
+        self.bright
+            .draw_fullscreen(builder, &self.bright_pipeline, self.scene_set.clone(), bright_push_constants);
+
+        // horizontally from the bright image into the blurred one, then vertically back
+        let blurs = [
+            (&self.blurred, &self.bright_set, [1.0, 0.0]),
+            (&self.bright, &self.blurred_set, [0.0, 1.0]),
+        ];
+        for _ in 0..BLUR_PASSES {
+            for (target, set, direction) in &blurs {
+                let push_constants = blur_shader::ty::PushConstants {
+                    inverseSize: target.inverse_size(),
+                    direction: *direction,
+                };
+                target.draw_fullscreen(builder, &self.blur_pipeline, (*set).clone(), push_constants);
+            }
+        }
+
+        let composite_push_constants = composite_shader::ty::PushConstants {
+            inverseSize: self.scene_inverse_size,
+            intensity: settings.intensity,
+        };
+        builder
+            .begin_render_pass(
+                self.composite_framebuffer.clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap()
+            .draw(
+                self.composite_pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                self.composite_set.clone(),
+                composite_push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
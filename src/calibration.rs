@@ -0,0 +1,124 @@
+use cgmath::Point3;
+
+use crate::debug_draw::DebugLines;
+
+/// Display gammas the striped patches are matched against
+const GAMMAS: [f32; 5] = [1.8, 2.0, 2.2, 2.4, 2.6];
+const RAMP_STEPS: u32 = 16;
+/// Patches in the near black and near white rows, each a step further from black or white
+const LEVEL_PATCHES: u32 = 8;
+const LEVEL_STEP: f32 = 1.0 / 64.0;
+const CHECKER_SIZE: u32 = 16;
+/// Height of the gamma labels in pixels
+const LABEL_HEIGHT: f32 = 16.0;
+const LABEL_COLOR: [f32; 3] = [0.0, 0.0, 0.0];
+
+/// Test pattern for the `gamma`, `brightness` and `contrast` settings, in five rows filling the
+/// window: a smooth and a stepped gray ramp, stripes of black and white next to the solid gray
+/// they match at each display gamma, and checkerboards of black and white against levels just
+/// above black and just below white. With good settings the ramps are even, the stripes blend
+/// into the patch labelled with the display's gamma and every checkerboard is visible.
+pub fn draw(lines: &mut DebugLines, extent: [u32; 2]) {
+    let mut canvas = Canvas { lines, extent };
+    let [width, height] = extent;
+    let row = |index: u32| (index * height / 5, (index + 1) * height / 5);
+
+    let (top, bottom) = row(0);
+    for x in 0..width {
+        canvas.vertical(x, top, bottom, x as f32 / (width - 1).max(1) as f32);
+    }
+
+    let (top, bottom) = row(1);
+    for x in 0..width {
+        let step = x * RAMP_STEPS / width;
+        canvas.vertical(x, top, bottom, step as f32 / (RAMP_STEPS - 1) as f32);
+    }
+
+    let (top, bottom) = row(2);
+    for (i, gamma) in GAMMAS.iter().enumerate() {
+        let left = i as u32 * width / GAMMAS.len() as u32;
+        let right = (i as u32 + 1) * width / GAMMAS.len() as u32;
+        let middle = (left + right) / 2;
+
+        // alternating rows average half the light of white, whatever the display does to gray
+        for y in top..bottom {
+            canvas.horizontal(y, left, middle, (y % 2) as f32);
+        }
+        let matching_gray = 0.5f32.powf(1.0 / gamma);
+        canvas.fill(middle, top, right, bottom, matching_gray);
+        canvas.label(&format!("{:.1}", gamma), (middle + right) as f32 / 2.0, (top + bottom) as f32 / 2.0);
+    }
+
+    let (top, bottom) = row(3);
+    canvas.checkerboards(top, bottom, 0.0, LEVEL_STEP);
+
+    let (top, bottom) = row(4);
+    canvas.checkerboards(top, bottom, 1.0, -LEVEL_STEP);
+}
+
+/// Draws in pixels, top left at 0, 0. The pattern is drawn without a camera, so pixels map
+/// straight to normalized device coordinates.
+struct Canvas<'a> {
+    lines: &'a mut DebugLines,
+    extent: [u32; 2],
+}
+
+impl Canvas<'_> {
+    fn point(&self, x: f32, y: f32) -> Point3<f32> {
+        Point3::new(
+            x / self.extent[0] as f32 * 2.0 - 1.0,
+            y / self.extent[1] as f32 * 2.0 - 1.0,
+            0.0,
+        )
+    }
+
+    /// Covers column `x` from row `top` up to `bottom`
+    fn vertical(&mut self, x: u32, top: u32, bottom: u32, gray: f32) {
+        let x = x as f32 + 0.5;
+        let (from, to) = (self.point(x, top as f32), self.point(x, bottom as f32));
+        self.lines.line(from, to, [gray; 3]);
+    }
+
+    /// Covers row `y` from column `left` up to `right`
+    fn horizontal(&mut self, y: u32, left: u32, right: u32, gray: f32) {
+        let y = y as f32 + 0.5;
+        let (from, to) = (self.point(left as f32, y), self.point(right as f32, y));
+        self.lines.line(from, to, [gray; 3]);
+    }
+
+    fn fill(&mut self, left: u32, top: u32, right: u32, bottom: u32, gray: f32) {
+        for y in top..bottom {
+            self.horizontal(y, left, right, gray);
+        }
+    }
+
+    /// `LEVEL_PATCHES` checkerboards side by side, `base` against `base + step`, then
+    /// `base + 2 * step` and so on
+    fn checkerboards(&mut self, top: u32, bottom: u32, base: f32, step: f32) {
+        let width = self.extent[0];
+        for patch in 0..LEVEL_PATCHES {
+            let left = patch * width / LEVEL_PATCHES;
+            let right = (patch + 1) * width / LEVEL_PATCHES;
+            let level = base + step * (patch + 1) as f32;
+
+            self.fill(left, top, right, bottom, base);
+            for y in top..bottom {
+                let offset = ((y - top) / CHECKER_SIZE % 2) * CHECKER_SIZE;
+                for x in (left + offset..right).step_by(2 * CHECKER_SIZE as usize) {
+                    self.horizontal(y, x, (x + CHECKER_SIZE).min(right), level);
+                }
+            }
+        }
+    }
+
+    /// `text` centered on `x`, `y`
+    fn label(&mut self, text: &str, x: f32, y: f32) {
+        // the glyph cell is half as wide as it is high
+        let right = self.point(LABEL_HEIGHT / 2.0, 0.0) - self.point(0.0, 0.0);
+        // Y points down the screen
+        let up = self.point(0.0, 0.0) - self.point(0.0, LABEL_HEIGHT);
+        let width = DebugLines::text_width(text) * LABEL_HEIGHT / 2.0;
+        let origin = self.point(x - width / 2.0, y + LABEL_HEIGHT / 2.0);
+        self.lines.text(text, origin, right, up, LABEL_COLOR);
+    }
+}
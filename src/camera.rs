@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use cgmath::{Deg, InnerSpace, Matrix4, Point3, Rad, Vector3};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use crate::{input::Input, scenes::CAMERA_DISTANCE};
+
+/// How fast the arrow keys orbit, per second
+const ORBIT_SPEED: Rad<f32> = Rad(1.5);
+/// Orbit per pixel the cursor moves while dragging
+const DRAG_SENSITIVITY: Rad<f32> = Rad(0.005);
+/// Distance is multiplied or divided by this per second W or S is held
+const ZOOM_FACTOR: f32 = 2.0;
+/// And by this per mouse wheel notch
+const WHEEL_ZOOM_FACTOR: f32 = 1.1;
+const MIN_DISTANCE: f32 = 0.1;
+const MAX_DISTANCE: f32 = 50.0;
+/// Straight above or below, look_at has no way to tell which way is up
+const MAX_PITCH: Deg<f32> = Deg(89.0);
+/// Keys the camera follows while they are held, the application doesn't act on them
+pub const KEYS: [VirtualKeyCode; 6] = [
+    VirtualKeyCode::Left,
+    VirtualKeyCode::Right,
+    VirtualKeyCode::Up,
+    VirtualKeyCode::Down,
+    VirtualKeyCode::W,
+    VirtualKeyCode::S,
+];
+
+/// Orbits a target point at a distance. Arrow keys and dragging with the middle mouse button
+/// turn around it, W and S or the mouse wheel move closer and further away. The default looks
+/// at the origin from `CAMERA_DISTANCE` along +Z.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    /// Angle around the Y axis, 0 is in front of the target on +Z
+    pub yaw: Rad<f32>,
+    /// Angle above the target's horizon
+    pub pitch: Rad<f32>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            target: Point3::new(0.0, 0.0, 0.0),
+            distance: CAMERA_DISTANCE,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+        }
+    }
+}
+
+impl Camera {
+    pub fn eye(&self) -> Point3<f32> {
+        let offset = Vector3::new(
+            self.pitch.0.cos() * self.yaw.0.sin(),
+            self.pitch.0.sin(),
+            self.pitch.0.cos() * self.yaw.0.cos(),
+        );
+        self.target + offset * self.distance
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.eye(), self.target, Vector3::unit_y())
+    }
+
+    /// Back to the default view
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Places the camera at `eye` looking at `target`, within the pitch limits
+    pub fn look_from(&mut self, eye: Point3<f32>, target: Point3<f32>) {
+        let offset = eye - target;
+        let distance = offset.magnitude();
+        if distance < f32::EPSILON {
+            return;
+        }
+
+        let max_pitch = Rad::from(MAX_PITCH);
+        self.target = target;
+        self.distance = distance;
+        self.yaw = Rad(offset.x.atan2(offset.z));
+        self.pitch = Rad((offset.y / distance).asin().clamp(-max_pitch.0, max_pitch.0));
+    }
+
+    /// Orbits `target` at `distance` from `yaw` and `pitch`, within the distance and pitch limits
+    pub fn set_pose(&mut self, target: Point3<f32>, distance: f32, yaw: Rad<f32>, pitch: Rad<f32>) {
+        let max_pitch = Rad::from(MAX_PITCH);
+        self.target = target;
+        self.distance = distance.clamp(MIN_DISTANCE, MAX_DISTANCE);
+        self.yaw = yaw;
+        self.pitch = Rad(pitch.0.clamp(-max_pitch.0, max_pitch.0));
+    }
+
+    pub fn orbit(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        let max_pitch = Rad::from(MAX_PITCH);
+        self.yaw += yaw;
+        self.pitch = Rad((self.pitch + pitch).0.clamp(-max_pitch.0, max_pitch.0));
+    }
+
+    /// Multiplies the distance to the target, within `MIN_DISTANCE` and `MAX_DISTANCE`
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    /// Moves by the frame's `input`, called once per frame with the time since the last one
+    pub fn update(&mut self, input: &Input, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f32();
+        let axis = |positive, negative| input.held(positive) as i32 as f32 - input.held(negative) as i32 as f32;
+
+        let yaw = axis(VirtualKeyCode::Right, VirtualKeyCode::Left);
+        let pitch = axis(VirtualKeyCode::Up, VirtualKeyCode::Down);
+        if yaw != 0.0 || pitch != 0.0 {
+            self.orbit(ORBIT_SPEED * yaw * seconds, ORBIT_SPEED * pitch * seconds);
+        }
+
+        let zoom = axis(VirtualKeyCode::S, VirtualKeyCode::W);
+        if zoom != 0.0 {
+            self.zoom(ZOOM_FACTOR.powf(zoom * seconds));
+        }
+
+        if input.button_held(MouseButton::Middle) {
+            // dragging right turns the scene right, so the camera goes left around it
+            let [dx, dy] = input.cursor_delta();
+            self.orbit(-DRAG_SENSITIVITY * dx as f32, DRAG_SENSITIVITY * dy as f32);
+        }
+
+        // scrolling up moves closer
+        let notches = input.scroll();
+        if notches != 0.0 {
+            self.zoom(WHEEL_ZOOM_FACTOR.powf(-notches));
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+};
+
+use log::{error, info};
+use vulkano::{
+    buffer::{BufferUsage, CpuAccessibleBuffer},
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    device::Device,
+    format::Format,
+    image::{ImageAccess, SampleCount},
+};
+
+use crate::{msaa, post};
+
+/// A copy of a swapchain image on its way to a PNG file
+pub struct Capture {
+    buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+    extent: [u32; 2],
+    layout: PixelLayout,
+    path: PathBuf,
+}
+
+impl Capture {
+    /// Allocates the buffer the frame is copied into. 8 and 10 bit RGBA and BGRA images can be
+    /// saved, and 16 bit float RGBA images, which are tone mapped.
+    pub fn new(device: &Arc<Device>, extent: [u32; 2], format: Format, path: PathBuf) -> Result<Self, String> {
+        let layout = PixelLayout::of(format).ok_or_else(|| format!("can't save images in the format {:?}", format))?;
+
+        let size = extent[0] as usize * extent[1] as usize * layout.bytes_per_pixel();
+        let buffer = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::transfer_destination(),
+            false,
+            (0..size).map(|_| 0u8),
+        )
+        .map_err(|e| format!("failed to allocate the capture buffer: {}", e))?;
+
+        Ok(Self {
+            buffer,
+            extent,
+            layout,
+            path,
+        })
+    }
+
+    /// Records the copy of `image` into the buffer, outside of render passes. A multisampled
+    /// image is resolved first, copies can only read single sampled images.
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<dyn ImageAccess + Send + Sync>,
+    ) -> Result<(), String> {
+        let image = if image.samples() != SampleCount::Sample1 {
+            msaa::resolve_color(builder, image)?
+        } else {
+            image
+        };
+        builder
+            .copy_image_to_buffer(image, self.buffer.clone())
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Encodes the image on a separate thread, must only be called once the copy has finished
+    pub fn save(self) {
+        thread::spawn(move || match self.write_png() {
+            Ok(()) => info!("Saved {}", self.path.display()),
+            Err(e) => error!("Failed to save {}: {}", self.path.display(), e),
+        });
+    }
+
+    /// Encodes the image on the calling thread, for when the process exits right after
+    pub fn save_blocking(self) -> Result<(), String> {
+        self.write_png().map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    fn write_png(&self) -> Result<(), String> {
+        let data = self.buffer.read().map_err(|e| e.to_string())?;
+        let pixels: Vec<u8> = data
+            .chunks_exact(self.layout.bytes_per_pixel())
+            .flat_map(|pixel| self.layout.to_rgba8(pixel))
+            .collect();
+
+        if let Some(directory) = self.path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+
+        let file = File::create(&self.path).map_err(|e| e.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.extent[0], self.extent[1]);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// How the pixels of a copied image are laid out in the buffer
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PixelLayout {
+    Rgba8,
+    Bgra8,
+    /// Packed into 32 bits with red in the lowest 10
+    A2b10g10r10,
+    /// Packed into 32 bits with blue in the lowest 10
+    A2r10g10b10,
+    /// Linear half floats, brighter than 1 where the image is
+    Rgba16Float,
+}
+
+impl PixelLayout {
+    fn of(format: Format) -> Option<Self> {
+        match format {
+            Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => Some(PixelLayout::Rgba8),
+            Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => Some(PixelLayout::Bgra8),
+            Format::A2B10G10R10UnormPack32 => Some(PixelLayout::A2b10g10r10),
+            Format::A2R10G10B10UnormPack32 => Some(PixelLayout::A2r10g10b10),
+            Format::R16G16B16A16Sfloat => Some(PixelLayout::Rgba16Float),
+            _ => None,
+        }
+    }
+
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelLayout::Rgba16Float => 8,
+            _ => 4,
+        }
+    }
+
+    /// The pixel as opaque 8 bit RGBA. Integer formats already hold what the display shows, float
+    /// ones are tone mapped and sRGB encoded. The swap chain is never translucent, but its alpha
+    /// channel is not guaranteed to be 1, so it is left out.
+    fn to_rgba8(self, pixel: &[u8]) -> [u8; 4] {
+        let ten_bits = |word: u32, shift: u32| (((word >> shift) & 0x3ff) * 255 / 1023) as u8;
+        match self {
+            PixelLayout::Rgba8 => [pixel[0], pixel[1], pixel[2], 255],
+            PixelLayout::Bgra8 => [pixel[2], pixel[1], pixel[0], 255],
+            PixelLayout::A2b10g10r10 => {
+                let word = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                [ten_bits(word, 0), ten_bits(word, 10), ten_bits(word, 20), 255]
+            }
+            PixelLayout::A2r10g10b10 => {
+                let word = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                [ten_bits(word, 20), ten_bits(word, 10), ten_bits(word, 0), 255]
+            }
+            PixelLayout::Rgba16Float => {
+                let channel = |i: usize| half_to_f32(u16::from_le_bytes([pixel[2 * i], pixel[2 * i + 1]]));
+                let [r, g, b] = post::tonemap([channel(0), channel(1), channel(2)])
+                    .map(|linear| (post::encode_srgb(linear) * 255.0).round() as u8);
+                [r, g, b, 255]
+            }
+        }
+    }
+}
+
+/// An IEEE half float as an f32
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// `screenshot_<frame>.png` in the working directory
+pub fn default_screenshot_path(frame: u64) -> PathBuf {
+    PathBuf::from(format!("screenshot_{:06}.png", frame))
+}
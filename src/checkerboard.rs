@@ -0,0 +1,289 @@
+use std::sync::Arc;
+
+use cgmath::{Matrix4, SquareMatrix};
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::{descriptor_set::PersistentDescriptorSet, DescriptorSet},
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImageAccess, ImageUsage},
+    pipeline::{
+        depth_stencil::{Compare, DepthStencil},
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::{post, renderer::DEPTH_FORMAT};
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod mask_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/checkerboard_mask.frag"
+    }
+}
+
+mod resolve_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/checkerboard_resolve.frag"
+    }
+}
+
+/// Shades half the pixels every frame, alternating in a checkerboard, and fills in the other half
+/// from the previous frame. A mask drawn first in the scene pass puts the nearest depth on the
+/// pixels that are skipped, so the depth test rejects everything drawn there before it is shaded.
+/// The resolve pass then follows the camera's motion back into the previous full image for each
+/// skipped pixel, clamped to the colors around it, and writes the full image and its depth for
+/// the passes after it.
+pub struct Checkerboard {
+    /// The scene pass draws into this in place of the post pass target
+    pub scene: Arc<ImageView<Arc<AttachmentImage>>>,
+    /// Depth of the full image, for the passes that read depth after the scene
+    pub depth: Arc<ImageView<Arc<AttachmentImage>>>,
+    /// The last full image, copied from the post pass target after every resolve
+    history: Arc<AttachmentImage>,
+    output: Arc<AttachmentImage>,
+    mask_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    resolve_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    /// Which half of the pixels is shaded this frame
+    parity: u32,
+    /// `None` until a frame was resolved, the history holds nothing before
+    previous_view_projection: Option<Matrix4<f32>>,
+}
+
+impl Checkerboard {
+    /// Recreated with the swap chain. `scene_render_pass` is what the mask is drawn in,
+    /// `depth_buffer` the scene's single sampled depth and `output` the post pass target.
+    pub fn new(
+        device: &Arc<Device>,
+        scene_render_pass: &Arc<RenderPass>,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+        output: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Self {
+        let dimensions = output.image().dimensions().width_height();
+        let sampled = |format: Format| ImageView::new(AttachmentImage::sampled(device.clone(), dimensions, format).unwrap()).unwrap();
+        let scene = sampled(post::INTERMEDIATE_FORMAT);
+        let depth = sampled(DEPTH_FORMAT);
+        let history_usage = ImageUsage {
+            sampled: true,
+            transfer_destination: true,
+            ..ImageUsage::none()
+        };
+        let history =
+            AttachmentImage::with_usage(device.clone(), dimensions, post::INTERMEDIATE_FORMAT, history_usage).unwrap();
+
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: post::INTERMEDIATE_FORMAT,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: DontCare,
+                        store: Store,
+                        format: DEPTH_FORMAT,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {depth}
+                }
+            )
+            .unwrap(),
+        );
+
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+            .expect("Failed to create checkerboard vertex shader module");
+        let mask_shader_module = mask_shader::Shader::load(device.clone())
+            .expect("Failed to create checkerboard mask shader module");
+        let resolve_shader_module = resolve_shader::Shader::load(device.clone())
+            .expect("Failed to create checkerboard resolve shader module");
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+        // both passes overwrite the depth of every pixel they keep
+        let overwrite_depth = DepthStencil {
+            depth_compare: Compare::Always,
+            depth_write: true,
+            ..DepthStencil::disabled()
+        };
+
+        let mask_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport.clone()])
+                .fragment_shader(mask_shader_module.main_entry_point(), ())
+                .depth_stencil(overwrite_depth.clone())
+                .render_pass(Subpass::from(scene_render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+        let resolve_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport])
+                .fragment_shader(resolve_shader_module.main_entry_point(), ())
+                .depth_stencil(overwrite_depth)
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let framebuffer = Arc::new(
+            Framebuffer::start(render_pass)
+                .add(output.clone())
+                .unwrap()
+                .add(depth.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let sampler = |filter: Filter| {
+            Sampler::new(
+                device.clone(),
+                filter,
+                filter,
+                MipmapMode::Nearest,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+            )
+            .unwrap()
+        };
+
+        // the history is sampled between pixels wherever the camera moved
+        let layout = resolve_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(scene.clone(), sampler(Filter::Nearest))
+                .unwrap()
+                .add_sampled_image(depth_buffer.clone(), sampler(Filter::Nearest))
+                .unwrap()
+                .add_sampled_image(ImageView::new(history.clone()).unwrap(), sampler(Filter::Linear))
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Self {
+            scene,
+            depth,
+            history,
+            output: output.image().clone(),
+            mask_pipeline,
+            resolve_pipeline,
+            framebuffer,
+            set,
+            parity: 0,
+            previous_view_projection: None,
+        }
+    }
+
+    /// Records the mask, first thing in the scene pass
+    pub fn draw_mask<L>(&self, builder: &mut AutoCommandBufferBuilder<L>) {
+        builder
+            .draw(
+                self.mask_pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                (),
+                mask_shader::ty::PushConstants { parity: self.parity },
+                vec![],
+            )
+            .unwrap();
+    }
+
+    /// Records the resolve pass and the copy into the history, after the scene pass and its depth
+    /// resolve. `view_projection` is the camera this frame was drawn with.
+    pub fn resolve(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        view_projection: Matrix4<f32>,
+    ) {
+        let previous = self.previous_view_projection.unwrap_or(view_projection);
+        let reprojection = previous * view_projection.invert().unwrap_or_else(Matrix4::identity);
+        let push_constants = resolve_shader::ty::PushConstants {
+            reprojection: reprojection.into(),
+            parity: self.parity,
+            history_valid: self.previous_view_projection.is_some() as u32,
+        };
+
+        builder
+            .begin_render_pass(
+                self.framebuffer.clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None, ClearValue::None],
+            )
+            .unwrap()
+            .draw(
+                self.resolve_pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                self.set.clone(),
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+
+        let [width, height] = self.history.dimensions().width_height();
+        builder
+            .copy_image(
+                self.output.clone(),
+                [0, 0, 0],
+                0,
+                0,
+                self.history.clone(),
+                [0, 0, 0],
+                0,
+                0,
+                [width, height, 1],
+                1,
+            )
+            .unwrap();
+    }
+
+    /// Switches to the other half of the pixels, once the frame drawn with `view_projection` was
+    /// submitted
+    pub fn end_frame(&mut self, view_projection: Matrix4<f32>) {
+        self.parity ^= 1;
+        self.previous_view_projection = Some(view_projection);
+    }
+}
@@ -0,0 +1,179 @@
+//! Clustered forward shading for many point lights. The view frustum is split into a grid of
+//! froxels, tiles of the screen sliced by depth, and a compute pass before the scene pass lists
+//! the lights whose spheres reach each of them in storage buffers. `lit.frag` then finds its
+//! pixel's froxel and only shades with the lights listed there, so hundreds of lights cost
+//! about as much as the few that overlap any one pixel, without a G-buffer.
+
+use std::{f32::consts::TAU, sync::Arc};
+
+use cgmath::{Matrix4, SquareMatrix};
+use vulkano::{
+    buffer::{BufferUsage, CpuBufferPool, DeviceLocalBuffer},
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor::DescriptorSet,
+    device::Queue,
+    pipeline::{ComputePipeline, ComputePipelineAbstract, GraphicsPipelineAbstract},
+};
+
+use crate::descriptors::{self, Resource};
+
+/// Tiles across, tiles down and depth slices, the same as in cluster_lights.comp and lit.frag
+pub const GRID: [u32; 3] = [16, 9, 24];
+/// Lights past this many in one froxel are left out of it
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 64;
+pub const MAX_LIGHTS: u32 = 1024;
+const CLUSTERS: u32 = GRID[0] * GRID[1] * GRID[2];
+/// Invocations per workgroup, one froxel each
+const WORKGROUP_SIZE: u32 = 64;
+const STORAGE_BUFFER: BufferUsage = BufferUsage {
+    storage_buffer: true,
+    ..BufferUsage::none()
+};
+
+mod shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/cluster_lights.comp"
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct PointLight {
+    /// In world space
+    pub position: [f32; 3],
+    /// Distance at which the light has faded out, the froxels it reaches are found by it
+    pub radius: f32,
+    /// Alpha is unused, it pads the color to a vec4
+    pub color: [f32; 4],
+}
+
+/// The uniform buffer both cluster_lights.comp and lit.frag read, std140 like the others
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct ClusterParams {
+    inverse_projection: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    screen_size: [f32; 2],
+    near: f32,
+    far: f32,
+    light_count: u32,
+    /// Nonzero to tint lit fragments by how many lights their froxel lists
+    heatmap: u32,
+}
+
+/// `count` lights circling the origin on rings of different radii, heights and speeds, each
+/// in its own hue, at `time` seconds
+pub fn orbiting_lights(count: u32, time: f32) -> Vec<PointLight> {
+    (0..count)
+        .map(|i| {
+            // the golden angle spreads consecutive lights around the circle and the hue wheel
+            let spread = (i as f32 * 0.618_034).fract();
+            let ring = (i % 7) as f32 / 6.0;
+            let angle = spread * TAU + time * (0.3 + 0.4 * ring) * if i % 2 == 0 { 1.0 } else { -1.0 };
+            let distance = 0.4 + 1.6 * ring;
+            let height = ((i % 5) as f32 / 4.0 - 0.5) * 1.2;
+            let hue = spread * 6.0;
+            let color = [0.0, 4.0, 2.0].map(|offset: f32| (((hue + offset) % 6.0 - 3.0).abs() - 1.0).clamp(0.0, 1.0));
+            PointLight {
+                position: [distance * angle.cos(), height, distance * angle.sin()],
+                radius: 0.6,
+                color: [color[0], color[1], color[2], 1.0],
+            }
+        })
+        .collect()
+}
+
+/// The light lists of every froxel and the pass that fills them, sized once for `GRID`
+pub struct ClusteredLights {
+    /// Lights shaded, changed with the `lights` command
+    pub count: u32,
+    /// Tints the `lit` view by the number of lights in each froxel, to check the assignment
+    pub heatmap: bool,
+    /// Whether the graphics queue can run the assignment pass, lights are left out otherwise
+    compute: bool,
+    pipeline: Arc<ComputePipeline>,
+    params: CpuBufferPool<ClusterParams>,
+    lights: CpuBufferPool<PointLight>,
+    counts: Arc<DeviceLocalBuffer<[u32]>>,
+    indices: Arc<DeviceLocalBuffer<[u32]>>,
+}
+
+impl ClusteredLights {
+    /// `queue` is the one the frames are recorded for
+    pub fn new(queue: &Arc<Queue>) -> Self {
+        let device = queue.device();
+        let shader = shader::Shader::load(device.clone()).expect("Failed to create light clustering shader module");
+        let pipeline = Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None).unwrap());
+        let buffer = |len| {
+            DeviceLocalBuffer::array(device.clone(), len as usize, STORAGE_BUFFER, Some(queue.family()))
+                .expect("Failed to create a light cluster buffer")
+        };
+
+        Self {
+            count: 0,
+            heatmap: false,
+            compute: queue.family().supports_compute(),
+            pipeline,
+            params: CpuBufferPool::uniform_buffer(device.clone()),
+            lights: CpuBufferPool::new(device.clone(), STORAGE_BUFFER),
+            counts: buffer(CLUSTERS),
+            indices: buffer(CLUSTERS * MAX_LIGHTS_PER_CLUSTER),
+        }
+    }
+
+    /// Assigns this frame's lights to the froxels, before the scene pass, and returns set 2 of
+    /// `pipeline` for its fragment shader. `None` when the pipeline reads no lights, which is
+    /// every view but `lit`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        screen_size: [u32; 2],
+        depth_range: [f32; 2],
+        time: f32,
+    ) -> Option<Arc<dyn DescriptorSet + Send + Sync>> {
+        let layout = pipeline.layout().descriptor_set_layout(2)?.clone();
+
+        let count = if self.compute { self.count.min(MAX_LIGHTS) } else { 0 };
+        let mut lights = orbiting_lights(count, time);
+        if lights.is_empty() {
+            // a buffer can't be empty, the shaders read none of it then
+            lights.push(PointLight::default());
+        }
+        let params = ClusterParams {
+            inverse_projection: projection.invert().unwrap_or_else(Matrix4::identity).into(),
+            view: view.into(),
+            screen_size: [screen_size[0] as f32, screen_size[1] as f32],
+            near: depth_range[0],
+            far: depth_range[1],
+            light_count: count,
+            heatmap: self.heatmap as u32,
+        };
+        let params = Arc::new(self.params.next(params).unwrap());
+        let lights = Arc::new(self.lights.chunk(lights).unwrap());
+        let resources = [
+            Resource::Buffer(params),
+            Resource::Buffer(lights),
+            Resource::Buffer(self.counts.clone()),
+            Resource::Buffer(self.indices.clone()),
+        ];
+
+        if count > 0 {
+            let compute_layout = self.pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+            builder
+                .dispatch(
+                    [CLUSTERS.div_ceil(WORKGROUP_SIZE), 1, 1],
+                    self.pipeline.clone(),
+                    descriptors::build(compute_layout, &resources),
+                    (),
+                    vec![],
+                )
+                .unwrap();
+        }
+        Some(descriptors::build(layout, &resources))
+    }
+}
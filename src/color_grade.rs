@@ -0,0 +1,320 @@
+//! A color grade after the post pass, in the same render pass: the first subpass runs the post
+//! pass into a transient attachment, the second reads it back as an input attachment and
+//! writes the graded image into the swap chain. Input attachments only give each pixel its own
+//! value, which is all a grade needs, and on tiled GPUs the attachment never leaves tile
+//! memory. The render pass is described by hand instead of with vulkano's macros so the
+//! dependency between the subpasses says exactly what waits for what.
+
+use std::{fmt, sync::Arc};
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::DescriptorSet,
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImageLayout, ImageUsage, SampleCount, SwapchainImage},
+    pipeline::{
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{
+        AttachmentDesc, Framebuffer, FramebufferAbstract, LoadOp, RenderPass, RenderPassDesc, StoreOp, Subpass,
+        SubpassDependencyDesc, SubpassDesc,
+    },
+    sync::{AccessFlags, PipelineStages},
+};
+use winit::window::Window;
+
+use crate::{
+    descriptors::{self, Resource},
+    error::Error,
+    post::{self, Calibration, PostProcess},
+};
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/color_grade.frag"
+    }
+}
+
+/// How the image is graded
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grade {
+    /// 0 is grayscale, 1 leaves the colors as they are
+    pub saturation: f32,
+    /// Above 0 shifts towards red, below 0 towards blue
+    pub temperature: f32,
+    /// How much darker the corners get
+    pub vignette: f32,
+}
+
+impl Grade {
+    pub const PRESETS: [(&'static str, Grade); 4] = [
+        (
+            "warm",
+            Grade {
+                saturation: 1.1,
+                temperature: 0.08,
+                vignette: 0.3,
+            },
+        ),
+        (
+            "cool",
+            Grade {
+                saturation: 0.9,
+                temperature: -0.08,
+                vignette: 0.3,
+            },
+        ),
+        (
+            "mono",
+            Grade {
+                saturation: 0.0,
+                temperature: 0.0,
+                vignette: 0.5,
+            },
+        ),
+        (
+            "film",
+            Grade {
+                saturation: 0.8,
+                temperature: 0.04,
+                vignette: 0.6,
+            },
+        ),
+    ];
+
+    pub fn preset(name: &str) -> Option<Grade> {
+        Self::PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, grade)| *grade)
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "saturation {}, temperature {}, vignette {}",
+            self.saturation, self.temperature, self.vignette
+        )
+    }
+}
+
+/// The post pass followed by the grade, drawn in place of `PostProcess::draw` while grading
+pub struct ColorGrade {
+    /// The post pass's pipeline, for the first subpass of this render pass
+    post_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    output_size: [u32; 2],
+}
+
+impl ColorGrade {
+    /// Recreated with the swap chain and `PostProcess`
+    pub fn new(
+        device: &Arc<Device>,
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        color_format: Format,
+    ) -> Result<Self, Error> {
+        let dimensions = swap_chain_images[0].dimensions();
+        // only ever read inside the render pass, so it needs no memory on GPUs that keep it on chip
+        let usage = ImageUsage {
+            color_attachment: true,
+            input_attachment: true,
+            transient_attachment: true,
+            ..ImageUsage::none()
+        };
+        let calibrated =
+            AttachmentImage::with_usage(device.clone(), dimensions, post::INTERMEDIATE_FORMAT, usage).unwrap();
+        let calibrated = ImageView::new(calibrated).unwrap();
+
+        let render_pass = create_render_pass(device, color_format);
+        let post_pipeline = PostProcess::create_pipeline(device, dimensions, &render_pass)?;
+        let pipeline = create_pipeline(device, dimensions, &render_pass)?;
+
+        let framebuffers = swap_chain_images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Arc::new(
+                    Framebuffer::start(render_pass.clone())
+                        .add(calibrated.clone())
+                        .unwrap()
+                        .add(view)
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                ) as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let set = descriptors::build(layout, &[Resource::Image(calibrated)]);
+
+        Ok(Self {
+            post_pipeline,
+            pipeline,
+            framebuffers,
+            set,
+            output_size: dimensions,
+        })
+    }
+
+    /// Records the post pass and the grade, after the scene pass and its depth resolve
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        post: &PostProcess,
+        calibration: Calibration,
+        tint: [f32; 3],
+        dither: bool,
+        grade: Grade,
+    ) {
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_index].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None, ClearValue::None],
+            )
+            .unwrap();
+        post.draw_subpass(builder, &self.post_pipeline, calibration, tint, dither);
+
+        let push_constants = fragment_shader::ty::PushConstants {
+            inverseOutputSize: [1.0 / self.output_size[0] as f32, 1.0 / self.output_size[1] as f32],
+            saturation: grade.saturation,
+            temperature: grade.temperature,
+            vignette: grade.vignette,
+        };
+        builder
+            .next_subpass(SubpassContents::Inline)
+            .unwrap()
+            .draw(
+                self.pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                self.set.clone(),
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
+
+/// Attachment 0 is the post pass's output, attachment 1 the swap chain image
+fn create_render_pass(device: &Arc<Device>, color_format: Format) -> Arc<RenderPass> {
+    const CALIBRATED: usize = 0;
+    const COLOR: usize = 1;
+
+    let attachment = |format, store, final_layout| AttachmentDesc {
+        format,
+        samples: SampleCount::Sample1,
+        load: LoadOp::DontCare,
+        store,
+        stencil_load: LoadOp::DontCare,
+        stencil_store: StoreOp::DontCare,
+        initial_layout: ImageLayout::ColorAttachmentOptimal,
+        final_layout,
+    };
+    let attachments = vec![
+        // nothing reads it after the render pass, so it is never written to memory
+        attachment(post::INTERMEDIATE_FORMAT, StoreOp::DontCare, ImageLayout::ShaderReadOnlyOptimal),
+        attachment(color_format, StoreOp::Store, ImageLayout::ColorAttachmentOptimal),
+    ];
+
+    let subpasses = vec![
+        // the post pass, into the transient attachment
+        SubpassDesc {
+            color_attachments: vec![(CALIBRATED, ImageLayout::ColorAttachmentOptimal)],
+            depth_stencil: None,
+            input_attachments: vec![],
+            resolve_attachments: vec![],
+            preserve_attachments: vec![COLOR],
+        },
+        // the grade, reading it back
+        SubpassDesc {
+            color_attachments: vec![(COLOR, ImageLayout::ColorAttachmentOptimal)],
+            depth_stencil: None,
+            input_attachments: vec![(CALIBRATED, ImageLayout::ShaderReadOnlyOptimal)],
+            resolve_attachments: vec![],
+            preserve_attachments: vec![],
+        },
+    ];
+
+    // the grade's fragment shader reads what the post pass's color output wrote. By region,
+    // since each pixel only waits for the same pixel of the first subpass.
+    let dependencies = vec![SubpassDependencyDesc {
+        source_subpass: 0,
+        destination_subpass: 1,
+        source_stages: PipelineStages {
+            color_attachment_output: true,
+            ..PipelineStages::none()
+        },
+        destination_stages: PipelineStages {
+            fragment_shader: true,
+            ..PipelineStages::none()
+        },
+        source_access: AccessFlags {
+            color_attachment_write: true,
+            ..AccessFlags::none()
+        },
+        destination_access: AccessFlags {
+            input_attachment_read: true,
+            ..AccessFlags::none()
+        },
+        by_region: true,
+    }];
+
+    let desc = RenderPassDesc::new(attachments, subpasses, dependencies);
+    Arc::new(RenderPass::new(device.clone(), desc).unwrap())
+}
+
+fn create_pipeline(
+    device: &Arc<Device>,
+    dimensions: [u32; 2],
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "color grade vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "color grade fragment",
+        source,
+    })?;
+
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+        depth_range: 0.0..1.0,
+    };
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition {})
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports(vec![viewport])
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 1).unwrap())
+            .build(device.clone())?,
+    ))
+}
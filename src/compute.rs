@@ -0,0 +1,220 @@
+//! A particle fountain simulated by a compute shader. The particles live in a storage buffer
+//! on the GPU: every frame a dispatch moves them before the scene pass, which reads the same
+//! buffer as the vertex buffer of a point list. Both are recorded into the frame's command
+//! buffer, vulkano puts the barrier between the shader writes and the vertex reads.
+
+use std::sync::Arc;
+
+use cgmath::Matrix4;
+use vulkano::{
+    buffer::{BufferUsage, DeviceLocalBuffer},
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer},
+    descriptor::DescriptorSet,
+    device::Device,
+    impl_vertex,
+    pipeline::{ComputePipeline, ComputePipelineAbstract, GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{RenderPass, Subpass},
+};
+
+use crate::{
+    descriptors::{self, Resource},
+    error::Error,
+    point_cloud,
+    staging::Staging,
+};
+
+pub const DEFAULT_PARTICLES: u32 = 16384;
+/// Invocations per workgroup of the update shader
+const WORKGROUP_SIZE: u32 = 256;
+/// Longest lifetime the shader spawns particles with, the first spawns are spread over it
+const MAX_LIFETIME: f32 = 4.0;
+const POINT_SIZE: f32 = 6.0;
+/// Most simulated time one frame catches up on, so a stalled frame doesn't throw the particles
+/// through the floor
+const MAX_CATCH_UP: f32 = 0.1;
+/// Most fixed steps dispatched in one frame, the time of any more is dropped
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+mod update_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/particles.comp"
+    }
+}
+
+pub mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/particle.vert"
+    }
+}
+
+/// One particle as the compute shader stores it and the vertex shader reads it
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct Particle {
+    /// w is the time the particle has left
+    position: [f32; 4],
+    /// w is the lifetime the particle was spawned with, 0 before its first spawn
+    velocity: [f32; 4],
+    /// The position before the last step, particles are drawn between it and `position`
+    previous: [f32; 4],
+}
+
+impl_vertex!(Particle, position, velocity, previous);
+
+/// The particle buffer and the compute pipeline that updates it
+pub struct ParticleSystem {
+    pub count: u32,
+    particles: Arc<DeviceLocalBuffer<[Particle]>>,
+    pipeline: Arc<ComputePipeline>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    /// Seconds every dispatch moves the particles by
+    step: f32,
+    /// Seconds not simulated yet, less than a step after `advance`
+    pending: f32,
+    /// Dispatches the next update records
+    steps: u32,
+    time: f32,
+}
+
+impl ParticleSystem {
+    /// Usable once `staging` was submitted. The simulation moves in fixed steps of `step` seconds.
+    pub fn new(staging: &mut Staging, count: u32, step: f32) -> Result<Self, Error> {
+        let device = staging.queue().device().clone();
+        // every particle waits for a different time before its first spawn, so they don't all
+        // leave the source at once
+        let particles = (0..count).map(|i| {
+            let position = [0.0, 0.0, 0.0, i as f32 / count as f32 * MAX_LIFETIME];
+            Particle {
+                position,
+                velocity: [0.0; 4],
+                previous: position,
+            }
+        });
+        let usage = BufferUsage {
+            storage_buffer: true,
+            vertex_buffer: true,
+            ..BufferUsage::none()
+        };
+        let particles = staging.device_local_buffer(particles, usage)?;
+
+        let shader = update_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "particle update",
+            source,
+        })?;
+        let pipeline = Arc::new(ComputePipeline::new(device, &shader.main_entry_point(), &(), None)?);
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let set = descriptors::build(layout, &[Resource::Buffer(particles.clone())]);
+
+        Ok(Self {
+            count,
+            particles,
+            pipeline,
+            set,
+            step,
+            pending: 0.0,
+            steps: 0,
+            time: 0.0,
+        })
+    }
+
+    /// Changes the length of the fixed step, like when the window moved to a monitor with
+    /// another refresh rate
+    pub fn set_step(&mut self, step: f32) {
+        self.step = step;
+    }
+
+    /// Adds `seconds` to the time to simulate and sets how many steps the next recorded update
+    /// takes, the rest carries over to the next frame
+    pub fn advance(&mut self, seconds: f32) {
+        self.pending = (self.pending + seconds).min(MAX_CATCH_UP);
+        let steps = (self.pending / self.step) as u32;
+        self.pending -= steps as f32 * self.step;
+        self.steps = steps.min(MAX_STEPS_PER_FRAME);
+        self.time += self.steps as f32 * self.step;
+    }
+
+    /// How far the frame is from the state before the last step to the latest one, 0 to 1. The
+    /// particles are drawn a step behind the simulation, so a display faster than the steps
+    /// sees them move smoothly instead of stopping between steps.
+    pub fn interpolation(&self) -> f32 {
+        (self.pending / self.step).clamp(0.0, 1.0)
+    }
+
+    /// Records the dispatches that move every particle, outside of render passes
+    pub fn update(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        for step in 0..self.steps {
+            let push_constants = update_shader::ty::PushConstants {
+                delta: self.step,
+                // the time at the end of this step, `time` is at the end of the last one
+                time: self.time - (self.steps - 1 - step) as f32 * self.step,
+                count: self.count,
+            };
+            builder
+                .dispatch(
+                    [self.count.div_ceil(WORKGROUP_SIZE), 1, 1],
+                    self.pipeline.clone(),
+                    self.set.clone(),
+                    push_constants,
+                    vec![],
+                )
+                .unwrap();
+        }
+    }
+
+    /// Draws the particles as points, after `update` in the same command buffer
+    pub fn draw<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+        view_projection: Matrix4<f32>,
+        max_point_size: f32,
+    ) {
+        let push_constants = vertex_shader::ty::PushConstants {
+            view_projection: view_projection.into(),
+            point_size: POINT_SIZE,
+            max_point_size,
+            interpolation: self.interpolation(),
+        };
+        builder
+            .draw(
+                pipeline.clone(),
+                dynamic_state,
+                vec![self.particles.clone()],
+                (),
+                push_constants,
+                vec![],
+            )
+            .unwrap();
+    }
+}
+
+/// Draws `Particle`s as round points, with the point cloud's fragment shader
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "particle vertex",
+        source,
+    })?;
+    let frag_shader_module =
+        point_cloud::fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "particle fragment",
+            source,
+        })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Particle>()
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .point_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .depth_stencil_simple_depth()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
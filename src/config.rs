@@ -0,0 +1,203 @@
+use std::{iter::Peekable, net::SocketAddr, path::PathBuf};
+
+use crate::{
+    scenes::SceneId,
+    settings::{self, Settings},
+};
+
+/// Number of frames `--smoke-test` renders when no count is given
+const DEFAULT_SMOKE_TEST_FRAMES: u64 = 100;
+/// Number of frames `--headless` renders when no count is given
+const DEFAULT_HEADLESS_FRAMES: u64 = 1;
+const DEFAULT_HEADLESS_OUTPUT: &str = "headless";
+
+pub const USAGE: &str = "usage: vulkan-tutorial-rs [options]
+  --settings <file>            settings file, settings.cfg by default
+  --window <width>x<height>    window size in logical pixels
+  --fullscreen [exclusive]     borderless or exclusive fullscreen, Alt+Enter toggles it
+  --monitor <index|name>       monitor to go fullscreen on, see --print-device-info
+  --vsync <on|off>             wait for vertical blank when presenting
+  --present-mode <mode>        auto, fifo, relaxed, mailbox or immediate
+  --msaa <samples>             1, 2, 4, 8, 16, 32 or 64 samples per pixel
+  --low-power                  integrated GPU, FIFO, half resolution, redraw on demand
+  --validation <on|off>        Vulkan validation layers
+  --validation-types <types>   general, validation or performance messages to log, or all
+  --gpu <index|name>           GPU to use, see --print-device-info
+  --pipeline-cache <file|off>  where compiled pipelines are kept between runs
+  --deferred                   light the scene from a G-buffer instead of while drawing it
+  --set <key=value>            any other setting from the settings file
+  --print-device-info          list the GPUs and exit
+  --smoke-test [frames]        render some frames and exit, 1 on validation errors
+  --listen <address:port>      accept console commands over TCP
+  --frame-log <file.csv>       write the metrics of every frame to a CSV file
+  --headless [frames]          render without a window into PNG files and exit
+  --output <directory>         where --headless writes its frames, headless by default
+  --scene <name|number>        demo scene --headless renders";
+
+/// What the command line asked for. Settings given there are applied over the settings file,
+/// which keeps the defaults of everything else.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub settings_path: PathBuf,
+    /// `(key, value)` pairs in the settings file's format
+    pub overrides: Vec<(String, String)>,
+    pub print_device_info: bool,
+    /// Frames to render before exiting, for `--smoke-test`
+    pub smoke_test_frames: Option<u64>,
+    pub listen: Option<SocketAddr>,
+    pub frame_log: Option<PathBuf>,
+    /// Frames to render without a window, for `--headless`
+    pub headless_frames: Option<u64>,
+    pub headless_output: PathBuf,
+    pub headless_scene: SceneId,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            settings_path: PathBuf::from(settings::DEFAULT_PATH),
+            overrides: Vec::new(),
+            print_device_info: false,
+            smoke_test_frames: None,
+            listen: None,
+            frame_log: None,
+            headless_frames: None,
+            headless_output: PathBuf::from(DEFAULT_HEADLESS_OUTPUT),
+            headless_scene: SceneId::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Parses the arguments after the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut config = Config::default();
+        let mut args = args.into_iter().peekable();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--settings" => config.settings_path = PathBuf::from(value("--settings")?),
+                "--window" => config.set("window_size", &value("--window")?)?,
+                "--fullscreen" => {
+                    let mode = args.next_if(|mode| mode == "exclusive");
+                    config.set("fullscreen", mode.as_deref().unwrap_or("borderless"))?
+                }
+                "--monitor" => config.set("monitor", &value("--monitor")?)?,
+                "--vsync" => config.set("vsync", &value("--vsync")?)?,
+                "--present-mode" => config.set("present_mode", &value("--present-mode")?)?,
+                "--msaa" => config.set("msaa", &value("--msaa")?)?,
+                "--low-power" => config.set("low_power", "on")?,
+                "--validation" => config.set("validation", &value("--validation")?)?,
+                "--validation-types" => config.set("validation_messages", &value("--validation-types")?)?,
+                "--gpu" => config.set("gpu", &value("--gpu")?)?,
+                "--pipeline-cache" => config.set("pipeline_cache", &value("--pipeline-cache")?)?,
+                "--deferred" => config.set("deferred", "on")?,
+                "--set" => {
+                    let setting = value("--set")?;
+                    match setting.split_once('=') {
+                        Some((key, value)) => config.set(key.trim(), value.trim())?,
+                        None => return Err(format!("--set expects key=value, got '{}'", setting)),
+                    }
+                }
+                "--print-device-info" => config.print_device_info = true,
+                "--smoke-test" => {
+                    config.smoke_test_frames = Some(optional_count(&mut args, "--smoke-test", DEFAULT_SMOKE_TEST_FRAMES)?)
+                }
+                "--headless" => {
+                    config.headless_frames = Some(optional_count(&mut args, "--headless", DEFAULT_HEADLESS_FRAMES)?)
+                }
+                "--frame-log" => config.frame_log = Some(PathBuf::from(value("--frame-log")?)),
+                "--output" => config.headless_output = PathBuf::from(value("--output")?),
+                "--scene" => config.headless_scene = value("--scene")?.parse()?,
+                "--listen" => {
+                    let address = value("--listen")?;
+                    let address = address
+                        .parse()
+                        .map_err(|_| format!("--listen expects an address like 127.0.0.1:7878, got '{}'", address))?;
+                    config.listen = Some(address);
+                }
+                other => return Err(format!("unknown option '{}'", other)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Checks the value right away so mistakes are reported before the window opens
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        Settings::default().set(key, value).map_err(|e| format!("{}: {}", key, e))?;
+        self.overrides.retain(|(overridden, _)| overridden != key);
+        self.overrides.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
+/// The frame count after `option`, which may be left out
+fn optional_count(
+    args: &mut Peekable<impl Iterator<Item = String>>,
+    option: &str,
+    default: u64,
+) -> Result<u64, String> {
+    match args.peek() {
+        Some(count) if !count.starts_with("--") => {
+            let count = args.next().unwrap();
+            count
+                .parse()
+                .map_err(|_| format!("{} expects a frame count, got '{}'", option, count))
+        }
+        _ => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, String> {
+        Config::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments() {
+        assert_eq!(parse(&[]), Ok(Config::default()));
+    }
+
+    #[test]
+    fn settings_become_overrides() {
+        let config = parse(&["--window", "800x600", "--vsync", "off", "--set", "vsync = on"]).unwrap();
+        assert_eq!(
+            config.overrides,
+            vec![
+                ("window_size".to_string(), "800x600".to_string()),
+                ("vsync".to_string(), "on".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn optional_frame_counts() {
+        let config = parse(&["--smoke-test", "--headless", "5"]).unwrap();
+        assert_eq!(config.smoke_test_frames, Some(DEFAULT_SMOKE_TEST_FRAMES));
+        assert_eq!(config.headless_frames, Some(5));
+        assert!(parse(&["--smoke-test", "many"]).is_err());
+    }
+
+    #[test]
+    fn fullscreen_mode() {
+        let exclusive = parse(&["--fullscreen", "exclusive"]).unwrap();
+        assert_eq!(exclusive.overrides, vec![("fullscreen".to_string(), "exclusive".to_string())]);
+        let borderless = parse(&["--fullscreen", "--low-power"]).unwrap();
+        assert_eq!(borderless.overrides[0], ("fullscreen".to_string(), "borderless".to_string()));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["--window"]).is_err());
+        assert!(parse(&["--msaa", "3"]).is_err());
+        assert!(parse(&["--set", "vsync"]).is_err());
+        assert!(parse(&["--listen", "localhost"]).is_err());
+        assert!(parse(&["--scene", "nowhere"]).is_err());
+    }
+}
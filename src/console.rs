@@ -0,0 +1,735 @@
+use std::{
+    io::{self, BufRead},
+    path::PathBuf,
+    str::FromStr,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use cgmath::{Deg, Point3, Vector3};
+use vulkano::swapchain::PresentMode;
+
+use crate::{
+    clusters,
+    color_grade::Grade,
+    compute,
+    debug_view::DebugView,
+    decals::DecalKind,
+    god_rays::MAX_STEPS,
+    instancing,
+    mipmaps::MipGeneration,
+    motion_blur,
+    parallel,
+    portal::{self, PortalKind},
+    post_effects::Effect,
+    primitives::Primitive,
+    projection::AspectPolicy,
+    scenes::SceneId,
+    section::SectionNormal,
+    shader::ShaderStage,
+    spline::PathFollower,
+    stats::StatsDisplay,
+    swapchain,
+};
+
+/// Commands that can be typed into the terminal while the application is running
+#[derive(Debug)]
+pub enum Command {
+    /// `view <shaded|lit|uv|mip|depth>` switches the fragment shader used for the scene
+    SetDebugView(DebugView),
+    /// `scene <name|number>` switches to one of the built-in demo scenes
+    SetScene(SceneId),
+    /// `scenes` lists the built-in demo scenes
+    ListScenes,
+    /// `fov <degrees>` sets the vertical field of view
+    SetFov(Deg<f32>),
+    /// `near <distance>` moves the near clip plane
+    SetNear(f32),
+    /// `far <distance>` moves the far clip plane
+    SetFar(f32),
+    /// `aspect <window|ratio>` sets how the aspect ratio is chosen
+    SetAspect(AspectPolicy),
+    /// `projection` prints the current projection parameters
+    ShowProjection,
+    /// `spawn <x> <y> <z>` places an object at the given world position
+    Spawn(Point3<f32>),
+    /// `despawn <id|all>` removes one or all spawned objects
+    Despawn(Option<u32>),
+    /// `objects` lists the spawned objects
+    ListObjects,
+    /// `measure <on|off>` toggles picking two points to measure their distance
+    SetMeasureMode(bool),
+    /// `screenshot [file.png]` saves the next frame
+    Screenshot(Option<PathBuf>),
+    /// `turntable <frames> [radius] [elevation] [directory]` orbits the camera once, saving every frame,
+    /// `turntable off` stops it
+    Turntable(Option<TurntableSettings>),
+    /// `sky <on|off>` draws the daylight sky behind the scene
+    SetSky(bool),
+    /// `sky time <hours>` sets the time of day
+    SetSkyHour(f32),
+    /// `sky day <seconds>` sets how long a day lasts, 0 stops the sun
+    SetDayLength(f32),
+    /// `sky haze <turbidity>` sets how hazy the atmosphere is, from 2 to 10
+    SetTurbidity(f32),
+    /// `sky` prints the time of day and sun direction
+    ShowSky,
+    /// `skybox <+x> <-x> <+y> <-y> <+z> <-z>` draws six face images behind the scene, `skybox
+    /// <file.hdr>` an equirectangular panorama, `skybox off` removes it
+    LoadSkybox(Option<Vec<PathBuf>>),
+    /// `skybox` prints what the skybox was loaded from
+    ShowSkybox,
+    /// `godrays <on|off>` draws light shafts from the sun while the sky is on
+    SetGodRays(bool),
+    /// `godrays density <0..1>` sets how far towards the sun the rays are blurred
+    SetGodRayDensity(f32),
+    /// `godrays steps <count>` sets the samples per pixel along the rays
+    SetGodRaySteps(u32),
+    /// `godrays` prints the light shaft settings
+    ShowGodRays,
+    /// `motionblur <on|off>` blurs the scene along the motion of every pixel since the last frame
+    SetMotionBlur(bool),
+    /// `motionblur samples <count>` sets the samples along each pixel's motion
+    SetMotionBlurSamples(u32),
+    /// `motionblur shutter <0..1>` sets the share of the motion between frames that is blurred
+    SetShutter(f32),
+    /// `motionblur` prints the motion blur settings
+    ShowMotionBlur,
+    /// `bloom <on|off>` adds a glow around what is brighter than the threshold
+    SetBloom(bool),
+    /// `bloom threshold <brightness>` sets the brightness above which the scene glows
+    SetBloomThreshold(f32),
+    /// `bloom intensity <scale>` sets how strongly the glow is added
+    SetBloomIntensity(f32),
+    /// `bloom` prints the bloom settings
+    ShowBloom,
+    /// `postfx <gamma|vignette|grayscale|fxaa>...` applies the effects to the scene in the order
+    /// given, `postfx off` none of them
+    SetPostEffects(Vec<Effect>),
+    /// `postfx` prints the effects in order
+    ShowPostEffects,
+    /// `load <file.ply|file.stl|file.obj>` replaces the scene with a mesh file, `scene` switches back
+    LoadMesh(PathBuf),
+    /// `primitive <sphere|plane|cube|cylinder|torus>` replaces the scene with a generated mesh,
+    /// `scene` switches back
+    LoadPrimitive(Primitive),
+    /// `texture <file.png|file.jpg>` samples an image in the scene's fragment shader, `texture off`
+    /// goes back to plain vertex colors
+    LoadTexture(Option<PathBuf>),
+    /// `emissive <r> <g> <b>` makes the scene give off light of that color, `emissive off` stops it
+    SetEmissiveColor([f32; 3]),
+    /// `emissive intensity <scale>` scales the emission, above 1 it glows through the bloom
+    SetEmissiveIntensity(f32),
+    /// `emissive texture <file.png|file.jpg>` multiplies the emission per texel, `emissive texture
+    /// off` glows evenly again
+    LoadEmissiveMap(Option<PathBuf>),
+    /// `emissive` prints the scene's emission
+    ShowEmission,
+    /// `mipmaps <on|off>` switches between sampling the texture's mip chain and only its full
+    /// resolution level
+    SetMipmaps(bool),
+    /// `mipgen <auto|blit|compute|subgroup>` picks how textures loaded afterwards get their mip
+    /// levels, `auto` takes the fastest the device supports
+    SetMipGeneration(Option<MipGeneration>),
+    /// `dither <on|off>` adds blue noise to the scene when it is written to the swap chain, hiding
+    /// the bands in smooth gradients
+    SetDither(bool),
+    /// `tint <r> <g> <b>` multiplies the scene by a color in the post pass, `tint off` sets it back
+    /// to white
+    SetTint([f32; 3]),
+    /// `tint` prints the tint
+    ShowTint,
+    /// `checkerboard <on|off>` shades half the pixels every frame and fills in the rest from the
+    /// frame before
+    SetCheckerboard(bool),
+    /// `subgroups [scalar]` prints the subgroup operations compute shaders can use and runs the
+    /// culling and luminance demos with them, `scalar` forces the fallbacks to compare
+    Subgroups { scalar: bool },
+    /// `shader <vert|frag> <file>` replaces the scene pipeline's vertex or fragment shader with a
+    /// GLSL or SPIR-V file, `shader reset` goes back to the built-in ones
+    LoadShader(Option<(ShaderStage, PathBuf)>),
+    /// `audio <file.wav>` drives the scene shaders with the spectrum of a WAV file, `audio off`
+    /// stops it
+    LoadAudio(Option<PathBuf>),
+    /// `audio` prints the playback position and the band levels
+    ShowAudio,
+    /// `timeline <file>` loads a keyframe timeline and plays it, `timeline off` stops it
+    LoadTimeline(Option<PathBuf>),
+    /// `timeline <play|pause>` resumes or pauses the timeline, like Space in the window
+    PlayTimeline(bool),
+    /// `timeline seek <seconds>` jumps to a point of the timeline
+    SeekTimeline(f32),
+    /// `timeline` prints the playback position
+    ShowTimeline,
+    /// `path <file> [object <id>]` moves the camera or a spawned object along a spline path,
+    /// `path off` stops it
+    LoadPath(Option<(PathBuf, PathFollower)>),
+    /// `path speed <units/s>` sets how fast the path is followed
+    SetPathSpeed(f32),
+    /// `path` prints where the follower is on the path
+    ShowPath,
+    /// `framelog <file.csv>` writes the metrics of every frame to a CSV file, `framelog off`
+    /// finishes it
+    FrameLog(Option<PathBuf>),
+    /// `framelog` prints where the frame metrics are written
+    ShowFrameLog,
+    /// `points <file.xyz>` loads a point cloud, `points off` removes it
+    LoadPoints(Option<PathBuf>),
+    /// `points` prints how much of the point cloud is loaded
+    ShowPoints,
+    /// `world <on|off>` streams an endless landscape in chunks around the camera, which moves
+    /// forward over it
+    SetWorld(bool),
+    /// `world speed <units/s>` sets how fast the camera moves over the world
+    SetWorldSpeed(f32),
+    /// `world splat <on|off>` draws the world with blended detail textures or its vertex colors
+    SetWorldSplatting(bool),
+    /// `world triplanar <on|off>` projects the detail textures from the sides on steep slopes
+    SetWorldTriplanar(bool),
+    /// `world grass <on|off>` draws the grass on the world or leaves it out
+    SetWorldGrass(bool),
+    /// `world` prints how many chunks are loaded and waiting
+    ShowWorld,
+    /// `minimap <on|off>` draws an overhead view of the scene in the top right corner
+    SetMinimap(bool),
+    /// `minimap extent <units>` sets how far the overhead view reaches from its center
+    SetMinimapExtent(f32),
+    /// `minimap every <frames>` redraws the overhead view only every that many frames
+    SetMinimapInterval(u32),
+    /// `minimap` prints the minimap settings
+    ShowMinimap,
+    /// `queries <on|off>` records occlusion and pipeline statistics queries around the scene's
+    /// draws
+    SetQueries(bool),
+    /// `queries` prints the counters of every queried draw of the last frame with results
+    ShowQueries,
+    /// `portal <mirror|window|off>` places a surface behind the scene that shows it from
+    /// another camera
+    SetPortal(Option<PortalKind>),
+    /// `portal depth <views>` sets how many views of a window are nested in each other
+    SetPortalDepth(u32),
+    /// `portal` prints the portal's kind and depth
+    ShowPortal,
+    /// `ui <on|off>` shows the debug panel, like F1 in the window
+    SetDebugUi(bool),
+    /// `stats <off|log|title>` chooses where the frame rate and frame time percentiles are shown
+    /// every few seconds
+    SetStats(StatsDisplay),
+    /// `stats` prints the frame rate and frame time percentiles
+    ShowStats,
+    /// `plot <on|off>` graphs frame times in a corner of the window
+    SetPlot(bool),
+    /// `plot` prints the latest and average frame times
+    ShowPlot,
+    /// `decal <bullet|marking> [seconds]` projects a decal onto the surface under the cursor, kept
+    /// until it is removed when no lifetime is given
+    PlaceDecal(DecalKind, Option<Duration>),
+    /// `decal clear` removes every decal
+    ClearDecals,
+    /// `decals` lists the placed decals
+    ListDecals,
+    /// `section <x|y|z|nx,ny,nz> [distance]` cuts the scene open with a plane, `section off` removes it
+    SetSection(Option<(Vector3<f32>, f32)>),
+    /// `section cap <on|off>` fills the cross section with a flat color
+    SetSectionCap(bool),
+    /// `section` prints the section plane
+    ShowSection,
+    /// `instances <count|on|off>` draws a grid of quads with a single instanced draw call
+    SetInstances(Option<u32>),
+    /// `instances` prints how many quads are drawn
+    ShowInstances,
+    /// `particles <count|on|off>` runs the compute shader particle fountain
+    SetParticles(Option<u32>),
+    /// `particles` prints how many particles are simulated
+    ShowParticles,
+    /// `parallel <threads|on|off>` records the scene's draws into secondary command buffers on
+    /// several threads
+    SetRecordingThreads(Option<usize>),
+    /// `parallel` prints how the scene's draws are recorded
+    ShowRecordingThreads,
+    /// `point-size <pixels>` sets the size of a point one unit away from the camera
+    SetPointSize(f32),
+    /// `grade <warm|cool|mono|film|off>` grades the image in a second subpass after the post pass
+    SetGrade(Option<Grade>),
+    /// `grade` prints the color grade
+    ShowGrade,
+    /// `cull <on|off>` skips the spawned objects outside the view frustum
+    SetCulling(bool),
+    /// `vsync <on|off>` picks the present mode that waits for vertical blank, or the fastest one,
+    /// when no present mode is set
+    SetVsync(bool),
+    /// `present <auto|fifo|relaxed|mailbox|immediate>` sets the present mode, `auto` leaves it
+    /// to vsync
+    SetPresentMode(Option<PresentMode>),
+    /// `present` prints the present mode in use and those the surface supports
+    ShowPresentMode,
+    /// `vertices <file.csv>` runs the scene mesh's vertices through the vertex stage in a compute
+    /// pass and writes the results
+    CaptureVertices(PathBuf),
+    /// `cull` prints how draws are culled and whether the device has conditional rendering
+    ShowCulling,
+    /// `light <x> <y> <z>` moves the point light of the `lit` view, in world space
+    SetLightPosition(Point3<f32>),
+    /// `light color <r> <g> <b>` sets the light's color
+    SetLightColor([f32; 3]),
+    /// `light ambient <0..1>` sets the share of the surface color lit without the light
+    SetAmbient(f32),
+    /// `light shininess <exponent>` sets the exponent of the specular highlight
+    SetShininess(f32),
+    /// `light` prints the light
+    ShowLight,
+    /// `lights <count|off>` adds point lights orbiting the scene to the `lit` view, assigned to
+    /// froxels by a compute pass
+    SetLightCount(u32),
+    /// `lights` prints how many lights orbit the scene
+    ShowLightCount,
+    /// `lights heatmap <on|off>` tints the `lit` view by the number of lights in each froxel
+    SetLightHeatmap(bool),
+    /// `hiz <level|off>` shows a level of the Hi-Z pyramid in place of the scene
+    SetHiZView(Option<usize>),
+    /// `hiz` prints the sizes of the Hi-Z levels
+    ShowHiZ,
+}
+
+/// Options of the `turntable` command, `None` means the default
+#[derive(Clone, Debug)]
+pub struct TurntableSettings {
+    pub frames: u32,
+    pub radius: Option<f32>,
+    pub elevation: Option<Deg<f32>>,
+    pub output: Option<PathBuf>,
+}
+
+/// A finite number, `nan` and `inf` parse as floats but mean nothing here
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|number: &f32| number.is_finite())
+        .ok_or_else(|| format!("expected a number, got '{}'", value))
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["view", view] => Ok(Command::SetDebugView(view.parse()?)),
+            ["view", ..] => Err("usage: view <shaded|lit|uv|mip|depth>".to_string()),
+            ["scene", scene] => Ok(Command::SetScene(scene.parse()?)),
+            ["scene", ..] => Err("usage: scene <name|number>".to_string()),
+            ["scenes"] => Ok(Command::ListScenes),
+            ["fov", value] => Ok(Command::SetFov(Deg(parse_number(value)?))),
+            ["near", value] => Ok(Command::SetNear(parse_number(value)?)),
+            ["far", value] => Ok(Command::SetFar(parse_number(value)?)),
+            ["aspect", value] => Ok(Command::SetAspect(value.parse()?)),
+            [command @ ("fov" | "near" | "far" | "aspect"), ..] => {
+                Err(format!("usage: {} <value>", command))
+            }
+            ["projection"] => Ok(Command::ShowProjection),
+            ["spawn", x, y, z] => Ok(Command::Spawn(Point3::new(
+                parse_number(x)?,
+                parse_number(y)?,
+                parse_number(z)?,
+            ))),
+            ["spawn", ..] => Err("usage: spawn <x> <y> <z>".to_string()),
+            ["despawn", "all"] => Ok(Command::Despawn(None)),
+            ["despawn", id] => id
+                .parse()
+                .map(|id| Command::Despawn(Some(id)))
+                .map_err(|_| format!("expected an object id or 'all', got '{}'", id)),
+            ["despawn", ..] => Err("usage: despawn <id|all>".to_string()),
+            ["objects"] => Ok(Command::ListObjects),
+            ["measure", "on"] => Ok(Command::SetMeasureMode(true)),
+            ["measure", "off"] => Ok(Command::SetMeasureMode(false)),
+            ["measure", ..] => Err("usage: measure <on|off>".to_string()),
+            ["screenshot"] => Ok(Command::Screenshot(None)),
+            ["screenshot", path] => Ok(Command::Screenshot(Some(PathBuf::from(path)))),
+            ["turntable", "off"] => Ok(Command::Turntable(None)),
+            ["turntable", frames, rest @ ..] if rest.len() <= 3 => {
+                let frames = frames
+                    .parse()
+                    .map_err(|_| format!("expected a frame count, found '{}'", frames))?;
+                Ok(Command::Turntable(Some(TurntableSettings {
+                    frames,
+                    radius: rest.first().map(|radius| parse_number(radius)).transpose()?,
+                    elevation: rest.get(1).map(|elevation| parse_number(elevation)).transpose()?.map(Deg),
+                    output: rest.get(2).map(PathBuf::from),
+                })))
+            }
+            ["screenshot", ..] | ["turntable", ..] => Err(
+                "usage: screenshot [file.png], turntable <frames> [radius] [elevation] [directory]|off".to_string(),
+            ),
+            ["sky"] => Ok(Command::ShowSky),
+            ["sky", "on"] => Ok(Command::SetSky(true)),
+            ["sky", "off"] => Ok(Command::SetSky(false)),
+            ["sky", "time", hours] => Ok(Command::SetSkyHour(parse_number(hours)?)),
+            ["sky", "day", seconds] => Ok(Command::SetDayLength(parse_number(seconds)?)),
+            ["sky", "haze", turbidity] => Ok(Command::SetTurbidity(parse_number(turbidity)?)),
+            ["sky", ..] => Err("usage: sky [on|off|time <hours>|day <seconds>|haze <turbidity>]".to_string()),
+            ["skybox"] => Ok(Command::ShowSkybox),
+            ["skybox", "off"] => Ok(Command::LoadSkybox(None)),
+            ["skybox", paths @ ..] if paths.len() == 1 || paths.len() == 6 => {
+                Ok(Command::LoadSkybox(Some(paths.iter().map(PathBuf::from).collect())))
+            }
+            ["skybox", ..] => Err("usage: skybox [<+x> <-x> <+y> <-y> <+z> <-z>|<file.hdr>|off]".to_string()),
+            ["godrays"] => Ok(Command::ShowGodRays),
+            ["godrays", "on"] => Ok(Command::SetGodRays(true)),
+            ["godrays", "off"] => Ok(Command::SetGodRays(false)),
+            ["godrays", "density", density] => Ok(Command::SetGodRayDensity(parse_number(density)?)),
+            ["godrays", "steps", steps] => match steps.parse() {
+                Ok(steps) if (1..=MAX_STEPS).contains(&steps) => Ok(Command::SetGodRaySteps(steps)),
+                _ => Err(format!("expected a step count from 1 to {}, got '{}'", MAX_STEPS, steps)),
+            },
+            ["godrays", ..] => Err("usage: godrays [on|off|density <0..1>|steps <count>]".to_string()),
+            ["motionblur"] => Ok(Command::ShowMotionBlur),
+            ["motionblur", "on"] => Ok(Command::SetMotionBlur(true)),
+            ["motionblur", "off"] => Ok(Command::SetMotionBlur(false)),
+            ["motionblur", "samples", samples] => match samples.parse() {
+                Ok(samples) if (1..=motion_blur::MAX_SAMPLES).contains(&samples) => {
+                    Ok(Command::SetMotionBlurSamples(samples))
+                }
+                _ => Err(format!("expected a sample count from 1 to {}, got '{}'", motion_blur::MAX_SAMPLES, samples)),
+            },
+            ["motionblur", "shutter", shutter] => Ok(Command::SetShutter(parse_number(shutter)?)),
+            ["motionblur", ..] => Err("usage: motionblur [on|off|samples <count>|shutter <0..1>]".to_string()),
+            ["bloom"] => Ok(Command::ShowBloom),
+            ["bloom", "on"] => Ok(Command::SetBloom(true)),
+            ["bloom", "off"] => Ok(Command::SetBloom(false)),
+            ["bloom", "threshold", threshold] => Ok(Command::SetBloomThreshold(parse_number(threshold)?)),
+            ["bloom", "intensity", intensity] => Ok(Command::SetBloomIntensity(parse_number(intensity)?)),
+            ["bloom", ..] => Err("usage: bloom [on|off|threshold <brightness>|intensity <scale>]".to_string()),
+            ["postfx"] => Ok(Command::ShowPostEffects),
+            ["postfx", "off"] => Ok(Command::SetPostEffects(Vec::new())),
+            ["postfx", effects @ ..] => Ok(Command::SetPostEffects(
+                effects.iter().map(|effect| effect.parse()).collect::<Result<_, _>>()?,
+            )),
+            ["load", path] => Ok(Command::LoadMesh(PathBuf::from(path))),
+            ["primitive", primitive] => Ok(Command::LoadPrimitive(primitive.parse()?)),
+            ["primitive", ..] => Err("usage: primitive <sphere|plane|cube|cylinder|torus>".to_string()),
+            ["texture", "off"] => Ok(Command::LoadTexture(None)),
+            ["texture", path] => Ok(Command::LoadTexture(Some(PathBuf::from(path)))),
+            ["texture", ..] => Err("usage: texture <file.png|file.jpg|off>".to_string()),
+            ["emissive"] => Ok(Command::ShowEmission),
+            ["emissive", "off"] => Ok(Command::SetEmissiveColor([0.0; 3])),
+            ["emissive", "intensity", intensity] => Ok(Command::SetEmissiveIntensity(parse_number(intensity)?)),
+            ["emissive", "texture", "off"] => Ok(Command::LoadEmissiveMap(None)),
+            ["emissive", "texture", path] => Ok(Command::LoadEmissiveMap(Some(PathBuf::from(path)))),
+            ["emissive", r, g, b] => Ok(Command::SetEmissiveColor([
+                parse_number(r)?,
+                parse_number(g)?,
+                parse_number(b)?,
+            ])),
+            ["emissive", ..] => Err(
+                "usage: emissive [<r> <g> <b>|off|intensity <scale>|texture <file.png|file.jpg|off>]".to_string(),
+            ),
+            ["mipmaps", "on"] => Ok(Command::SetMipmaps(true)),
+            ["mipmaps", "off"] => Ok(Command::SetMipmaps(false)),
+            ["mipmaps", ..] => Err("usage: mipmaps <on|off>".to_string()),
+            ["mipgen", "auto"] => Ok(Command::SetMipGeneration(None)),
+            ["mipgen", "blit"] => Ok(Command::SetMipGeneration(Some(MipGeneration::Blit))),
+            ["mipgen", "compute"] => Ok(Command::SetMipGeneration(Some(MipGeneration::Compute))),
+            ["mipgen", "subgroup"] => Ok(Command::SetMipGeneration(Some(MipGeneration::ComputeSubgroup))),
+            ["mipgen", ..] => Err("usage: mipgen <auto|blit|compute|subgroup>".to_string()),
+            ["dither", "on"] => Ok(Command::SetDither(true)),
+            ["dither", "off"] => Ok(Command::SetDither(false)),
+            ["dither", ..] => Err("usage: dither <on|off>".to_string()),
+            ["tint"] => Ok(Command::ShowTint),
+            ["tint", "off"] => Ok(Command::SetTint([1.0; 3])),
+            ["tint", r, g, b] => Ok(Command::SetTint([parse_number(r)?, parse_number(g)?, parse_number(b)?])),
+            ["tint", ..] => Err("usage: tint [<r> <g> <b>|off]".to_string()),
+            ["checkerboard", "on"] => Ok(Command::SetCheckerboard(true)),
+            ["checkerboard", "off"] => Ok(Command::SetCheckerboard(false)),
+            ["checkerboard", ..] => Err("usage: checkerboard <on|off>".to_string()),
+            ["subgroups"] => Ok(Command::Subgroups { scalar: false }),
+            ["subgroups", "scalar"] => Ok(Command::Subgroups { scalar: true }),
+            ["subgroups", ..] => Err("usage: subgroups [scalar]".to_string()),
+            ["shader", "reset"] => Ok(Command::LoadShader(None)),
+            ["shader", "vert", path] => Ok(Command::LoadShader(Some((ShaderStage::Vertex, PathBuf::from(path))))),
+            ["shader", "frag", path] => Ok(Command::LoadShader(Some((ShaderStage::Fragment, PathBuf::from(path))))),
+            ["shader", ..] => Err("usage: shader <vert|frag> <file>|reset".to_string()),
+            ["ui", "on"] => Ok(Command::SetDebugUi(true)),
+            ["ui", "off"] => Ok(Command::SetDebugUi(false)),
+            ["ui", ..] => Err("usage: ui <on|off>".to_string()),
+            ["stats"] => Ok(Command::ShowStats),
+            ["stats", "off"] => Ok(Command::SetStats(StatsDisplay::Off)),
+            ["stats", "log"] => Ok(Command::SetStats(StatsDisplay::Log)),
+            ["stats", "title"] => Ok(Command::SetStats(StatsDisplay::Title)),
+            ["stats", ..] => Err("usage: stats [off|log|title]".to_string()),
+            ["plot"] => Ok(Command::ShowPlot),
+            ["plot", "on"] => Ok(Command::SetPlot(true)),
+            ["plot", "off"] => Ok(Command::SetPlot(false)),
+            ["plot", ..] => Err("usage: plot [on|off]".to_string()),
+            ["decal", "clear"] => Ok(Command::ClearDecals),
+            ["decal", kind] => Ok(Command::PlaceDecal(kind.parse()?, None)),
+            ["decal", kind, seconds] => {
+                // too long for a `Duration` is as wrong as negative
+                let lifetime = Some(parse_number(seconds)?)
+                    .filter(|&seconds| seconds > 0.0)
+                    .and_then(|seconds| Duration::try_from_secs_f32(seconds).ok())
+                    .ok_or("decal lifetime must be a positive number of seconds")?;
+                Ok(Command::PlaceDecal(kind.parse()?, Some(lifetime)))
+            }
+            ["decal", ..] => Err("usage: decal <bullet|marking> [seconds] | decal clear".to_string()),
+            ["decals"] => Ok(Command::ListDecals),
+            ["section"] => Ok(Command::ShowSection),
+            ["section", "off"] => Ok(Command::SetSection(None)),
+            ["section", "cap", "on"] => Ok(Command::SetSectionCap(true)),
+            ["section", "cap", "off"] => Ok(Command::SetSectionCap(false)),
+            ["section", normal] => {
+                let SectionNormal(normal) = normal.parse()?;
+                Ok(Command::SetSection(Some((normal, 0.0))))
+            }
+            ["section", normal, distance] => {
+                let SectionNormal(normal) = normal.parse()?;
+                Ok(Command::SetSection(Some((normal, parse_number(distance)?))))
+            }
+            ["section", ..] => {
+                Err("usage: section [<x|y|z|nx,ny,nz> [distance]|off|cap <on|off>]".to_string())
+            }
+            ["audio"] => Ok(Command::ShowAudio),
+            ["audio", "off"] => Ok(Command::LoadAudio(None)),
+            ["audio", path] => Ok(Command::LoadAudio(Some(PathBuf::from(path)))),
+            ["audio", ..] => Err("usage: audio [file.wav|off]".to_string()),
+            ["timeline"] => Ok(Command::ShowTimeline),
+            ["timeline", "off"] => Ok(Command::LoadTimeline(None)),
+            ["timeline", "play"] => Ok(Command::PlayTimeline(true)),
+            ["timeline", "pause"] => Ok(Command::PlayTimeline(false)),
+            ["timeline", "seek", seconds] => Ok(Command::SeekTimeline(parse_number(seconds)?)),
+            ["timeline", path] => Ok(Command::LoadTimeline(Some(PathBuf::from(path)))),
+            ["timeline", ..] => Err("usage: timeline [file|play|pause|seek <seconds>|off]".to_string()),
+            ["path"] => Ok(Command::ShowPath),
+            ["path", "off"] => Ok(Command::LoadPath(None)),
+            ["path", "speed", speed] => Ok(Command::SetPathSpeed(parse_number(speed)?)),
+            ["path", file] => Ok(Command::LoadPath(Some((PathBuf::from(file), PathFollower::Camera)))),
+            ["path", file, "object", id] => {
+                let id = id.parse().map_err(|_| format!("expected an object id, found '{}'", id))?;
+                Ok(Command::LoadPath(Some((PathBuf::from(file), PathFollower::Object(id)))))
+            }
+            ["path", ..] => Err("usage: path [<file> [object <id>]|speed <units/s>|off]".to_string()),
+            ["framelog"] => Ok(Command::ShowFrameLog),
+            ["framelog", "off"] => Ok(Command::FrameLog(None)),
+            ["framelog", path] => Ok(Command::FrameLog(Some(PathBuf::from(path)))),
+            ["framelog", ..] => Err("usage: framelog [file.csv|off]".to_string()),
+            ["points", "off"] => Ok(Command::LoadPoints(None)),
+            ["points", path] => Ok(Command::LoadPoints(Some(PathBuf::from(path)))),
+            ["points"] => Ok(Command::ShowPoints),
+            ["point-size", size] => Ok(Command::SetPointSize(parse_number(size)?)),
+            ["world"] => Ok(Command::ShowWorld),
+            ["world", "on"] => Ok(Command::SetWorld(true)),
+            ["world", "off"] => Ok(Command::SetWorld(false)),
+            ["world", "speed", speed] => Ok(Command::SetWorldSpeed(parse_number(speed)?)),
+            ["world", "splat", "on"] => Ok(Command::SetWorldSplatting(true)),
+            ["world", "splat", "off"] => Ok(Command::SetWorldSplatting(false)),
+            ["world", "triplanar", "on"] => Ok(Command::SetWorldTriplanar(true)),
+            ["world", "triplanar", "off"] => Ok(Command::SetWorldTriplanar(false)),
+            ["world", "grass", "on"] => Ok(Command::SetWorldGrass(true)),
+            ["world", "grass", "off"] => Ok(Command::SetWorldGrass(false)),
+            ["world", ..] => Err(
+                "usage: world [on|off|speed <units/s>|splat <on|off>|triplanar <on|off>|grass <on|off>]".to_string(),
+            ),
+            ["minimap"] => Ok(Command::ShowMinimap),
+            ["minimap", "on"] => Ok(Command::SetMinimap(true)),
+            ["minimap", "off"] => Ok(Command::SetMinimap(false)),
+            ["minimap", "extent", extent] => match parse_number(extent)? {
+                extent if extent > 0.0 && extent.is_finite() => Ok(Command::SetMinimapExtent(extent)),
+                _ => Err(format!("expected a positive extent, found '{}'", extent)),
+            },
+            ["minimap", "every", frames] => match frames.parse() {
+                Ok(frames) if frames > 0 => Ok(Command::SetMinimapInterval(frames)),
+                _ => Err(format!("expected a positive number of frames, found '{}'", frames)),
+            },
+            ["minimap", ..] => Err("usage: minimap [on|off|extent <units>|every <frames>]".to_string()),
+            ["queries"] => Ok(Command::ShowQueries),
+            ["queries", "on"] => Ok(Command::SetQueries(true)),
+            ["queries", "off"] => Ok(Command::SetQueries(false)),
+            ["queries", ..] => Err("usage: queries [on|off]".to_string()),
+            ["portal"] => Ok(Command::ShowPortal),
+            ["portal", "off"] => Ok(Command::SetPortal(None)),
+            ["portal", "depth", depth] => match depth.parse() {
+                Ok(depth) if (1..=portal::MAX_DEPTH).contains(&depth) => Ok(Command::SetPortalDepth(depth)),
+                _ => Err(format!("expected a depth from 1 to {}, found '{}'", portal::MAX_DEPTH, depth)),
+            },
+            ["portal", kind] => Ok(Command::SetPortal(Some(kind.parse()?))),
+            ["portal", ..] => Err("usage: portal [mirror|window|off|depth <views>]".to_string()),
+            ["instances"] => Ok(Command::ShowInstances),
+            ["instances", "on"] => Ok(Command::SetInstances(Some(instancing::DEFAULT_INSTANCES))),
+            ["instances", "off"] => Ok(Command::SetInstances(None)),
+            ["instances", count] => match count.parse() {
+                Ok(count) if count > 0 => Ok(Command::SetInstances(Some(count))),
+                _ => Err(format!("expected a positive instance count, found '{}'", count)),
+            },
+            ["instances", ..] => Err("usage: instances [<count>|on|off]".to_string()),
+            ["particles"] => Ok(Command::ShowParticles),
+            ["particles", "on"] => Ok(Command::SetParticles(Some(compute::DEFAULT_PARTICLES))),
+            ["particles", "off"] => Ok(Command::SetParticles(None)),
+            ["particles", count] => match count.parse() {
+                Ok(count) if count > 0 => Ok(Command::SetParticles(Some(count))),
+                _ => Err(format!("expected a positive particle count, found '{}'", count)),
+            },
+            ["particles", ..] => Err("usage: particles [<count>|on|off]".to_string()),
+            ["parallel"] => Ok(Command::ShowRecordingThreads),
+            ["parallel", "on"] => Ok(Command::SetRecordingThreads(Some(parallel::default_threads()))),
+            ["parallel", "off"] => Ok(Command::SetRecordingThreads(None)),
+            ["parallel", threads] => match threads.parse() {
+                Ok(threads) if threads > 0 => Ok(Command::SetRecordingThreads(Some(threads))),
+                _ => Err(format!("expected a positive thread count, found '{}'", threads)),
+            },
+            ["parallel", ..] => Err("usage: parallel [<threads>|on|off]".to_string()),
+            ["grade"] => Ok(Command::ShowGrade),
+            ["grade", "off"] => Ok(Command::SetGrade(None)),
+            ["grade", name] => match Grade::preset(name) {
+                Some(grade) => Ok(Command::SetGrade(Some(grade))),
+                None => Err(format!("unknown grade '{}', expected warm, cool, mono, film or off", name)),
+            },
+            ["vsync", "on"] => Ok(Command::SetVsync(true)),
+            ["vsync", "off"] => Ok(Command::SetVsync(false)),
+            ["vsync", ..] => Err("usage: vsync <on|off>".to_string()),
+            ["present"] => Ok(Command::ShowPresentMode),
+            ["present", mode] => swapchain::parse_present_mode(mode).map(Command::SetPresentMode),
+            ["present", ..] => Err("usage: present [auto|fifo|relaxed|mailbox|immediate]".to_string()),
+            ["vertices", path] => Ok(Command::CaptureVertices(PathBuf::from(path))),
+            ["vertices", ..] => Err("usage: vertices <file.csv>".to_string()),
+            ["cull"] => Ok(Command::ShowCulling),
+            ["cull", "on"] => Ok(Command::SetCulling(true)),
+            ["cull", "off"] => Ok(Command::SetCulling(false)),
+            ["cull", ..] => Err("usage: cull [on|off]".to_string()),
+            ["light"] => Ok(Command::ShowLight),
+            ["light", "color", r, g, b] => Ok(Command::SetLightColor([
+                parse_number(r)?,
+                parse_number(g)?,
+                parse_number(b)?,
+            ])),
+            ["light", "ambient", ambient] => Ok(Command::SetAmbient(parse_number(ambient)?)),
+            ["light", "shininess", shininess] => match parse_number(shininess)? {
+                shininess if shininess >= 1.0 => Ok(Command::SetShininess(shininess)),
+                _ => Err(format!("expected a shininess of at least 1, got '{}'", shininess)),
+            },
+            ["light", x, y, z] => Ok(Command::SetLightPosition(Point3::new(
+                parse_number(x)?,
+                parse_number(y)?,
+                parse_number(z)?,
+            ))),
+            ["light", ..] => Err(
+                "usage: light [<x> <y> <z>|color <r> <g> <b>|ambient <0..1>|shininess <exponent>]".to_string(),
+            ),
+            ["lights"] => Ok(Command::ShowLightCount),
+            ["lights", "off"] => Ok(Command::SetLightCount(0)),
+            ["lights", "heatmap", "on"] => Ok(Command::SetLightHeatmap(true)),
+            ["lights", "heatmap", "off"] => Ok(Command::SetLightHeatmap(false)),
+            ["lights", count] => match count.parse() {
+                Ok(count) if (1..=clusters::MAX_LIGHTS).contains(&count) => Ok(Command::SetLightCount(count)),
+                _ => Err(format!("expected a light count from 1 to {}, got '{}'", clusters::MAX_LIGHTS, count)),
+            },
+            ["lights", ..] => Err("usage: lights [<count>|off|heatmap <on|off>]".to_string()),
+            ["hiz"] => Ok(Command::ShowHiZ),
+            ["hiz", "off"] => Ok(Command::SetHiZView(None)),
+            ["hiz", level] => match level.parse() {
+                Ok(level) => Ok(Command::SetHiZView(Some(level))),
+                Err(_) => Err(format!("expected a level number or off, found '{}'", level)),
+            },
+            ["hiz", ..] => Err("usage: hiz [<level>|off]".to_string()),
+            ["grade", ..] => Err("usage: grade <warm|cool|mono|film|off>".to_string()),
+            ["load", ..] => Err("usage: load <file.ply|file.stl|file.obj>".to_string()),
+            ["points", ..] | ["point-size", ..] => {
+                Err("usage: points [<file.xyz|file.ply>|off], point-size <pixels>".to_string())
+            }
+            [other, ..] => Err(format!("unknown command '{}'", other)),
+            [] => Err("empty command".to_string()),
+        }
+    }
+}
+
+/// Reads commands from stdin on a background thread so the render loop never blocks on input
+pub struct Console {
+    lines: Receiver<String>,
+    sender: Sender<String>,
+}
+
+impl Console {
+    pub fn spawn() -> Self {
+        let (sender, lines) = mpsc::channel();
+        let stdin_sender = sender.clone();
+
+        thread::Builder::new()
+            .name("console".to_string())
+            .spawn(move || {
+                // stops once stdin is closed or the application has gone away
+                for line in io::stdin().lock().lines().map_while(Result::ok) {
+                    if stdin_sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn console thread");
+
+        Self { lines, sender }
+    }
+
+    /// Lines sent here are handled like the ones typed into the terminal
+    pub fn sender(&self) -> Sender<String> {
+        self.sender.clone()
+    }
+
+    /// Returns all commands entered since the last call, reporting the ones that failed to parse
+    pub fn poll(&self) -> Vec<Command> {
+        self.lines
+            .try_iter()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match line.parse() {
+                Ok(command) => Some(command),
+                Err(e) => {
+                    println!("console: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Command, String> {
+        line.parse()
+    }
+
+    #[test]
+    fn commands_with_arguments() {
+        assert!(matches!(parse("view uv"), Ok(Command::SetDebugView(DebugView::Uv))));
+        assert!(matches!(parse("  despawn   all "), Ok(Command::Despawn(None))));
+        assert!(matches!(parse("despawn 3"), Ok(Command::Despawn(Some(3)))));
+        assert!(matches!(parse("near 0.5"), Ok(Command::SetNear(near)) if near == 0.5));
+        match parse("spawn 1 -2 3.5") {
+            Ok(Command::Spawn(point)) => assert_eq!(point, Point3::new(1.0, -2.0, 3.5)),
+            other => panic!("expected spawn, got {:?}", other),
+        }
+        assert!(matches!(parse("vsync off"), Ok(Command::SetVsync(false))));
+        assert!(matches!(parse("present mailbox"), Ok(Command::SetPresentMode(Some(PresentMode::Mailbox)))));
+        assert!(matches!(parse("present auto"), Ok(Command::SetPresentMode(None))));
+        assert!(matches!(parse("world splat off"), Ok(Command::SetWorldSplatting(false))));
+        assert!(matches!(parse("world triplanar on"), Ok(Command::SetWorldTriplanar(true))));
+        assert!(matches!(parse("world grass off"), Ok(Command::SetWorldGrass(false))));
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert!(parse("frobnicate").is_err());
+        assert!(parse("view").is_err());
+        assert!(parse("view sideways").is_err());
+        assert!(parse("spawn 1 2").is_err());
+        assert!(parse("despawn one").is_err());
+        assert!(parse("fov wide").is_err());
+        assert!(parse("decal bullet -1").is_err());
+        assert!(parse("decal bullet 1e20").is_err());
+        assert!(parse("decal bullet inf").is_err());
+        assert!(parse("near nan").is_err());
+        assert!(parse("far inf").is_err());
+        assert!(parse("fov NaN").is_err());
+        assert!(matches!(parse("decal bullet 2.5"), Ok(Command::PlaceDecal(_, Some(_)))));
+    }
+}
@@ -0,0 +1,51 @@
+//! Skipping spawned objects outside the view frustum. The decision is made on the CPU while the
+//! scene's draws are collected. `VK_EXT_conditional_rendering` would let the GPU make it from
+//! a predicate buffer or occlusion results without a readback, but vulkano 0.24 can neither
+//! create a buffer with the conditional rendering usage nor record the extension's commands
+//! into the command buffers it builds, so the device's support is only reported.
+
+use std::fmt;
+
+use vulkano::{device::DeviceExtensions, instance::PhysicalDevice};
+
+/// Whether the device could skip draws from a predicate written on the GPU
+pub fn conditional_rendering_supported(physical_device: PhysicalDevice) -> bool {
+    DeviceExtensions::supported_by_device(physical_device).ext_conditional_rendering
+        && physical_device.supported_features().conditional_rendering
+}
+
+/// How the scene's draws are culled, toggled with the `cull` command
+pub struct Culling {
+    /// Skips objects outside the frustum, otherwise they are only counted
+    pub enabled: bool,
+    conditional_rendering: bool,
+}
+
+impl Culling {
+    pub fn new(physical_device: PhysicalDevice) -> Self {
+        Self {
+            enabled: true,
+            conditional_rendering: conditional_rendering_supported(physical_device),
+        }
+    }
+
+    /// Whether the device has `VK_EXT_conditional_rendering`
+    pub fn conditional_rendering(&self) -> bool {
+        self.conditional_rendering
+    }
+}
+
+impl fmt::Display for Culling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.enabled {
+            write!(f, "objects outside the frustum are skipped on the CPU")?;
+        } else {
+            write!(f, "off, every object is drawn")?;
+        }
+        if self.conditional_rendering {
+            write!(f, " (the device supports conditional rendering, which vulkano 0.24 can't record)")
+        } else {
+            write!(f, " (the device has no conditional rendering)")
+        }
+    }
+}
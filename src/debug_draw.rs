@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use cgmath::{Point3, Vector3};
+use vulkano::{
+    device::Device,
+    impl_vertex,
+    pipeline::{GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{RenderPass, Subpass},
+};
+
+use crate::error::Error;
+
+pub mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/line.vert"
+    }
+}
+
+pub mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/line.frag"
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl_vertex!(LineVertex, position, color);
+
+/// Segments of the seven segment digits, in a cell one unit wide and two units high
+const SEGMENTS: [[[f32; 2]; 2]; 7] = [
+    [[0.0, 2.0], [1.0, 2.0]], // top
+    [[1.0, 2.0], [1.0, 1.0]], // top right
+    [[1.0, 1.0], [1.0, 0.0]], // bottom right
+    [[0.0, 0.0], [1.0, 0.0]], // bottom
+    [[0.0, 0.0], [0.0, 1.0]], // bottom left
+    [[0.0, 1.0], [0.0, 2.0]], // top left
+    [[0.0, 1.0], [1.0, 1.0]], // middle
+];
+
+/// Bit `i` set means segment `i` of `SEGMENTS` is lit
+const DIGITS: [u8; 10] = [
+    0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110, 0b1101101, 0b1111101, 0b0000111, 0b1111111,
+    0b1101111,
+];
+
+/// Horizontal distance between glyphs, in cell units
+const ADVANCE: f32 = 1.6;
+
+/// Line list in world space, rebuilt whenever what it shows changes
+#[derive(Default)]
+pub struct DebugLines {
+    vertices: Vec<LineVertex>,
+}
+
+impl DebugLines {
+    pub fn line(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 3]) {
+        self.vertices.push(LineVertex {
+            position: from.into(),
+            color,
+        });
+        self.vertices.push(LineVertex {
+            position: to.into(),
+            color,
+        });
+    }
+
+    /// Three axis aligned lines crossing at `center`
+    pub fn cross(&mut self, center: Point3<f32>, size: f32, color: [f32; 3]) {
+        for axis in &[Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            let half = axis * (size / 2.0);
+            self.line(center - half, center + half, color);
+        }
+    }
+
+    /// Draws digits, '.' and '-' with seven segment strokes, other characters leave a gap.
+    /// `right` and `up` span one glyph cell, so their length sets the text size.
+    pub fn text(&mut self, text: &str, origin: Point3<f32>, right: Vector3<f32>, up: Vector3<f32>, color: [f32; 3]) {
+        let to_world = |cell: Point3<f32>, [x, y]: [f32; 2]| cell + right * x + up * (y / 2.0);
+
+        for (i, character) in text.chars().enumerate() {
+            let cell = origin + right * (i as f32 * ADVANCE);
+            let segments = match character {
+                '0'..='9' => DIGITS[character as usize - '0' as usize],
+                '-' => 0b1000000,
+                '.' => {
+                    self.line(to_world(cell, [0.5, 0.0]), to_world(cell, [0.5, 0.25]), color);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            for (segment, [from, to]) in SEGMENTS.iter().enumerate() {
+                if segments & (1 << segment) != 0 {
+                    self.line(to_world(cell, *from), to_world(cell, *to), color);
+                }
+            }
+        }
+    }
+
+    /// Width of `text` in cell units, to center it
+    pub fn text_width(text: &str) -> f32 {
+        (text.chars().count() as f32 - 1.0).max(0.0) * ADVANCE + 1.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn vertices(&self) -> &[LineVertex] {
+        &self.vertices
+    }
+}
+
+/// Lines are drawn without depth testing, so they stay visible behind geometry
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "line vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "line fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<LineVertex>()
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .line_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
@@ -0,0 +1,335 @@
+use std::sync::Arc;
+
+use egui::{ClippedMesh, CtxRef, TextureId};
+use log::error;
+use vulkano::{
+    buffer::{BufferUsage, CpuBufferPool},
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::{descriptor_set::PersistentDescriptorSet, DescriptorSet},
+    device::{Device, Queue},
+    format::{ClearValue, Format},
+    image::{view::ImageView, ImageAccess, SwapchainImage},
+    impl_vertex,
+    pipeline::{
+        blend::{AttachmentBlend, BlendFactor, BlendOp},
+        viewport::{Scissor, Viewport},
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+use winit::{event::WindowEvent, window::Window};
+
+use crate::staging::Staging;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/ui.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/ui.frag"
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct UiVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl_vertex!(UiVertex, position, uv, color);
+
+/// Values the debug panel lets the user change, written back by the app after every frame
+pub struct PanelValues {
+    pub clear_color: [f32; 3],
+    /// Radians per second of scenes that rotate
+    pub rotation_speed: f32,
+    /// Playback position and length of the loaded timeline, the panel can scrub through it
+    pub timeline: Option<(f32, f32)>,
+}
+
+/// An egui panel with what the app reports about the device and the frame. Input goes to the
+/// panel first while it is shown, the window only sees what the panel didn't use.
+pub struct DebugUi {
+    pub enabled: bool,
+    context: CtxRef,
+    state: egui_winit::State,
+}
+
+impl DebugUi {
+    pub fn new(window: &Window) -> Self {
+        Self {
+            enabled: false,
+            context: CtxRef::default(),
+            state: egui_winit::State::new(window),
+        }
+    }
+
+    /// Whether the panel took `event`, always `false` while it is hidden
+    pub fn on_event(&mut self, event: &WindowEvent<'_>) -> bool {
+        self.enabled && self.state.on_event(&self.context, event)
+    }
+
+    /// Lays the panel out for this frame, `report` are the lines of `(name, value)` it shows.
+    /// Nothing is drawn while the panel is hidden.
+    pub fn run(&mut self, window: &Window, report: &[(&str, String)], values: &mut PanelValues) -> Vec<ClippedMesh> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        self.context.begin_frame(self.state.take_egui_input(window));
+        egui::Window::new("Debug").default_width(280.0).show(&self.context, |ui| {
+            egui::Grid::new("report").num_columns(2).show(ui, |ui| {
+                for (name, value) in report {
+                    ui.label(*name);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Clear color");
+                ui.color_edit_button_rgb(&mut values.clear_color);
+            });
+            ui.add(egui::Slider::new(&mut values.rotation_speed, -5.0..=5.0).text("Rotation speed"));
+            if let Some((time, duration)) = &mut values.timeline {
+                ui.add(egui::Slider::new(time, 0.0..=*duration).text("Timeline"));
+            }
+        });
+
+        let (output, shapes) = self.context.end_frame();
+        self.state.handle_output(window, &self.context, output);
+        self.context.tessellate(shapes)
+    }
+
+    /// Logical pixels are scaled by this to physical ones
+    pub fn pixels_per_point(&self) -> f32 {
+        self.context.pixels_per_point()
+    }
+
+    pub fn font(&self) -> Arc<egui::Texture> {
+        self.context.texture()
+    }
+}
+
+/// Draws egui's meshes onto the swap chain image in a render pass of their own, after the post
+/// pass and the other overlays. egui's colors are sRGB, which the UNORM swap chain takes as is.
+pub struct UiRenderer {
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    dimensions: [u32; 2],
+    /// The meshes change every frame, the pools reuse buffers the GPU is done with
+    vertex_pool: CpuBufferPool<UiVertex>,
+    index_pool: CpuBufferPool<u32>,
+    sampler: Arc<Sampler>,
+    /// Version of the font texture that was uploaded and its descriptor set
+    font: Option<(u64, Arc<dyn DescriptorSet + Send + Sync>)>,
+}
+
+impl UiRenderer {
+    /// Recreated with the swap chain, like the framebuffers it draws into
+    pub fn new(device: &Arc<Device>, swap_chain_images: &[Arc<SwapchainImage<Window>>], color_format: Format) -> Self {
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+            .expect("Failed to create UI vertex shader module");
+        let frag_shader_module = fragment_shader::Shader::load(device.clone())
+            .expect("Failed to create UI fragment shader module");
+
+        // egui's colors have premultiplied alpha
+        let premultiplied = AttachmentBlend {
+            enabled: true,
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::One,
+            color_destination: BlendFactor::OneMinusSrcAlpha,
+            alpha_op: BlendOp::Add,
+            alpha_source: BlendFactor::OneMinusDstAlpha,
+            alpha_destination: BlendFactor::One,
+            mask_red: true,
+            mask_green: true,
+            mask_blue: true,
+            mask_alpha: true,
+        };
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<UiVertex>()
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports_scissors_dynamic(1)
+                .fragment_shader(frag_shader_module.main_entry_point(), ())
+                .cull_mode_disabled()
+                .blend_collective(premultiplied)
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let framebuffers = swap_chain_images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Arc::new(Framebuffer::start(render_pass.clone()).add(view).unwrap().build().unwrap())
+                    as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        Self {
+            pipeline,
+            framebuffers,
+            dimensions: swap_chain_images[0].dimensions().width_height(),
+            vertex_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            index_pool: CpuBufferPool::new(device.clone(), BufferUsage::index_buffer()),
+            sampler,
+            font: None,
+        }
+    }
+
+    /// Uploads egui's font texture when it changed since the last upload, waiting for the copy
+    pub fn update_font(&mut self, queue: &Arc<Queue>, font: &egui::Texture) {
+        if self.font.as_ref().is_some_and(|(version, _)| *version == font.version) {
+            return;
+        }
+
+        let pixels: Vec<_> = font.srgba_pixels(1.0).map(|color| color.to_array()).collect();
+        let mut staging = Staging::new(queue);
+        let image = staging
+            .image(pixels.into_iter(), [font.width as u32, font.height as u32], Format::R8G8B8A8Unorm)
+            .and_then(|image| {
+                staging.submit_and_wait()?;
+                Ok(image)
+            });
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => {
+                error!("Failed to upload the UI font: {}", e);
+                return;
+            }
+        };
+
+        let layout = self.pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(ImageView::new(image).unwrap(), self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        self.font = Some((font.version, set));
+    }
+
+    /// Records a render pass drawing `meshes`, whose coordinates are in logical pixels
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        meshes: &[ClippedMesh],
+        pixels_per_point: f32,
+    ) {
+        let font = match &self.font {
+            Some((_, font)) if !meshes.is_empty() => font,
+            _ => return,
+        };
+
+        let [width, height] = self.dimensions;
+        let push_constants = vertex_shader::ty::PushConstants {
+            screen_size: [width as f32 / pixels_per_point, height as f32 / pixels_per_point],
+        };
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_index].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap();
+
+        // only the font texture is used, nothing registers textures of its own
+        for ClippedMesh(clip, mesh) in meshes.iter().filter(|ClippedMesh(_, mesh)| mesh.texture_id == TextureId::Egui) {
+            let min = [
+                (clip.min.x * pixels_per_point).round().clamp(0.0, width as f32) as u32,
+                (clip.min.y * pixels_per_point).round().clamp(0.0, height as f32) as u32,
+            ];
+            let max = [
+                (clip.max.x * pixels_per_point).round().clamp(0.0, width as f32) as u32,
+                (clip.max.y * pixels_per_point).round().clamp(0.0, height as f32) as u32,
+            ];
+            if mesh.indices.is_empty() || max[0] <= min[0] || max[1] <= min[1] {
+                continue;
+            }
+
+            let dynamic_state = DynamicState {
+                viewports: Some(vec![viewport.clone()]),
+                scissors: Some(vec![Scissor {
+                    origin: [min[0] as i32, min[1] as i32],
+                    dimensions: [max[0] - min[0], max[1] - min[1]],
+                }]),
+                ..DynamicState::none()
+            };
+            let vertices = mesh.vertices.iter().map(|vertex| UiVertex {
+                position: [vertex.pos.x, vertex.pos.y],
+                uv: [vertex.uv.x, vertex.uv.y],
+                color: vertex.color.to_array().map(|channel| channel as f32 / 255.0),
+            });
+            let vertex_buffer = self.vertex_pool.chunk(vertices).unwrap();
+            let index_buffer = self.index_pool.chunk(mesh.indices.iter().copied()).unwrap();
+
+            builder
+                .draw_indexed(
+                    self.pipeline.clone(),
+                    &dynamic_state,
+                    vec![Arc::new(vertex_buffer)],
+                    index_buffer,
+                    font.clone(),
+                    push_constants,
+                    vec![],
+                )
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+use std::{ffi::CString, sync::Arc};
+
+use ash::vk::{self, Handle};
+use vulkano::{device::Device, VulkanObject};
+
+/// Attaches a name to a Vulkan object so validation messages and capture tools refer to it by name.
+/// Does nothing when `VK_EXT_debug_utils` is not enabled.
+pub fn set_object_name<T: VulkanObject>(device: &Arc<Device>, object: &T, name: &str) {
+    let instance = device.instance();
+    if !instance.loaded_extensions().ext_debug_utils {
+        return;
+    }
+
+    let name = match CString::new(name) {
+        Ok(name) => name,
+        Err(_) => return,
+    };
+
+    let handle = object.internal_object();
+    let info = vk::DebugUtilsObjectNameInfoEXT {
+        object_type: T::Object::TYPE,
+        object_handle: handle.as_raw(),
+        p_object_name: name.as_ptr(),
+        ..Default::default()
+    };
+
+    unsafe {
+        // naming is purely diagnostic, a failure here must not affect rendering
+        let _ = instance
+            .fns()
+            .ext_debug_utils
+            .set_debug_utils_object_name_ext(device.internal_object(), &info);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{fmt, str::FromStr};
+
+/// Which fragment shader is used to shade the scene
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    /// Regular shading
+    #[default]
+    Shaded,
+    /// Blinn-Phong shading by the point light of `lighting::Light`
+    Lit,
+    /// UV coordinates and their screen-space derivatives
+    Uv,
+    /// Mip level the sampler would pick for each pixel
+    MipLevel,
+    /// Distance from the camera with lines showing how depth buffer precision is distributed
+    Depth,
+}
+
+pub mod lit_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/lit.frag"
+    }
+}
+
+pub mod uv_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/debug_uv.frag"
+    }
+}
+
+pub mod mip_level_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/debug_mip.frag"
+    }
+}
+
+pub mod depth_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/debug_depth.frag"
+    }
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 5] = [
+        DebugView::Shaded,
+        DebugView::Lit,
+        DebugView::Uv,
+        DebugView::MipLevel,
+        DebugView::Depth,
+    ];
+
+    /// Source file of the fragment shader, in `src/shaders`
+    pub fn shader_file(&self) -> &'static str {
+        match self {
+            DebugView::Shaded => "triangle.frag",
+            DebugView::Lit => "lit.frag",
+            DebugView::Uv => "debug_uv.frag",
+            DebugView::MipLevel => "debug_mip.frag",
+            DebugView::Depth => "debug_depth.frag",
+        }
+    }
+}
+
+impl FromStr for DebugView {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shaded" | "none" => Ok(DebugView::Shaded),
+            "lit" => Ok(DebugView::Lit),
+            "uv" => Ok(DebugView::Uv),
+            "mip" => Ok(DebugView::MipLevel),
+            "depth" => Ok(DebugView::Depth),
+            other => Err(format!(
+                "unknown debug view '{}', expected shaded, lit, uv, mip or depth",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DebugView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DebugView::Shaded => "shaded",
+            DebugView::Lit => "lit",
+            DebugView::Uv => "uv",
+            DebugView::MipLevel => "mip",
+            DebugView::Depth => "depth",
+        };
+
+        write!(f, "{}", name)
+    }
+}
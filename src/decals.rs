@@ -0,0 +1,416 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3, Vector4};
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::{descriptor_set::PersistentDescriptorSet, DescriptorSet},
+    device::{Device, Queue},
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImmutableImage, SwapchainImage},
+    pipeline::{
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+use winit::window::Window;
+
+use crate::{error::Error, staging::Staging};
+
+/// Oldest decals are removed once there are more than this
+const MAX_DECALS: usize = 64;
+/// Decals fade out over the last part of their lifetime
+const FADE_TIME: Duration = Duration::from_secs(1);
+/// Depth of the projection box relative to its size, how far from the picked point surfaces still get the decal
+const BOX_DEPTH: f32 = 0.5;
+/// Width and height of one decal in the atlas
+const TILE_SIZE: u32 = 64;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/decal.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/decal.frag"
+    }
+}
+
+/// Which tile of the decal atlas is projected
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecalKind {
+    BulletHole = 0,
+    /// Yellow and black hazard stripes
+    Marking = 1,
+}
+
+const KINDS: [DecalKind; 2] = [DecalKind::BulletHole, DecalKind::Marking];
+
+impl FromStr for DecalKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bullet" => Ok(DecalKind::BulletHole),
+            "marking" => Ok(DecalKind::Marking),
+            other => Err(format!("unknown decal '{}', expected bullet or marking", other)),
+        }
+    }
+}
+
+impl fmt::Display for DecalKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DecalKind::BulletHole => "bullet",
+            DecalKind::Marking => "marking",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A texture projected onto whatever surfaces are inside a box placed on the scene
+#[derive(Copy, Clone, Debug)]
+pub struct Decal {
+    pub id: u32,
+    pub kind: DecalKind,
+    pub position: Point3<f32>,
+    /// Points away from the surface, the texture is projected along it
+    pub normal: Vector3<f32>,
+    pub size: f32,
+    spawned: Instant,
+    /// `None` keeps the decal until it is removed
+    lifetime: Option<Duration>,
+}
+
+impl Decal {
+    /// Box centered on the surface, its Z axis along the normal
+    pub fn model(&self) -> Matrix4<f32> {
+        let z = self.normal.normalize();
+        let reference = if z.y.abs() < 0.99 { Vector3::unit_y() } else { Vector3::unit_x() };
+        let x = reference.cross(z).normalize();
+        let y = z.cross(x);
+
+        Matrix4::from_cols(
+            (x * self.size).extend(0.0),
+            (y * self.size).extend(0.0),
+            (z * self.size * BOX_DEPTH).extend(0.0),
+            Vector4::new(self.position.x, self.position.y, self.position.z, 1.0),
+        )
+    }
+
+    /// 1.0 until the decal starts fading out at the end of its lifetime
+    pub fn opacity(&self, now: Instant) -> f32 {
+        match self.lifetime {
+            Some(lifetime) => {
+                let remaining = lifetime.saturating_sub(now.saturating_duration_since(self.spawned));
+                (remaining.as_secs_f32() / FADE_TIME.as_secs_f32()).min(1.0)
+            }
+            None => 1.0,
+        }
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        matches!(self.lifetime, Some(lifetime) if now.saturating_duration_since(self.spawned) >= lifetime)
+    }
+}
+
+impl fmt::Display for Decal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} at ({:.2}, {:.2}, {:.2})",
+            self.id, self.kind, self.position.x, self.position.y, self.position.z
+        )?;
+        match self.lifetime {
+            Some(lifetime) => {
+                let remaining = lifetime.saturating_sub(self.spawned.elapsed());
+                write!(f, ", {:.1}s left", remaining.as_secs_f32())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Decals placed on the scene, removed when their lifetime runs out
+#[derive(Default)]
+pub struct DecalList {
+    decals: Vec<Decal>,
+    next_id: u32,
+}
+
+impl DecalList {
+    pub fn spawn(
+        &mut self,
+        kind: DecalKind,
+        position: Point3<f32>,
+        normal: Vector3<f32>,
+        size: f32,
+        lifetime: Option<Duration>,
+    ) -> Decal {
+        let decal = Decal {
+            id: self.next_id,
+            kind,
+            position,
+            normal,
+            size,
+            spawned: Instant::now(),
+            lifetime,
+        };
+
+        self.next_id += 1;
+        if self.decals.len() >= MAX_DECALS {
+            self.decals.remove(0);
+        }
+        self.decals.push(decal);
+        decal
+    }
+
+    /// Drops decals whose lifetime has run out, called once per frame
+    pub fn remove_expired(&mut self) {
+        let now = Instant::now();
+        self.decals.retain(|decal| !decal.is_expired(now));
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Decal> {
+        self.decals.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decals.is_empty()
+    }
+}
+
+/// Draws decals in a render pass after the scene. Every decal draws the back faces of its box
+/// without depth testing, and each covered pixel reads the scene's depth to find the surface
+/// behind it. Only the parts of that surface inside the box get the texture.
+pub struct DecalRenderer {
+    pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    descriptor_set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl DecalRenderer {
+    /// Recreated with the swap chain, like the framebuffers it draws into
+    pub fn new(
+        device: &Arc<Device>,
+        queue: &Arc<Queue>,
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        color_format: Format,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Result<Self, Error> {
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+
+        // the depth buffer is smaller when the scene is drawn at a lower resolution
+        let pipeline = Self::create_pipeline(device, swap_chain_images[0].dimensions(), &render_pass)?;
+
+        let framebuffers = swap_chain_images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Arc::new(Framebuffer::start(render_pass.clone()).add(view).unwrap().build().unwrap())
+                    as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        // depth formats don't have to support linear filtering
+        let depth_sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+        let atlas_sampler = Sampler::simple_repeat_linear_no_mipmap(device.clone());
+
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let descriptor_set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(depth_buffer.clone(), depth_sampler)
+                .unwrap()
+                .add_sampled_image(create_atlas(queue)?, atlas_sampler)
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Ok(Self {
+            pipeline,
+            framebuffers,
+            descriptor_set,
+        })
+    }
+
+    fn create_pipeline(
+        device: &Arc<Device>,
+        dimensions: [u32; 2],
+        render_pass: &Arc<RenderPass>,
+    ) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "decal vertex",
+            source,
+        })?;
+        let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "decal fragment",
+            source,
+        })?;
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        // back faces still cover the box on screen when the camera is inside it
+        Ok(Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport])
+                .fragment_shader(frag_shader_module.main_entry_point(), ())
+                .front_face_counter_clockwise()
+                .cull_mode_front()
+                .blend_alpha_blending()
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())?,
+        ))
+    }
+
+    /// Records the decal pass, nothing is recorded when there are no decals
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        view_projection: Matrix4<f32>,
+        decals: &DecalList,
+    ) {
+        if decals.is_empty() {
+            return;
+        }
+
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_index].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap();
+
+        let now = Instant::now();
+        for decal in decals.iter() {
+            let push_constants = vertex_shader::ty::PushConstants {
+                mvp: (view_projection * decal.model()).into(),
+                opacity: decal.opacity(now),
+                kind: decal.kind as u32,
+            };
+
+            builder
+                .draw(
+                    self.pipeline.clone(),
+                    &DynamicState::none(),
+                    BufferlessVertices {
+                        vertices: 36,
+                        instances: 1,
+                    },
+                    self.descriptor_set.clone(),
+                    push_constants,
+                    vec![],
+                )
+                .unwrap();
+        }
+
+        builder.end_render_pass().unwrap();
+    }
+}
+
+/// The decal kinds side by side, drawn procedurally until textures can be loaded from files
+fn create_atlas(queue: &Arc<Queue>) -> Result<Arc<ImageView<Arc<ImmutableImage>>>, Error> {
+    let width = TILE_SIZE * KINDS.len() as u32;
+    let pixels = (0..TILE_SIZE).flat_map(move |y| {
+        (0..width).map(move |x| {
+            let kind = KINDS[(x / TILE_SIZE) as usize];
+            // -1 to 1 across the tile
+            let half = TILE_SIZE as f32 / 2.0;
+            let u = ((x % TILE_SIZE) as f32 + 0.5) / half - 1.0;
+            let v = (y as f32 + 0.5) / half - 1.0;
+            match kind {
+                DecalKind::BulletHole => bullet_hole(u, v),
+                DecalKind::Marking => marking(u, v),
+            }
+        })
+    });
+
+    let mut staging = Staging::new(queue);
+    let image = staging.image(pixels.collect::<Vec<_>>().into_iter(), [width, TILE_SIZE], Format::R8G8B8A8Srgb)?;
+    staging.submit_and_wait()?;
+
+    Ok(ImageView::new(image).unwrap())
+}
+
+/// Dark hole with a scorched ring and a few cracks
+fn bullet_hole(u: f32, v: f32) -> [u8; 4] {
+    let radius = (u * u + v * v).sqrt();
+    let cracks = (v.atan2(u) * 7.0).sin().abs().powi(8) * 0.25;
+
+    if radius < 0.3 {
+        [15, 13, 12, 255]
+    } else if radius < 0.55 + cracks {
+        let scorch = (radius - 0.3) / (0.25 + cracks);
+        let shade = (30.0 + 50.0 * scorch) as u8;
+        [shade, (shade as f32 * 0.9) as u8, (shade as f32 * 0.8) as u8, (255.0 * (1.0 - scorch * scorch)) as u8]
+    } else {
+        [0, 0, 0, 0]
+    }
+}
+
+/// Diagonal yellow and black stripes in a square
+fn marking(u: f32, v: f32) -> [u8; 4] {
+    if u.abs() > 0.9 || v.abs() > 0.9 {
+        return [0, 0, 0, 0];
+    }
+
+    if ((u + v) * 3.0).floor() as i32 % 2 == 0 {
+        [230, 180, 20, 235]
+    } else {
+        [25, 25, 25, 235]
+    }
+}
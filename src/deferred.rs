@@ -0,0 +1,365 @@
+//! Deferred shading, picked with the `deferred` setting in place of shading while drawing. One
+//! render pass with two subpasses: the first draws the scene's meshes into a G-buffer of albedo,
+//! normal and depth attachments, the second runs a fullscreen triangle that reads them back as
+//! input attachments and lights every pixel once, however many triangles overlapped it. Like
+//! `color_grade`, the render pass is described by hand so the dependency between the subpasses
+//! says what the lighting waits for: the color and depth writes of the same pixel. Albedo and
+//! normals never leave the render pass, the depth is stored for the passes that read it after.
+
+use std::sync::Arc;
+
+use cgmath::{EuclideanSpace, Matrix4, Point3, SquareMatrix, Transform};
+use vulkano::{
+    buffer::CpuBufferPool,
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    device::Device,
+    format::Format,
+    image::{view::ImageView, AttachmentImage, ImageAccess, ImageLayout, ImageUsage, SampleCount},
+    pipeline::{
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{
+        AttachmentDesc, Framebuffer, FramebufferAbstract, LoadOp, RenderPass, RenderPassDesc, StoreOp, Subpass,
+        SubpassDependencyDesc, SubpassDesc,
+    },
+    sync::{AccessFlags, PipelineStages},
+};
+
+use crate::{
+    descriptors::{self, Resource},
+    error::Error,
+    lighting::Light,
+    parallel::SceneDraw,
+    post,
+    renderer::{self, vertex_shader, DEPTH_FORMAT},
+    vertex::Vertex,
+};
+
+/// Vertex colors times the texture, 8 bits are all they have
+const ALBEDO_FORMAT: Format = Format::R8G8B8A8Unorm;
+/// World space normals, signed
+const NORMAL_FORMAT: Format = Format::R16G16B16A16Sfloat;
+
+mod gbuffer_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/gbuffer.frag"
+    }
+}
+
+mod fullscreen_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod light_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/deferred_light.frag"
+    }
+}
+
+/// The uniform buffer of deferred_light.frag, std140 like the others
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct LightParams {
+    inverse_view_projection: [[f32; 4]; 4],
+    light_position: [f32; 3],
+    ambient: f32,
+    light_color: [f32; 3],
+    shininess: f32,
+    camera_position: [f32; 3],
+}
+
+/// The G-buffer and both subpasses, drawn in place of the scene pass and recreated with the
+/// swap chain
+pub struct Deferred {
+    /// Without and with culling disabled, for cross sections, like the scene pipeline variants
+    gbuffer_pipelines: [Arc<dyn GraphicsPipelineAbstract + Send + Sync>; 2],
+    light_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    /// Albedo, normal and depth, in the bindings deferred_light.frag reads them from
+    inputs: [Resource; 3],
+    params: CpuBufferPool<LightParams>,
+    scene_size: [u32; 2],
+}
+
+impl Deferred {
+    /// `target` is the post pass's input the lit scene is written to, `depth_buffer` the scene's
+    /// depth, which has to be an input attachment too
+    pub fn new(
+        device: &Arc<Device>,
+        target: &Arc<ImageView<Arc<AttachmentImage>>>,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Result<Self, Error> {
+        let scene_size = target.image().dimensions().width_height();
+        // only ever read inside the render pass, so it needs no memory on GPUs that keep it on chip
+        let usage = ImageUsage {
+            color_attachment: true,
+            input_attachment: true,
+            transient_attachment: true,
+            ..ImageUsage::none()
+        };
+        let attachment = |format| {
+            let image = AttachmentImage::with_usage(device.clone(), scene_size, format, usage).unwrap();
+            ImageView::new(image).unwrap()
+        };
+        let albedo = attachment(ALBEDO_FORMAT);
+        let normal = attachment(NORMAL_FORMAT);
+
+        let render_pass = create_render_pass(device);
+        let gbuffer_pipelines = [
+            create_gbuffer_pipeline(device, &render_pass, false)?,
+            create_gbuffer_pipeline(device, &render_pass, true)?,
+        ];
+        let light_pipeline = create_light_pipeline(device, &render_pass, scene_size)?;
+        let framebuffer = Arc::new(
+            Framebuffer::start(render_pass)
+                .add(albedo.clone())
+                .unwrap()
+                .add(normal.clone())
+                .unwrap()
+                .add(depth_buffer.clone())
+                .unwrap()
+                .add(target.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Ok(Self {
+            gbuffer_pipelines,
+            light_pipeline,
+            framebuffer,
+            inputs: [
+                Resource::Image(albedo),
+                Resource::Image(normal),
+                Resource::Image(depth_buffer.clone()),
+            ],
+            params: CpuBufferPool::uniform_buffer(device.clone()),
+            scene_size,
+        })
+    }
+
+    /// The pipeline of the first subpass, which the scene's draws are built for. It takes the
+    /// scene pipeline's vertex shader, sets and push constants.
+    pub fn pipeline(&self, double_sided: bool) -> &Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+        &self.gbuffer_pipelines[double_sided as usize]
+    }
+
+    /// Records the whole render pass: `draws` into the G-buffer, then the lighting of every
+    /// pixel that was drawn into `target`, the others keep `clear_color`
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        draws: &[SceneDraw],
+        double_sided: bool,
+        clear_color: [f32; 3],
+        projection: Matrix4<f32>,
+        view: Matrix4<f32>,
+        light: &Light,
+    ) {
+        let clear_values = vec![
+            [0.0, 0.0, 0.0, 0.0].into(),
+            [0.0, 0.0, 0.0, 0.0].into(),
+            1.0.into(),
+            [clear_color[0], clear_color[1], clear_color[2], 1.0].into(),
+        ];
+        builder
+            .begin_render_pass(self.framebuffer.clone(), SubpassContents::Inline, clear_values)
+            .unwrap();
+        let dynamic_state = renderer::dynamic_state(self.scene_size);
+        for draw in draws {
+            draw.record(builder, self.pipeline(double_sided), &dynamic_state);
+        }
+
+        let camera_position = view
+            .invert()
+            .map_or(Point3::origin(), |camera| camera.transform_point(Point3::origin()));
+        let params = LightParams {
+            inverse_view_projection: (projection * view).invert().unwrap_or_else(Matrix4::identity).into(),
+            light_position: light.position.into(),
+            ambient: light.ambient,
+            light_color: light.color,
+            shininess: light.shininess,
+            camera_position: camera_position.into(),
+        };
+        let mut resources = self.inputs.to_vec();
+        resources.push(Resource::Buffer(Arc::new(self.params.next(params).unwrap())));
+        let layout = self.light_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let push_constants = light_shader::ty::PushConstants {
+            inverseSize: [1.0 / self.scene_size[0] as f32, 1.0 / self.scene_size[1] as f32],
+        };
+        builder
+            .next_subpass(SubpassContents::Inline)
+            .unwrap()
+            .draw(
+                self.light_pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                descriptors::build(layout, &resources),
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
+
+/// Attachments 0 and 1 are the albedo and normal, 2 the depth and 3 the lit scene
+fn create_render_pass(device: &Arc<Device>) -> Arc<RenderPass> {
+    const ALBEDO: usize = 0;
+    const NORMAL: usize = 1;
+    const DEPTH: usize = 2;
+    const OUTPUT: usize = 3;
+
+    let attachment = |format, store, layout| AttachmentDesc {
+        format,
+        samples: SampleCount::Sample1,
+        load: LoadOp::Clear,
+        store,
+        stencil_load: LoadOp::DontCare,
+        stencil_store: StoreOp::DontCare,
+        initial_layout: layout,
+        final_layout: layout,
+    };
+    let attachments = vec![
+        // nothing reads the G-buffer after the render pass, so it is never written to memory
+        attachment(ALBEDO_FORMAT, StoreOp::DontCare, ImageLayout::ColorAttachmentOptimal),
+        attachment(NORMAL_FORMAT, StoreOp::DontCare, ImageLayout::ColorAttachmentOptimal),
+        // the lens flare, god rays, motion blur and Hi-Z read it after
+        attachment(DEPTH_FORMAT, StoreOp::Store, ImageLayout::DepthStencilAttachmentOptimal),
+        attachment(post::INTERMEDIATE_FORMAT, StoreOp::Store, ImageLayout::ColorAttachmentOptimal),
+    ];
+
+    let subpasses = vec![
+        // the meshes, into the G-buffer
+        SubpassDesc {
+            color_attachments: vec![
+                (ALBEDO, ImageLayout::ColorAttachmentOptimal),
+                (NORMAL, ImageLayout::ColorAttachmentOptimal),
+            ],
+            depth_stencil: Some((DEPTH, ImageLayout::DepthStencilAttachmentOptimal)),
+            input_attachments: vec![],
+            resolve_attachments: vec![],
+            preserve_attachments: vec![],
+        },
+        // the lighting, reading it back
+        SubpassDesc {
+            color_attachments: vec![(OUTPUT, ImageLayout::ColorAttachmentOptimal)],
+            depth_stencil: None,
+            input_attachments: vec![
+                (ALBEDO, ImageLayout::ShaderReadOnlyOptimal),
+                (NORMAL, ImageLayout::ShaderReadOnlyOptimal),
+                (DEPTH, ImageLayout::DepthStencilReadOnlyOptimal),
+            ],
+            resolve_attachments: vec![],
+            preserve_attachments: vec![],
+        },
+    ];
+
+    // the lighting's fragment shader reads what the first subpass's color outputs and depth
+    // test wrote. Depth is written in the late fragment tests at the latest. By region, since
+    // each pixel only waits for the same pixel of the G-buffer.
+    let dependencies = vec![SubpassDependencyDesc {
+        source_subpass: 0,
+        destination_subpass: 1,
+        source_stages: PipelineStages {
+            color_attachment_output: true,
+            late_fragment_tests: true,
+            ..PipelineStages::none()
+        },
+        destination_stages: PipelineStages {
+            fragment_shader: true,
+            ..PipelineStages::none()
+        },
+        source_access: AccessFlags {
+            color_attachment_write: true,
+            depth_stencil_attachment_write: true,
+            ..AccessFlags::none()
+        },
+        destination_access: AccessFlags {
+            input_attachment_read: true,
+            ..AccessFlags::none()
+        },
+        by_region: true,
+    }];
+
+    let desc = RenderPassDesc::new(attachments, subpasses, dependencies);
+    Arc::new(RenderPass::new(device.clone(), desc).unwrap())
+}
+
+fn create_gbuffer_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+    double_sided: bool,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "G-buffer vertex",
+        source,
+    })?;
+    let frag_shader_module = gbuffer_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "G-buffer fragment",
+        source,
+    })?;
+
+    let builder = GraphicsPipeline::start()
+        .vertex_input_single_buffer::<Vertex>()
+        .vertex_shader(vert_shader_module.main_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(frag_shader_module.main_entry_point(), ());
+    let builder = if double_sided {
+        builder.cull_mode_disabled()
+    } else {
+        builder.cull_mode_back()
+    };
+
+    Ok(Arc::new(
+        builder
+            .front_face_counter_clockwise()
+            .depth_stencil_simple_depth()
+            .blend_pass_through()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
+
+fn create_light_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+    scene_size: [u32; 2],
+) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+    let vert_shader_module = fullscreen_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "deferred vertex",
+        source,
+    })?;
+    let frag_shader_module = light_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "deferred lighting",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition {})
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [scene_size[0] as f32, scene_size[1] as f32],
+                depth_range: 0.0..1.0,
+            }])
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 1).unwrap())
+            .build(device.clone())?,
+    ))
+}
@@ -0,0 +1,128 @@
+//! Descriptor sets built from a list of resources instead of a chain of `add_*` calls, and a
+//! cache that hands out the same set again for the same layout and resources. Sets of
+//! resources that live across frames, like textures and storage buffers, are built once; sets
+//! of per frame uniform buffers are built with `build`, caching them would only grow the cache.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use vulkano::{
+    buffer::BufferAccess,
+    descriptor::{
+        descriptor_set::{PersistentDescriptorSet, UnsafeDescriptorSetLayout},
+        DescriptorSet,
+    },
+    image::view::ImageViewAbstract,
+    sampler::Sampler,
+};
+
+/// Most bindings a set built here can have
+pub const MAX_BINDINGS: usize = 4;
+
+/// One binding of a descriptor set, in binding order
+#[derive(Clone)]
+pub enum Resource {
+    /// A uniform or storage buffer
+    Buffer(Arc<dyn BufferAccess + Send + Sync>),
+    /// A storage image
+    Image(Arc<dyn ImageViewAbstract + Send + Sync>),
+    /// A combined image sampler, like a texture
+    SampledImage(Arc<dyn ImageViewAbstract + Send + Sync>, Arc<Sampler>),
+}
+
+impl Resource {
+    /// Identities of the objects bound, which stay valid while a cached set holds them
+    fn key(&self) -> [usize; 2] {
+        match self {
+            Resource::Buffer(buffer) => [address(buffer), 0],
+            Resource::Image(view) => [address(view), 0],
+            Resource::SampledImage(view, sampler) => [address(view), address(sampler)],
+        }
+    }
+}
+
+fn address<T: ?Sized>(resource: &Arc<T>) -> usize {
+    Arc::as_ptr(resource) as *const () as usize
+}
+
+/// Descriptor set built from the builder, as the trait object the draw calls take
+macro_rules! finish {
+    ($builder:expr) => {{
+        let set: Arc<dyn DescriptorSet + Send + Sync> =
+            Arc::new($builder.build().expect("Failed to build a descriptor set"));
+        set
+    }};
+}
+
+/// Adds the resources one at a time, one level per binding in `$levels`. Every `add_*` call
+/// changes the builder's type, and vulkano doesn't export the trait a generic function would
+/// need, so every order of resource kinds is spelled out with concrete types.
+macro_rules! add_bindings {
+    ($builder:expr, $resources:expr,) => {{
+        debug_assert!($resources.is_empty());
+        finish!($builder)
+    }};
+    ($builder:expr, $resources:expr, $level:tt $($levels:tt)*) => {
+        match $resources.split_first() {
+            None => finish!($builder),
+            Some((Resource::Buffer(buffer), rest)) => {
+                let builder = $builder.add_buffer(buffer.clone()).expect(MISMATCH);
+                add_bindings!(builder, rest, $($levels)*)
+            }
+            Some((Resource::Image(view), rest)) => {
+                let builder = $builder.add_image(view.clone()).expect(MISMATCH);
+                add_bindings!(builder, rest, $($levels)*)
+            }
+            Some((Resource::SampledImage(view, sampler), rest)) => {
+                let builder = $builder.add_sampled_image(view.clone(), sampler.clone()).expect(MISMATCH);
+                add_bindings!(builder, rest, $($levels)*)
+            }
+        }
+    };
+}
+
+const MISMATCH: &str = "Descriptor set resource doesn't match its binding";
+
+/// A set with `resources` at bindings 0, 1 and so on of `layout`. Panics if they don't match
+/// the layout, like a shader and the code feeding it disagreeing.
+pub fn build(layout: Arc<UnsafeDescriptorSetLayout>, resources: &[Resource]) -> Arc<dyn DescriptorSet + Send + Sync> {
+    assert!(
+        resources.len() <= MAX_BINDINGS,
+        "{} descriptor set bindings, at most {} are supported",
+        resources.len(),
+        MAX_BINDINGS
+    );
+    // one level for each of the MAX_BINDINGS bindings
+    add_bindings!(PersistentDescriptorSet::start(layout), resources, 1 2 3 4)
+}
+
+/// Layout address and the keys of the resources bound
+type CacheKey = (usize, Vec<[usize; 2]>);
+
+/// Descriptor sets already built, keyed by layout and resources. Shared by reference, so
+/// recording code that only borrows the app can still fill it.
+#[derive(Default)]
+pub struct DescriptorCache {
+    sets: Mutex<HashMap<CacheKey, Arc<dyn DescriptorSet + Send + Sync>>>,
+}
+
+impl DescriptorCache {
+    /// The set with `resources` in `layout`, built the first time it is asked for
+    pub fn get(
+        &self,
+        layout: &Arc<UnsafeDescriptorSetLayout>,
+        resources: &[Resource],
+    ) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let key = (address(layout), resources.iter().map(Resource::key).collect());
+        let mut sets = self.sets.lock().unwrap();
+        sets.entry(key).or_insert_with(|| build(layout.clone(), resources)).clone()
+    }
+
+    /// Drops every set, which releases the resources they hold. Called when pipelines are
+    /// rebuilt, whose old layouts no set will be asked for again.
+    pub fn clear(&self) {
+        self.sets.lock().unwrap().clear();
+    }
+}
@@ -1,28 +1,150 @@
-use std::{sync::Arc, usize};
+use std::{fmt, sync::Arc, usize};
 use vulkano::{
     device::{Device, DeviceExtensions, Features, Queue},
-    instance::{Instance, PhysicalDevice},
+    instance::{Instance, PhysicalDevice, PhysicalDeviceType},
     swapchain::Surface,
 };
 use winit::window::Window;
 
-/// Structure that holds all necessary queue IDs for future reference
-pub struct QueueCollection {
-    pub graphics_queue_id: Option<u32>,
-    pub presentation_queue_id: Option<u32>,
+use crate::error::Error;
+
+/// What the queues of one family can do
+#[derive(Clone, Debug)]
+pub struct QueueFamilyInfo {
+    pub id: u32,
+    pub queue_count: usize,
+    pub graphics: bool,
+    pub compute: bool,
+    /// Graphics and compute families can always transfer, this is only set when the family says so
+    pub transfer: bool,
+    pub present: bool,
 }
 
-impl QueueCollection {
-    fn all_present(&self) -> bool {
-        self.graphics_queue_id.is_some() && self.presentation_queue_id.is_some()
+impl QueueFamilyInfo {
+    /// Only copies, the kind of family DMA engines are exposed as
+    pub fn is_transfer_only(&self) -> bool {
+        self.transfer && !self.graphics && !self.compute
+    }
+}
+
+/// All queue families of a physical device and which of them suit each kind of work
+#[derive(Clone, Debug)]
+pub struct QueueFamilies {
+    pub families: Vec<QueueFamilyInfo>,
+}
+
+impl QueueFamilies {
+    pub fn new(surface: &Arc<Surface<Window>>, device: PhysicalDevice) -> Self {
+        let families = device
+            .queue_families()
+            .map(|family| QueueFamilyInfo {
+                id: family.id(),
+                queue_count: family.queues_count(),
+                graphics: family.supports_graphics(),
+                compute: family.supports_compute(),
+                transfer: family.explicitly_supports_transfers(),
+                present: surface.is_supported(family).unwrap_or(false),
+            })
+            .collect();
+
+        Self { families }
+    }
+
+    /// A graphics family, one that can present as well if there is one
+    pub fn graphics(&self) -> Option<u32> {
+        self.find(|family| family.graphics && family.present)
+            .or_else(|| self.find(|family| family.graphics))
+    }
+
+    /// The graphics family when it can present, so no ownership transfers are needed
+    pub fn present(&self) -> Option<u32> {
+        self.find(|family| family.graphics && family.present)
+            .or_else(|| self.find(|family| family.present))
+    }
+
+    /// A transfer only family for uploads that run alongside rendering, `None` means uploads go
+    /// through the graphics queue
+    pub fn transfer(&self) -> Option<u32> {
+        self.find(QueueFamilyInfo::is_transfer_only)
+    }
+
+    /// A compute family without graphics, for work that overlaps with rendering
+    pub fn async_compute(&self) -> Option<u32> {
+        self.find(|family| family.compute && !family.graphics)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.graphics().is_some() && self.present().is_some()
     }
 
     pub fn is_shared(&self) -> bool {
-        if self.all_present() {
-            self.graphics_queue_id.unwrap() == self.presentation_queue_id.unwrap()
-        } else {
-            false
+        self.is_complete() && self.graphics() == self.present()
+    }
+
+    fn find(&self, predicate: impl Fn(&QueueFamilyInfo) -> bool) -> Option<u32> {
+        self.families.iter().find(|family| predicate(family)).map(|family| family.id)
+    }
+}
+
+impl fmt::Display for QueueFamilies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for family in &self.families {
+            let capabilities: Vec<_> = [
+                (family.graphics, "graphics"),
+                (family.compute, "compute"),
+                (family.transfer, "transfer"),
+                (family.present, "present"),
+            ]
+            .iter()
+            .filter(|(supported, _)| *supported)
+            .map(|(_, name)| *name)
+            .collect();
+
+            writeln!(
+                f,
+                "  family {}: {} queue{}, {}",
+                family.id,
+                family.queue_count,
+                if family.queue_count == 1 { "" } else { "s" },
+                if capabilities.is_empty() { "nothing".to_string() } else { capabilities.join(" ") }
+            )?;
         }
+
+        let picked = |id: Option<u32>| id.map_or("none".to_string(), |id| format!("family {}", id));
+        writeln!(f, "  graphics: {}", picked(self.graphics()))?;
+        writeln!(f, "  present: {}", picked(self.present()))?;
+        writeln!(f, "  uploads: {}", picked(self.transfer().or_else(|| self.graphics())))?;
+        write!(f, "  async compute: {}", picked(self.async_compute()))
+    }
+}
+
+/// Every physical device with its type and queue families, for `--print-device-info`
+pub fn device_report(surface: &Arc<Surface<Window>>, instance: &Arc<Instance>) -> String {
+    PhysicalDevice::enumerate(instance)
+        .map(|device| {
+            let properties = device.properties();
+            let score = score_physical_device(surface, device)
+                .map_or("unsuitable".to_string(), |score| format!("score {}", score));
+            format!(
+                "{}: {} ({:?}), Vulkan {}, {}\n{}\n",
+                device.index(),
+                properties.device_name.as_deref().unwrap_or("unknown device"),
+                properties.device_type.unwrap(),
+                device.api_version(),
+                score,
+                QueueFamilies::new(surface, device)
+            )
+        })
+        .collect()
+}
+
+/// Optional features are enabled whenever the device has them, users check `enabled_features()`
+fn device_features(physical_device: PhysicalDevice) -> Features {
+    Features {
+        large_points: physical_device.supported_features().large_points,
+        pipeline_statistics_query: physical_device.supported_features().pipeline_statistics_query,
+        occlusion_query_precise: physical_device.supported_features().occlusion_query_precise,
+        ..Features::none()
     }
 }
 
@@ -33,68 +155,173 @@ fn device_extensions(physical_device: PhysicalDevice) -> DeviceExtensions {
     }
 }
 
+/// The queues created with the device, one per family that `QueueFamilies` picked. Queues of
+/// families that were picked for more than one kind of work are shared.
+#[derive(Clone)]
+pub struct QueueCollection {
+    pub graphics: Arc<Queue>,
+    pub present: Arc<Queue>,
+    /// On a transfer only family, uploads there overlap with rendering
+    pub transfer: Option<Arc<Queue>>,
+    /// On a compute family without graphics
+    pub compute: Option<Arc<Queue>>,
+}
+
+impl QueueCollection {
+    /// The transfer queue, or the graphics queue on devices without one
+    pub fn uploads(&self) -> Arc<Queue> {
+        self.transfer.clone().unwrap_or_else(|| self.graphics.clone())
+    }
+
+    /// The async compute queue, or the graphics queue on devices without one
+    pub fn compute_work(&self) -> Arc<Queue> {
+        self.compute.clone().unwrap_or_else(|| self.graphics.clone())
+    }
+}
+
+/// Creates the device on the best scoring physical device, or the one `gpu` names with its index
+/// or part of its name. With `prefer_integrated` a suitable integrated GPU scores best.
 pub fn create_device(
     surface: &Arc<Surface<Window>>,
     instance: &Arc<Instance>,
-) -> (Arc<Device>, Arc<Queue>, Arc<Queue>) {
-    let device = pick_physical_device(surface, instance);
-    let queue_collection = find_queue_families(surface, &device);
+    gpu: Option<&str>,
+    prefer_integrated: bool,
+) -> Result<(Arc<Device>, QueueCollection), Error> {
+    let device = pick_physical_device(instance, gpu, |device| {
+        let score = score_physical_device(surface, device)?;
+        // integrated GPUs draw less power, a suitable one wins over any discrete GPU
+        let integrated = device.properties().device_type == Some(PhysicalDeviceType::IntegratedGpu);
+        Some(if prefer_integrated && integrated { score + INTEGRATED_PREFERENCE } else { score })
+    })?;
+    let queue_families = QueueFamilies::new(surface, device);
 
-    if !queue_collection.all_present() {
-        panic!("No suitable queue collections was found");
+    if !queue_families.is_complete() {
+        return Err(Error::NoSuitableDevice);
     }
 
-    // find queues we need among all available queues
-    let queues: Vec<_> = device
-        .queue_families()
-        .enumerate()
-        .filter(|(i, _)| {
-            *i == queue_collection.graphics_queue_id.unwrap() as usize
-                || *i == queue_collection.presentation_queue_id.unwrap() as usize
-        })
-        .map(|(_, v)| (v, 1.0))
-        .collect();
+    // one queue per distinct family, in the order the families are picked
+    let graphics_family = queue_families.graphics().unwrap();
+    let present_family = queue_families.present().unwrap();
+    let transfer_family = queue_families.transfer();
+    let compute_family = queue_families.async_compute();
+    let mut distinct_families = Vec::new();
+    for family in [Some(graphics_family), Some(present_family), transfer_family, compute_family].iter().flatten() {
+        if !distinct_families.contains(family) {
+            distinct_families.push(*family);
+        }
+    }
 
-    let (device, mut queues) = Device::new(
+    let (device, queues) = Device::new(
         device,
-        &Features::none(),
+        &device_features(device),
         &device_extensions(device),
-        queues,
-    )
-    .unwrap();
+        distinct_families.iter().map(|&id| (device.queue_family_by_id(id).unwrap(), 1.0)),
+    )?;
 
-    let graphics_queue = queues.next().unwrap();
-    let presentation_queue = queues.next().unwrap_or(graphics_queue.clone());
+    let queues: Vec<_> = queues.collect();
+    let queue = |family: u32| queues[distinct_families.iter().position(|&id| id == family).unwrap()].clone();
+    let queues = QueueCollection {
+        graphics: queue(graphics_family),
+        present: queue(present_family),
+        transfer: transfer_family.map(queue),
+        compute: compute_family.map(queue),
+    };
 
-    (device, graphics_queue, presentation_queue)
+    Ok((device, queues))
 }
 
-fn pick_physical_device<'a>(
-    surface: &'a Arc<Surface<Window>>,
-    instance: &'a Arc<Instance>,
-) -> PhysicalDevice<'a> {
-    PhysicalDevice::enumerate(&instance)
-        .find(|device| find_queue_families(surface, &device).all_present())
-        .expect("Could not find suitable physical device")
+/// How well `device` suits the app, `None` if it can't run it at all: it needs graphics and
+/// present queues, the extensions the app enables and a surface format and present mode for the
+/// swap chain. Discrete GPUs come before integrated, virtual and CPU ones, the largest image size
+/// breaks ties.
+pub fn score_physical_device(surface: &Arc<Surface<Window>>, device: PhysicalDevice) -> Option<u32> {
+    if !QueueFamilies::new(surface, device).is_complete()
+        || !check_device_extension_support(&device)
+        || !is_swap_chain_adequate(surface, device)
+    {
+        return None;
+    }
+
+    Some(type_score(device))
 }
 
-fn find_queue_families(surface: &Arc<Surface<Window>>, device: &PhysicalDevice) -> QueueCollection {
-    let mut collection = QueueCollection {
-        graphics_queue_id: None,
-        presentation_queue_id: None,
+/// Like `score_physical_device` for rendering without a window, which only needs a graphics queue
+pub fn score_headless_device(device: PhysicalDevice) -> Option<u32> {
+    device
+        .queue_families()
+        .any(|family| family.supports_graphics())
+        .then(|| type_score(device))
+}
+
+/// Added to an integrated GPU's score in low power mode, more than the gap to a discrete GPU
+const INTEGRATED_PREFERENCE: u32 = 100_000;
+
+fn type_score(device: PhysicalDevice) -> u32 {
+    let properties = device.properties();
+    let type_score = match properties.device_type {
+        Some(PhysicalDeviceType::DiscreteGpu) => 100_000,
+        Some(PhysicalDeviceType::IntegratedGpu) => 50_000,
+        Some(PhysicalDeviceType::VirtualGpu) => 20_000,
+        Some(PhysicalDeviceType::Cpu) => 10_000,
+        _ => 0,
     };
+    type_score + properties.max_image_dimension2_d.unwrap_or(0).min(32_768)
+}
+
+/// Creates a device with one graphics queue for rendering without a window, on the best scoring
+/// physical device or the one `gpu` names
+pub fn create_headless_device(instance: &Arc<Instance>, gpu: Option<&str>) -> Result<(Arc<Device>, Arc<Queue>), Error> {
+    let device = pick_physical_device(instance, gpu, score_headless_device)?;
+    let family = device.queue_families().find(|family| family.supports_graphics()).unwrap();
+
+    let (device, mut queues) = Device::new(
+        device,
+        &device_features(device),
+        &DeviceExtensions::required_extensions(device),
+        std::iter::once((family, 1.0)),
+    )?;
 
-    for queue_family in device.queue_families().into_iter() {
-        if queue_family.supports_graphics() {
-            collection.graphics_queue_id = Some(queue_family.id());
+    Ok((device, queues.next().unwrap()))
+}
+
+fn pick_physical_device<'a>(
+    instance: &'a Arc<Instance>,
+    gpu: Option<&str>,
+    score: impl Fn(PhysicalDevice) -> Option<u32>,
+) -> Result<PhysicalDevice<'a>, Error> {
+    let gpu = match gpu {
+        Some(gpu) => gpu,
+        None => {
+            return PhysicalDevice::enumerate(instance)
+                .filter_map(|device| Some((score(device)?, device)))
+                // the first of equally scored devices, like the order drivers list them in
+                .min_by_key(|(score, device)| (std::cmp::Reverse(*score), device.index()))
+                .map(|(_, device)| device)
+                .ok_or(Error::NoSuitableDevice);
         }
+    };
 
-        if surface.is_supported(queue_family).unwrap() {
-            collection.presentation_queue_id = Some(queue_family.id());
+    let name = |device: &PhysicalDevice| device.properties().device_name.clone().unwrap_or_default();
+    let device = match gpu.parse::<usize>() {
+        Ok(index) => PhysicalDevice::from_index(instance, index),
+        Err(_) => {
+            let lowercase = gpu.to_lowercase();
+            PhysicalDevice::enumerate(instance).find(|device| name(device).to_lowercase().contains(&lowercase))
         }
     }
+    .ok_or_else(|| Error::GpuNotFound(gpu.to_string()))?;
+
+    if score(device).is_none() {
+        return Err(Error::UnsuitableGpu(name(&device)));
+    }
+    Ok(device)
+}
 
-    collection
+/// At least one surface format and present mode, so a swap chain can be created
+fn is_swap_chain_adequate(surface: &Arc<Surface<Window>>, device: PhysicalDevice) -> bool {
+    surface.capabilities(device).is_ok_and(|capabilities| {
+        !capabilities.supported_formats.is_empty() && capabilities.present_modes.iter().next().is_some()
+    })
 }
 
 fn check_device_extension_support(device: &PhysicalDevice) -> bool {
@@ -0,0 +1,61 @@
+use thiserror::Error;
+use vulkano::{
+    command_buffer::{BuildError, CommandBufferExecError, CopyBufferError, CopyBufferImageError},
+    device::DeviceCreationError,
+    image::ImageCreationError,
+    memory::DeviceMemoryAllocError,
+    pipeline::{ComputePipelineCreationError, GraphicsPipelineCreationError},
+    swapchain::{CapabilitiesError, SwapchainCreationError},
+    sync::FlushError,
+    OomError,
+};
+
+/// Why setting up the device or creating its resources failed, with what the user can do about
+/// it where there is something
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(
+        "no GPU has graphics and present queues, the swap chain extension and a usable surface, \
+         see --print-device-info"
+    )]
+    NoSuitableDevice,
+    #[error("no GPU matches --gpu {0}, see --print-device-info")]
+    GpuNotFound(String),
+    #[error("{0} can't run the app, see --print-device-info")]
+    UnsuitableGpu(String),
+    #[error("failed to create the device: {0}")]
+    DeviceCreation(#[from] DeviceCreationError),
+    #[error("failed to get the surface capabilities: {0}")]
+    SurfaceCapabilities(#[from] CapabilitiesError),
+    /// The surface lacks what the swap chain needs, like a format or an image usage
+    #[error("the surface doesn't support the swap chain: {0}")]
+    UnsupportedSurface(String),
+    #[error("failed to build the swap chain: {0}")]
+    SwapChainCreation(#[from] SwapchainCreationError),
+    #[error("failed to create the {name} shader module: {source}")]
+    ShaderModule {
+        name: &'static str,
+        source: OomError,
+    },
+    #[error("failed to build the pipeline: {0}")]
+    PipelineCreation(#[from] GraphicsPipelineCreationError),
+    #[error("failed to build the compute pipeline: {0}")]
+    ComputePipelineCreation(#[from] ComputePipelineCreationError),
+    #[error("failed to allocate a buffer: {0}")]
+    BufferAllocation(#[from] DeviceMemoryAllocError),
+    #[error("failed to create an image: {0}")]
+    ImageCreation(#[from] ImageCreationError),
+    #[error("failed to record the copy into a buffer: {0}")]
+    BufferCopy(#[from] CopyBufferError),
+    #[error("failed to record the copy into an image: {0}")]
+    ImageCopy(#[from] CopyBufferImageError),
+    #[error("the world's chunk generators stopped")]
+    ChunkGeneratorsStopped,
+    #[error("failed to build a command buffer: {0}")]
+    CommandBufferBuild(#[from] BuildError),
+    #[error("failed to submit a command buffer: {0}")]
+    CommandBufferExecution(#[from] CommandBufferExecError),
+    /// Submitting or waiting for work on a queue failed, like when the device was lost
+    #[error("failed to submit to the GPU: {0}")]
+    Flush(#[from] FlushError),
+}
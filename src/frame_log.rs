@@ -0,0 +1,134 @@
+//! Per frame metrics written to a CSV file for offline analysis. The GPU times of a frame are
+//! only known once the frame has finished, so rows wait in the frame's in-flight slot until the
+//! slot comes around again, and are written in frame order.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    gpu_timer::{GpuTimer, GpuTimes},
+    renderer::MAX_FRAMES_IN_FLIGHT,
+};
+
+const HEADER: &str = "frame,frame_ms,cpu_ms,gpu_scene_ms,gpu_effects_ms,gpu_post_ms,gpu_overlay_ms,\
+                      draw_calls,triangles,visible_objects,resident_mb";
+/// Rows written between flushes, so a crash loses at most about a second of them
+const FLUSH_INTERVAL: u64 = 60;
+
+/// What the scene pass drew, counted while recording it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameCounters {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    /// Spawned objects inside the view frustum
+    pub visible_objects: u32,
+    /// Draws recorded with occlusion and pipeline statistics queries, see `queries`
+    pub queried_draws: u32,
+}
+
+impl FrameCounters {
+    pub fn draw(&mut self, triangles: u64) {
+        self.draw_calls += 1;
+        self.triangles += triangles;
+    }
+}
+
+/// Everything about a frame but its GPU times
+pub struct FrameRow {
+    pub frame: u64,
+    pub interval: Duration,
+    pub cpu_time: Duration,
+    pub counters: FrameCounters,
+}
+
+/// An open CSV file and the rows still waiting for their GPU times
+pub struct FrameLog {
+    pub path: PathBuf,
+    writer: BufWriter<File>,
+    pending: Vec<Option<FrameRow>>,
+    rows: u64,
+}
+
+impl FrameLog {
+    /// Overwrites `path`
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", HEADER).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            pending: (0..MAX_FRAMES_IN_FLIGHT).map(|_| None).collect(),
+            rows: 0,
+        })
+    }
+
+    /// Writes the row of the frame that used `slot` before with its GPU times, call once its
+    /// fence was waited for. The times are left empty when they are `None`.
+    pub fn complete(&mut self, slot: usize, gpu_times: Option<GpuTimes>) -> Result<(), String> {
+        match self.pending[slot].take() {
+            Some(row) => self.write(row, gpu_times),
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps the row of the frame just submitted in `slot` until it has finished
+    pub fn record(&mut self, slot: usize, row: FrameRow) {
+        self.pending[slot] = Some(row);
+    }
+
+    /// Writes the rows still waiting with their GPU times from `timer` and flushes the file,
+    /// after every frame has finished. Returns the number of rows written.
+    pub fn finish(mut self, mut timer: Option<&mut GpuTimer>) -> Result<u64, String> {
+        let mut slots: Vec<_> = (0..self.pending.len()).collect();
+        slots.sort_by_key(|&slot| self.pending[slot].as_ref().map(|row| row.frame));
+        for slot in slots {
+            let gpu_times = timer.as_mut().and_then(|timer| timer.read(slot));
+            self.complete(slot, gpu_times)?;
+        }
+        self.writer.flush().map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        Ok(self.rows)
+    }
+
+    fn write(&mut self, row: FrameRow, gpu_times: Option<GpuTimes>) -> Result<(), String> {
+        let ms = |duration: Duration| format!("{:.3}", duration.as_secs_f64() * 1000.0);
+        let gpu = match gpu_times {
+            Some(times) => [times.scene, times.effects, times.post, times.overlays].map(ms).join(","),
+            None => ",,,".to_string(),
+        };
+        let resident = resident_memory().map_or(String::new(), |bytes| format!("{:.1}", bytes as f64 / 1048576.0));
+
+        writeln!(
+            self.writer,
+            "{},{},{},{},{},{},{},{}",
+            row.frame,
+            ms(row.interval),
+            ms(row.cpu_time),
+            gpu,
+            row.counters.draw_calls,
+            row.counters.triangles,
+            row.counters.visible_objects,
+            resident
+        )
+        .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+
+        self.rows += 1;
+        if self.rows.is_multiple_of(FLUSH_INTERVAL) {
+            self.writer.flush().map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Resident memory of the process in bytes, only known on Linux
+fn resident_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,44 @@
+use cgmath::{Matrix, Matrix4, Vector4};
+
+use crate::ray::Aabb;
+
+/// The six planes of a view frustum in world space, each stored as `(normal, offset)` so that points
+/// inside satisfy `normal · p + offset >= 0`
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a view-projection matrix with Vulkan's 0..1 clip depth
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Self {
+        let row = |i| view_projection.row(i);
+
+        Self {
+            planes: [
+                row(3) + row(0),
+                row(3) - row(0),
+                row(3) + row(1),
+                row(3) - row(1),
+                row(2),
+                row(3) - row(2),
+            ],
+        }
+    }
+
+    /// The planes as `[normal.x, normal.y, normal.z, offset]`, for shaders doing the same test
+    pub fn planes(&self) -> [[f32; 4]; 6] {
+        self.planes.map(Into::into)
+    }
+
+    /// Conservative test: boxes crossing a corner of the frustum may be reported as visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal is the last one to leave the frustum
+            let x = if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x };
+            let y = if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y };
+            let z = if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z };
+
+            plane.x * x + plane.y * y + plane.z * z + plane.w >= 0.0
+        })
+    }
+}
@@ -0,0 +1,128 @@
+//! Windowed, borderless fullscreen and exclusive fullscreen, on the monitor the settings pick.
+//! Exclusive fullscreen switches the monitor to one of its video modes, borderless covers it at
+//! its current mode. Either way the window's size changes and the swap chain follows it.
+
+use std::{fmt, str::FromStr};
+
+use log::{info, warn};
+use winit::{
+    monitor::{MonitorHandle, VideoMode},
+    window::Fullscreen,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FullscreenMode {
+    Windowed,
+    /// A window without decorations covering the monitor
+    Borderless,
+    /// The window owns the monitor, which switches to the video mode picked for it
+    Exclusive,
+}
+
+impl FullscreenMode {
+    pub fn is_fullscreen(self) -> bool {
+        self != FullscreenMode::Windowed
+    }
+}
+
+impl FromStr for FullscreenMode {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "off" | "windowed" => Ok(FullscreenMode::Windowed),
+            // `on` was the only fullscreen there was
+            "on" | "borderless" => Ok(FullscreenMode::Borderless),
+            "exclusive" => Ok(FullscreenMode::Exclusive),
+            other => Err(format!("expected off, borderless or exclusive, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for FullscreenMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FullscreenMode::Windowed => "off",
+            FullscreenMode::Borderless => "borderless",
+            FullscreenMode::Exclusive => "exclusive",
+        })
+    }
+}
+
+/// The monitor `selector` names by index into `monitors` or part of its name, or `current` when
+/// there is no selector or nothing matches it
+pub fn pick_monitor(
+    mut monitors: impl Iterator<Item = MonitorHandle>,
+    current: Option<MonitorHandle>,
+    selector: Option<&str>,
+) -> Option<MonitorHandle> {
+    let selector = match selector {
+        Some(selector) => selector,
+        None => return current,
+    };
+    let picked = match selector.parse::<usize>() {
+        Ok(index) => monitors.nth(index),
+        Err(_) => {
+            let selector = selector.to_lowercase();
+            monitors.find(|monitor| {
+                monitor
+                    .name()
+                    .is_some_and(|name| name.to_lowercase().contains(&selector))
+            })
+        }
+    };
+    if picked.is_none() {
+        warn!("No monitor matches '{}', using the window's", selector);
+    }
+    picked.or(current)
+}
+
+/// One line per monitor with the index and name the `monitor` setting takes
+pub fn monitor_report(monitors: impl Iterator<Item = MonitorHandle>) -> String {
+    let mut report = String::from("Monitors:\n");
+    for (index, monitor) in monitors.enumerate() {
+        let size = monitor.size();
+        report.push_str(&format!(
+            "  {}: {} {}x{}, {} video modes\n",
+            index,
+            monitor.name().unwrap_or_else(|| "unnamed".to_string()),
+            size.width,
+            size.height,
+            monitor.video_modes().count()
+        ));
+    }
+    report
+}
+
+/// The monitor's mode at its current resolution with the highest refresh rate and bit depth
+fn exclusive_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .max_by_key(|mode| (mode.refresh_rate(), mode.bit_depth()))
+}
+
+/// What winit needs to put the window in `mode` on `monitor`. Platforms that list no video
+/// modes, like Wayland, get borderless fullscreen instead of exclusive.
+pub fn fullscreen(mode: FullscreenMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        FullscreenMode::Windowed => None,
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        FullscreenMode::Exclusive => match monitor.as_ref().and_then(exclusive_mode) {
+            Some(video_mode) => {
+                info!(
+                    "Exclusive fullscreen at {}x{}, {} Hz",
+                    video_mode.size().width,
+                    video_mode.size().height,
+                    video_mode.refresh_rate()
+                );
+                Some(Fullscreen::Exclusive(video_mode))
+            }
+            None => {
+                warn!("The monitor lists no video modes, using borderless fullscreen");
+                Some(Fullscreen::Borderless(monitor))
+            }
+        },
+    }
+}
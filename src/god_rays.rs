@@ -0,0 +1,327 @@
+use std::{fmt, sync::Arc};
+
+use cgmath::{Matrix4, Vector3};
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::{descriptor_set::PersistentDescriptorSet, DescriptorSet},
+    device::Device,
+    format::ClearValue,
+    image::{view::ImageView, AttachmentImage, ImageAccess},
+    pipeline::{
+        blend::{AttachmentBlend, BlendFactor, BlendOp},
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::post;
+
+/// Weight of each sample relative to the one before it, further from the pixel counts less
+const DECAY: f32 = 0.97;
+const INTENSITY: f32 = 0.6;
+/// Distance of the sun from the screen center, in NDC, where the rays start to fade out
+const EDGE_FADE_START: f32 = 1.0;
+pub const MAX_STEPS: u32 = 256;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod rays_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/god_rays.frag"
+    }
+}
+
+mod composite_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/god_rays_composite.frag"
+    }
+}
+
+/// Controls of the `godrays` command, kept while the passes are rebuilt with the swap chain
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GodRaySettings {
+    pub enabled: bool,
+    /// Share of the way from each pixel to the sun the samples cover, longer rays for more
+    pub density: f32,
+    /// Samples per pixel, fewer are faster but show steps along the rays
+    pub steps: u32,
+}
+
+impl Default for GodRaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 0.8,
+            steps: 64,
+        }
+    }
+}
+
+impl fmt::Display for GodRaySettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, density {:.2}, {} steps",
+            if self.enabled { "on" } else { "off" },
+            self.density,
+            self.steps
+        )
+    }
+}
+
+/// Light shafts from the sun, blurred radially out of the visible sky in screen space. A pass at
+/// half resolution samples the scene along the line from each pixel towards the sun, counting
+/// only pixels where the depth buffer shows sky, so anything in front of the sun casts shadows
+/// into the rays. A second pass adds the rays onto the scene target before the post pass
+/// calibrates it.
+pub struct GodRays {
+    rays_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    composite_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    rays_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    composite_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    rays_set: Arc<dyn DescriptorSet + Send + Sync>,
+    composite_set: Arc<dyn DescriptorSet + Send + Sync>,
+    rays_size: [u32; 2],
+    scene_size: [u32; 2],
+}
+
+impl GodRays {
+    /// Recreated with the swap chain. `scene` is the post pass target, `depth_buffer` the single
+    /// sampled depth of the full image.
+    pub fn new(
+        device: &Arc<Device>,
+        scene: &Arc<ImageView<Arc<AttachmentImage>>>,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Self {
+        let scene_size = scene.image().dimensions().width_height();
+        let rays_size = [(scene_size[0] / 2).max(1), (scene_size[1] / 2).max(1)];
+        let rays = ImageView::new(AttachmentImage::sampled(device.clone(), rays_size, post::INTERMEDIATE_FORMAT).unwrap())
+            .unwrap();
+
+        let render_pass = |load_scene: bool| {
+            let render_pass = if load_scene {
+                vulkano::single_pass_renderpass!(
+                    device.clone(),
+                    attachments: {
+                        color: {
+                            load: Load,
+                            store: Store,
+                            format: post::INTERMEDIATE_FORMAT,
+                            samples: 1,
+                        }
+                    },
+                    pass: {
+                        color: [color],
+                        depth_stencil: {}
+                    }
+                )
+            } else {
+                vulkano::single_pass_renderpass!(
+                    device.clone(),
+                    attachments: {
+                        color: {
+                            load: DontCare,
+                            store: Store,
+                            format: post::INTERMEDIATE_FORMAT,
+                            samples: 1,
+                        }
+                    },
+                    pass: {
+                        color: [color],
+                        depth_stencil: {}
+                    }
+                )
+            };
+            Arc::new(render_pass.unwrap())
+        };
+        let rays_render_pass = render_pass(false);
+        let composite_render_pass = render_pass(true);
+
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+            .expect("Failed to create god rays vertex shader module");
+        let rays_shader_module =
+            rays_shader::Shader::load(device.clone()).expect("Failed to create god rays shader module");
+        let composite_shader_module = composite_shader::Shader::load(device.clone())
+            .expect("Failed to create god rays composite shader module");
+
+        let viewport = |[width, height]: [u32; 2]| Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        };
+        let additive = AttachmentBlend {
+            enabled: true,
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::One,
+            color_destination: BlendFactor::One,
+            alpha_op: BlendOp::Add,
+            alpha_source: BlendFactor::Zero,
+            alpha_destination: BlendFactor::One,
+            mask_red: true,
+            mask_green: true,
+            mask_blue: true,
+            mask_alpha: true,
+        };
+
+        let rays_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport(rays_size)])
+                .fragment_shader(rays_shader_module.main_entry_point(), ())
+                .render_pass(Subpass::from(rays_render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+        let composite_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport(scene_size)])
+                .fragment_shader(composite_shader_module.main_entry_point(), ())
+                .blend_collective(additive)
+                .render_pass(Subpass::from(composite_render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let rays_framebuffer =
+            Arc::new(Framebuffer::start(rays_render_pass).add(rays.clone()).unwrap().build().unwrap());
+        let composite_framebuffer =
+            Arc::new(Framebuffer::start(composite_render_pass).add(scene.clone()).unwrap().build().unwrap());
+
+        let sampler = |filter: Filter| {
+            Sampler::new(
+                device.clone(),
+                filter,
+                filter,
+                MipmapMode::Nearest,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+            )
+            .unwrap()
+        };
+
+        // depth formats don't have to support linear filtering
+        let layout = rays_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let rays_set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(scene.clone(), sampler(Filter::Linear))
+                .unwrap()
+                .add_sampled_image(depth_buffer.clone(), sampler(Filter::Nearest))
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        let layout = composite_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let composite_set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(rays, sampler(Filter::Linear))
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Self {
+            rays_pipeline,
+            composite_pipeline,
+            rays_framebuffer,
+            composite_framebuffer,
+            rays_set,
+            composite_set,
+            rays_size,
+            scene_size,
+        }
+    }
+
+    /// Records both passes after the scene and before the post pass, nothing is drawn if the sun
+    /// is behind the camera or set
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        settings: GodRaySettings,
+        view_projection: Matrix4<f32>,
+        sun_direction: Vector3<f32>,
+    ) {
+        // projected as a direction like the lens flare does
+        let clip = view_projection * sun_direction.extend(0.0);
+        if !settings.enabled || settings.steps == 0 || clip.w <= 0.0 || sun_direction.y < -0.05 {
+            return;
+        }
+
+        let sun = [clip.x / clip.w, clip.y / clip.w];
+        let distance_from_center = sun[0].abs().max(sun[1].abs());
+        let edge_fade = 1.0 - ((distance_from_center - EDGE_FADE_START) / EDGE_FADE_START).clamp(0.0, 1.0);
+        let dusk_fade = ((sun_direction.y + 0.05) / 0.15).clamp(0.0, 1.0);
+        let intensity = INTENSITY * edge_fade * dusk_fade;
+        if intensity <= 0.0 {
+            return;
+        }
+
+        let inverse_size = |[width, height]: [u32; 2]| [1.0 / width as f32, 1.0 / height as f32];
+        let rays_push_constants = rays_shader::ty::PushConstants {
+            sun: [sun[0] * 0.5 + 0.5, sun[1] * 0.5 + 0.5],
+            inverseSize: inverse_size(self.rays_size),
+            density: settings.density.clamp(0.0, 1.0),
+            decay: DECAY,
+            intensity,
+            steps: settings.steps.min(MAX_STEPS),
+        };
+        let composite_push_constants = composite_shader::ty::PushConstants {
+            inverseSize: inverse_size(self.scene_size),
+        };
+        let triangle = BufferlessVertices {
+            vertices: 3,
+            instances: 1,
+        };
+
+        builder
+            .begin_render_pass(self.rays_framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
+            .unwrap()
+            .draw(
+                self.rays_pipeline.clone(),
+                &DynamicState::none(),
+                triangle,
+                self.rays_set.clone(),
+                rays_push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+            .begin_render_pass(
+                self.composite_framebuffer.clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap()
+            .draw(
+                self.composite_pipeline.clone(),
+                &DynamicState::none(),
+                triangle,
+                self.composite_set.clone(),
+                composite_push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
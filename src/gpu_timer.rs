@@ -0,0 +1,142 @@
+//! GPU time of each part of a frame, measured with timestamps written into the frame's command
+//! buffer. The results of a frame are only available once it has finished, so they are read
+//! when its in-flight slot comes around again.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    device::Queue,
+    query::{QueryPool, QueryResultFlags, QueryType},
+    sync::PipelineStage,
+};
+
+use crate::renderer::MAX_FRAMES_IN_FLIGHT;
+
+/// Points of a frame's command buffer a timestamp is written at
+#[derive(Clone, Copy, Debug)]
+pub enum Mark {
+    Start = 0,
+    SceneDone = 1,
+    EffectsDone = 2,
+    PostDone = 3,
+    End = 4,
+}
+
+const MARKS: u32 = 5;
+
+/// GPU time of the parts of a frame between consecutive `Mark`s
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GpuTimes {
+    /// The scene render pass, or the geometry and lighting passes of deferred shading, and the
+    /// minimap's overhead view
+    pub scene: Duration,
+    /// Resolves, god rays, motion blur, bloom and the post effect chain
+    pub effects: Duration,
+    /// The post pass writing the swap chain image
+    pub post: Duration,
+    /// Decals, the lens flare, the minimap, the plot and the debug panel drawn over the post pass
+    pub overlays: Duration,
+}
+
+impl GpuTimes {
+    pub fn total(&self) -> Duration {
+        self.scene + self.effects + self.post + self.overlays
+    }
+}
+
+impl fmt::Display for GpuTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{:.2} ms (scene {:.2}, effects {:.2}, post {:.2}, overlays {:.2})",
+            ms(self.total()),
+            ms(self.scene),
+            ms(self.effects),
+            ms(self.post),
+            ms(self.overlays)
+        )
+    }
+}
+
+/// Timestamps at each `Mark`, one set per frame in flight
+pub struct GpuTimer {
+    pool: Arc<QueryPool>,
+    /// Nanoseconds per tick
+    period: f32,
+    valid_bits: u32,
+    /// Slots whose last recorded frame was submitted and not read yet. Queries of the others
+    /// were never written, or were reset by a command buffer that didn't run.
+    submitted: Vec<bool>,
+}
+
+impl GpuTimer {
+    /// `None` on queues that can't write timestamps
+    pub fn new(queue: &Arc<Queue>) -> Option<Self> {
+        let valid_bits = queue.family().timestamp_valid_bits()?;
+        let period = queue.device().physical_device().properties().timestamp_period?;
+        let pool = QueryPool::new(
+            queue.device().clone(),
+            QueryType::Timestamp,
+            MARKS * MAX_FRAMES_IN_FLIGHT as u32,
+        )
+        .ok()?;
+        Some(Self {
+            pool: Arc::new(pool),
+            period,
+            valid_bits,
+            submitted: vec![false; MAX_FRAMES_IN_FLIGHT],
+        })
+    }
+
+    /// Has to be recorded outside of render passes, `Mark::Start` resets the slot's queries
+    pub fn mark(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, slot: usize, mark: Mark) {
+        let first = slot as u32 * MARKS;
+        unsafe {
+            if let Mark::Start = mark {
+                builder.reset_query_pool(self.pool.clone(), first..first + MARKS).unwrap();
+            }
+            builder
+                .write_timestamp(self.pool.clone(), first + mark as u32, PipelineStage::BottomOfPipe)
+                .unwrap();
+        }
+    }
+
+    /// Call once the frame recorded in `slot` was submitted, its times can be read after it
+    pub fn submitted(&mut self, slot: usize) {
+        self.submitted[slot] = true;
+    }
+
+    /// The times of the frame last submitted in `slot`, once it has finished. Each frame is
+    /// only read once, `None` when its timestamps aren't all available.
+    pub fn read(&mut self, slot: usize) -> Option<GpuTimes> {
+        if !std::mem::replace(&mut self.submitted[slot], false) {
+            return None;
+        }
+
+        let first = slot as u32 * MARKS;
+        let mut ticks = [0u64; MARKS as usize];
+        let available = self
+            .pool
+            .queries_range(first..first + MARKS)?
+            .get_results(&mut ticks, QueryResultFlags::default())
+            .ok()?;
+        if !available {
+            return None;
+        }
+
+        // the counter wraps around above its valid bits
+        let mask = u64::MAX >> (64 - self.valid_bits.clamp(1, 64));
+        let elapsed = |from: Mark, to: Mark| {
+            let ticks = (ticks[to as usize] & mask).wrapping_sub(ticks[from as usize] & mask) & mask;
+            Duration::from_nanos((ticks as f64 * self.period as f64) as u64)
+        };
+        Some(GpuTimes {
+            scene: elapsed(Mark::Start, Mark::SceneDone),
+            effects: elapsed(Mark::SceneDone, Mark::EffectsDone),
+            post: elapsed(Mark::EffectsDone, Mark::PostDone),
+            overlays: elapsed(Mark::PostDone, Mark::End),
+        })
+    }
+}
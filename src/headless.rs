@@ -0,0 +1,231 @@
+//! Rendering without a window, for `--headless`. The demo scene is drawn into an offscreen image
+//! and every frame is written to a PNG file, so rendering can be checked where there is no display.
+
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use cgmath::{Matrix4, SquareMatrix};
+use log::{info, warn};
+use vulkano::{
+    buffer::{BufferUsage, CpuAccessibleBuffer},
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBuffer, SubpassContents},
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImageUsage},
+    instance::InstanceExtensions,
+    pipeline::cache::PipelineCache,
+    sync::GpuFuture,
+};
+
+use crate::{
+    audio,
+    camera::Camera,
+    capture::Capture,
+    debug_draw::{self, DebugLines},
+    debug_view::DebugView,
+    descriptors::DescriptorCache,
+    device,
+    lighting::Light,
+    msaa::Multisampling,
+    pipeline_cache,
+    projection::Projection,
+    renderer::{self, vertex_shader},
+    scene::Emission,
+    scenes::{Animation, SceneId},
+    settings::Settings,
+    shader_reload::ReloadedShaders,
+    staging::Staging,
+    texture::Texture,
+    uniforms::{self, UniformBufferObject, UniformBuffers},
+    GraphicsApplication,
+};
+
+/// The frames are 8 bit like the swap chain, the post pass's calibration is left out
+const OUTPUT_FORMAT: Format = Format::R8G8B8A8Unorm;
+/// Animations advance by one frame at this rate whatever the rendering takes, so runs repeat
+const FRAME_RATE: f32 = 60.0;
+
+/// Renders `frames` frames of `scene` at the window size from the settings into
+/// `output/frame_0000.png` and so on. Fails on validation errors, like `--smoke-test`.
+pub fn run(settings: &Settings, scene: SceneId, frames: u64, output: &Path) -> Result<(), String> {
+    fs::create_dir_all(output).map_err(|e| format!("{}: {}", output.display(), e))?;
+    let instance = GraphicsApplication::create_vk_instance(settings.validation, InstanceExtensions::none());
+    let validation_errors = Arc::new(AtomicUsize::new(0));
+    // reports validation messages while it is alive
+    let _debug_callback = GraphicsApplication::create_debug_callback(&instance, &validation_errors, settings);
+    let (device, queue) =
+        device::create_headless_device(&instance, settings.gpu.as_deref()).map_err(|e| e.to_string())?;
+    info!(
+        "Rendering headless on {}",
+        device.physical_device().properties().device_name.as_deref().unwrap_or("unknown device")
+    );
+
+    let extent = settings.window_size;
+    let samples = renderer::sample_count(&device, settings.msaa, extent);
+    let render_pass = renderer::create_render_pass(&device, OUTPUT_FORMAT, samples);
+    let target_usage = ImageUsage {
+        color_attachment: true,
+        transfer_source: true,
+        ..ImageUsage::none()
+    };
+    let target = ImageView::new(
+        AttachmentImage::with_usage(device.clone(), extent, OUTPUT_FORMAT, target_usage)
+            .map_err(|e| format!("Failed to create the offscreen image: {}", e))?,
+    )
+    .unwrap();
+    let depth_buffer = renderer::create_depth_buffer(&device, extent);
+    let multisampling = (samples > 1)
+        .then(|| Multisampling::new(&device, samples, OUTPUT_FORMAT, &depth_buffer))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let framebuffer = renderer::create_framebuffer(&render_pass, &target, &depth_buffer, multisampling.as_ref());
+
+    let pipeline_cache = match &settings.pipeline_cache {
+        Some(path) => pipeline_cache::load(&device, path),
+        None => PipelineCache::empty(device.clone()).unwrap(),
+    };
+    let graphics_pipeline = renderer::create_graphics_pipeline(
+        &device,
+        &render_pass,
+        DebugView::Shaded,
+        false,
+        &pipeline_cache,
+        &ReloadedShaders::new(&device),
+    )
+    .map_err(|e| e.to_string())?;
+    let line_pipeline = debug_draw::create_pipeline(&device, &render_pass).map_err(|e| e.to_string())?;
+    let dynamic_state = renderer::dynamic_state(extent);
+
+    let demo_scene = scene.scene();
+    let (vertices, indices) = demo_scene.mesh();
+    let mut staging = Staging::new(&queue);
+    let vertex_buffer = renderer::create_vertex_buffer(&mut staging, &vertices).map_err(|e| e.to_string())?;
+    let index_buffer = renderer::create_index_buffer(&mut staging, &indices).map_err(|e| e.to_string())?;
+    let white_texture = Texture::white(&mut staging).map_err(|e| e.to_string())?;
+    staging.submit_and_wait().map_err(|e| e.to_string())?;
+
+    let uniform_buffers = UniformBuffers::new(&device);
+    let descriptors = DescriptorCache::default();
+    let projection = Projection {
+        fov_y: settings.fov_y,
+        near: settings.near,
+        far: settings.far,
+        ..Projection::default()
+    };
+    let view = Camera::default().view_matrix();
+
+    for frame in 0..frames {
+        let time = frame as f32 / FRAME_RATE;
+        let model = match demo_scene.animation {
+            Animation::Rotate => demo_scene.model() * uniforms::rotation(time),
+            _ => demo_scene.model(),
+        };
+
+        let mut builder =
+            AutoCommandBufferBuilder::primary(device.clone(), queue.family(), CommandBufferUsage::OneTimeSubmit)
+                .unwrap();
+        let clear_color = settings.clear_color;
+        let mut clear_values = vec![[clear_color[0], clear_color[1], clear_color[2], 1.0].into(), 1.0.into()];
+        if multisampling.is_some() {
+            clear_values.push(ClearValue::None);
+        }
+        builder
+            .begin_render_pass(framebuffer.clone(), SubpassContents::Inline, clear_values)
+            .unwrap();
+
+        match demo_scene.pattern {
+            Some(draw_pattern) => {
+                let mut lines = DebugLines::default();
+                draw_pattern(&mut lines, extent);
+                let line_buffer = CpuAccessibleBuffer::from_iter(
+                    device.clone(),
+                    BufferUsage::vertex_buffer(),
+                    false,
+                    lines.vertices().iter().cloned(),
+                )
+                .unwrap();
+                builder
+                    .draw(
+                        line_pipeline.clone(),
+                        &dynamic_state,
+                        vec![line_buffer],
+                        (),
+                        debug_draw::vertex_shader::ty::PushConstants {
+                            view_projection: Matrix4::<f32>::identity().into(),
+                        },
+                        vec![],
+                    )
+                    .unwrap();
+            }
+            None => {
+                let uniforms = UniformBufferObject::new(
+                    model,
+                    view,
+                    projection.matrix(extent),
+                    [0.0; audio::BANDS],
+                    &Light::default(),
+                    Emission::default(),
+                    frame,
+                );
+                let mut sets = vec![uniform_buffers.descriptor_set(&graphics_pipeline, uniforms)];
+                sets.extend(white_texture.descriptor_set(&white_texture, &descriptors, &graphics_pipeline, 1, true));
+                let push_constants = vertex_shader::ty::PushConstants {
+                    clip_plane: [0.0; 4],
+                    depth_range: [projection.near, projection.far],
+                    time,
+                    animation: demo_scene.animation as u32,
+                    frame: frame as u32,
+                    section: 0,
+                    interpolation: 0.0,
+                };
+                builder
+                    .draw_indexed(
+                        graphics_pipeline.clone(),
+                        &dynamic_state,
+                        vec![vertex_buffer.clone()],
+                        index_buffer.clone(),
+                        sets,
+                        push_constants,
+                        vec![],
+                    )
+                    .unwrap();
+            }
+        }
+
+        builder.end_render_pass().unwrap();
+
+        let path = output.join(format!("frame_{:04}.png", frame));
+        let capture = Capture::new(&device, extent, OUTPUT_FORMAT, path)?;
+        capture
+            .record(&mut builder, target.image().clone())
+            .map_err(|e| format!("Failed to copy frame {}: {}", frame, e))?;
+
+        builder
+            .build()
+            .unwrap()
+            .execute(queue.clone())
+            .map_err(|e| format!("Failed to submit frame {}: {}", frame, e))?
+            .then_signal_fence_and_flush()
+            .and_then(|future| future.wait(None))
+            .map_err(|e| format!("Failed to render frame {}: {:?}", frame, e))?;
+        capture.save_blocking()?;
+    }
+
+    if let Some(path) = &settings.pipeline_cache {
+        if let Err(e) = pipeline_cache::save(&pipeline_cache, path) {
+            warn!("Failed to save the pipeline cache: {}", e);
+        }
+    }
+
+    let errors = validation_errors.load(Ordering::SeqCst);
+    println!("headless: rendered {} frames of {} into {}", frames, demo_scene.name, output.display());
+    if errors > 0 {
+        return Err(format!("{} validation errors", errors));
+    }
+    Ok(())
+}
@@ -0,0 +1,275 @@
+//! Hierarchical depth: a pyramid of the depth buffer built by a compute pass after the scene
+//! every frame. Each texel holds the farthest and the nearest depth of the pixels under it, so
+//! an occlusion test can reject a box whose nearest depth is behind the farthest one of the few
+//! texels it covers, and a screen space ray march can skip a whole texel while the ray stays in
+//! front of its nearest depth. The levels are separate images, like the ones `mipmaps` builds,
+//! since vulkano 0.24 can only view a whole mip chain.
+
+use std::sync::Arc;
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::DescriptorSet,
+    device::Queue,
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImageAccess, ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage},
+    pipeline::{
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        ComputePipeline, ComputePipelineAbstract, GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::{
+    descriptors::{self, Resource},
+    post,
+};
+
+/// Red is the farthest depth, green the nearest. `rg32f` storage images need an optional
+/// device feature, `rgba32f` ones don't.
+pub const FORMAT: Format = Format::R32G32B32A32Sfloat;
+const WORKGROUP_SIZE: u32 = 8;
+
+mod depth_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/hiz_depth.comp"
+    }
+}
+
+mod reduce_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/hiz_reduce.comp"
+    }
+}
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod debug_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/hiz_debug.frag"
+    }
+}
+
+/// The pyramid and the passes that build and show it, recreated with the swap chain
+pub struct HiZ {
+    levels: Vec<Arc<ImageView<Arc<StorageImage>>>>,
+    depth_pipeline: Arc<ComputePipeline>,
+    reduce_pipeline: Arc<ComputePipeline>,
+    /// The depth buffer into level 0, then each level into the next one
+    sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+    debug_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    debug_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    debug_sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+    scene_size: [u32; 2],
+}
+
+impl HiZ {
+    /// `depth_buffer` is the single sampled depth of the full image, level 0 is half its size.
+    /// `scene` is the post pass target the debug view draws into.
+    pub fn new(
+        queue: &Arc<Queue>,
+        scene: &Arc<ImageView<Arc<AttachmentImage>>>,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Self {
+        let device = queue.device();
+        let scene_size = scene.image().dimensions().width_height();
+        let usage = ImageUsage {
+            storage: true,
+            sampled: true,
+            ..ImageUsage::none()
+        };
+        let levels = level_sizes(depth_buffer.image().dimensions().width_height())
+            .into_iter()
+            .map(|[width, height]| {
+                let image = StorageImage::with_usage(
+                    device.clone(),
+                    ImageDimensions::Dim2d {
+                        width,
+                        height,
+                        array_layers: 1,
+                    },
+                    FORMAT,
+                    usage,
+                    ImageCreateFlags::none(),
+                    Some(queue.family()),
+                )
+                .expect("Failed to create a Hi-Z level");
+                ImageView::new(image).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let depth_shader_module =
+            depth_shader::Shader::load(device.clone()).expect("Failed to create Hi-Z depth shader module");
+        let reduce_shader_module =
+            reduce_shader::Shader::load(device.clone()).expect("Failed to create Hi-Z reduce shader module");
+        let depth_pipeline = Arc::new(
+            ComputePipeline::new(device.clone(), &depth_shader_module.main_entry_point(), &(), None).unwrap(),
+        );
+        let reduce_pipeline = Arc::new(
+            ComputePipeline::new(device.clone(), &reduce_shader_module.main_entry_point(), &(), None).unwrap(),
+        );
+
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        let layout = depth_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let mut sets = vec![descriptors::build(
+            layout,
+            &[
+                Resource::SampledImage(depth_buffer.clone(), sampler.clone()),
+                Resource::Image(levels[0].clone()),
+            ],
+        )];
+        let layout = reduce_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        sets.extend(levels.windows(2).map(|pair| {
+            descriptors::build(
+                layout.clone(),
+                &[Resource::Image(pair[0].clone()), Resource::Image(pair[1].clone())],
+            )
+        }));
+
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: post::INTERMEDIATE_FORMAT,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+        let vert_shader_module =
+            vertex_shader::Shader::load(device.clone()).expect("Failed to create Hi-Z vertex shader module");
+        let debug_shader_module =
+            debug_shader::Shader::load(device.clone()).expect("Failed to create Hi-Z debug shader module");
+        let debug_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [scene_size[0] as f32, scene_size[1] as f32],
+                    depth_range: 0.0..1.0,
+                }])
+                .fragment_shader(debug_shader_module.main_entry_point(), ())
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+        let debug_framebuffer =
+            Arc::new(Framebuffer::start(render_pass).add(scene.clone()).unwrap().build().unwrap());
+        let layout = debug_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let debug_sets = levels
+            .iter()
+            .map(|level| descriptors::build(layout.clone(), &[Resource::SampledImage(level.clone(), sampler.clone())]))
+            .collect();
+
+        Self {
+            levels,
+            depth_pipeline,
+            reduce_pipeline,
+            sets,
+            debug_pipeline,
+            debug_framebuffer,
+            debug_sets,
+            scene_size,
+        }
+    }
+
+    /// The pyramid, finest level first, valid after `record` for the rest of the frame
+    pub fn levels(&self) -> &[Arc<ImageView<Arc<StorageImage>>>] {
+        &self.levels
+    }
+
+    /// Records the passes that build every level from the depth buffer, after the scene pass
+    pub fn record(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        for (index, (level, set)) in self.levels.iter().zip(&self.sets).enumerate() {
+            let [width, height] = level.image().dimensions().width_height();
+            let group_counts = [width.div_ceil(WORKGROUP_SIZE), height.div_ceil(WORKGROUP_SIZE), 1];
+            let pipeline = if index == 0 {
+                &self.depth_pipeline
+            } else {
+                &self.reduce_pipeline
+            };
+            builder
+                .dispatch(group_counts, pipeline.clone(), set.clone(), (), vec![])
+                .unwrap();
+        }
+    }
+
+    /// Replaces the scene with `level` stretched over it, gray by distance and red where the
+    /// nearest and farthest depths of a texel are apart. Levels past the last show the last.
+    pub fn draw_debug(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        level: usize,
+        depth_range: [f32; 2],
+    ) {
+        let set = &self.debug_sets[level.min(self.debug_sets.len() - 1)];
+        let push_constants = debug_shader::ty::PushConstants {
+            inverseSize: [1.0 / self.scene_size[0] as f32, 1.0 / self.scene_size[1] as f32],
+            depthRange: depth_range,
+        };
+        builder
+            .begin_render_pass(self.debug_framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
+            .unwrap()
+            .draw(
+                self.debug_pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                set.clone(),
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
+
+/// Sizes of the levels for a depth buffer of `size`, each half the one before down to 1x1
+pub fn level_sizes(size: [u32; 2]) -> Vec<[u32; 2]> {
+    let mut sizes = Vec::new();
+    let mut level = size;
+    loop {
+        level = [(level[0] / 2).max(1), (level[1] / 2).max(1)];
+        sizes.push(level);
+        if level == [1, 1] {
+            return sizes;
+        }
+    }
+}
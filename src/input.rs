@@ -0,0 +1,150 @@
+//! Keyboard and mouse state gathered from the window's events between two frames. The event
+//! loop only feeds events in, the frame then asks what is held, what was pressed or released
+//! since the last frame and how far the cursor and the wheel moved.
+
+use std::collections::HashSet;
+
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// Pixels of a touchpad scroll that count as one wheel notch
+const PIXELS_PER_LINE: f64 = 20.0;
+
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    held_keys: HashSet<VirtualKeyCode>,
+    /// In the order they were pressed, a key pressed twice in one frame is in here twice
+    pressed_keys: Vec<VirtualKeyCode>,
+    released_keys: Vec<VirtualKeyCode>,
+    held_buttons: HashSet<MouseButton>,
+    pressed_buttons: Vec<MouseButton>,
+    released_buttons: Vec<MouseButton>,
+    modifiers: ModifiersState,
+    /// In physical pixels from the window's top left corner, `None` before it first moves
+    cursor: Option<[f64; 2]>,
+    cursor_delta: [f64; 2],
+    /// Wheel notches, positive scrolling up
+    scroll: f32,
+}
+
+impl Input {
+    /// Takes in an event of the window, the ones that aren't keyboard or mouse input are ignored
+    pub fn on_event(&mut self, event: &WindowEvent<'_>) {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    // held keys repeat their press events, only the first one counts
+                    if self.held_keys.insert(*key) {
+                        self.pressed_keys.push(*key);
+                    }
+                }
+                ElementState::Released => {
+                    if self.held_keys.remove(key) {
+                        self.released_keys.push(*key);
+                    }
+                }
+            },
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    self.held_buttons.insert(*button);
+                    self.pressed_buttons.push(*button);
+                }
+                ElementState::Released => {
+                    if self.held_buttons.remove(button) {
+                        self.released_buttons.push(*button);
+                    }
+                }
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some(last) = self.cursor {
+                    self.cursor_delta[0] += position.x - last[0];
+                    self.cursor_delta[1] += position.y - last[1];
+                }
+                self.cursor = Some([position.x, position.y]);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+                };
+            }
+            // the releases go to the window that has the focus now, nothing stays held down
+            WindowEvent::Focused(false) => {
+                self.released_keys.extend(self.held_keys.drain());
+                self.released_buttons.extend(self.held_buttons.drain());
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => (),
+        }
+    }
+
+    /// Forgets what happened since the last frame, what is held stays held
+    pub fn end_frame(&mut self) {
+        self.pressed_keys.clear();
+        self.released_keys.clear();
+        self.pressed_buttons.clear();
+        self.released_buttons.clear();
+        self.cursor_delta = [0.0, 0.0];
+        self.scroll = 0.0;
+    }
+
+    pub fn held(&self, key: VirtualKeyCode) -> bool {
+        self.held_keys.contains(&key)
+    }
+
+    /// Whether `key` went down since the last frame
+    pub fn pressed(&self, key: VirtualKeyCode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    /// Whether `key` went up since the last frame
+    pub fn released(&self, key: VirtualKeyCode) -> bool {
+        self.released_keys.contains(&key)
+    }
+
+    /// Keys that went down since the last frame, in order
+    pub fn pressed_keys(&self) -> &[VirtualKeyCode] {
+        &self.pressed_keys
+    }
+
+    pub fn button_held(&self, button: MouseButton) -> bool {
+        self.held_buttons.contains(&button)
+    }
+
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
+    pub fn button_released(&self, button: MouseButton) -> bool {
+        self.released_buttons.contains(&button)
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    /// Where the cursor is, the window's top left corner before it first moves
+    pub fn cursor(&self) -> [f64; 2] {
+        self.cursor.unwrap_or([0.0, 0.0])
+    }
+
+    /// Pixels the cursor moved since the last frame
+    pub fn cursor_delta(&self) -> [f64; 2] {
+        self.cursor_delta
+    }
+
+    /// Wheel notches scrolled since the last frame, positive up
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use cgmath::Matrix4;
+use vulkano::{
+    buffer::{BufferAccess, BufferUsage, ImmutableBuffer, TypedBufferAccess},
+    command_buffer::{AutoCommandBufferBuilder, DynamicState},
+    device::Device,
+    impl_vertex,
+    pipeline::{vertex::OneVertexOneInstanceDefinition, GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{RenderPass, Subpass},
+};
+
+use crate::{error::Error, staging::Staging, vertex::Vertex};
+
+/// Side length of the whole grid in world units, whatever the instance count
+const GRID_SIZE: f32 = 3.0;
+/// Share of a grid cell its quad covers
+const QUAD_FILL: f32 = 0.8;
+/// Depth of the grid, just behind the demo scenes
+const GRID_DEPTH: f32 = -0.5;
+pub const DEFAULT_INSTANCES: u32 = 400;
+
+pub mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/instanced.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/instanced.frag"
+    }
+}
+
+/// Per instance vertex input, read once per quad instead of once per vertex
+#[derive(Copy, Clone, Default)]
+pub struct InstanceData {
+    offset: [f32; 3],
+    scale: f32,
+    tint: [f32; 3],
+}
+
+impl_vertex!(InstanceData, offset, scale, tint);
+
+/// The quad mesh drawn `count` times in a grid with a single draw call
+pub struct InstancedQuads {
+    pub count: u32,
+    instances: Arc<ImmutableBuffer<[InstanceData]>>,
+}
+
+impl InstancedQuads {
+    /// Usable once `staging` was submitted
+    pub fn new(staging: &mut Staging, count: u32) -> Result<Self, Error> {
+        let instances = staging.buffer(grid(count).into_iter(), BufferUsage::vertex_buffer())?;
+        Ok(Self { count, instances })
+    }
+
+    /// The instance count comes from the length of the instance buffer
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+        vertex_buffer: &Arc<dyn BufferAccess + Send + Sync>,
+        index_buffer: &Arc<dyn TypedBufferAccess<Content = [u32]> + Send + Sync>,
+        view_projection: Matrix4<f32>,
+        time: f32,
+    ) {
+        let push_constants = vertex_shader::ty::PushConstants {
+            view_projection: view_projection.into(),
+            time,
+        };
+        builder
+            .draw_indexed(
+                pipeline.clone(),
+                dynamic_state,
+                vec![vertex_buffer.clone(), self.instances.clone()],
+                index_buffer.clone(),
+                (),
+                push_constants,
+                vec![],
+            )
+            .unwrap();
+    }
+}
+
+/// A square grid of `count` quads facing the camera, filled row by row, colored by their place
+fn grid(count: u32) -> Vec<InstanceData> {
+    let columns = (count as f32).sqrt().ceil().max(1.0) as u32;
+    let cell = GRID_SIZE / columns as f32;
+    let rows = count.div_ceil(columns);
+
+    (0..count)
+        .map(|i| {
+            let (column, row) = (i % columns, i / columns);
+            let u = (column as f32 + 0.5) / columns as f32;
+            let v = (row as f32 + 0.5) / rows as f32;
+            InstanceData {
+                offset: [(u - 0.5) * GRID_SIZE, (v - 0.5) * cell * rows as f32, GRID_DEPTH],
+                scale: cell * QUAD_FILL,
+                tint: [u, v, 1.0 - u * v],
+            }
+        })
+        .collect()
+}
+
+/// Takes the quad's vertices from the first buffer and `InstanceData` from the second
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "instanced vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "instanced fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new())
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .cull_mode_disabled()
+            .depth_stencil_simple_depth()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
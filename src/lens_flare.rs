@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use cgmath::{Matrix4, Vector3};
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::{descriptor_set::PersistentDescriptorSet, DescriptorSet},
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, SwapchainImage},
+    pipeline::{
+        blend::{AttachmentBlend, BlendFactor, BlendOp},
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+use winit::window::Window;
+
+use crate::error::Error;
+
+/// Sprites drawn by flare.vert, six vertices each
+const SPRITES: usize = 7;
+/// Distance from the screen center, in NDC, where the flare starts to fade out
+const EDGE_FADE_START: f32 = 0.8;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/flare.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/flare.frag"
+    }
+}
+
+/// Glare and ghost sprites for the sun, drawn in a render pass after the scene. The scene's depth
+/// buffer can't be read while it is attached, so the flare gets its own pass that samples it
+/// around the sun to find out how much of the sun is hidden.
+pub struct LensFlare {
+    pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    depth_set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl LensFlare {
+    /// Recreated with the swap chain, like the framebuffers it draws into
+    pub fn new(
+        device: &Arc<Device>,
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        color_format: Format,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Result<Self, Error> {
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+
+        // the depth buffer is smaller when the scene is drawn at a lower resolution
+        let dimensions = swap_chain_images[0].dimensions();
+        let pipeline = Self::create_pipeline(device, dimensions, &render_pass)?;
+
+        let framebuffers = swap_chain_images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Arc::new(Framebuffer::start(render_pass.clone()).add(view).unwrap().build().unwrap())
+                    as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        // depth formats don't have to support linear filtering
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let depth_set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(depth_buffer.clone(), sampler)
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Ok(Self {
+            pipeline,
+            framebuffers,
+            depth_set,
+        })
+    }
+
+    fn create_pipeline(
+        device: &Arc<Device>,
+        dimensions: [u32; 2],
+        render_pass: &Arc<RenderPass>,
+    ) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "flare vertex",
+            source,
+        })?;
+        let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "flare fragment",
+            source,
+        })?;
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        let additive = AttachmentBlend {
+            enabled: true,
+            color_op: BlendOp::Add,
+            color_source: BlendFactor::One,
+            color_destination: BlendFactor::One,
+            alpha_op: BlendOp::Max,
+            alpha_source: BlendFactor::One,
+            alpha_destination: BlendFactor::One,
+            mask_red: true,
+            mask_green: true,
+            mask_blue: true,
+            mask_alpha: true,
+        };
+
+        Ok(Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport])
+                .fragment_shader(frag_shader_module.main_entry_point(), ())
+                .blend_collective(additive)
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())?,
+        ))
+    }
+
+    /// Records the overlay pass, nothing is drawn if the sun is behind the camera or set
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        view_projection: Matrix4<f32>,
+        sun_direction: Vector3<f32>,
+    ) {
+        // the sun is infinitely far away, so it is projected as a direction (w = 0)
+        let clip = view_projection * sun_direction.extend(0.0);
+        if clip.w <= 0.0 || sun_direction.y < -0.05 {
+            return;
+        }
+
+        let sun = [clip.x / clip.w, clip.y / clip.w];
+        let distance_from_center = sun[0].abs().max(sun[1].abs());
+        let edge_fade = 1.0 - ((distance_from_center - EDGE_FADE_START) / (1.5 - EDGE_FADE_START)).clamp(0.0, 1.0);
+        let dusk_fade = ((sun_direction.y + 0.05) / 0.15).clamp(0.0, 1.0);
+        let intensity = edge_fade * dusk_fade;
+        if intensity <= 0.0 {
+            return;
+        }
+
+        let dimensions = self.framebuffers[image_index].dimensions();
+        let push_constants = vertex_shader::ty::PushConstants {
+            sun,
+            aspect: dimensions[0] as f32 / dimensions[1].max(1) as f32,
+            intensity,
+        };
+
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_index].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap()
+            .draw(
+                self.pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: SPRITES * 6,
+                    instances: 1,
+                },
+                self.depth_set.clone(),
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+pub mod app;
+pub mod audio;
+pub mod bloom;
+pub mod calibration;
+pub mod camera;
+pub mod capture;
+pub mod checkerboard;
+pub mod clusters;
+pub mod color_grade;
+pub mod compute;
+pub mod config;
+pub mod console;
+pub mod culling;
+pub mod debug_draw;
+pub mod debug_ui;
+pub mod debug_utils;
+pub mod debug_view;
+pub mod decals;
+pub mod deferred;
+pub mod descriptors;
+pub mod device;
+pub mod error;
+pub mod frame_log;
+pub mod frustum;
+pub mod fullscreen;
+pub mod god_rays;
+pub mod gpu_timer;
+pub mod headless;
+pub mod hiz;
+pub mod input;
+pub mod instancing;
+pub mod lens_flare;
+pub mod lighting;
+pub mod logging;
+pub mod measure;
+pub mod memory_budget;
+pub mod mesh;
+pub mod minimap;
+pub mod mipmaps;
+pub mod model;
+pub mod motion_blur;
+pub mod msaa;
+pub mod objects;
+pub mod panic_hook;
+pub mod parallel;
+pub mod pipeline_cache;
+pub mod plot;
+pub mod ply;
+pub mod point_cloud;
+pub mod portal;
+pub mod post;
+pub mod post_effects;
+pub mod primitives;
+pub mod projection;
+pub mod queries;
+pub mod ray;
+pub mod refresh;
+pub mod remote;
+pub mod render_target;
+pub mod renderer;
+pub mod scene;
+pub mod scenes;
+pub mod section;
+pub mod settings;
+pub mod shader;
+pub mod shader_reload;
+pub mod sky;
+pub mod skybox;
+pub mod spline;
+pub mod staging;
+pub mod state;
+pub mod stats;
+pub mod stl;
+pub mod subgroups;
+pub mod swapchain;
+pub mod terrain;
+pub mod texture;
+pub mod timeline;
+pub mod turntable;
+pub mod uniforms;
+pub mod validation;
+pub mod vegetation;
+pub mod vertex;
+pub mod vertex_capture;
+pub mod world;
+
+pub use app::GraphicsApplication;
@@ -0,0 +1,50 @@
+//! The point light of the `lit` view. The scene's fragment shader for that view shades with
+//! Blinn-Phong: an ambient term, a diffuse term from the angle between the normal and the
+//! light, and a specular highlight from the angle between the normal and the vector halfway
+//! between the light and the camera, which is cheaper than a reflected vector and keeps the
+//! highlight round at grazing angles.
+
+use std::fmt;
+
+use cgmath::Point3;
+
+/// Changed with the `light` command, passed to the shaders in the uniform buffer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    /// In world space
+    pub position: Point3<f32>,
+    pub color: [f32; 3],
+    /// Share of the surface color lit without the light reaching it
+    pub ambient: f32,
+    /// Exponent of the specular term, higher for smaller, sharper highlights
+    pub shininess: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            // above and to the right of the default camera
+            position: Point3::new(1.5, 2.0, 3.0),
+            color: [1.0, 1.0, 1.0],
+            ambient: 0.15,
+            shininess: 32.0,
+        }
+    }
+}
+
+impl fmt::Display for Light {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "at ({:.2}, {:.2}, {:.2}), color ({:.2}, {:.2}, {:.2}), ambient {:.2}, shininess {:.0}",
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            self.color[0],
+            self.color[1],
+            self.color[2],
+            self.ambient,
+            self.shininess
+        )
+    }
+}
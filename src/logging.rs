@@ -0,0 +1,51 @@
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Index of the frame currently being prepared, shared with the logger and the validation callback
+static FRAME_INDEX: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_frame_index(frame_index: u64) {
+    FRAME_INDEX.store(frame_index, Ordering::Relaxed);
+}
+
+pub fn frame_index() -> u64 {
+    FRAME_INDEX.load(Ordering::Relaxed)
+}
+
+/// Writes `log` records to stderr, prefixed with the frame they were emitted in
+struct FrameLogger;
+
+impl Log for FrameLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "[frame {}] {:<5} {}: {}",
+                frame_index(),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+static LOGGER: FrameLogger = FrameLogger;
+
+pub fn init() {
+    // only fails if a logger was already installed, which is fine to keep
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
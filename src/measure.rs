@@ -0,0 +1,51 @@
+use cgmath::{EuclideanSpace, MetricSpace, Point3, Vector3};
+
+use crate::debug_draw::DebugLines;
+
+const LINE_COLOR: [f32; 3] = [1.0, 0.9, 0.1];
+const MARKER_SIZE: f32 = 0.04;
+/// Width of one glyph of the distance label in world units
+const LABEL_GLYPH_SIZE: f32 = 0.04;
+
+/// Distance between two picked points. The first click sets the start, the second the end and
+/// the third starts over from a new start.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Measurement {
+    start: Option<Point3<f32>>,
+    end: Option<Point3<f32>>,
+}
+
+impl Measurement {
+    /// Starts a new measurement once both ends are set
+    pub fn add_point(&mut self, point: Point3<f32>) {
+        match (self.start, self.end) {
+            (Some(_), None) => self.end = Some(point),
+            _ => {
+                self.start = Some(point);
+                self.end = None;
+            }
+        }
+    }
+
+    pub fn distance(&self) -> Option<f32> {
+        Some(self.start?.distance(self.end?))
+    }
+
+    /// Markers on the picked points, the line between them and the distance above its middle.
+    /// `right` and `up` are the camera axes in world space, so the label faces the camera.
+    pub fn draw(&self, lines: &mut DebugLines, right: Vector3<f32>, up: Vector3<f32>) {
+        for point in self.start.iter().chain(self.end.iter()) {
+            lines.cross(*point, MARKER_SIZE, LINE_COLOR);
+        }
+
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            lines.line(start, end, LINE_COLOR);
+
+            let label = format!("{:.3}", start.distance(end));
+            let right = right * LABEL_GLYPH_SIZE;
+            let up = up * LABEL_GLYPH_SIZE * 2.0;
+            let origin = start.midpoint(end) - right * (DebugLines::text_width(&label) / 2.0) + up * 0.5;
+            lines.text(&label, origin, right, up, LINE_COLOR);
+        }
+    }
+}
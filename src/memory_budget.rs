@@ -0,0 +1,71 @@
+//! Estimates of the memory the window sized render targets take, checked against the device
+//! local memory so low memory GPUs get fewer MSAA samples instead of failed allocations.
+
+use log::{info, warn};
+use vulkano::instance::PhysicalDevice;
+
+/// Share of the device local memory the render targets may take, the rest is left for meshes,
+/// textures and whatever else runs on the GPU
+const BUDGET_SHARE: f64 = 0.25;
+
+/// Bytes per pixel of the targets made at the window size, whatever the settings
+const POST_BYTES_PER_PIXEL: u64 = {
+    // the post process target and the depth buffer
+    let scene = 8 + 2;
+    // god rays at half resolution in each direction
+    let god_rays = 8 / 4;
+    // motion blur velocities and the blurred image
+    let motion_blur = 4 + 8;
+    scene + god_rays + motion_blur
+};
+/// Bytes per pixel and sample of the multisampled color and depth images
+const MSAA_BYTES_PER_SAMPLE: u64 = 8 + 2;
+
+/// Bytes of the render targets at `dimensions` with `samples` MSAA samples
+pub fn render_target_bytes(dimensions: [u32; 2], samples: u32) -> u64 {
+    let pixels = dimensions[0] as u64 * dimensions[1] as u64;
+    let msaa = if samples > 1 { samples as u64 * MSAA_BYTES_PER_SAMPLE } else { 0 };
+    pixels * (POST_BYTES_PER_PIXEL + msaa)
+}
+
+/// Bytes the render targets may take on `physical_device`
+pub fn budget(physical_device: PhysicalDevice) -> u64 {
+    let device_local: u64 = physical_device
+        .memory_heaps()
+        .filter(|heap| heap.is_device_local())
+        .map(|heap| heap.size() as u64)
+        .sum();
+    (device_local as f64 * BUDGET_SHARE) as u64
+}
+
+/// Halves `samples` until the render targets at `dimensions` fit the budget, logging why
+pub fn fit_sample_count(physical_device: PhysicalDevice, dimensions: [u32; 2], samples: u32) -> u32 {
+    let budget = budget(physical_device);
+    let mut fitted = samples;
+    while fitted > 1 && render_target_bytes(dimensions, fitted) > budget {
+        fitted /= 2;
+    }
+
+    let megabytes = |bytes: u64| bytes / (1024 * 1024);
+    if fitted != samples {
+        info!(
+            "{}x multisampling needs {} MB of render targets at {}x{}, more than the {} MB budget, using {}x",
+            samples,
+            megabytes(render_target_bytes(dimensions, samples)),
+            dimensions[0],
+            dimensions[1],
+            megabytes(budget),
+            fitted
+        );
+    }
+    if render_target_bytes(dimensions, fitted) > budget {
+        warn!(
+            "The render targets need {} MB at {}x{}, more than the {} MB budget, allocating them may fail",
+            megabytes(render_target_bytes(dimensions, fitted)),
+            dimensions[0],
+            dimensions[1],
+            megabytes(budget)
+        );
+    }
+    fitted
+}
@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{model, point_cloud::PointVertex, ply, ray::Aabb, stl, vertex::Vertex};
+
+/// Size of the cube imported data is scaled into, so any model fits the default camera
+pub const FIT_SIZE: f32 = 1.5;
+/// Color of vertices in files that do not store any
+pub const DEFAULT_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+
+/// Mesh data read from a file, before it is converted into one of the vertex formats.
+/// The attribute lists are either empty or have one entry per position.
+#[derive(Default)]
+pub struct ImportedMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub colors: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    /// Triangle list, empty for point clouds
+    pub indices: Vec<u32>,
+}
+
+impl ImportedMesh {
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.positions.iter().map(|&position| Point3::from(position)))
+    }
+
+    /// Files without normals get smooth ones from their faces, for the `lit` view
+    pub fn vertices(&self) -> Vec<Vertex> {
+        let computed;
+        let normals = if self.normals.is_empty() {
+            computed = self.smooth_normals();
+            &computed
+        } else {
+            &self.normals
+        };
+        (0..self.positions.len())
+            .map(|i| {
+                let vertex = Vertex::new(
+                    self.positions[i],
+                    self.colors.get(i).copied().unwrap_or(DEFAULT_COLOR),
+                    self.uvs.get(i).copied().unwrap_or_default(),
+                );
+                match normals.get(i) {
+                    Some(&normal) => vertex.with_normal(normal),
+                    None => vertex,
+                }
+            })
+            .collect()
+    }
+
+    /// The sum of the normals of the faces around each vertex, weighted by their area since the
+    /// cross product's length is twice the triangle's area
+    fn smooth_normals(&self) -> Vec<[f32; 3]> {
+        let mut sums = vec![Vector3::new(0.0, 0.0, 0.0); self.positions.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| Point3::from(self.positions[triangle[corner] as usize]));
+            let normal = (b - a).cross(c - a);
+            for &index in triangle {
+                sums[index as usize] += normal;
+            }
+        }
+        sums.into_iter()
+            .map(|sum| if sum.magnitude2() > 0.0 { sum.normalize() } else { Vector3::unit_z() }.into())
+            .collect()
+    }
+
+    pub fn point_vertices(&self) -> Vec<PointVertex> {
+        (0..self.positions.len())
+            .map(|i| PointVertex::new(self.positions[i], self.colors.get(i).copied().unwrap_or(DEFAULT_COLOR)))
+            .collect()
+    }
+}
+
+/// Imports a mesh, picking the format from the file extension
+pub fn load(path: &Path) -> Result<ImportedMesh, String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    let mesh = match extension.as_deref() {
+        Some("ply") => ply::load(path),
+        Some("stl") => stl::load(path),
+        Some("obj") => model::load(path),
+        _ => Err("unsupported mesh format, expected a .ply, .stl or .obj file".to_string()),
+    }
+    .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    if let Some(&index) = mesh.indices.iter().find(|&&index| index as usize >= mesh.positions.len()) {
+        return Err(format!("{}: vertex index {} is out of range", path.display(), index));
+    }
+
+    Ok(mesh)
+}
@@ -0,0 +1,266 @@
+//! An overhead view of the scene drawn with a second camera into a target of its own, then
+//! shown in the top right corner of the window. The scene's renderables are drawn again with
+//! an orthographic camera looking straight down at the main camera's target, every few frames
+//! when drawing it every frame costs too much.
+
+use std::{fmt, sync::Arc};
+
+use cgmath::{Matrix4, Point3, Vector3};
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::DescriptorSet,
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, ImageAccess, ImageUsage, SwapchainImage},
+    pipeline::{
+        cache::PipelineCache,
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, Subpass},
+};
+use winit::window::Window;
+
+use crate::{
+    debug_view::DebugView,
+    descriptors,
+    error::Error,
+    parallel::SceneDraw,
+    post,
+    projection,
+    render_target::RenderTarget,
+    renderer,
+    shader_reload::ReloadedShaders,
+};
+
+/// Width and height of the overhead view in pixels, drawn at this size in the corner too
+const SIZE: u32 = 256;
+/// Pixels between the view and the window's edges
+const MARGIN: u32 = 16;
+/// Height of the overhead camera above its target, the clip planes are set so everything
+/// from there down to as far below the target is seen
+const ALTITUDE: f32 = 50.0;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod composite_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/minimap.frag"
+    }
+}
+
+/// What the `minimap` command changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinimapSettings {
+    pub enabled: bool,
+    /// World units from the center to the edges of the view
+    pub extent: f32,
+    /// Frames between redraws, the corner shows the last one in between
+    pub interval: u32,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            extent: 10.0,
+            interval: 1,
+        }
+    }
+}
+
+impl fmt::Display for MinimapSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, extent {:.1}, every {} frame{}",
+            if self.enabled { "on" } else { "off" },
+            self.extent,
+            self.interval,
+            if self.interval == 1 { "" } else { "s" }
+        )
+    }
+}
+
+/// The overhead camera's matrices, looking down at `center` with -Z pointing up on the map
+pub fn camera(center: Point3<f32>, extent: f32) -> (Matrix4<f32>, Matrix4<f32>) {
+    let eye = center + Vector3::unit_y() * ALTITUDE;
+    let view = Matrix4::look_at_rh(eye, center, -Vector3::unit_z());
+    let projection = projection::orthographic(extent, extent, 0.1, ALTITUDE * 2.0);
+    (view, projection)
+}
+
+/// The scene pass drawing the overhead view into a target of its own, and the pass copying it
+/// into the corner of swap chain images. Recreated with the swap chain.
+pub struct Minimap {
+    /// Like the scene pass's, without multisampling
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    /// Shaded, for the single sampled render pass
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    composite_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    composite_framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    composite_set: Arc<dyn DescriptorSet + Send + Sync>,
+    /// Where the view is placed in swap chain images
+    corner: Viewport,
+    /// Frame the minimap was made in, it is drawn on that frame and every `interval` after it
+    first_frame: u64,
+}
+
+impl Minimap {
+    /// `cache` and `shaders` are the scene pipelines', the overhead view is drawn with the same
+    /// shaders
+    pub fn new(
+        device: &Arc<Device>,
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        color_format: Format,
+        cache: &Arc<PipelineCache>,
+        shaders: &ReloadedShaders,
+        frame: u64,
+    ) -> Result<Self, Error> {
+        let target = RenderTarget::new(device, [SIZE, SIZE], post::INTERMEDIATE_FORMAT, ImageUsage::none());
+        let depth_buffer = renderer::create_depth_buffer(device, [SIZE, SIZE]);
+        let render_pass = renderer::create_render_pass(device, post::INTERMEDIATE_FORMAT, 1);
+        let framebuffer = renderer::create_framebuffer(&render_pass, target.view(), &depth_buffer, None);
+        let pipeline =
+            renderer::create_graphics_pipeline(device, &render_pass, DebugView::Shaded, false, cache, shaders)?;
+
+        let composite_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+        let composite_framebuffers = swap_chain_images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Arc::new(Framebuffer::start(composite_pass.clone()).add(view).unwrap().build().unwrap())
+                    as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        let vert_shader_module =
+            vertex_shader::Shader::load(device.clone()).expect("Failed to create minimap vertex shader module");
+        let frag_shader_module =
+            composite_shader::Shader::load(device.clone()).expect("Failed to create minimap shader module");
+        let composite_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(frag_shader_module.main_entry_point(), ())
+                .render_pass(Subpass::from(composite_pass, 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+        let layout = composite_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let composite_set = descriptors::build(layout, &[target.sampled()]);
+
+        // a window smaller than the view shows it from its left edge
+        let width = swap_chain_images[0].dimensions().width();
+        let corner = Viewport {
+            origin: [width.saturating_sub(SIZE + MARGIN) as f32, MARGIN as f32],
+            dimensions: [SIZE as f32, SIZE as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        Ok(Self {
+            framebuffer,
+            pipeline,
+            composite_pipeline,
+            composite_framebuffers,
+            composite_set,
+            corner,
+            first_frame: frame,
+        })
+    }
+
+    /// The pipeline the overhead view's draws have to be built for
+    pub fn pipeline(&self) -> &Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+        &self.pipeline
+    }
+
+    /// Whether `frame` redraws the overhead view, every `interval` frames
+    pub fn due(&self, frame: u64, interval: u32) -> bool {
+        frame.wrapping_sub(self.first_frame).is_multiple_of(interval.max(1) as u64)
+    }
+
+    /// Records the overhead view's render pass, `draws` are made with `camera`'s matrices
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        draws: &[SceneDraw],
+        clear_color: [f32; 3],
+    ) {
+        let clear_values = vec![[clear_color[0], clear_color[1], clear_color[2], 1.0].into(), 1.0.into()];
+        builder
+            .begin_render_pass(self.framebuffer.clone(), SubpassContents::Inline, clear_values)
+            .unwrap();
+        let dynamic_state = renderer::dynamic_state([SIZE, SIZE]);
+        for draw in draws {
+            draw.record(builder, &self.pipeline, &dynamic_state);
+        }
+        builder.end_render_pass().unwrap();
+    }
+
+    /// Copies the last overhead view into the corner of swap chain image `image_index`,
+    /// encoded with the display calibration's `gamma`
+    pub fn composite(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        gamma: f32,
+    ) {
+        let dynamic_state = DynamicState {
+            viewports: Some(vec![self.corner.clone()]),
+            ..DynamicState::none()
+        };
+        let push_constants = composite_shader::ty::PushConstants {
+            origin: self.corner.origin,
+            inverseSize: [1.0 / SIZE as f32; 2],
+            gamma,
+        };
+
+        builder
+            .begin_render_pass(
+                self.composite_framebuffers[image_index].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap()
+            .draw(
+                self.composite_pipeline.clone(),
+                &dynamic_state,
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                self.composite_set.clone(),
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
@@ -0,0 +1,379 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use vulkano::{
+    buffer::{BufferUsage, CpuAccessibleBuffer},
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBuffer},
+    descriptor::descriptor_set::PersistentDescriptorSet,
+    device::Queue,
+    format::Format,
+    image::{
+        immutable::SubImage, view::ImageView, ImageAccess, ImageCreateFlags, ImageDimensions, ImageLayout, ImageUsage,
+        ImmutableImage, MipmapsCount, StorageImage,
+    },
+    pipeline::{ComputePipeline, ComputePipelineAbstract},
+    query::{QueryPool, QueryResultFlags, QueryType},
+    sampler::Filter,
+    sync::{GpuFuture, PipelineStage},
+};
+
+mod downsample_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/mip_downsample.comp"
+    }
+}
+
+mod subgroup_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/mip_downsample_subgroup.comp",
+        vulkan_version: "1.1",
+        spirv_version: "1.3"
+    }
+}
+
+/// Format of the textures the mip chains are made for
+pub const TEXTURE_FORMAT: Format = Format::R8G8B8A8Srgb;
+/// sRGB formats can rarely be storage images, the compute path writes the same bytes as this
+/// and copies them over
+const STORAGE_FORMAT: Format = Format::R8G8B8A8Unorm;
+/// Width and height of the texels each workgroup writes
+const WORKGROUP_SIZE: u32 = 8;
+
+/// How the mip chain of a texture is filled in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MipGeneration {
+    /// Each level is blitted down from the one above it, works everywhere
+    Blit,
+    /// A compute shader averages each level down from the one above it
+    Compute,
+    /// Like `Compute`, but every dispatch writes two levels, the second one averaged with
+    /// subgroup quad operations
+    ComputeSubgroup,
+}
+
+impl MipGeneration {
+    pub fn supported(self, queue: &Arc<Queue>) -> bool {
+        let physical_device = queue.device().physical_device();
+        let compute = queue.family().supports_compute()
+            && STORAGE_FORMAT.properties(physical_device).optimal_tiling_features.storage_image;
+
+        match self {
+            MipGeneration::Blit => true,
+            MipGeneration::Compute => compute,
+            MipGeneration::ComputeSubgroup => {
+                // only reported by Vulkan 1.1 devices, which the shader is built for
+                let properties = physical_device.properties();
+                compute
+                    && properties.subgroup_supported_operations.is_some_and(|operations| operations.quad)
+                    && properties.subgroup_supported_stages.is_some_and(|stages| stages.compute)
+                    && properties.subgroup_size.is_some_and(|size| size >= 4)
+            }
+        }
+    }
+
+    /// The first of the subgroup path, the plain compute path and blitting the device supports
+    pub fn pick(queue: &Arc<Queue>) -> Self {
+        [MipGeneration::ComputeSubgroup, MipGeneration::Compute, MipGeneration::Blit]
+            .iter()
+            .copied()
+            .find(|generation| generation.supported(queue))
+            .unwrap()
+    }
+}
+
+impl fmt::Display for MipGeneration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MipGeneration::Blit => write!(f, "blit"),
+            MipGeneration::Compute => write!(f, "compute"),
+            MipGeneration::ComputeSubgroup => write!(f, "compute with subgroups"),
+        }
+    }
+}
+
+/// Uploads RGBA pixels into a `TEXTURE_FORMAT` image with a full mip chain and waits for it.
+/// Also returns how long the GPU took for the levels below the first, when the queue has
+/// timestamps.
+pub fn generate(
+    queue: &Arc<Queue>,
+    dimensions: [u32; 2],
+    pixels: Vec<u8>,
+    generation: MipGeneration,
+) -> Result<(Arc<ImmutableImage>, Option<Duration>), String> {
+    if !generation.supported(queue) {
+        return Err(format!("{} mip generation is not supported by this device", generation));
+    }
+
+    let device = queue.device();
+    let usage = ImageUsage {
+        transfer_source: true,
+        transfer_destination: true,
+        sampled: true,
+        ..ImageUsage::none()
+    };
+    let (image, initializer) = ImmutableImage::uninitialized(
+        device.clone(),
+        ImageDimensions::Dim2d {
+            width: dimensions[0],
+            height: dimensions[1],
+            array_layers: 1,
+        },
+        TEXTURE_FORMAT,
+        MipmapsCount::Log2,
+        usage,
+        ImageCreateFlags::none(),
+        ImageLayout::ShaderReadOnlyOptimal,
+        device.active_queue_families(),
+    )
+    .map_err(|e| format!("failed to create the texture image: {}", e))?;
+
+    let pixels = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false, pixels.into_iter())
+        .map_err(|e| format!("failed to upload the texture: {}", e))?;
+
+    let mut builder =
+        AutoCommandBufferBuilder::primary(device.clone(), queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    builder
+        .copy_buffer_to_image_dimensions(
+            pixels.clone(),
+            level(Arc::new(initializer), 0),
+            [0, 0, 0],
+            [dimensions[0], dimensions[1], 1],
+            0,
+            1,
+            0,
+        )
+        .unwrap();
+
+    let timer = Timer::new(queue);
+    if let Some(timer) = &timer {
+        timer.start(&mut builder);
+    }
+    match generation {
+        MipGeneration::Blit => blit_levels(&mut builder, &image),
+        MipGeneration::Compute => compute_levels(&mut builder, queue, &image, pixels, false)?,
+        MipGeneration::ComputeSubgroup => compute_levels(&mut builder, queue, &image, pixels, true)?,
+    }
+    if let Some(timer) = &timer {
+        timer.stop(&mut builder);
+    }
+
+    builder
+        .build()
+        .unwrap()
+        .execute(queue.clone())
+        .map_err(|e| format!("failed to generate the mip levels: {}", e))?
+        .then_signal_fence_and_flush()
+        .and_then(|future| future.wait(None))
+        .map_err(|e| format!("failed to generate the mip levels: {}", e))?;
+
+    Ok((image, timer.and_then(|timer| timer.elapsed())))
+}
+
+/// Like `ImmutableImage::from_iter`, every level is written through a view of only that level,
+/// so the image's own tracking doesn't get in the way while it is being filled
+fn level(image: Arc<dyn ImageAccess + Send + Sync>, level: u32) -> Arc<SubImage> {
+    SubImage::new(image, level, 1, 0, 1, ImageLayout::ShaderReadOnlyOptimal)
+}
+
+fn level_dimensions(image: &ImmutableImage, level: u32) -> [u32; 2] {
+    image.dimensions().mipmap_dimensions(level).unwrap().width_height()
+}
+
+fn blit_levels(builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, image: &Arc<ImmutableImage>) {
+    for target in 1..image.mipmap_levels() {
+        let [source_width, source_height] = level_dimensions(image, target - 1);
+        let [width, height] = level_dimensions(image, target);
+        builder
+            .blit_image(
+                level(image.clone(), target - 1),
+                [0, 0, 0],
+                [source_width as i32, source_height as i32, 1],
+                0,
+                target - 1,
+                level(image.clone(), target),
+                [0, 0, 0],
+                [width as i32, height as i32, 1],
+                0,
+                target,
+                1,
+                Filter::Linear,
+            )
+            .unwrap();
+    }
+}
+
+/// Downsamples into one storage image per level, then copies them into the texture's levels
+fn compute_levels(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    queue: &Arc<Queue>,
+    image: &Arc<ImmutableImage>,
+    pixels: Arc<CpuAccessibleBuffer<[u8]>>,
+    subgroups: bool,
+) -> Result<(), String> {
+    let device = queue.device();
+    let levels = image.mipmap_levels();
+    let usage = ImageUsage {
+        storage: true,
+        transfer_source: true,
+        transfer_destination: true,
+        ..ImageUsage::none()
+    };
+    let storage = (0..levels)
+        .map(|target| {
+            let [width, height] = level_dimensions(image, target);
+            StorageImage::with_usage(
+                device.clone(),
+                ImageDimensions::Dim2d {
+                    width,
+                    height,
+                    array_layers: 1,
+                },
+                STORAGE_FORMAT,
+                usage,
+                ImageCreateFlags::none(),
+                Some(queue.family()),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("failed to create the mip level images: {}", e))?;
+    let views = storage
+        .iter()
+        .map(|image| ImageView::new(image.clone()).unwrap())
+        .collect::<Vec<_>>();
+    builder.copy_buffer_to_image(pixels, storage[0].clone()).unwrap();
+
+    let shader = downsample_shader::Shader::load(device.clone()).expect("Failed to create mip shader module");
+    let pipeline = Arc::new(
+        ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None)
+            .map_err(|e| format!("failed to create the mip pipeline: {}", e))?,
+    );
+    let subgroup_pipeline = if subgroups {
+        let shader =
+            subgroup_shader::Shader::load(device.clone()).expect("Failed to create subgroup mip shader module");
+        Some(Arc::new(
+            ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None)
+                .map_err(|e| format!("failed to create the subgroup mip pipeline: {}", e))?,
+        ))
+    } else {
+        None
+    };
+
+    let mut target = 1;
+    while target < levels {
+        let [width, height] = level_dimensions(image, target);
+        let group_counts = [
+            width.div_ceil(WORKGROUP_SIZE),
+            height.div_ceil(WORKGROUP_SIZE),
+            1,
+        ];
+
+        match &subgroup_pipeline {
+            Some(pipeline) if target + 1 < levels => {
+                let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+                let set = PersistentDescriptorSet::start(layout)
+                    .add_image(views[target as usize - 1].clone())
+                    .unwrap()
+                    .add_image(views[target as usize].clone())
+                    .unwrap()
+                    .add_image(views[target as usize + 1].clone())
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                builder
+                    .dispatch(group_counts, pipeline.clone(), Arc::new(set), (), vec![])
+                    .unwrap();
+                target += 2;
+            }
+            _ => {
+                let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+                let set = PersistentDescriptorSet::start(layout)
+                    .add_image(views[target as usize - 1].clone())
+                    .unwrap()
+                    .add_image(views[target as usize].clone())
+                    .unwrap()
+                    .build()
+                    .unwrap();
+                builder
+                    .dispatch(group_counts, pipeline.clone(), Arc::new(set), (), vec![])
+                    .unwrap();
+                target += 1;
+            }
+        }
+    }
+
+    for target in 1..levels {
+        let [width, height] = level_dimensions(image, target);
+        builder
+            .copy_image(
+                storage[target as usize].clone(),
+                [0, 0, 0],
+                0,
+                0,
+                level(image.clone(), target),
+                [0, 0, 0],
+                0,
+                target,
+                [width, height, 1],
+                1,
+            )
+            .unwrap();
+    }
+    Ok(())
+}
+
+/// A timestamp before and after the generation, for the queues that can write them
+struct Timer {
+    pool: Arc<QueryPool>,
+    /// Nanoseconds per tick
+    period: f32,
+    valid_bits: u32,
+}
+
+impl Timer {
+    fn new(queue: &Arc<Queue>) -> Option<Self> {
+        let valid_bits = queue.family().timestamp_valid_bits()?;
+        let period = queue.device().physical_device().properties().timestamp_period?;
+        let pool = QueryPool::new(queue.device().clone(), QueryType::Timestamp, 2).ok()?;
+        Some(Self {
+            pool: Arc::new(pool),
+            period,
+            valid_bits,
+        })
+    }
+
+    /// Once everything recorded before has finished, which is the upload of the first level
+    fn start(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        unsafe {
+            builder.reset_query_pool(self.pool.clone(), 0..2).unwrap();
+            builder
+                .write_timestamp(self.pool.clone(), 0, PipelineStage::BottomOfPipe)
+                .unwrap();
+        }
+    }
+
+    fn stop(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        unsafe {
+            builder
+                .write_timestamp(self.pool.clone(), 1, PipelineStage::BottomOfPipe)
+                .unwrap();
+        }
+    }
+
+    /// Time between the two timestamps, after the command buffer has finished
+    fn elapsed(&self) -> Option<Duration> {
+        let mut ticks = [0u64; 2];
+        let flags = QueryResultFlags {
+            wait: true,
+            ..QueryResultFlags::default()
+        };
+        if !self.pool.queries_range(0..2)?.get_results(&mut ticks, flags).ok()? {
+            return None;
+        }
+
+        // the counter wraps around above its valid bits
+        let mask = u64::MAX >> (64 - self.valid_bits.clamp(1, 64));
+        let elapsed = (ticks[1] & mask).wrapping_sub(ticks[0] & mask) & mask;
+        Some(Duration::from_nanos((elapsed as f64 * self.period as f64) as u64))
+    }
+}
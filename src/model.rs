@@ -0,0 +1,76 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::mesh::{ImportedMesh, DEFAULT_COLOR};
+
+/// Reads a Wavefront OBJ file, all of its objects end up in one mesh. Faces are triangulated and
+/// corners that share position, texture coordinate and normal become one vertex. Vertices take the
+/// diffuse color of their material when the MTL file can be read.
+pub fn load(path: &Path) -> Result<ImportedMesh, String> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        ignore_points: true,
+        ignore_lines: true,
+        ..Default::default()
+    };
+    let (models, materials) = tobj::load_obj(path, &options).map_err(|e| e.to_string())?;
+    // a missing or broken MTL file only costs the colors
+    let materials = materials.unwrap_or_default();
+
+    let mut mesh = ImportedMesh::default();
+    let mut unique_vertices = HashMap::new();
+
+    for (model_index, model) in models.iter().enumerate() {
+        let source = &model.mesh;
+        let color = source
+            .material_id
+            .and_then(|id| materials.get(id))
+            .map_or(DEFAULT_COLOR, |material| material.diffuse);
+
+        for (corner, &position_index) in source.indices.iter().enumerate() {
+            let texcoord_index = source.texcoord_indices.get(corner).copied();
+            let normal_index = source.normal_indices.get(corner).copied();
+            let key = (model_index, position_index, texcoord_index, normal_index);
+
+            if let Some(&index) = unique_vertices.get(&key) {
+                mesh.indices.push(index);
+                continue;
+            }
+
+            let p = position_index as usize * 3;
+            let position = match source.positions.get(p..p + 3) {
+                Some(&[x, y, z]) => [x, y, z],
+                _ => return Err(format!("vertex index {} is out of range", position_index)),
+            };
+            mesh.positions.push(position);
+            mesh.colors.push(match source.vertex_color.get(p..p + 3) {
+                Some(&[r, g, b]) => [r, g, b],
+                _ => color,
+            });
+
+            // OBJ puts v = 0 at the bottom of the image, Vulkan at the top
+            let uv = texcoord_index
+                .map(|t| t as usize * 2)
+                .and_then(|t| source.texcoords.get(t..t + 2))
+                .map_or([0.0, 0.0], |uv| [uv[0], 1.0 - uv[1]]);
+            mesh.uvs.push(uv);
+
+            let normal = normal_index
+                .map(|n| n as usize * 3)
+                .and_then(|n| source.normals.get(n..n + 3));
+            if let Some(&[x, y, z]) = normal {
+                mesh.normals.push([x, y, z]);
+            }
+
+            let index = mesh.positions.len() as u32 - 1;
+            unique_vertices.insert(key, index);
+            mesh.indices.push(index);
+        }
+    }
+
+    // normals are all or nothing, like the other attributes
+    if mesh.normals.len() != mesh.positions.len() {
+        mesh.normals.clear();
+    }
+
+    Ok(mesh)
+}
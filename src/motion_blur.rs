@@ -0,0 +1,422 @@
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use cgmath::{Matrix4, SquareMatrix};
+use vulkano::{
+    buffer::{BufferAccess, TypedBufferAccess},
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::{descriptor_set::PersistentDescriptorSet, DescriptorSet},
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImageAccess, ImageUsage},
+    pipeline::{
+        depth_stencil::{Compare, DepthStencil},
+        vertex::{BufferlessDefinition, BufferlessVertices, SingleBufferDefinition},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::{post, renderer::DEPTH_FORMAT, vertex::Vertex, world::ChunkCoord};
+
+/// Screen space motion in texture coordinates, signed and well below a pixel for slow motion
+pub const VELOCITY_FORMAT: Format = Format::R16G16Sfloat;
+pub const MAX_SAMPLES: u32 = 32;
+
+mod fullscreen_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod background_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/velocity_background.frag"
+    }
+}
+
+mod velocity_vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/velocity.vert"
+    }
+}
+
+mod velocity_fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/velocity.frag"
+    }
+}
+
+mod blur_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/motion_blur.frag"
+    }
+}
+
+/// Controls of the `motionblur` command, kept while the passes are rebuilt with the swap chain
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionBlurSettings {
+    pub enabled: bool,
+    /// Scene samples along the motion of each pixel
+    pub samples: u32,
+    /// Share of the time between frames the shutter is open, 1 smears over all of the motion
+    pub shutter: f32,
+}
+
+impl Default for MotionBlurSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            samples: 8,
+            shutter: 0.5,
+        }
+    }
+}
+
+impl fmt::Display for MotionBlurSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {} samples, shutter {:.2}",
+            if self.enabled { "on" } else { "off" },
+            self.samples,
+            self.shutter
+        )
+    }
+}
+
+/// Identifies a mesh draw across frames, so its model matrix of the previous frame can be found
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DrawId {
+    Scene,
+    Object(u32),
+    /// A chunk of the streamed world
+    Chunk(ChunkCoord),
+}
+
+/// A mesh drawn in the scene pass, drawn again into the velocity buffer. Only motion of the
+/// whole mesh through `model` is followed, the vertices themselves are the same every frame.
+pub struct MotionDraw {
+    pub id: DrawId,
+    pub model: Matrix4<f32>,
+    pub vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    pub index_buffer: Arc<dyn TypedBufferAccess<Content = [u32]> + Send + Sync>,
+}
+
+/// The camera and model matrices of the last submitted frame
+#[derive(Default)]
+pub struct MotionHistory {
+    view_projection: Option<Matrix4<f32>>,
+    models: HashMap<DrawId, Matrix4<f32>>,
+}
+
+impl MotionHistory {
+    /// Draws that weren't in the previous frame and the very first frame don't move
+    fn previous_mvp(&self, draw: &MotionDraw, view_projection: Matrix4<f32>) -> Matrix4<f32> {
+        let model = self.models.get(&draw.id).copied().unwrap_or(draw.model);
+        self.view_projection.unwrap_or(view_projection) * model
+    }
+
+    /// Remembers the frame drawn with `view_projection` and `draws`, once it was submitted
+    pub fn end_frame(&mut self, view_projection: Matrix4<f32>, draws: &[MotionDraw]) {
+        self.view_projection = Some(view_projection);
+        self.models = draws.iter().map(|draw| (draw.id, draw.model)).collect();
+    }
+}
+
+/// Blurs the scene along the motion of every pixel since the previous frame. The meshes are drawn
+/// a second time into a velocity buffer, tested against the finished depth buffer so only the
+/// visible surfaces write their motion, from the current and previous model matrices and camera.
+/// Pixels without a mesh get the camera's motion of the far plane. The blur pass then averages the
+/// scene along that motion and the result is copied back into the post pass target.
+pub struct MotionBlur {
+    /// Motion of every pixel, for passes that reproject the previous frame
+    pub velocity: Arc<ImageView<Arc<AttachmentImage>>>,
+    blurred: Arc<AttachmentImage>,
+    scene: Arc<AttachmentImage>,
+    background_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    velocity_pipeline: Arc<GraphicsPipeline<SingleBufferDefinition<Vertex>>>,
+    blur_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    velocity_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    blur_framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    blur_set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl MotionBlur {
+    /// Recreated with the swap chain. `scene` is the post pass target, `depth_buffer` the single
+    /// sampled depth of the full image, which the velocity pass tests against.
+    pub fn new(
+        device: &Arc<Device>,
+        scene: &Arc<ImageView<Arc<AttachmentImage>>>,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Self {
+        let dimensions = scene.image().dimensions().width_height();
+        let velocity =
+            ImageView::new(AttachmentImage::sampled(device.clone(), dimensions, VELOCITY_FORMAT).unwrap()).unwrap();
+        let blurred_usage = ImageUsage {
+            transfer_source: true,
+            ..ImageUsage::none()
+        };
+        let blurred =
+            AttachmentImage::with_usage(device.clone(), dimensions, post::INTERMEDIATE_FORMAT, blurred_usage).unwrap();
+
+        let velocity_render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    velocity: {
+                        load: Clear,
+                        store: Store,
+                        format: VELOCITY_FORMAT,
+                        samples: 1,
+                    },
+                    depth: {
+                        load: Load,
+                        store: Store,
+                        format: DEPTH_FORMAT,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [velocity],
+                    depth_stencil: {depth}
+                }
+            )
+            .unwrap(),
+        );
+        let blur_render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: post::INTERMEDIATE_FORMAT,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+
+        let fullscreen_shader_module = fullscreen_shader::Shader::load(device.clone())
+            .expect("Failed to create motion blur vertex shader module");
+        let background_shader_module = background_shader::Shader::load(device.clone())
+            .expect("Failed to create velocity background shader module");
+        let velocity_vertex_shader_module = velocity_vertex_shader::Shader::load(device.clone())
+            .expect("Failed to create velocity vertex shader module");
+        let velocity_fragment_shader_module = velocity_fragment_shader::Shader::load(device.clone())
+            .expect("Failed to create velocity fragment shader module");
+        let blur_shader_module =
+            blur_shader::Shader::load(device.clone()).expect("Failed to create motion blur shader module");
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+        // the depth buffer is already complete, only what is visible passes
+        let test_depth = DepthStencil {
+            depth_compare: Compare::LessOrEqual,
+            depth_write: false,
+            ..DepthStencil::disabled()
+        };
+        let velocity_subpass = Subpass::from(velocity_render_pass.clone(), 0).unwrap();
+
+        let background_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(fullscreen_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport.clone()])
+                .fragment_shader(background_shader_module.main_entry_point(), ())
+                .depth_stencil(test_depth.clone())
+                .render_pass(velocity_subpass.clone())
+                .build(device.clone())
+                .unwrap(),
+        );
+        let velocity_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(velocity_vertex_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport.clone()])
+                .fragment_shader(velocity_fragment_shader_module.main_entry_point(), ())
+                .cull_mode_back()
+                .front_face_counter_clockwise()
+                .depth_stencil(test_depth)
+                .render_pass(velocity_subpass)
+                .build(device.clone())
+                .unwrap(),
+        );
+        let blur_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(fullscreen_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport])
+                .fragment_shader(blur_shader_module.main_entry_point(), ())
+                .render_pass(Subpass::from(blur_render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        );
+
+        let velocity_framebuffer = Arc::new(
+            Framebuffer::start(velocity_render_pass)
+                .add(velocity.clone())
+                .unwrap()
+                .add(depth_buffer.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+        let blur_framebuffer = Arc::new(
+            Framebuffer::start(blur_render_pass)
+                .add(ImageView::new(blurred.clone()).unwrap())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let sampler = |filter: Filter| {
+            Sampler::new(
+                device.clone(),
+                filter,
+                filter,
+                MipmapMode::Nearest,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+            )
+            .unwrap()
+        };
+        let layout = blur_pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let blur_set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(scene.clone(), sampler(Filter::Linear))
+                .unwrap()
+                .add_sampled_image(velocity.clone(), sampler(Filter::Nearest))
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Self {
+            velocity,
+            blurred,
+            scene: scene.image().clone(),
+            background_pipeline,
+            velocity_pipeline,
+            blur_pipeline,
+            velocity_framebuffer,
+            blur_framebuffer,
+            blur_set,
+        }
+    }
+
+    /// Records the velocity pass, the blur and the copy back into the scene, after the scene and
+    /// before the post pass. `draws` are the meshes of this frame, `view_projection` its camera.
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        settings: MotionBlurSettings,
+        history: &MotionHistory,
+        view_projection: Matrix4<f32>,
+        draws: &[MotionDraw],
+    ) {
+        let [width, height] = self.blurred.dimensions().width_height();
+        let inverse_size = [1.0 / width as f32, 1.0 / height as f32];
+        let previous = history.view_projection.unwrap_or(view_projection);
+        let background_push_constants = background_shader::ty::PushConstants {
+            reprojection: (previous * view_projection.invert().unwrap_or_else(Matrix4::identity)).into(),
+            inverseSize: inverse_size,
+        };
+        let triangle = BufferlessVertices {
+            vertices: 3,
+            instances: 1,
+        };
+
+        builder
+            .begin_render_pass(
+                self.velocity_framebuffer.clone(),
+                SubpassContents::Inline,
+                vec![[0.0, 0.0, 0.0, 0.0].into(), ClearValue::None],
+            )
+            .unwrap()
+            .draw(
+                self.background_pipeline.clone(),
+                &DynamicState::none(),
+                triangle,
+                (),
+                background_push_constants,
+                vec![],
+            )
+            .unwrap();
+
+        for draw in draws {
+            let push_constants = velocity_vertex_shader::ty::PushConstants {
+                mvp: (view_projection * draw.model).into(),
+                previous_mvp: history.previous_mvp(draw, view_projection).into(),
+            };
+            builder
+                .draw_indexed(
+                    self.velocity_pipeline.clone(),
+                    &DynamicState::none(),
+                    vec![draw.vertex_buffer.clone()],
+                    draw.index_buffer.clone(),
+                    (),
+                    push_constants,
+                    vec![],
+                )
+                .unwrap();
+        }
+        builder.end_render_pass().unwrap();
+
+        let blur_push_constants = blur_shader::ty::PushConstants {
+            inverseSize: inverse_size,
+            shutter: settings.shutter.clamp(0.0, 1.0),
+            samples: settings.samples.clamp(1, MAX_SAMPLES),
+        };
+        builder
+            .begin_render_pass(self.blur_framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
+            .unwrap()
+            .draw(
+                self.blur_pipeline.clone(),
+                &DynamicState::none(),
+                triangle,
+                self.blur_set.clone(),
+                blur_push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap()
+            .copy_image(
+                self.blurred.clone(),
+                [0, 0, 0],
+                0,
+                0,
+                self.scene.clone(),
+                [0, 0, 0],
+                0,
+                0,
+                [width, height, 1],
+                1,
+            )
+            .unwrap();
+    }
+}
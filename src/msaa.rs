@@ -0,0 +1,286 @@
+use std::{convert::TryInto, sync::Arc};
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::{descriptor_set::PersistentDescriptorSet, DescriptorSet},
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImageAccess, ImageUsage, SampleCounts},
+    instance::PhysicalDevice,
+    pipeline::{
+        depth_stencil::{Compare, DepthStencil},
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::error::Error;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/depth_resolve.frag"
+    }
+}
+
+/// Sample counts the scene pass can use on this device, in increasing order, `1` is always there
+pub fn supported_sample_counts(physical_device: PhysicalDevice) -> Vec<u32> {
+    let flags = |counts: SampleCounts| {
+        [
+            counts.sample1,
+            counts.sample2,
+            counts.sample4,
+            counts.sample8,
+            counts.sample16,
+            counts.sample32,
+            counts.sample64,
+        ]
+    };
+
+    let properties = physical_device.properties();
+    let color = flags(properties.framebuffer_color_sample_counts.unwrap());
+    let depth = flags(properties.framebuffer_depth_sample_counts.unwrap());
+    // the depth resolve pass samples the multisampled depth image
+    let sampled_depth = flags(properties.sampled_image_depth_sample_counts.unwrap());
+
+    (0..color.len())
+        .filter(|&i| i == 0 || (color[i] && depth[i] && sampled_depth[i]))
+        .map(|i| 1 << i)
+        .collect()
+}
+
+/// The highest supported sample count that is not above `requested`
+pub fn choose_sample_count(physical_device: PhysicalDevice, requested: u32) -> u32 {
+    supported_sample_counts(physical_device)
+        .into_iter()
+        .rev()
+        .find(|&samples| samples <= requested)
+        .unwrap_or(1)
+}
+
+/// Images the scene is drawn into when multisampling. The scene pass resolves the color samples
+/// into the swap chain image itself, the depth samples get a pass of their own that writes the
+/// nearest one into the single sampled depth buffer, which the decal and lens flare passes read.
+pub struct Multisampling {
+    pub color: Arc<ImageView<Arc<AttachmentImage>>>,
+    pub depth: Arc<ImageView<Arc<AttachmentImage>>>,
+    pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    depth_set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl Multisampling {
+    /// Recreated with the swap chain, `depth_buffer` is where the resolved depth ends up
+    pub fn new(
+        device: &Arc<Device>,
+        samples: u32,
+        color_format: Format,
+        depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) -> Result<Self, Error> {
+        let dimensions = depth_buffer.image().dimensions().width_height();
+        let depth_format = depth_buffer.image().format();
+        let sample_count = samples.try_into().expect("unsupported sample count");
+
+        // the color samples are never read after the resolve, so they don't need memory backing
+        let color = AttachmentImage::transient_multisampled(device.clone(), dimensions, sample_count, color_format)
+            .unwrap();
+        let depth = AttachmentImage::sampled_multisampled(device.clone(), dimensions, sample_count, depth_format)
+            .unwrap();
+        let color = ImageView::new(color).unwrap();
+        let depth = ImageView::new(depth).unwrap();
+
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    depth: {
+                        load: DontCare,
+                        store: Store,
+                        format: depth_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [],
+                    depth_stencil: {depth}
+                }
+            )
+            .unwrap(),
+        );
+
+        let pipeline = Self::create_pipeline(device, dimensions, &render_pass)?;
+        let framebuffer = Arc::new(
+            Framebuffer::start(render_pass.clone())
+                .add(depth_buffer.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        // texelFetch doesn't filter, but depth formats don't have to support linear filtering
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let depth_set = Arc::new(
+            PersistentDescriptorSet::start(layout)
+                .add_sampled_image(depth.clone(), sampler)
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        Ok(Self {
+            color,
+            depth,
+            pipeline,
+            framebuffer,
+            depth_set,
+        })
+    }
+
+    fn create_pipeline(
+        device: &Arc<Device>,
+        dimensions: [u32; 2],
+        render_pass: &Arc<RenderPass>,
+    ) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "depth resolve vertex",
+            source,
+        })?;
+        let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "depth resolve fragment",
+            source,
+        })?;
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        // every pixel is overwritten, whatever was in the depth buffer before
+        let depth_stencil = DepthStencil {
+            depth_compare: Compare::Always,
+            depth_write: true,
+            ..DepthStencil::disabled()
+        };
+
+        Ok(Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport])
+                .fragment_shader(frag_shader_module.main_entry_point(), ())
+                .depth_stencil(depth_stencil)
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())?,
+        ))
+    }
+
+    /// Records the depth resolve pass, after the scene pass and before anything that samples the
+    /// depth buffer
+    pub fn resolve_depth(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        builder
+            .begin_render_pass(self.framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
+            .unwrap()
+            .draw(
+                self.pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                self.depth_set.clone(),
+                (),
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
+
+/// Records a resolve of the multisampled color `image` into a new single sampled image that can
+/// be copied from, for captures of images that are still multisampled. The resolve is done by
+/// an empty render pass with a resolve attachment, there is no resolve command to record.
+pub fn resolve_color(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    image: Arc<dyn ImageAccess + Send + Sync>,
+) -> Result<Arc<AttachmentImage>, String> {
+    let device = image.inner().image.device().clone();
+    let format = image.format();
+    let dimensions = image.dimensions().width_height();
+    let usage = ImageUsage {
+        color_attachment: true,
+        transfer_source: true,
+        ..ImageUsage::none()
+    };
+    let resolved = AttachmentImage::with_usage(device.clone(), dimensions, format, usage)
+        .map_err(|e| format!("failed to allocate the resolve target: {}", e))?;
+
+    let render_pass = Arc::new(
+        vulkano::single_pass_renderpass!(
+            device,
+            attachments: {
+                multisampled: {
+                    load: Load,
+                    store: DontCare,
+                    format: format,
+                    samples: image.samples() as u32,
+                },
+                resolved: {
+                    load: DontCare,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [multisampled],
+                depth_stencil: {},
+                resolve: [resolved]
+            }
+        )
+        .map_err(|e| format!("failed to create the resolve render pass: {}", e))?,
+    );
+    let framebuffer = Framebuffer::start(render_pass)
+        .add(ImageView::new(image).map_err(|e| e.to_string())?)
+        .and_then(|framebuffer| framebuffer.add(ImageView::new(resolved.clone()).unwrap()))
+        .and_then(|framebuffer| framebuffer.build())
+        .map_err(|e| format!("failed to create the resolve framebuffer: {}", e))?;
+
+    builder
+        .begin_render_pass(
+            Arc::new(framebuffer),
+            SubpassContents::Inline,
+            vec![ClearValue::None, ClearValue::None],
+        )
+        .map_err(|e| format!("failed to record the resolve: {}", e))?
+        .end_render_pass()
+        .map_err(|e| format!("failed to record the resolve: {}", e))?;
+    Ok(resolved)
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use cgmath::{EuclideanSpace, Matrix4, Point3, Vector3};
+
+/// Size of the marker quad drawn for each spawned object
+const OBJECT_SCALE: f32 = 0.2;
+
+/// An object placed into the world at runtime, drawn with the shared marker mesh
+#[derive(Copy, Clone, Debug)]
+pub struct SpawnedObject {
+    pub id: u32,
+    pub position: Point3<f32>,
+}
+
+impl SpawnedObject {
+    /// Stands the marker quad upright on top of `position`
+    pub fn model(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.position.to_vec())
+            * Matrix4::from_translation(Vector3::new(0.0, OBJECT_SCALE / 2.0, 0.0))
+            * Matrix4::from_scale(OBJECT_SCALE)
+    }
+}
+
+impl fmt::Display for SpawnedObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} at ({:.2}, {:.2}, {:.2})",
+            self.id, self.position.x, self.position.y, self.position.z
+        )
+    }
+}
+
+/// Objects spawned at runtime. The mesh buffers are shared and referenced by every command buffer
+/// that draws them, so removing an object never frees memory the GPU is still reading.
+#[derive(Default)]
+pub struct ObjectList {
+    objects: Vec<SpawnedObject>,
+    next_id: u32,
+}
+
+impl ObjectList {
+    pub fn spawn(&mut self, position: Point3<f32>) -> SpawnedObject {
+        let object = SpawnedObject {
+            id: self.next_id,
+            position,
+        };
+
+        self.next_id += 1;
+        self.objects.push(object);
+        object
+    }
+
+    /// Removes the object with the given id, returning it if it existed
+    pub fn despawn(&mut self, id: u32) -> Option<SpawnedObject> {
+        let index = self.objects.iter().position(|object| object.id == id)?;
+        Some(self.objects.remove(index))
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut SpawnedObject> {
+        self.objects.iter_mut().find(|object| object.id == id)
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SpawnedObject> {
+        self.objects.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    panic,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use vulkano::swapchain::Surface;
+use winit::window::Window;
+
+use crate::gpu_timer::GpuTimes;
+
+/// What the renderer last did, kept in atomics so it can be read from a panic hook without locking
+#[derive(Default)]
+pub struct FrameSnapshot {
+    pub frame: AtomicU64,
+    pub image_index: AtomicUsize,
+    pub cpu_time_us: AtomicU64,
+    pub validation_errors: Arc<AtomicUsize>,
+    /// Microseconds of the last `GpuTimes` read, in field order, valid once `has_gpu_times` is set
+    gpu_time_us: [AtomicU64; 4],
+    has_gpu_times: AtomicBool,
+}
+
+impl FrameSnapshot {
+    pub fn new(validation_errors: Arc<AtomicUsize>) -> Self {
+        Self {
+            validation_errors,
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&self, frame: u64, image_index: usize, cpu_time_us: u64) {
+        self.frame.store(frame, Ordering::Relaxed);
+        self.image_index.store(image_index, Ordering::Relaxed);
+        self.cpu_time_us.store(cpu_time_us, Ordering::Relaxed);
+    }
+
+    /// Keeps the GPU times of the latest frame they could be read for
+    pub fn record_gpu(&self, times: GpuTimes) {
+        let parts = [times.scene, times.effects, times.post, times.overlays];
+        for (stored, part) in self.gpu_time_us.iter().zip(parts.iter()) {
+            stored.store(part.as_micros() as u64, Ordering::Relaxed);
+        }
+        self.has_gpu_times.store(true, Ordering::Relaxed);
+    }
+
+    /// `None` before the first frame's times were read, or without a GPU timer
+    pub fn gpu_times(&self) -> Option<GpuTimes> {
+        if !self.has_gpu_times.load(Ordering::Relaxed) {
+            return None;
+        }
+        let part = |index: usize| Duration::from_micros(self.gpu_time_us[index].load(Ordering::Relaxed));
+        Some(GpuTimes {
+            scene: part(0),
+            effects: part(1),
+            post: part(2),
+            overlays: part(3),
+        })
+    }
+}
+
+/// Installs a panic hook that gives the desktop back (cursor, fullscreen) and prints the last frame's
+/// state before the default hook reports the panic. Has to be called on the thread running the
+/// event loop, the only one the window may be touched from.
+pub fn install(surface: Arc<Surface<Window>>, snapshot: Arc<FrameSnapshot>) {
+    let default_hook = panic::take_hook();
+    let main_thread = thread::current().id();
+
+    panic::set_hook(Box::new(move |info| {
+        // panics on the console, world or loader threads leave the window alone
+        if thread::current().id() == main_thread {
+            let window = surface.window();
+            // both can fail if the window is already gone, there is nothing left to restore then
+            let _ = window.set_cursor_grab(false);
+            window.set_cursor_visible(true);
+            window.set_fullscreen(None);
+        }
+
+        log::logger().flush();
+
+        eprintln!(
+            "last frame: #{} (swap chain image {}), {} us of CPU time, {} validation errors so far",
+            snapshot.frame.load(Ordering::Relaxed),
+            snapshot.image_index.load(Ordering::Relaxed),
+            snapshot.cpu_time_us.load(Ordering::Relaxed),
+            snapshot.validation_errors.load(Ordering::Relaxed),
+        );
+        match snapshot.gpu_times() {
+            Some(times) => eprintln!("last GPU time: {}", times),
+            None => eprintln!("last GPU time: not measured"),
+        }
+
+        default_hook(info);
+    }));
+}
@@ -0,0 +1,104 @@
+//! Scene draws recorded into secondary command buffers on several threads, which the frame's
+//! primary command buffer then executes in order. Everything a draw needs is gathered on the
+//! main thread first, the threads only record.
+
+use std::{sync::Arc, thread};
+
+use vulkano::{
+    buffer::{BufferAccess, TypedBufferAccess},
+    command_buffer::{
+        AutoCommandBufferBuilder, CommandBufferUsage, DynamicState, SecondaryAutoCommandBuffer,
+    },
+    descriptor::DescriptorSet,
+    device::Queue,
+    pipeline::GraphicsPipelineAbstract,
+    render_pass::Subpass,
+};
+
+use crate::renderer::vertex_shader;
+
+/// One draw of the scene pipeline, ready to be recorded on any thread
+pub struct SceneDraw {
+    pub vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    pub index_buffer: Arc<dyn TypedBufferAccess<Content = [u32]> + Send + Sync>,
+    pub sets: Vec<Arc<dyn DescriptorSet + Send + Sync>>,
+    pub push_constants: vertex_shader::ty::PushConstants,
+}
+
+impl SceneDraw {
+    pub fn record<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+    ) {
+        builder
+            .draw_indexed(
+                pipeline.clone(),
+                dynamic_state,
+                vec![self.vertex_buffer.clone()],
+                self.index_buffer.clone(),
+                self.sets.clone(),
+                self.push_constants,
+                vec![],
+            )
+            .unwrap();
+    }
+}
+
+/// A builder for commands executed inside `subpass`. Command pools belong to the thread that
+/// made them, so it has to be built on the thread it was created on.
+pub fn secondary_builder(
+    queue: &Arc<Queue>,
+    subpass: &Subpass,
+) -> AutoCommandBufferBuilder<SecondaryAutoCommandBuffer> {
+    AutoCommandBufferBuilder::secondary_graphics(
+        queue.device().clone(),
+        queue.family(),
+        CommandBufferUsage::OneTimeSubmit,
+        subpass.clone(),
+    )
+    .unwrap()
+}
+
+/// Splits `draws` into at most `threads` runs of neighbouring draws and records each into a
+/// secondary command buffer on its own thread. Executing them in the returned order draws
+/// everything in the order of `draws`.
+pub fn record(
+    queue: &Arc<Queue>,
+    subpass: &Subpass,
+    pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    dynamic_state: &DynamicState,
+    draws: &[SceneDraw],
+    threads: usize,
+) -> Vec<SecondaryAutoCommandBuffer> {
+    if draws.is_empty() {
+        return Vec::new();
+    }
+
+    let chunk_size = draws.len().div_ceil(threads.max(1));
+    thread::scope(|scope| {
+        let recorders: Vec<_> = draws
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut builder = secondary_builder(queue, subpass);
+                    for draw in chunk {
+                        draw.record(&mut builder, pipeline, dynamic_state);
+                    }
+                    builder.build().unwrap()
+                })
+            })
+            .collect();
+
+        recorders
+            .into_iter()
+            .map(|recorder| recorder.join().expect("A recording thread panicked"))
+            .collect()
+    })
+}
+
+/// Threads `parallel on` records with, one per core the system reports
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
@@ -0,0 +1,70 @@
+//! The pipeline cache kept on disk between runs, so the driver can skip compiling the pipelines
+//! it compiled last time. Loaded at startup and saved on exit, a file from another GPU or driver
+//! version is ignored.
+
+use std::{convert::TryInto, fs, path::Path, sync::Arc};
+
+use log::{info, warn};
+use vulkano::{device::Device, pipeline::cache::PipelineCache};
+
+/// File the cache is kept in when the `pipeline_cache` setting isn't changed
+pub const DEFAULT_PATH: &str = "pipeline_cache.bin";
+/// Length of the header version one of the cache data starts with
+const HEADER_LENGTH: usize = 32;
+const HEADER_VERSION_ONE: u32 = 1;
+
+/// The cache saved at `path`, or an empty one when there is none or it was made by another GPU
+/// or driver
+pub fn load(device: &Arc<Device>, path: &Path) -> Arc<PipelineCache> {
+    match fs::read(path) {
+        Ok(data) => match check_header(device, &data) {
+            Ok(()) => {
+                // the header matches this device and driver, which validate the rest themselves
+                match unsafe { PipelineCache::with_data(device.clone(), &data) } {
+                    Ok(cache) => {
+                        info!("Loaded {} bytes of pipeline cache from {}", data.len(), path.display());
+                        return cache;
+                    }
+                    Err(e) => warn!("Ignoring the pipeline cache in {}: {}", path.display(), e),
+                }
+            }
+            Err(e) => info!("Ignoring the pipeline cache in {}: {}", path.display(), e),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+        Err(e) => warn!("Failed to read the pipeline cache {}: {}", path.display(), e),
+    }
+    PipelineCache::empty(device.clone()).unwrap()
+}
+
+/// Writes `cache` to `path`, through a temporary file so a crash never leaves half a cache
+pub fn save(cache: &PipelineCache, path: &Path) -> Result<(), String> {
+    let data = cache
+        .get_data()
+        .map_err(|e| format!("failed to get the pipeline cache data: {}", e))?;
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, &data)
+        .and_then(|()| fs::rename(&temporary, path))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    info!("Saved {} bytes of pipeline cache to {}", data.len(), path.display());
+    Ok(())
+}
+
+/// The header names the vendor, device and driver build that wrote the data
+fn check_header(device: &Device, data: &[u8]) -> Result<(), String> {
+    if data.len() < HEADER_LENGTH {
+        return Err("the file is too short".to_string());
+    }
+    let word = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    if word(0) as usize != HEADER_LENGTH || word(4) != HEADER_VERSION_ONE {
+        return Err("unknown header".to_string());
+    }
+
+    let properties = device.physical_device().properties();
+    if Some(word(8)) != properties.vendor_id || Some(word(12)) != properties.device_id {
+        return Err("it was written for another GPU".to_string());
+    }
+    if properties.pipeline_cache_uuid.as_ref().map(|uuid| &uuid[..]) != Some(&data[16..32]) {
+        return Err("it was written by another driver version".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector3};
+use vulkano::{
+    buffer::CpuBufferPool,
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer, SubpassContents},
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, ImageAccess, SwapchainImage},
+    pipeline::GraphicsPipelineAbstract,
+    render_pass::{Framebuffer, FramebufferAbstract},
+};
+use winit::window::Window;
+
+use crate::debug_draw::{self, DebugLines, LineVertex};
+use crate::error::Error;
+use crate::renderer;
+
+/// Samples kept per series, one per frame
+const HISTORY: usize = 240;
+/// Top left corner and size of the plot area in normalized device coordinates, Y points down
+const ORIGIN: [f32; 2] = [-0.95, -0.95];
+const SIZE: [f32; 2] = [0.6, 0.35];
+/// Height of the axis labels in normalized device coordinates
+const LABEL_HEIGHT: f32 = 0.03;
+const AXIS_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+const GRID_COLOR: [f32; 3] = [0.3, 0.3, 0.3];
+const FRAME_TIME_COLOR: [f32; 3] = [0.2, 0.9, 0.3];
+const CPU_TIME_COLOR: [f32; 3] = [1.0, 0.6, 0.1];
+
+/// The most recent values of one quantity, oldest first
+pub struct TimeSeries {
+    color: [f32; 3],
+    samples: VecDeque<f32>,
+}
+
+impl TimeSeries {
+    pub fn new(color: [f32; 3]) -> Self {
+        Self {
+            color,
+            samples: VecDeque::with_capacity(HISTORY),
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        if self.samples.len() == HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+    }
+
+    pub fn latest(&self) -> Option<f32> {
+        self.samples.back().copied()
+    }
+
+    pub fn average(&self) -> Option<f32> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().sum::<f32>() / self.samples.len() as f32)
+    }
+
+    fn max(&self) -> f32 {
+        self.samples.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+/// Line plot of a few time series sharing one Y axis, drawn in a corner of the screen
+pub struct Plot {
+    pub series: Vec<TimeSeries>,
+}
+
+impl Plot {
+    /// Milliseconds between frames in green and milliseconds spent preparing each frame in orange
+    pub fn frame_times() -> Self {
+        Self {
+            series: vec![TimeSeries::new(FRAME_TIME_COLOR), TimeSeries::new(CPU_TIME_COLOR)],
+        }
+    }
+
+    /// Adds one sample to every series, in the order they were created
+    pub fn push(&mut self, values: &[f32]) {
+        for (series, value) in self.series.iter_mut().zip(values) {
+            series.push(*value);
+        }
+    }
+
+    /// Axes, horizontal gridlines with their values and one line strip per series. `aspect` is the
+    /// width of the window over its height, so labels keep their shape.
+    pub fn draw(&self, lines: &mut DebugLines, aspect: f32) {
+        let max = self.series.iter().map(TimeSeries::max).fold(0.0, f32::max);
+        let step = grid_step(max / 4.0);
+        let decimals = (-step.log10().floor()).max(0.0) as usize;
+        let top = (max / step).ceil().max(1.0) * step;
+
+        let [left, bottom] = [ORIGIN[0], ORIGIN[1] + SIZE[1]];
+        let point = |x: f32, y: f32| Point3::new(left + x * SIZE[0], bottom - y / top * SIZE[1], 0.0);
+
+        // the label grid cell is half as wide as it is high
+        let right = Vector3::new(LABEL_HEIGHT / 2.0 / aspect, 0.0, 0.0);
+        let up = Vector3::new(0.0, -LABEL_HEIGHT, 0.0);
+
+        for grid_line in 1..=(top / step).round() as u32 {
+            let value = grid_line as f32 * step;
+            lines.line(point(0.0, value), point(1.0, value), GRID_COLOR);
+
+            let label = format!("{:.*}", decimals, value);
+            let width = DebugLines::text_width(&label) + 1.0;
+            lines.text(&label, point(0.0, value) - right * width - up * 0.5, right, up, AXIS_COLOR);
+        }
+
+        lines.line(point(0.0, 0.0), point(1.0, 0.0), AXIS_COLOR);
+        lines.line(point(0.0, 0.0), point(0.0, top), AXIS_COLOR);
+
+        for series in &self.series {
+            // the newest sample is on the right edge, a partly filled history starts further in
+            let start = HISTORY - series.samples.len();
+            let x = |i: usize| (start + i) as f32 / (HISTORY - 1) as f32;
+
+            for (i, pair) in series.samples.iter().collect::<Vec<_>>().windows(2).enumerate() {
+                lines.line(point(x(i), *pair[0]), point(x(i + 1), *pair[1]), series.color);
+            }
+        }
+    }
+}
+
+/// 1, 2 or 5 times a power of ten, at least `rough`
+fn grid_step(rough: f32) -> f32 {
+    if rough <= 0.0 {
+        return 1.0;
+    }
+
+    let magnitude = 10f32.powf(rough.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap()
+}
+
+/// Draws screen space lines in a render pass after everything else, so plots stay on top
+pub struct PlotRenderer {
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    dimensions: [u32; 2],
+    /// The lines change every frame, the pool reuses buffers the GPU is done with
+    vertex_pool: CpuBufferPool<LineVertex>,
+}
+
+impl PlotRenderer {
+    /// Recreated with the swap chain, like the framebuffers it draws into
+    pub fn new(
+        device: &Arc<Device>,
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        color_format: Format,
+    ) -> Result<Self, Error> {
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: Load,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+
+        let dimensions = swap_chain_images[0].dimensions().width_height();
+        let pipeline = debug_draw::create_pipeline(device, &render_pass)?;
+
+        let framebuffers = swap_chain_images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Arc::new(Framebuffer::start(render_pass.clone()).add(view).unwrap().build().unwrap())
+                    as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        Ok(Self {
+            pipeline,
+            framebuffers,
+            dimensions,
+            vertex_pool: CpuBufferPool::vertex_buffer(device.clone()),
+        })
+    }
+
+    /// `lines` are in normalized device coordinates
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        lines: &DebugLines,
+    ) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let vertex_buffer = self.vertex_pool.chunk(lines.vertices().iter().cloned()).unwrap();
+        let push_constants = debug_draw::vertex_shader::ty::PushConstants {
+            view_projection: Matrix4::<f32>::identity().into(),
+        };
+
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_index].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap()
+            .draw(
+                self.pipeline.clone(),
+                &renderer::dynamic_state(self.dimensions),
+                vec![Arc::new(vertex_buffer)],
+                (),
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
@@ -0,0 +1,395 @@
+//! Reader for the PLY format (http://paulbourke.net/dataformats/ply/), ascii and binary little
+//! endian. Only the `vertex` and `face` elements are used, all other elements are skipped.
+
+use std::{convert::TryInto, fs, path::Path, str::SplitAsciiWhitespace};
+
+use crate::mesh::ImportedMesh;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "char" | "int8" => Ok(Scalar::Int8),
+            "uchar" | "uint8" => Ok(Scalar::UInt8),
+            "short" | "int16" => Ok(Scalar::Int16),
+            "ushort" | "uint16" => Ok(Scalar::UInt16),
+            "int" | "int32" => Ok(Scalar::Int32),
+            "uint" | "uint32" => Ok(Scalar::UInt32),
+            "float" | "float32" => Ok(Scalar::Float32),
+            "double" | "float64" => Ok(Scalar::Float64),
+            _ => Err(format!("unknown property type '{}'", name)),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::UInt8 => 1,
+            Scalar::Int16 | Scalar::UInt16 => 2,
+            Scalar::Int32 | Scalar::UInt32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Scalar::Float32 | Scalar::Float64)
+    }
+
+    /// `bytes` holds exactly `self.size()` bytes
+    fn read_little_endian(self, bytes: &[u8]) -> f64 {
+        match self {
+            Scalar::Int8 => bytes[0] as i8 as f64,
+            Scalar::UInt8 => bytes[0] as f64,
+            Scalar::Int16 => i16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::UInt16 => u16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::Int32 => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::UInt32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::Float32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::Float64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        }
+    }
+}
+
+enum Property {
+    Scalar { name: String, ty: Scalar },
+    List { name: String, count: Scalar, item: Scalar },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Vertex attributes the importer understands, in the order they are kept while reading a vertex
+#[derive(Copy, Clone)]
+enum Attribute {
+    X,
+    Y,
+    Z,
+    NormalX,
+    NormalY,
+    NormalZ,
+    Red,
+    Green,
+    Blue,
+    U,
+    V,
+}
+
+impl Attribute {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "x" => Some(Attribute::X),
+            "y" => Some(Attribute::Y),
+            "z" => Some(Attribute::Z),
+            "nx" => Some(Attribute::NormalX),
+            "ny" => Some(Attribute::NormalY),
+            "nz" => Some(Attribute::NormalZ),
+            "red" | "r" | "diffuse_red" => Some(Attribute::Red),
+            "green" | "g" | "diffuse_green" => Some(Attribute::Green),
+            "blue" | "b" | "diffuse_blue" => Some(Attribute::Blue),
+            "u" | "s" | "texture_u" | "texture_s" => Some(Attribute::U),
+            "v" | "t" | "texture_v" | "texture_t" => Some(Attribute::V),
+            _ => None,
+        }
+    }
+}
+
+/// Values of the file body, read one scalar at a time
+enum Body<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], position: usize },
+}
+
+impl Body<'_> {
+    fn next(&mut self, ty: Scalar) -> Result<f64, String> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens.next().ok_or("unexpected end of file")?;
+                token.parse().map_err(|_| format!("expected a number, found '{}'", token))
+            }
+            Body::Binary { bytes, position } => {
+                let end = *position + ty.size();
+                let value = bytes.get(*position..end).ok_or("unexpected end of file")?;
+                *position = end;
+                Ok(ty.read_little_endian(value))
+            }
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<ImportedMesh, String> {
+    let data = fs::read(path).map_err(|e| format!("failed to read file: {}", e))?;
+    parse(&data)
+}
+
+fn parse(data: &[u8]) -> Result<ImportedMesh, String> {
+    let (header_end, body_start) = find_header_end(data).ok_or("missing end_header, this is not a PLY file")?;
+    let header = std::str::from_utf8(&data[..header_end]).map_err(|_| "the header is not valid text")?;
+    let (encoding, elements) = parse_header(header)?;
+
+    let mut body = match encoding {
+        Encoding::Ascii => {
+            let text = std::str::from_utf8(&data[body_start..]).map_err(|_| "the ascii body is not valid text")?;
+            Body::Ascii(text.split_ascii_whitespace())
+        }
+        Encoding::BinaryLittleEndian => Body::Binary {
+            bytes: &data[body_start..],
+            position: 0,
+        },
+    };
+
+    let mut mesh = ImportedMesh::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, &mut body, &mut mesh)?,
+            "face" => read_faces(element, &mut body, &mut mesh)?,
+            _ => skip_element(element, &mut body)?,
+        }
+    }
+
+    Ok(mesh)
+}
+
+/// Where the `end_header` line starts and where the body after it does. Only a line that is
+/// exactly `end_header` ends the header, not a comment mentioning it or the binary body.
+fn find_header_end(data: &[u8]) -> Option<(usize, usize)> {
+    let mut line_start = 0;
+    while line_start < data.len() {
+        let line_end = data[line_start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(data.len(), |newline| line_start + newline);
+        let line = &data[line_start..line_end];
+        if line.strip_suffix(b"\r").unwrap_or(line) == b"end_header" {
+            return Some((line_start, (line_end + 1).min(data.len())));
+        }
+        line_start = line_end + 1;
+    }
+    None
+}
+
+fn parse_header(header: &str) -> Result<(Encoding, Vec<Element>), String> {
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err("missing 'ply' magic, this is not a PLY file".to_string());
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", "ascii", _] => encoding = Some(Encoding::Ascii),
+            ["format", "binary_little_endian", _] => encoding = Some(Encoding::BinaryLittleEndian),
+            ["format", format, ..] => return Err(format!("unsupported PLY format '{}'", format)),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("invalid element count '{}'", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or("property before the first element")?;
+                element.properties.push(Property::List {
+                    name: name.to_string(),
+                    count: Scalar::parse(count)?,
+                    item: Scalar::parse(item)?,
+                });
+            }
+            ["property", ty, name] => {
+                let element = elements.last_mut().ok_or("property before the first element")?;
+                element.properties.push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: Scalar::parse(ty)?,
+                });
+            }
+            _ => return Err(format!("unexpected header line '{}'", line)),
+        }
+    }
+
+    let encoding = encoding.ok_or("missing format line")?;
+    Ok((encoding, elements))
+}
+
+fn read_vertices(element: &Element, body: &mut Body, mesh: &mut ImportedMesh) -> Result<(), String> {
+    let attribute = |name: &str| {
+        element.properties.iter().any(|property| match property {
+            Property::Scalar { name: property_name, .. } => property_name == name,
+            Property::List { .. } => false,
+        })
+    };
+    let has_normals = attribute("nx") && attribute("ny") && attribute("nz");
+    let has_colors = ["red", "r", "diffuse_red"].iter().any(|name| attribute(name));
+    let has_uvs = ["u", "s", "texture_u", "texture_s"].iter().any(|name| attribute(name));
+
+    for _ in 0..element.count {
+        // indexed by `Attribute`, colors default to white when only some channels are present
+        let mut values = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 0.0];
+
+        for property in &element.properties {
+            match property {
+                Property::Scalar { name, ty } => {
+                    let value = body.next(*ty)?;
+                    if let Some(attribute) = Attribute::from_name(name) {
+                        let is_color = matches!(attribute, Attribute::Red | Attribute::Green | Attribute::Blue);
+                        // integer colors are stored as 0-255
+                        let scale = if is_color && !ty.is_float() { 255.0 } else { 1.0 };
+                        values[attribute as usize] = (value / scale) as f32;
+                    }
+                }
+                Property::List { count, item, .. } => skip_list(*count, *item, body)?,
+            }
+        }
+
+        mesh.positions.push([values[0], values[1], values[2]]);
+        if has_normals {
+            mesh.normals.push([values[3], values[4], values[5]]);
+        }
+        if has_colors {
+            mesh.colors.push([values[6], values[7], values[8]]);
+        }
+        if has_uvs {
+            mesh.uvs.push([values[9], values[10]]);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the polygons, which are split into triangle fans
+fn read_faces(element: &Element, body: &mut Body, mesh: &mut ImportedMesh) -> Result<(), String> {
+    let mut polygon = Vec::new();
+
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match property {
+                Property::List { name, count, item } if name == "vertex_indices" || name == "vertex_index" => {
+                    let length = body.next(*count)? as usize;
+                    polygon.clear();
+                    for _ in 0..length {
+                        polygon.push(body.next(*item)? as u32);
+                    }
+
+                    for i in 1..polygon.len().saturating_sub(1) {
+                        mesh.indices.extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+                    }
+                }
+                Property::List { count, item, .. } => skip_list(*count, *item, body)?,
+                Property::Scalar { ty, .. } => {
+                    body.next(*ty)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_element(element: &Element, body: &mut Body) -> Result<(), String> {
+    for _ in 0..element.count {
+        for property in &element.properties {
+            match property {
+                Property::Scalar { ty, .. } => {
+                    body.next(*ty)?;
+                }
+                Property::List { count, item, .. } => skip_list(*count, *item, body)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_list(count: Scalar, item: Scalar, body: &mut Body) -> Result<(), String> {
+    let length = body.next(count)? as usize;
+    for _ in 0..length {
+        body.next(item)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_with_attributes() {
+        let data = b"ply\nformat ascii 1.0\ncomment made by hand\nelement vertex 3\n\
+            property float x\nproperty float y\nproperty float z\n\
+            property float nx\nproperty float ny\nproperty float nz\n\
+            property uchar red\nproperty uchar green\nproperty uchar blue\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            0 0 0 0 0 1 255 0 0\n1 0 0 0 0 1 0 255 0\n0 1 0 0 0 1 0 0 255\n3 0 1 2\n";
+        let mesh = parse(data).unwrap();
+        assert_eq!(mesh.positions, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(mesh.normals, vec![[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(mesh.colors[1], [0.0, 1.0, 0.0]);
+        assert!(mesh.uvs.is_empty());
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn binary_quad_is_split_into_a_fan() {
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 4\n\
+            property float x\nproperty float y\nproperty float z\n\
+            element face 1\nproperty list uchar uint vertex_indices\nend_header\n"
+            .to_vec();
+        for corner in &[[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]] {
+            for value in corner {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        data.push(4);
+        for index in 0u32..4 {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let mesh = parse(&data).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn unknown_elements_are_skipped() {
+        let data = b"ply\nformat ascii 1.0\nelement camera 1\nproperty float zoom\n\
+            element vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n\
+            2.5\n1 2 3\n";
+        assert_eq!(parse(data).unwrap().positions, vec![[1.0, 2.0, 3.0]]);
+    }
+
+    #[test]
+    fn end_header_in_a_comment() {
+        let data = b"ply\r\nformat ascii 1.0\r\ncomment no end_header here\r\nelement vertex 1\r\n\
+            property float x\r\nproperty float y\r\nproperty float z\r\nend_header\r\n1 2 3\r\n";
+        assert_eq!(parse(data).unwrap().positions, vec![[1.0, 2.0, 3.0]]);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(parse(b"ply\nformat ascii 1.0\n").is_err());
+        assert!(parse(b"ply\nformat ascii 1.0\ncomment end_header\n").is_err());
+        assert!(parse(b"obj\nformat ascii 1.0\nend_header\n").is_err());
+        assert!(parse(b"ply\nformat binary_big_endian 1.0\nend_header\n").is_err());
+        assert!(parse(b"ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nend_header\n1\n").is_err());
+    }
+}
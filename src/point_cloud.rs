@@ -0,0 +1,337 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+use cgmath::{Matrix4, Point3, SquareMatrix};
+use log::info;
+use vulkano::{
+    buffer::{BufferUsage, ImmutableBuffer},
+    device::{Device, Queue},
+    impl_vertex,
+    pipeline::{GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{RenderPass, Subpass},
+};
+
+use crate::{
+    error::Error,
+    frustum::Frustum,
+    mesh::{self, FIT_SIZE},
+    ray::Aabb,
+    staging::{Staging, Upload},
+};
+
+/// Points are bucketed into a grid of this many cells per axis over the cloud's bounds
+const GRID_CELLS: usize = 8;
+/// Upper limit of points per vertex buffer, bigger cells are split
+const MAX_CHUNK_POINTS: usize = 1 << 16;
+/// Chunks uploaded per frame, so loading a big cloud does not stall rendering
+const MAX_UPLOADS_PER_FRAME: usize = 4;
+const DEFAULT_POINT_SIZE: f32 = 8.0;
+
+pub mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/point.vert"
+    }
+}
+
+pub mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/point.frag"
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct PointVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl_vertex!(PointVertex, position, color);
+
+impl PointVertex {
+    pub fn new(position: [f32; 3], color: [f32; 3]) -> Self {
+        Self { position, color }
+    }
+
+    fn point(&self) -> Point3<f32> {
+        Point3::from(self.position)
+    }
+}
+
+/// A spatially coherent group of points, the unit of uploading and culling
+struct PointChunk {
+    points: Vec<PointVertex>,
+    bounds: Aabb,
+}
+
+enum LoadEvent {
+    Started { total_points: usize, bounds: Aabb },
+    Chunk(PointChunk),
+    Failed(String),
+}
+
+struct GpuChunk {
+    buffer: Arc<ImmutableBuffer<[PointVertex]>>,
+    /// In the cloud's own coordinates, before `PointCloud::model`
+    bounds: Aabb,
+}
+
+/// A point cloud that is parsed on a background thread and uploaded chunk by chunk
+pub struct PointCloud {
+    pub path: PathBuf,
+    /// Centers the cloud at the origin and scales it to `FIT_SIZE`
+    pub model: Matrix4<f32>,
+    /// Size in pixels of a point one unit away from the camera
+    pub point_size: f32,
+    total_points: usize,
+    uploaded_points: usize,
+    chunks: Vec<GpuChunk>,
+    pending: Vec<PointChunk>,
+    events: Option<Receiver<LoadEvent>>,
+}
+
+impl PointCloud {
+    /// Starts loading an `.xyz` file with `x y z [r g b]` per line, or the vertices of a `.ply` file
+    pub fn load(path: &Path) -> Self {
+        let (sender, events) = mpsc::channel();
+        let thread_path = path.to_owned();
+
+        thread::Builder::new()
+            .name("point cloud loader".to_string())
+            .spawn(move || {
+                let points = match read_points(&thread_path) {
+                    Ok(points) => points,
+                    Err(e) => {
+                        let _ = sender.send(LoadEvent::Failed(e));
+                        return;
+                    }
+                };
+
+                let bounds = match Aabb::from_points(points.iter().map(PointVertex::point)) {
+                    Some(bounds) => bounds,
+                    None => {
+                        let _ = sender.send(LoadEvent::Failed("the file contains no points".to_string()));
+                        return;
+                    }
+                };
+
+                let total_points = points.len();
+                if sender.send(LoadEvent::Started { total_points, bounds }).is_err() {
+                    return;
+                }
+
+                for chunk in split_into_chunks(points, &bounds) {
+                    if sender.send(LoadEvent::Chunk(chunk)).is_err() {
+                        return;
+                    }
+                }
+            })
+            .expect("Failed to spawn point cloud loader thread");
+
+        Self {
+            path: path.to_owned(),
+            model: Matrix4::identity(),
+            point_size: DEFAULT_POINT_SIZE,
+            total_points: 0,
+            uploaded_points: 0,
+            chunks: vec![],
+            pending: vec![],
+            events: Some(events),
+        }
+    }
+
+    /// Receives parsed chunks and uploads a few of them, returns an error if loading failed. The
+    /// upload has to be joined into the next frame, which draws the new chunks.
+    pub fn update(&mut self, queue: &Arc<Queue>) -> Result<Option<Upload>, String> {
+        if let Some(events) = &self.events {
+            for event in events.try_iter() {
+                match event {
+                    LoadEvent::Started { total_points, bounds } => {
+                        self.total_points = total_points;
+                        self.model = bounds.fit_into(FIT_SIZE);
+                    }
+                    LoadEvent::Chunk(chunk) => self.pending.push(chunk),
+                    LoadEvent::Failed(e) => {
+                        self.events = None;
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let mut upload = None;
+        if !self.pending.is_empty() {
+            // this frame's chunks go up in a single submit
+            let mut staging = Staging::new(queue);
+            for chunk in self.pending.drain(..self.pending.len().min(MAX_UPLOADS_PER_FRAME)) {
+                let buffer = staging
+                    .buffer(chunk.points.iter().cloned(), BufferUsage::vertex_buffer())
+                    .map_err(|e| e.to_string())?;
+
+                self.uploaded_points += chunk.points.len();
+                self.chunks.push(GpuChunk {
+                    buffer,
+                    bounds: chunk.bounds,
+                });
+            }
+            upload = Some(staging.submit().map_err(|e| e.to_string())?);
+        }
+
+        if self.total_points > 0 && self.uploaded_points == self.total_points && self.events.is_some() {
+            info!(
+                "Loaded {} points in {} chunks from {}",
+                self.total_points,
+                self.chunks.len(),
+                self.path.display()
+            );
+            self.events = None;
+        }
+
+        Ok(upload)
+    }
+
+    /// Vertex buffers of the chunks that intersect the frustum, which is given in the cloud's own space
+    pub fn visible_chunks<'a>(
+        &'a self,
+        frustum: &'a Frustum,
+    ) -> impl Iterator<Item = Arc<ImmutableBuffer<[PointVertex]>>> + 'a {
+        self.chunks
+            .iter()
+            .filter(move |chunk| frustum.intersects_aabb(&chunk.bounds))
+            .map(|chunk| chunk.buffer.clone())
+    }
+
+    pub fn status(&self) -> String {
+        format!(
+            "{}: {} of {} points uploaded in {} chunks",
+            self.path.display(),
+            self.uploaded_points,
+            self.total_points,
+            self.chunks.len()
+        )
+    }
+}
+
+fn read_points(path: &Path) -> Result<Vec<PointVertex>, String> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if matches!(extension, Some(extension) if extension.eq_ignore_ascii_case("ply")) {
+        return mesh::load(path).map(|mesh| mesh.point_vertices());
+    }
+
+    let file = File::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    let mut points = Vec::new();
+
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+
+        let values = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("{}:{}: expected numbers", path.display(), number + 1))?;
+
+        let point = match values.as_slice() {
+            [x, y, z] => PointVertex::new([*x, *y, *z], [1.0, 1.0, 1.0]),
+            [x, y, z, r, g, b, ..] => {
+                // colors are stored either as 0-255 integers or as 0-1 floats
+                let scale = if *r > 1.0 || *g > 1.0 || *b > 1.0 { 255.0 } else { 1.0 };
+                PointVertex::new([*x, *y, *z], [r / scale, g / scale, b / scale])
+            }
+            _ => return Err(format!("{}:{}: expected x y z [r g b]", path.display(), number + 1)),
+        };
+
+        points.push(point);
+    }
+
+    Ok(points)
+}
+
+/// Buckets the points into a regular grid over `bounds`, so each chunk covers a small region
+fn split_into_chunks(points: Vec<PointVertex>, bounds: &Aabb) -> Vec<PointChunk> {
+    let size = bounds.max - bounds.min;
+    let cell_of = |point: &PointVertex| {
+        let cell = |value: f32, min: f32, extent: f32| {
+            if extent <= 0.0 {
+                0
+            } else {
+                (((value - min) / extent * GRID_CELLS as f32) as usize).min(GRID_CELLS - 1)
+            }
+        };
+
+        let [x, y, z] = point.position;
+        cell(x, bounds.min.x, size.x)
+            + cell(y, bounds.min.y, size.y) * GRID_CELLS
+            + cell(z, bounds.min.z, size.z) * GRID_CELLS * GRID_CELLS
+    };
+
+    let mut cells = vec![Vec::new(); GRID_CELLS * GRID_CELLS * GRID_CELLS];
+    for point in points {
+        cells[cell_of(&point)].push(point);
+    }
+
+    cells
+        .into_iter()
+        .flat_map(|cell| {
+            cell.chunks(MAX_CHUNK_POINTS)
+                .map(|points| points.to_vec())
+                .collect::<Vec<_>>()
+        })
+        .filter_map(|points| {
+            let bounds = Aabb::from_points(points.iter().map(PointVertex::point))?;
+            Some(PointChunk { points, bounds })
+        })
+        .collect()
+}
+
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "point vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "point fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<PointVertex>()
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .point_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .depth_stencil_simple_depth()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
+
+/// Largest point size the shader may write, only sizes of 1.0 are allowed without `large_points`
+pub fn max_point_size(device: &Arc<Device>) -> f32 {
+    if device.enabled_features().large_points {
+        device
+            .physical_device()
+            .properties()
+            .point_size_range
+            .map_or(1.0, |range| range[1])
+    } else {
+        1.0
+    }
+}
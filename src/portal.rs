@@ -0,0 +1,313 @@
+//! A surface in the scene that shows it from another camera: a mirror, or a window that looks
+//! out of a second surface somewhere else. What is seen through it is drawn into a target of its
+//! own before the scene pass, with the main camera moved by the portal's transform, and the
+//! surface samples that target where it covers the screen. A window's own view contains the
+//! window again, so its views are drawn innermost first, each showing the one drawn before it,
+//! down to the depth the `portal` command sets.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use cgmath::{Deg, Matrix, Matrix4, SquareMatrix, Vector3, Vector4};
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    device::Device,
+    image::ImageUsage,
+    pipeline::{
+        cache::PipelineCache,
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{FramebufferAbstract, RenderPass, Subpass},
+};
+
+use crate::{
+    debug_view::DebugView,
+    descriptors::DescriptorCache,
+    error::Error,
+    parallel::SceneDraw,
+    post,
+    render_target::RenderTarget,
+    renderer,
+    shader_reload::ReloadedShaders,
+};
+
+/// Views a window can nest, each one is a scene pass of its own
+pub const MAX_DEPTH: u32 = 4;
+/// The surface stands this far behind the origin, facing the default camera on +Z
+const SURFACE_Z: f32 = -1.5;
+const SURFACE_HALF_SIZE: [f32; 2] = [1.2, 0.9];
+/// How far behind the default camera a window's other side looks back at the scene from
+const WINDOW_EXIT_Z: f32 = 3.0;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/portal.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/portal.frag"
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortalKind {
+    /// Reflects the scene in front of it
+    Mirror,
+    /// Shows the scene from behind the default camera's position, looking the same way, so the
+    /// window is seen through itself
+    Window,
+}
+
+impl PortalKind {
+    /// Moves what is seen through the surface to where it appears behind it, the main camera's
+    /// view times this is the view of the camera behind the surface
+    fn transform(self) -> Matrix4<f32> {
+        match self {
+            PortalKind::Mirror => {
+                Matrix4::from_translation(Vector3::new(0.0, 0.0, SURFACE_Z))
+                    * Matrix4::from_nonuniform_scale(1.0, 1.0, -1.0)
+                    * Matrix4::from_translation(Vector3::new(0.0, 0.0, -SURFACE_Z))
+            }
+            PortalKind::Window => {
+                // the other side faces the surface's way after half a turn about Y
+                let entry = Matrix4::from_translation(Vector3::new(0.0, 0.0, SURFACE_Z));
+                let exit = Matrix4::from_translation(Vector3::new(0.0, 0.0, WINDOW_EXIT_Z))
+                    * Matrix4::from_angle_y(Deg(180.0));
+                entry * Matrix4::from_angle_y(Deg(180.0)) * exit.invert().unwrap()
+            }
+        }
+    }
+
+    /// A mirror seen in its own reflection is seen from behind, it only has one view
+    fn max_depth(self) -> u32 {
+        match self {
+            PortalKind::Mirror => 1,
+            PortalKind::Window => MAX_DEPTH,
+        }
+    }
+
+    fn reflectance(self) -> f32 {
+        match self {
+            PortalKind::Mirror => 0.9,
+            PortalKind::Window => 1.0,
+        }
+    }
+}
+
+impl FromStr for PortalKind {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "mirror" => Ok(PortalKind::Mirror),
+            "window" => Ok(PortalKind::Window),
+            other => Err(format!("expected mirror or window, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for PortalKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PortalKind::Mirror => "mirror",
+            PortalKind::Window => "window",
+        })
+    }
+}
+
+/// What the `portal` command changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortalSettings {
+    /// `None` without a portal
+    pub kind: Option<PortalKind>,
+    /// Nested views of a window, at most `MAX_DEPTH`
+    pub depth: u32,
+}
+
+impl Default for PortalSettings {
+    fn default() -> Self {
+        Self { kind: None, depth: 2 }
+    }
+}
+
+impl fmt::Display for PortalSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(kind) => write!(f, "{}, depth {}", kind, self.depth.min(kind.max_depth())),
+            None => write!(f, "off, depth {}", self.depth),
+        }
+    }
+}
+
+/// Places the unit quad in the XY plane over the surface
+pub fn surface_model() -> Matrix4<f32> {
+    Matrix4::from_translation(Vector3::new(0.0, 0.0, SURFACE_Z))
+        * Matrix4::from_nonuniform_scale(SURFACE_HALF_SIZE[0], SURFACE_HALF_SIZE[1], 1.0)
+}
+
+/// The targets of a portal's views and the scene pipeline drawing them, recreated with the
+/// swap chain
+pub struct Portals {
+    kind: PortalKind,
+    /// By depth, the first is seen through the surface in the scene pass
+    levels: Vec<(RenderTarget, Arc<dyn FramebufferAbstract + Send + Sync>)>,
+    /// Shaded and not culled, a mirror's view is reflected and turns every triangle around
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    /// Draws the surface into the views of a window
+    surface_pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    scene_size: [u32; 2],
+}
+
+impl Portals {
+    /// `depth` views of `scene_size` for a window, one for a mirror. `cache` and `shaders` are the
+    /// scene pipelines'.
+    pub fn new(
+        device: &Arc<Device>,
+        settings: (PortalKind, u32),
+        scene_size: [u32; 2],
+        cache: &Arc<PipelineCache>,
+        shaders: &ReloadedShaders,
+    ) -> Result<Self, Error> {
+        let (kind, depth) = settings;
+        let render_pass = renderer::create_render_pass(device, post::INTERMEDIATE_FORMAT, 1);
+        // the views are drawn one after the other, they can clear the same depth buffer
+        let depth_buffer = renderer::create_depth_buffer(device, scene_size);
+        let levels = (0..depth.clamp(1, kind.max_depth()))
+            .map(|_| {
+                let target = RenderTarget::new(device, scene_size, post::INTERMEDIATE_FORMAT, ImageUsage::none());
+                let framebuffer = renderer::create_framebuffer(&render_pass, target.view(), &depth_buffer, None);
+                (target, framebuffer)
+            })
+            .collect();
+        let pipeline =
+            renderer::create_graphics_pipeline(device, &render_pass, DebugView::Shaded, true, cache, shaders)?;
+
+        Ok(Self {
+            kind,
+            levels,
+            pipeline,
+            surface_pipeline: create_pipeline(device, &render_pass)?,
+            scene_size,
+        })
+    }
+
+    pub fn kind(&self) -> PortalKind {
+        self.kind
+    }
+
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The pipeline the views' draws have to be built for
+    pub fn pipeline(&self) -> &Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+        &self.pipeline
+    }
+
+    /// View matrix of the camera drawing the view at `level`, from the main camera's `view`
+    pub fn level_view(&self, view: Matrix4<f32>, level: usize) -> Matrix4<f32> {
+        let transform = self.kind.transform();
+        (0..=level).fold(view, |view, _| view * transform)
+    }
+
+    /// The world space plane, as (normal, -distance), that keeps what is in front of the other
+    /// side of the surface. Whatever lies between the camera behind the surface and the surface
+    /// would hide the view otherwise.
+    pub fn clip_plane(&self) -> Vector4<f32> {
+        let surface = Vector4::new(0.0, 0.0, 1.0, -SURFACE_Z);
+        -(self.kind.transform().transpose() * surface)
+    }
+
+    /// Records the render pass of the view at `level`, `draws` are made with its camera. The
+    /// surface is seen in a window's views but the innermost, showing the view drawn before.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_level(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        level: usize,
+        draws: &[SceneDraw],
+        clear_color: [f32; 3],
+        cache: &DescriptorCache,
+        view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+    ) {
+        let clear_values = vec![[clear_color[0], clear_color[1], clear_color[2], 1.0].into(), 1.0.into()];
+        builder
+            .begin_render_pass(self.levels[level].1.clone(), SubpassContents::Inline, clear_values)
+            .unwrap();
+        let dynamic_state = renderer::dynamic_state(self.scene_size);
+        for draw in draws {
+            draw.record(builder, &self.pipeline, &dynamic_state);
+        }
+        if level + 1 < self.levels.len() {
+            let view_projection = projection * self.level_view(view, level);
+            self.draw_surface(builder, &self.surface_pipeline, &dynamic_state, cache, level + 1, view_projection);
+        }
+        builder.end_render_pass().unwrap();
+    }
+
+    /// Draws the surface showing the view at `level`, with `pipeline` from `create_pipeline`
+    pub fn draw_surface<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        pipeline: &Arc<GraphicsPipeline<BufferlessDefinition>>,
+        dynamic_state: &DynamicState,
+        cache: &DescriptorCache,
+        level: usize,
+        view_projection: Matrix4<f32>,
+    ) {
+        let push_constants = vertex_shader::ty::PushConstants {
+            mvp: (view_projection * surface_model()).into(),
+            inverseSize: self.levels[level].0.inverse_size(),
+            reflectance: self.kind.reflectance(),
+        };
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap();
+        let set = cache.get(layout, &[self.levels[level].0.sampled()]);
+
+        builder
+            .draw(
+                pipeline.clone(),
+                dynamic_state,
+                BufferlessVertices {
+                    vertices: 6,
+                    instances: 1,
+                },
+                set,
+                push_constants,
+                vec![],
+            )
+            .unwrap();
+    }
+}
+
+/// The surface of a portal in `render_pass`, tested and written like the scene's meshes, so
+/// what stands in front of it hides it
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "portal vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "portal fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition {})
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .depth_stencil_simple_depth()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
@@ -0,0 +1,419 @@
+use std::{sync::Arc, time::Instant};
+
+use log::info;
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::DescriptorSet,
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, ImageUsage, ImmutableImage, SwapchainImage},
+    pipeline::{
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline, GraphicsPipelineAbstract,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+use winit::window::Window;
+
+use crate::{
+    descriptors::{self, Resource},
+    error::Error,
+    render_target::RenderTarget,
+    staging::Staging,
+};
+
+/// What the scene is drawn into, with enough precision that gradients don't band before they reach
+/// the swap chain
+pub const INTERMEDIATE_FORMAT: Format = Format::R16G16B16A16Sfloat;
+
+/// Side of the blue noise texture, it is tiled over the screen
+const NOISE_SIZE: usize = 64;
+/// Width of the Gaussian the void and cluster method measures how clustered pixels are with
+const NOISE_SIGMA: f32 = 1.5;
+/// Share of pixels set in the pattern the ranking starts from
+const INITIAL_DENSITY: usize = 10;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/post.frag"
+    }
+}
+
+/// Display calibration applied by the post pass, set in the settings file
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Calibration {
+    /// The image is raised to the power of 1 / gamma, above 1 brightens the midtones
+    pub gamma: f32,
+    /// Added to every channel after the contrast
+    pub brightness: f32,
+    /// Scales the distance of every channel from middle gray
+    pub contrast: f32,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+        }
+    }
+}
+
+/// The last scene pass: copies the scene from its 16 bit float target into the swap chain image,
+/// scaling it up when the scene is drawn at a lower resolution, multiplying it by the tint,
+/// applying the display calibration and adding blue noise below the size of one 8 bit step.
+/// Smooth gradients like the sky then come out as fine noise instead of bands. Decals, the lens
+/// flare and the plot are drawn straight into the swap chain image after it.
+pub struct PostProcess {
+    /// Attached to the scene pass in place of the swap chain image
+    pub target: RenderTarget,
+    pipeline: Arc<GraphicsPipeline<BufferlessDefinition>>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    output_size: [u32; 2],
+}
+
+impl PostProcess {
+    /// Recreated with the swap chain, `blue_noise` comes from `blue_noise_texture` and is kept.
+    /// The target is `scene_size`, the swap chain's size or smaller.
+    pub fn new(
+        device: &Arc<Device>,
+        swap_chain_images: &[Arc<SwapchainImage<Window>>],
+        color_format: Format,
+        scene_size: [u32; 2],
+        blue_noise: &Arc<ImageView<Arc<ImmutableImage>>>,
+    ) -> Result<Self, Error> {
+        let dimensions = swap_chain_images[0].dimensions();
+        // checkerboard rendering copies the finished image into its history, motion blur copies
+        // the blurred image back
+        let target_usage = ImageUsage {
+            transfer_source: true,
+            transfer_destination: true,
+            ..ImageUsage::none()
+        };
+        let target = RenderTarget::new(device, scene_size, INTERMEDIATE_FORMAT, target_usage);
+
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+
+        let pipeline = Self::create_pipeline(device, dimensions, &render_pass)?;
+
+        let framebuffers = swap_chain_images
+            .iter()
+            .map(|image| {
+                let view = ImageView::new(image.clone()).unwrap();
+                Arc::new(Framebuffer::start(render_pass.clone()).add(view).unwrap().build().unwrap())
+                    as Arc<dyn FramebufferAbstract + Send + Sync>
+            })
+            .collect();
+
+        // the noise is read with texelFetch, the sampler is only there to bind it
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        // the scene is filtered when scaled up, at the same size every pixel reads one texel center
+        let set = descriptors::build(
+            layout,
+            &[target.sampled(), Resource::SampledImage(blue_noise.clone(), sampler)],
+        );
+
+        Ok(Self {
+            target,
+            pipeline,
+            framebuffers,
+            set,
+            output_size: dimensions,
+        })
+    }
+
+    /// The post pass pipeline for the first subpass of `render_pass`, which may have more, like
+    /// the color grade's
+    pub fn create_pipeline(
+        device: &Arc<Device>,
+        dimensions: [u32; 2],
+        render_pass: &Arc<RenderPass>,
+    ) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+        let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "post vertex",
+            source,
+        })?;
+        let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+            name: "post fragment",
+            source,
+        })?;
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        Ok(Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(BufferlessDefinition {})
+                .vertex_shader(vert_shader_module.main_entry_point(), ())
+                .triangle_list()
+                .viewports(vec![viewport])
+                .fragment_shader(frag_shader_module.main_entry_point(), ())
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())?,
+        ))
+    }
+
+    /// Records the post pass, after the scene pass and its depth resolve
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        calibration: Calibration,
+        tint: [f32; 3],
+        dither: bool,
+    ) {
+        builder
+            .begin_render_pass(
+                self.framebuffers[image_index].clone(),
+                SubpassContents::Inline,
+                vec![ClearValue::None],
+            )
+            .unwrap();
+        self.draw_subpass(builder, &self.pipeline, calibration, tint, dither);
+        builder.end_render_pass().unwrap();
+    }
+
+    /// Records the post pass's draw into the subpass `pipeline` was made for, which has to be
+    /// recording
+    pub fn draw_subpass(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: &Arc<GraphicsPipeline<BufferlessDefinition>>,
+        calibration: Calibration,
+        tint: [f32; 3],
+        dither: bool,
+    ) {
+        let push_constants = fragment_shader::ty::PushConstants {
+            tint,
+            gamma: calibration.gamma,
+            brightness: calibration.brightness,
+            contrast: calibration.contrast,
+            dither: dither as u32,
+            inverseOutputSize: [1.0 / self.output_size[0] as f32, 1.0 / self.output_size[1] as f32],
+        };
+
+        builder
+            .draw(
+                pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                self.set.clone(),
+                push_constants,
+                vec![],
+            )
+            .unwrap();
+    }
+}
+
+/// Maps a linear color of any brightness into 0..1 for an 8 bit file: its luminance `l` becomes
+/// `l / (1 + l)` and the channels are scaled along, so hues stay as they are. For captures of
+/// images that hold more than the display shows, like float swap chains.
+pub fn tonemap(color: [f32; 3]) -> [f32; 3] {
+    let luminance = 0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2];
+    if luminance <= 0.0 {
+        return [0.0; 3];
+    }
+    let scale = 1.0 / (1.0 + luminance);
+    color.map(|channel| (channel.max(0.0) * scale).min(1.0))
+}
+
+/// The sRGB transfer function, for linear colors written to files that are read as sRGB
+pub fn encode_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse of `encode_srgb`, for colors read from 8 bit sRGB files into linear targets
+pub fn decode_srgb(encoded: f32) -> f32 {
+    if encoded <= 0.040_45 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Uploads the blue noise the post pass dithers with, created once and shared by every `PostProcess`.
+/// Usable once `staging` was submitted.
+pub fn blue_noise_texture(staging: &mut Staging) -> Result<Arc<ImageView<Arc<ImmutableImage>>>, Error> {
+    let start = Instant::now();
+    let noise = void_and_cluster(NOISE_SIZE);
+    info!("Generated {0}x{0} blue noise in {1} ms", NOISE_SIZE, start.elapsed().as_millis());
+
+    let image = staging.image(noise.into_iter(), [NOISE_SIZE as u32; 2], Format::R8Unorm)?;
+    Ok(ImageView::new(image).unwrap())
+}
+
+/// A binary pattern on a torus, with the energy every pixel gets from the set pixels around it
+#[derive(Clone)]
+struct Pattern<'a> {
+    size: usize,
+    set: Vec<bool>,
+    energy: Vec<f32>,
+    /// Energy a set pixel adds to a pixel, by their offset wrapped around the edges
+    kernel: &'a [f32],
+}
+
+impl<'a> Pattern<'a> {
+    fn new(size: usize, kernel: &'a [f32]) -> Self {
+        Self {
+            size,
+            set: vec![false; size * size],
+            energy: vec![0.0; size * size],
+            kernel,
+        }
+    }
+
+    fn toggle(&mut self, pixel: usize) {
+        self.set[pixel] = !self.set[pixel];
+        let sign = if self.set[pixel] { 1.0 } else { -1.0 };
+        let (px, py) = (pixel % self.size, pixel / self.size);
+
+        for (y, row) in self.energy.chunks_mut(self.size).enumerate() {
+            let dy = (y + self.size - py) % self.size;
+            let kernel_row = &self.kernel[dy * self.size..(dy + 1) * self.size];
+            // the row starts at offset -px, which wraps around to size - px
+            let (before, after) = kernel_row.split_at(self.size - px);
+            for (energy, weight) in row.iter_mut().zip(after.iter().chain(before)) {
+                *energy += sign * weight;
+            }
+        }
+    }
+
+    /// The set pixel with the most set pixels around it
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// The unset pixel furthest from any set pixel
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, set: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        self.energy
+            .iter()
+            .enumerate()
+            .filter(|&(pixel, _)| self.set[pixel] == set)
+            .reduce(|best, candidate| if better(*candidate.1, *best.1) { candidate } else { best })
+            .expect("pattern is all set or all unset")
+            .0
+    }
+}
+
+/// A tileable blue noise threshold map made with a simplified void and cluster method: pixels are
+/// ranked by taking tightest clusters out of, and filling largest voids in, a binary pattern, so
+/// every threshold level is spread evenly over the tile. A fixed seed makes it the same every run.
+fn void_and_cluster(size: usize) -> Vec<u8> {
+    let pixels = size * size;
+    let kernel: Vec<f32> = (0..pixels)
+        .map(|offset| {
+            let wrap = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (wrap(offset % size), wrap(offset / size));
+            (-(dx * dx + dy * dy) / (2.0 * NOISE_SIGMA * NOISE_SIGMA)).exp()
+        })
+        .collect();
+
+    // xorshift, only needs to scatter the initial pattern
+    let mut seed = 0x9e37_79b9_u32;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as usize
+    };
+
+    let mut pattern = Pattern::new(size, &kernel);
+    let initial_count = pixels / INITIAL_DENSITY;
+    let mut count = 0;
+    while count < initial_count {
+        let pixel = random() % pixels;
+        if !pattern.set[pixel] {
+            pattern.toggle(pixel);
+            count += 1;
+        }
+    }
+
+    // moves pixels from clusters into voids until the pattern is even
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; pixels];
+    let initial = pattern.clone();
+    for rank in (0..initial_count).rev() {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+
+    // the original method swaps the roles of set and unset pixels past half, filling voids all
+    // the way is close enough for dithering
+    pattern = initial;
+    for rank in initial_count..pixels {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    ranks.into_iter().map(|rank| (rank * 256 / pixels) as u8).collect()
+}
@@ -0,0 +1,208 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    descriptor::DescriptorSet,
+    device::Device,
+    image::ImageUsage,
+    pipeline::{vertex::BufferlessDefinition, GraphicsPipeline, GraphicsPipelineAbstract},
+};
+
+use crate::{descriptors, post, render_target::RenderTarget};
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/fullscreen.vert"
+    }
+}
+
+mod gamma_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/postfx_gamma.frag"
+    }
+}
+
+mod vignette_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/postfx_vignette.frag"
+    }
+}
+
+mod grayscale_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/postfx_grayscale.frag"
+    }
+}
+
+mod fxaa_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/postfx_fxaa.frag"
+    }
+}
+
+/// One full-screen pass of the chain, each is its own fragment shader
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// Encodes the linear scene with a 2.2 gamma
+    Gamma,
+    /// Darkens the corners
+    Vignette,
+    /// Keeps only the luminance
+    Grayscale,
+    /// Smooths jagged edges by blurring along them
+    Fxaa,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 4] = [Effect::Gamma, Effect::Vignette, Effect::Grayscale, Effect::Fxaa];
+}
+
+impl FromStr for Effect {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "gamma" => Ok(Effect::Gamma),
+            "vignette" => Ok(Effect::Vignette),
+            "grayscale" => Ok(Effect::Grayscale),
+            "fxaa" => Ok(Effect::Fxaa),
+            other => Err(format!("expected gamma, vignette, grayscale or fxaa, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Effect::Gamma => "gamma",
+            Effect::Vignette => "vignette",
+            Effect::Grayscale => "grayscale",
+            Effect::Fxaa => "fxaa",
+        })
+    }
+}
+
+/// Full-screen effects applied to the scene target in the order the `postfx` command lists
+/// them, after bloom and before the post pass. They read the scene and write back and forth
+/// between two targets of its size, the last result is copied back into the scene target.
+pub struct EffectChain {
+    /// By `Effect as usize`
+    pipelines: Vec<Arc<GraphicsPipeline<BufferlessDefinition>>>,
+    /// Written by the first effect and every other one after it, then by the ones in between
+    targets: [RenderTarget; 2],
+    /// By `Effect as usize`, then reading the scene, the first target and the second target
+    sets: Vec<[Arc<dyn DescriptorSet + Send + Sync>; 3]>,
+}
+
+impl EffectChain {
+    /// Recreated with the swap chain. `scene` is the post pass target.
+    pub fn new(device: &Arc<Device>, scene: &RenderTarget) -> Self {
+        let usage = ImageUsage {
+            transfer_source: true,
+            ..ImageUsage::none()
+        };
+        let targets = [
+            RenderTarget::new(device, scene.size(), post::INTERMEDIATE_FORMAT, usage),
+            RenderTarget::new(device, scene.size(), post::INTERMEDIATE_FORMAT, usage),
+        ];
+
+        let vert_shader_module = vertex_shader::Shader::load(device.clone())
+            .expect("Failed to create post effect vertex shader module");
+        let gamma_shader_module =
+            gamma_shader::Shader::load(device.clone()).expect("Failed to create gamma shader module");
+        let vignette_shader_module =
+            vignette_shader::Shader::load(device.clone()).expect("Failed to create vignette shader module");
+        let grayscale_shader_module =
+            grayscale_shader::Shader::load(device.clone()).expect("Failed to create grayscale shader module");
+        let fxaa_shader_module =
+            fxaa_shader::Shader::load(device.clone()).expect("Failed to create FXAA shader module");
+
+        // both targets have the same format, pipelines made for one draw into the other too.
+        // Every fragment shader makes a builder of its own type, so a closure can't build them.
+        macro_rules! pipeline {
+            ($fragment_shader_module:expr) => {
+                Arc::new(
+                    GraphicsPipeline::start()
+                        .vertex_input(BufferlessDefinition {})
+                        .vertex_shader(vert_shader_module.main_entry_point(), ())
+                        .triangle_list()
+                        .viewports(vec![targets[0].viewport()])
+                        .fragment_shader($fragment_shader_module.main_entry_point(), ())
+                        .render_pass(targets[0].subpass())
+                        .build(device.clone())
+                        .unwrap(),
+                )
+            };
+        }
+        let pipelines: Vec<Arc<GraphicsPipeline<BufferlessDefinition>>> = Effect::ALL
+            .iter()
+            .map(|effect| match effect {
+                Effect::Gamma => pipeline!(gamma_shader_module),
+                Effect::Vignette => pipeline!(vignette_shader_module),
+                Effect::Grayscale => pipeline!(grayscale_shader_module),
+                Effect::Fxaa => pipeline!(fxaa_shader_module),
+            })
+            .collect();
+
+        let sets = pipelines
+            .iter()
+            .map(|pipeline| {
+                let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+                [scene, &targets[0], &targets[1]]
+                    .map(|source| descriptors::build(layout.clone(), &[source.sampled()]))
+            })
+            .collect();
+
+        Self {
+            pipelines,
+            targets,
+            sets,
+        }
+    }
+
+    /// Records the effects in `effects`' order, nothing when it is empty. `scene` is the target
+    /// the chain was made for.
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        scene: &RenderTarget,
+        effects: &[Effect],
+    ) {
+        // 0 is the scene, 1 and 2 the targets
+        let mut source = 0;
+        for (index, &effect) in effects.iter().enumerate() {
+            let destination = index % 2;
+            self.targets[destination].draw_fullscreen(
+                builder,
+                &self.pipelines[effect as usize],
+                self.sets[effect as usize][source].clone(),
+                (),
+            );
+            source = destination + 1;
+        }
+
+        if source != 0 {
+            let result = self.targets[source - 1].view().image();
+            let [width, height] = scene.size();
+            builder
+                .copy_image(
+                    result.clone(),
+                    [0, 0, 0],
+                    0,
+                    0,
+                    scene.view().image().clone(),
+                    [0, 0, 0],
+                    0,
+                    0,
+                    [width, height, 1],
+                    1,
+                )
+                .unwrap();
+        }
+    }
+}
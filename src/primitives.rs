@@ -0,0 +1,239 @@
+//! Meshes generated in code, with normals and UVs, for testing lighting and texturing without a
+//! model file. Every shape is centered on the origin and winds its triangles counter-clockwise
+//! seen from outside. Besides the cube, which keeps a color per face, vertices are colored by
+//! their normal so the shape reads before anything lights it.
+
+use std::{
+    f32::consts::{PI, TAU},
+    fmt,
+    str::FromStr,
+};
+
+use cgmath::{Deg, Matrix4};
+
+use crate::vertex::Vertex;
+
+/// A shape the `primitive` command can show, at a size that fits the default camera
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Primitive {
+    Sphere,
+    Plane,
+    Cube,
+    Cylinder,
+    Torus,
+}
+
+impl Primitive {
+    pub const ALL: [Primitive; 5] = [
+        Primitive::Sphere,
+        Primitive::Plane,
+        Primitive::Cube,
+        Primitive::Cylinder,
+        Primitive::Torus,
+    ];
+
+    pub fn mesh(self) -> (Vec<Vertex>, Vec<u32>) {
+        match self {
+            Primitive::Sphere => sphere(0.6, 32, 16),
+            Primitive::Plane => plane(1.6, 8),
+            Primitive::Cube => cube(1.0),
+            Primitive::Cylinder => cylinder(0.5, 1.0, 32),
+            Primitive::Torus => torus(0.5, 0.2, 48, 24),
+        }
+    }
+
+    /// Tipped towards the camera, so the tops of the plane, cylinder and torus show
+    pub fn model(self) -> Matrix4<f32> {
+        Matrix4::from_angle_x(Deg(30.0))
+    }
+}
+
+impl FromStr for Primitive {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Primitive::ALL
+            .iter()
+            .copied()
+            .find(|primitive| primitive.to_string() == s)
+            .ok_or_else(|| format!("unknown primitive '{}', expected sphere, plane, cube, cylinder or torus", s))
+    }
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Primitive::Sphere => "sphere",
+            Primitive::Plane => "plane",
+            Primitive::Cube => "cube",
+            Primitive::Cylinder => "cylinder",
+            Primitive::Torus => "torus",
+        })
+    }
+}
+
+fn normal_color(normal: [f32; 3]) -> [f32; 3] {
+    normal.map(|component| component * 0.5 + 0.5)
+}
+
+/// A grid of `columns` by `rows` quads wrapped onto a surface. `surface` gets the grid's u and v
+/// from 0 to 1 and returns the position and normal there; seen from outside u runs to the right
+/// and v downwards, which is also how the texture is laid over it.
+fn grid(
+    columns: u32,
+    rows: u32,
+    surface: impl Fn(f32, f32) -> ([f32; 3], [f32; 3]),
+    vertices: &mut Vec<Vertex>,
+    indices: &mut Vec<u32>,
+) {
+    let first = vertices.len() as u32;
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+            let (pos, normal) = surface(u, v);
+            vertices.push(Vertex::new(pos, normal_color(normal), [u, v]).with_normal(normal));
+        }
+    }
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let top_left = first + row * (columns + 1) + column;
+            let bottom_left = top_left + columns + 1;
+            indices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                bottom_left + 1,
+                bottom_left + 1,
+                top_left + 1,
+                top_left,
+            ]);
+        }
+    }
+}
+
+/// A UV sphere, `segments` around the Y axis and `rings` from pole to pole. The seam where u
+/// wraps has its own vertices.
+pub fn sphere(radius: f32, segments: u32, rings: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    grid(
+        segments.max(3),
+        rings.max(2),
+        |u, v| {
+            let (around, down) = (u * TAU, v * PI);
+            let normal = [down.sin() * around.sin(), down.cos(), down.sin() * around.cos()];
+            (normal.map(|component| component * radius), normal)
+        },
+        &mut vertices,
+        &mut indices,
+    );
+    (vertices, indices)
+}
+
+/// A square in the XZ plane facing +Y, split into `cells` by `cells` quads
+pub fn plane(size: f32, cells: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    grid(
+        cells.max(1),
+        cells.max(1),
+        |u, v| ([(u - 0.5) * size, 0.0, (v - 0.5) * size], [0.0, 1.0, 0.0]),
+        &mut vertices,
+        &mut indices,
+    );
+    (vertices, indices)
+}
+
+/// A cube of side `size`, four vertices per face so every face gets its own normal, color and
+/// the whole texture
+pub fn cube(size: f32) -> (Vec<Vertex>, Vec<u32>) {
+    // normal, then the directions of the face's u and v axes
+    const FACES: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+    const COLORS: [[f32; 3]; 6] = [
+        [1.0, 0.4, 0.4],
+        [0.4, 1.0, 1.0],
+        [0.4, 1.0, 0.4],
+        [1.0, 0.4, 1.0],
+        [0.4, 0.4, 1.0],
+        [1.0, 1.0, 0.4],
+    ];
+
+    let half = size / 2.0;
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for ((normal, u, v), color) in FACES.iter().zip(COLORS.iter()) {
+        let first = vertices.len() as u32;
+        for &[s, t] in &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]] {
+            let pos = [0, 1, 2].map(|axis| (normal[axis] + (s * 2.0 - 1.0) * u[axis] + (t * 2.0 - 1.0) * v[axis]) * half);
+            // images are stored top row first
+            vertices.push(Vertex::new(pos, *color, [s, 1.0 - t]).with_normal(*normal));
+        }
+        indices.extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 3, first]);
+    }
+    (vertices, indices)
+}
+
+/// A closed cylinder along the Y axis, `segments` around it. The caps have their own vertices
+/// so the edges stay sharp, and map the texture's center to the axis.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    grid(
+        segments,
+        1,
+        |u, v| {
+            let normal = [(u * TAU).sin(), 0.0, (u * TAU).cos()];
+            ([normal[0] * radius, half - v * height, normal[2] * radius], normal)
+        },
+        &mut vertices,
+        &mut indices,
+    );
+
+    for &side in &[1.0f32, -1.0] {
+        let normal = [0.0, side, 0.0];
+        let center = vertices.len() as u32;
+        vertices.push(Vertex::new([0.0, side * half, 0.0], normal_color(normal), [0.5, 0.5]).with_normal(normal));
+        for segment in 0..=segments {
+            let (sin, cos) = (segment as f32 / segments as f32 * TAU).sin_cos();
+            // seen from outside, the bottom cap is the top one mirrored
+            let uv = [0.5 + 0.5 * sin * side, 0.5 + 0.5 * cos];
+            vertices.push(Vertex::new([sin * radius, side * half, cos * radius], normal_color(normal), uv).with_normal(normal));
+        }
+        for segment in 0..segments {
+            let (this, next) = (center + 1 + segment, center + 2 + segment);
+            if side > 0.0 {
+                indices.extend_from_slice(&[center, this, next]);
+            } else {
+                indices.extend_from_slice(&[center, next, this]);
+            }
+        }
+    }
+    (vertices, indices)
+}
+
+/// A ring around the Y axis, `segments` along it and `sides` around the tube.
+/// `major_radius` is the distance from the center to the middle of the tube.
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+    grid(
+        segments.max(3),
+        sides.max(3),
+        |u, v| {
+            let (around, tube) = (u * TAU, v * TAU);
+            let outward = [around.sin(), 0.0, around.cos()];
+            // v starts on the outer edge and goes down first, like on the sphere
+            let normal = [outward[0] * tube.cos(), -tube.sin(), outward[2] * tube.cos()];
+            let pos = [0, 1, 2].map(|axis| outward[axis] * major_radius + normal[axis] * minor_radius);
+            (pos, normal)
+        },
+        &mut vertices,
+        &mut indices,
+    );
+    (vertices, indices)
+}
@@ -0,0 +1,160 @@
+use std::{fmt, str::FromStr};
+
+use cgmath::{Deg, Matrix4, Rad};
+
+/// How the aspect ratio of the projection is chosen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AspectPolicy {
+    /// Follow the swap chain extent, so the image is never stretched
+    Window,
+    /// Always use the given width / height ratio, stretching the image if the window differs
+    Fixed(f32),
+}
+
+impl FromStr for AspectPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "window" => Ok(AspectPolicy::Window),
+            ratio => match ratio.parse::<f32>() {
+                Ok(ratio) if ratio > 0.0 => Ok(AspectPolicy::Fixed(ratio)),
+                _ => Err(format!("expected 'window' or a positive ratio, got '{}'", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for AspectPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AspectPolicy::Window => write!(f, "window"),
+            AspectPolicy::Fixed(ratio) => write!(f, "{}", ratio),
+        }
+    }
+}
+
+/// Perspective projection parameters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Projection {
+    /// Vertical field of view
+    pub fov_y: Deg<f32>,
+    pub near: f32,
+    pub far: f32,
+    pub aspect: AspectPolicy,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self {
+            fov_y: Deg(45.0),
+            near: 0.1,
+            far: 100.0,
+            aspect: AspectPolicy::Window,
+        }
+    }
+}
+
+impl Projection {
+    pub const MIN_FOV_Y: Deg<f32> = Deg(10.0);
+    pub const MAX_FOV_Y: Deg<f32> = Deg(170.0);
+
+    /// Clamps the field of view to `MIN_FOV_Y..=MAX_FOV_Y`, rejecting NaN and infinity, which
+    /// clamping would let through
+    pub fn set_fov_y(&mut self, fov_y: Deg<f32>) -> Result<(), String> {
+        if !fov_y.0.is_finite() {
+            return Err(format!("the field of view needs to be a finite angle, got {}", fov_y.0));
+        }
+
+        self.fov_y = Deg(fov_y.0.clamp(Self::MIN_FOV_Y.0, Self::MAX_FOV_Y.0));
+        Ok(())
+    }
+
+    /// Sets the clip planes, rejecting combinations that would produce a degenerate matrix
+    pub fn set_planes(&mut self, near: f32, far: f32) -> Result<(), String> {
+        if !near.is_finite() || !far.is_finite() || near <= 0.0 || far <= near {
+            return Err(format!("clip planes need 0 < near < far, got near {} and far {}", near, far));
+        }
+
+        self.near = near;
+        self.far = far;
+        Ok(())
+    }
+
+    pub fn aspect_ratio(&self, extent: [u32; 2]) -> f32 {
+        match self.aspect {
+            AspectPolicy::Window => extent[0] as f32 / extent[1].max(1) as f32,
+            AspectPolicy::Fixed(ratio) => ratio,
+        }
+    }
+
+    /// Right handed perspective projection for Vulkan clip space: Y points down and depth goes
+    /// from 0 at the near plane to 1 at the far plane
+    pub fn matrix(&self, extent: [u32; 2]) -> Matrix4<f32> {
+        let focal_length = 1.0 / (Rad::from(self.fov_y).0 / 2.0).tan();
+        let aspect = self.aspect_ratio(extent);
+        let depth_scale = self.far / (self.near - self.far);
+
+        #[rustfmt::skip]
+        let matrix = Matrix4::new(
+            focal_length / aspect, 0.0, 0.0, 0.0,
+            0.0, -focal_length, 0.0, 0.0,
+            0.0, 0.0, depth_scale, -1.0,
+            0.0, 0.0, self.near * depth_scale, 0.0,
+        );
+
+        matrix
+    }
+}
+
+/// Right handed orthographic projection for Vulkan clip space, seeing `half_width` and
+/// `half_height` either side of the view axis between `near` and `far`
+pub fn orthographic(half_width: f32, half_height: f32, near: f32, far: f32) -> Matrix4<f32> {
+    let depth_scale = 1.0 / (near - far);
+
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+        1.0 / half_width, 0.0, 0.0, 0.0,
+        0.0, -1.0 / half_height, 0.0, 0.0,
+        0.0, 0.0, depth_scale, 0.0,
+        0.0, 0.0, near * depth_scale, 1.0,
+    );
+
+    matrix
+}
+
+impl fmt::Display for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "fov {}°, near {}, far {}, aspect {}",
+            self.fov_y.0, self.near, self.far, self.aspect
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_planes_need_finite_ordered_distances() {
+        let mut projection = Projection::default();
+        assert!(projection.set_planes(0.5, 50.0).is_ok());
+        for (near, far) in [(f32::NAN, 50.0), (0.5, f32::NAN), (0.5, f32::INFINITY), (0.0, 50.0), (2.0, 1.0)] {
+            assert!(projection.set_planes(near, far).is_err(), "near {} far {}", near, far);
+        }
+        assert_eq!((projection.near, projection.far), (0.5, 50.0));
+    }
+
+    #[test]
+    fn field_of_view_is_clamped_and_finite() {
+        let mut projection = Projection::default();
+        assert!(projection.set_fov_y(Deg(500.0)).is_ok());
+        assert_eq!(projection.fov_y, Projection::MAX_FOV_Y);
+        assert!(projection.set_fov_y(Deg(f32::NAN)).is_err());
+        assert!(projection.set_fov_y(Deg(f32::NEG_INFINITY)).is_err());
+        assert_eq!(projection.fov_y, Projection::MAX_FOV_Y);
+        assert!(projection.matrix([800, 600]).x.x.is_finite());
+    }
+}
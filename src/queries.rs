@@ -0,0 +1,237 @@
+//! Occlusion and pipeline statistics queries around each draw of the scene pass, turned on with
+//! the `queries` command to find out which draws cost the most and which are hidden behind
+//! others. Like the GPU timer, a frame's results are read when its in-flight slot comes around
+//! again.
+
+use std::{fmt, sync::Arc};
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer},
+    device::Device,
+    query::{QueryControlFlags, QueryPipelineStatisticFlags, QueryPool, QueryResultFlags, QueryType},
+};
+
+use crate::renderer::MAX_FRAMES_IN_FLIGHT;
+
+/// Draws per frame that get queries, the scene pass's draws after them don't
+pub const MAX_QUERIED_DRAWS: u32 = 256;
+
+/// Counted in this order, the order of the flags' bits
+const STATISTICS: QueryPipelineStatisticFlags = QueryPipelineStatisticFlags {
+    input_assembly_vertices: true,
+    input_assembly_primitives: true,
+    fragment_shader_invocations: true,
+    vertex_shader_invocations: false,
+    geometry_shader_invocations: false,
+    geometry_shader_primitives: false,
+    clipping_invocations: false,
+    clipping_primitives: false,
+    tessellation_control_shader_patches: false,
+    tessellation_evaluation_shader_invocations: false,
+    compute_shader_invocations: false,
+};
+
+/// What one draw did on the GPU
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawCounters {
+    /// Samples that passed the depth test, without the `occlusion_query_precise` feature any
+    /// number above 0 may be reported as 1
+    pub samples: u64,
+    /// `None` without the `pipeline_statistics_query` feature
+    pub statistics: Option<DrawStatistics>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawStatistics {
+    pub vertices: u64,
+    pub primitives: u64,
+    pub fragment_invocations: u64,
+}
+
+/// The counters of every queried draw of a frame, in the order they were drawn
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryReport {
+    pub draws: Vec<DrawCounters>,
+}
+
+impl QueryReport {
+    /// Draws none of whose samples passed the depth test
+    pub fn occluded(&self) -> usize {
+        self.draws.iter().filter(|draw| draw.samples == 0).count()
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.draws.iter().map(|draw| draw.samples).sum()
+    }
+
+    /// The statistics of all draws added up, `None` without pipeline statistics
+    pub fn statistics(&self) -> Option<DrawStatistics> {
+        self.draws.iter().try_fold(DrawStatistics::default(), |total, draw| {
+            let draw = draw.statistics?;
+            Some(DrawStatistics {
+                vertices: total.vertices + draw.vertices,
+                primitives: total.primitives + draw.primitives,
+                fragment_invocations: total.fragment_invocations + draw.fragment_invocations,
+            })
+        })
+    }
+}
+
+impl fmt::Display for QueryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} draws, {} occluded, {} samples passed",
+            self.draws.len(),
+            self.occluded(),
+            self.samples()
+        )?;
+        if let Some(statistics) = self.statistics() {
+            write!(
+                f,
+                ", {} vertices, {} primitives, {} fragment invocations",
+                statistics.vertices, statistics.primitives, statistics.fragment_invocations
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Query pools with `MAX_QUERIED_DRAWS` queries per frame in flight
+pub struct DrawQueries {
+    occlusion: Arc<QueryPool>,
+    /// `None` where the device doesn't have the `pipeline_statistics_query` feature
+    statistics: Option<Arc<QueryPool>>,
+    precise: bool,
+    /// Draws queried by the frame last submitted in each slot and not read yet
+    submitted: Vec<u32>,
+    latest: Option<QueryReport>,
+}
+
+impl DrawQueries {
+    pub fn new(device: &Arc<Device>) -> Result<Self, String> {
+        let count = MAX_QUERIED_DRAWS * MAX_FRAMES_IN_FLIGHT as u32;
+        let occlusion = QueryPool::new(device.clone(), QueryType::Occlusion, count)
+            .map_err(|e| format!("can't create the occlusion queries: {}", e))?;
+        let features = device.enabled_features();
+        let statistics = if features.pipeline_statistics_query {
+            let pool = QueryPool::new(device.clone(), QueryType::PipelineStatistics(STATISTICS), count)
+                .map_err(|e| format!("can't create the pipeline statistics queries: {}", e))?;
+            Some(Arc::new(pool))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            occlusion: Arc::new(occlusion),
+            statistics,
+            precise: features.occlusion_query_precise,
+            submitted: vec![0; MAX_FRAMES_IN_FLIGHT],
+            latest: None,
+        })
+    }
+
+    pub fn has_statistics(&self) -> bool {
+        self.statistics.is_some()
+    }
+
+    /// Has to be recorded before the scene pass in every frame that queries draws
+    pub fn reset(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, slot: usize) {
+        let queries = Self::range(slot, MAX_QUERIED_DRAWS);
+        unsafe {
+            builder.reset_query_pool(self.occlusion.clone(), queries.clone()).unwrap();
+            if let Some(statistics) = &self.statistics {
+                builder.reset_query_pool(statistics.clone(), queries).unwrap();
+            }
+        }
+    }
+
+    /// Starts the queries of the `draw`th draw, which has to be below `MAX_QUERIED_DRAWS`
+    pub fn begin(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, slot: usize, draw: u32) {
+        let query = Self::range(slot, MAX_QUERIED_DRAWS).start + draw;
+        let flags = QueryControlFlags { precise: self.precise };
+        unsafe {
+            builder.begin_query(self.occlusion.clone(), query, flags).unwrap();
+            if let Some(statistics) = &self.statistics {
+                builder
+                    .begin_query(statistics.clone(), query, QueryControlFlags { precise: false })
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Ends what `begin` started, in the same subpass
+    pub fn end(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, slot: usize, draw: u32) {
+        let query = Self::range(slot, MAX_QUERIED_DRAWS).start + draw;
+        builder.end_query(self.occlusion.clone(), query).unwrap();
+        if let Some(statistics) = &self.statistics {
+            builder.end_query(statistics.clone(), query).unwrap();
+        }
+    }
+
+    /// Call once the frame recorded in `slot` was submitted with `draws` queried draws
+    pub fn submitted(&mut self, slot: usize, draws: u32) {
+        self.submitted[slot] = draws;
+    }
+
+    /// Reads the results of the frame last submitted in `slot` once it has finished, they
+    /// become the latest report when all of them are available
+    pub fn read(&mut self, slot: usize) {
+        let draws = std::mem::replace(&mut self.submitted[slot], 0);
+        if draws == 0 {
+            return;
+        }
+
+        let queries = Self::range(slot, draws);
+        let mut samples = vec![0u64; draws as usize];
+        let available = self
+            .occlusion
+            .queries_range(queries.clone())
+            .and_then(|range| range.get_results(&mut samples, QueryResultFlags::default()).ok());
+        if available != Some(true) {
+            return;
+        }
+
+        let statistics = match &self.statistics {
+            Some(pool) => {
+                let mut values = vec![0u64; draws as usize * STATISTICS.count()];
+                let available = pool
+                    .queries_range(queries)
+                    .and_then(|range| range.get_results(&mut values, QueryResultFlags::default()).ok());
+                if available != Some(true) {
+                    return;
+                }
+                values
+                    .chunks(STATISTICS.count())
+                    .map(|values| {
+                        Some(DrawStatistics {
+                            vertices: values[0],
+                            primitives: values[1],
+                            fragment_invocations: values[2],
+                        })
+                    })
+                    .collect()
+            }
+            None => vec![None; draws as usize],
+        };
+
+        self.latest = Some(QueryReport {
+            draws: samples
+                .into_iter()
+                .zip(statistics)
+                .map(|(samples, statistics)| DrawCounters { samples, statistics })
+                .collect(),
+        });
+    }
+
+    /// The results of the last frame that were read, `None` before the first
+    pub fn latest(&self) -> Option<&QueryReport> {
+        self.latest.as_ref()
+    }
+
+    /// The first `draws` queries of `slot`
+    fn range(slot: usize, draws: u32) -> std::ops::Range<u32> {
+        let first = slot as u32 * MAX_QUERIED_DRAWS;
+        first..first + draws
+    }
+}
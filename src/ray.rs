@@ -0,0 +1,174 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3, Vector4};
+
+/// Half line in world space
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    /// Unit length
+    pub direction: Vector3<f32>,
+}
+
+/// Plane made of all points `p` with `normal · p = distance`
+#[derive(Copy, Clone, Debug)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+/// Axis aligned bounding box in world space
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Ray {
+    /// Unprojects a cursor position (in physical pixels, origin top left) through the camera.
+    /// The ray starts on the near plane. Returns `None` if the matrices can't be inverted.
+    pub fn from_cursor(
+        cursor: [f64; 2],
+        extent: [u32; 2],
+        view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+    ) -> Option<Ray> {
+        // Vulkan NDC has Y pointing down like window coordinates, so no flip is needed
+        let x = (2.0 * cursor[0] / extent[0].max(1) as f64 - 1.0) as f32;
+        let y = (2.0 * cursor[1] / extent[1].max(1) as f64 - 1.0) as f32;
+
+        let inverse = (projection * view).invert()?;
+        let unproject = |depth: f32| {
+            let point = inverse * Vector4::new(x, y, depth, 1.0);
+            Point3::from_homogeneous(point)
+        };
+
+        let near = unproject(0.0);
+        let far = unproject(1.0);
+
+        Some(Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        })
+    }
+
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// Returns where the ray crosses the plane, or `None` if it is parallel or the plane is behind it
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<Point3<f32>> {
+        let denominator = plane.normal.dot(self.direction);
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let distance = (plane.distance - plane.normal.dot(self.origin.to_vec())) / denominator;
+        if distance < 0.0 {
+            return None;
+        }
+
+        Some(self.at(distance))
+    }
+
+    /// Returns the distance along the ray to the first point inside the box, using the slab method
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut entry = 0.0f32;
+        let mut exit = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / self.direction[axis];
+            let mut near = (aabb.min[axis] - self.origin[axis]) * inverse_direction;
+            let mut far = (aabb.max[axis] - self.origin[axis]) * inverse_direction;
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+
+            // NaN comes from a ray lying exactly on a slab boundary, which counts as inside
+            if !near.is_nan() {
+                entry = entry.max(near);
+            }
+            if !far.is_nan() {
+                exit = exit.min(far);
+            }
+
+            if entry > exit {
+                return None;
+            }
+        }
+
+        Some(entry)
+    }
+
+    /// Returns the distance along the ray to a triangle, from either side (Möller–Trumbore)
+    pub fn intersect_triangle(&self, triangle: &[Point3<f32>; 3]) -> Option<f32> {
+        let [a, b, c] = *triangle;
+        let edge1 = b - a;
+        let edge2 = c - a;
+
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let inverse_determinant = 1.0 / determinant;
+        let to_origin = self.origin - a;
+        let u = to_origin.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = to_origin.cross(edge1);
+        let v = self.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge2.dot(q) * inverse_determinant;
+        if distance < 0.0 {
+            return None;
+        }
+
+        Some(distance)
+    }
+}
+
+impl Plane {
+    /// Horizontal plane at the given height, facing up
+    pub fn ground(height: f32) -> Self {
+        Self {
+            normal: Vector3::unit_y(),
+            distance: height,
+        }
+    }
+}
+
+impl Aabb {
+    /// Smallest box containing all points, `None` if there are none
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Aabb { min: first, max: first }, |aabb, point| Aabb {
+            min: Point3::new(aabb.min.x.min(point.x), aabb.min.y.min(point.y), aabb.min.z.min(point.z)),
+            max: Point3::new(aabb.max.x.max(point.x), aabb.max.y.max(point.y), aabb.max.z.max(point.z)),
+        }))
+    }
+
+    /// Box around the mesh positions after transforming them into world space
+    pub fn from_transformed_points(
+        points: impl IntoIterator<Item = Point3<f32>>,
+        model: Matrix4<f32>,
+    ) -> Option<Self> {
+        Self::from_points(points.into_iter().map(|point| model.transform_point(point)))
+    }
+
+    /// Centers the box at the origin and scales it uniformly so its largest side is `size` long
+    pub fn fit_into(&self, size: f32) -> Matrix4<f32> {
+        let extent = self.max - self.min;
+        let largest = extent.x.max(extent.y).max(extent.z);
+        let scale = if largest > 0.0 { size / largest } else { 1.0 };
+        let center = self.min.midpoint(self.max);
+
+        Matrix4::from_scale(scale) * Matrix4::from_translation(-center.to_vec())
+    }
+}
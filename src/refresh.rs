@@ -0,0 +1,91 @@
+//! The refresh rate of the monitor the window is on, which the frame rate cap and the fixed
+//! simulation step default to. Checked again whenever the window moves, a move to a monitor
+//! with a different rate is reported as a `RefreshRateChanged` for the app to pass on.
+
+use std::time::Duration;
+
+use log::info;
+use winit::{monitor::MonitorHandle, window::Window};
+
+/// Used when the platform doesn't say, like on Wayland, or the window has no monitor
+pub const FALLBACK_REFRESH_RATE: u32 = 60;
+
+/// Refresh rate of `monitor` in Hz. winit lists a monitor's video modes but not which one is
+/// current, so this is the fastest mode at the monitor's current resolution.
+pub fn monitor_refresh_rate(monitor: &MonitorHandle) -> Option<u32> {
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .map(|mode| mode.refresh_rate() as u32)
+        .filter(|&rate| rate > 0)
+        .max()
+}
+
+/// The window moved to a monitor that refreshes at a different rate
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshRateChanged {
+    pub monitor: Option<String>,
+    pub previous: u32,
+    pub rate: u32,
+}
+
+/// Follows the monitor the window is on
+pub struct RefreshRate {
+    monitor: Option<MonitorHandle>,
+    rate: u32,
+}
+
+impl RefreshRate {
+    pub fn new(window: &Window) -> Self {
+        let monitor = window.current_monitor();
+        let rate = monitor.as_ref().and_then(monitor_refresh_rate);
+        match rate {
+            Some(rate) => info!("{} refreshes at {} Hz", monitor_name(monitor.as_ref()), rate),
+            None => info!(
+                "The refresh rate of {} is unknown, assuming {} Hz",
+                monitor_name(monitor.as_ref()),
+                FALLBACK_REFRESH_RATE
+            ),
+        }
+        Self {
+            monitor,
+            rate: rate.unwrap_or(FALLBACK_REFRESH_RATE),
+        }
+    }
+
+    /// In Hz
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Time between two refreshes
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.rate as f64)
+    }
+
+    /// Looks up the window's monitor again, after it moved or its scale factor changed. Only a
+    /// different rate is reported, moving between monitors of the same rate changes nothing.
+    pub fn update(&mut self, window: &Window) -> Option<RefreshRateChanged> {
+        let monitor = window.current_monitor();
+        if monitor == self.monitor {
+            return None;
+        }
+
+        let rate = monitor.as_ref().and_then(monitor_refresh_rate).unwrap_or(FALLBACK_REFRESH_RATE);
+        let changed = RefreshRateChanged {
+            monitor: monitor.as_ref().and_then(MonitorHandle::name),
+            previous: self.rate,
+            rate,
+        };
+        self.monitor = monitor;
+        self.rate = rate;
+        Some(changed).filter(|changed| changed.rate != changed.previous)
+    }
+}
+
+fn monitor_name(monitor: Option<&MonitorHandle>) -> String {
+    monitor
+        .and_then(MonitorHandle::name)
+        .unwrap_or_else(|| "the monitor".to_string())
+}
@@ -0,0 +1,105 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{atomic::Ordering, mpsc::Sender, Arc},
+    thread,
+};
+
+use log::{error, info};
+
+use crate::{console::Command, panic_hook::FrameSnapshot};
+
+/// Accepts TCP connections that speak a line protocol: `stats` is answered with the last frame's
+/// statistics as a JSON object, every other line is a console command. Commands are run on the
+/// render thread like typed ones and their output goes to the application's terminal, the
+/// connection only gets `{"ok":true}` or the parse error back.
+pub fn listen(address: SocketAddr, commands: Sender<String>, snapshot: Arc<FrameSnapshot>) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!("Listening for remote commands on {}", listener.local_addr()?);
+
+    thread::Builder::new()
+        .name("remote".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let commands = commands.clone();
+                        let snapshot = snapshot.clone();
+                        // one thread per connection, this is for a handful of monitoring tools
+                        let spawned = thread::Builder::new()
+                            .name("remote connection".to_string())
+                            .spawn(move || serve(stream, commands, snapshot));
+                        if let Err(e) = spawned {
+                            error!("Failed to start a remote connection thread: {}", e);
+                        }
+                    }
+                    Err(e) => error!("Failed to accept a remote connection: {}", e),
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+fn serve(stream: TcpStream, commands: Sender<String>, snapshot: Arc<FrameSnapshot>) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "unknown peer".to_string(), |address| address.to_string());
+    info!("Remote connection from {}", peer);
+
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            error!("Failed to set up the connection from {}: {}", peer, e);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let reply = if line == "stats" {
+            stats_json(&snapshot)
+        } else {
+            match line.parse::<Command>() {
+                // the render thread parses it again, the channel carries lines like the terminal does
+                Ok(_) if commands.send(line.to_string()).is_ok() => r#"{"ok":true}"#.to_string(),
+                Ok(_) => break,
+                Err(e) => format!(r#"{{"error":"{}"}}"#, escape_json(&e)),
+            }
+        };
+
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+
+    info!("Remote connection from {} closed", peer);
+}
+
+fn stats_json(snapshot: &FrameSnapshot) -> String {
+    format!(
+        r#"{{"frame":{},"image_index":{},"cpu_time_us":{},"validation_errors":{}}}"#,
+        snapshot.frame.load(Ordering::Relaxed),
+        snapshot.image_index.load(Ordering::Relaxed),
+        snapshot.cpu_time_us.load(Ordering::Relaxed),
+        snapshot.validation_errors.load(Ordering::Relaxed),
+    )
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            character if character.is_control() => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
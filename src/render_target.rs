@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use vulkano::{
+    command_buffer::{AutoCommandBufferBuilder, DynamicState, PrimaryAutoCommandBuffer, SubpassContents},
+    descriptor::DescriptorSet,
+    device::Device,
+    format::{ClearValue, Format},
+    image::{view::ImageView, AttachmentImage, ImageAccess, ImageUsage},
+    pipeline::{
+        vertex::{BufferlessDefinition, BufferlessVertices},
+        viewport::Viewport,
+        GraphicsPipeline,
+    },
+    render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::descriptors::Resource;
+
+/// An offscreen color image that full-screen passes draw into and later passes sample, with
+/// the single subpass render pass and framebuffer writing it. The render pass doesn't load
+/// what was there, so a pass into it has to cover every pixel, which the full-screen triangle
+/// does.
+pub struct RenderTarget {
+    view: Arc<ImageView<Arc<AttachmentImage>>>,
+    /// Linear and clamped, scaling the image to another size filters it
+    sampler: Arc<Sampler>,
+    render_pass: Arc<RenderPass>,
+    framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+}
+
+impl RenderTarget {
+    /// A `size` image of `format`, usable as a color attachment and sampled on top of `usage`
+    pub fn new(device: &Arc<Device>, size: [u32; 2], format: Format, usage: ImageUsage) -> Self {
+        let usage = ImageUsage {
+            sampled: true,
+            ..usage
+        };
+        let view = ImageView::new(AttachmentImage::with_usage(device.clone(), size, format, usage).unwrap()).unwrap();
+
+        let render_pass = Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+            )
+            .unwrap(),
+        );
+        let framebuffer = Arc::new(Framebuffer::start(render_pass.clone()).add(view.clone()).unwrap().build().unwrap())
+            as Arc<dyn FramebufferAbstract + Send + Sync>;
+
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        Self {
+            view,
+            sampler,
+            render_pass,
+            framebuffer,
+        }
+    }
+
+    pub fn view(&self) -> &Arc<ImageView<Arc<AttachmentImage>>> {
+        &self.view
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.view.image().dimensions().width_height()
+    }
+
+    /// The size of a texel in texture coordinates, what full-screen shaders divide
+    /// `gl_FragCoord` by
+    pub fn inverse_size(&self) -> [f32; 2] {
+        let [width, height] = self.size();
+        [1.0 / width as f32, 1.0 / height as f32]
+    }
+
+    /// The image with its sampler, for descriptor sets of passes that read it
+    pub fn sampled(&self) -> Resource {
+        Resource::SampledImage(self.view.clone(), self.sampler.clone())
+    }
+
+    /// The subpass pipelines drawing into the image are made for
+    pub fn subpass(&self) -> Subpass {
+        Subpass::from(self.render_pass.clone(), 0).unwrap()
+    }
+
+    /// Covers the whole image
+    pub fn viewport(&self) -> Viewport {
+        let [width, height] = self.size();
+        Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        }
+    }
+
+    /// Records a render pass drawing the full-screen triangle with `pipeline`, which has to be
+    /// made for `subpass`
+    pub fn draw_fullscreen<Pc>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        pipeline: &Arc<GraphicsPipeline<BufferlessDefinition>>,
+        set: Arc<dyn DescriptorSet + Send + Sync>,
+        push_constants: Pc,
+    ) {
+        builder
+            .begin_render_pass(self.framebuffer.clone(), SubpassContents::Inline, vec![ClearValue::None])
+            .unwrap()
+            .draw(
+                pipeline.clone(),
+                &DynamicState::none(),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                set,
+                push_constants,
+                vec![],
+            )
+            .unwrap()
+            .end_render_pass()
+            .unwrap();
+    }
+}
@@ -0,0 +1,410 @@
+//! Creation of the Vulkan objects the scene is drawn with: the render pass and its framebuffers,
+//! the scene pipeline and the vertex and index buffers. They don't depend on the application's
+//! state, so they can be rebuilt whenever the swap chain or a setting changes.
+
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use log::info;
+use vulkano::{
+    buffer::{BufferAccess, BufferUsage, TypedBufferAccess},
+    device::Device,
+    format::Format,
+    image::{view::ImageView, AttachmentImage},
+    command_buffer::DynamicState,
+    pipeline::{cache::PipelineCache, viewport::Viewport, GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{Framebuffer, FramebufferAbstract, RenderPass, Subpass},
+    sync::{self, FenceSignalFuture, FlushError, GpuFuture},
+};
+
+use crate::{debug_view::{self, DebugView}, error::Error, memory_budget, msaa::{self, Multisampling}, shader_reload::ReloadedShaders, staging::Staging, vertex::{self, Vertex}};
+
+/// Format of the depth buffer, D16 is supported as a depth attachment on every device
+pub const DEPTH_FORMAT: Format = Format::D16Unorm;
+
+pub mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/triangle.vert"
+    }
+}
+
+pub mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/triangle.frag"
+    }
+}
+
+/// Source file of the scene's vertex shader, the fragment shaders are named by `DebugView`
+pub const VERTEX_SHADER_FILE: &str = "triangle.vert";
+
+/// Whether the scene pipeline is built from the shader source `file_name`, only those are
+/// reloaded while the application runs
+pub fn is_scene_shader(file_name: &str) -> bool {
+    file_name == VERTEX_SHADER_FILE || DebugView::ALL.iter().any(|view| view.shader_file() == file_name)
+}
+
+/// Usable once `staging` was submitted
+pub fn create_vertex_buffer(staging: &mut Staging, vert: &[Vertex]) -> Result<Arc<dyn BufferAccess + Send + Sync>, Error> {
+    // storage buffer use lets `vertex_capture` read the vertices
+    let usage = BufferUsage {
+        storage_buffer: true,
+        ..BufferUsage::vertex_buffer()
+    };
+    Ok(staging.buffer(vert.iter().cloned(), usage)?)
+}
+
+/// Usable once `staging` was submitted
+pub fn create_index_buffer(
+    staging: &mut Staging,
+    idx: &[u32],
+) -> Result<Arc<dyn TypedBufferAccess<Content = [u32]> + Send + Sync>, Error> {
+    Ok(staging.buffer(idx.iter().cloned(), BufferUsage::index_buffer())?)
+}
+
+/// How many frames can be queued on the GPU while the CPU records the next one
+pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
+/// Signalled when a frame's commands have run and its image was handed to the presentation engine
+pub type FrameFence = Arc<FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>>;
+
+/// A ring of fences, one per frame in flight. Recording a frame only waits for the frame that
+/// used the same slot `MAX_FRAMES_IN_FLIGHT` frames ago, not the one before it, so the CPU works
+/// on the next frame while the GPU draws the last.
+pub struct FramesInFlight {
+    device: Arc<Device>,
+    fences: Vec<Option<FrameFence>>,
+    current: usize,
+}
+
+impl FramesInFlight {
+    pub fn new(device: &Arc<Device>) -> Self {
+        Self {
+            device: device.clone(),
+            fences: vec![None; MAX_FRAMES_IN_FLIGHT],
+            current: 0,
+        }
+    }
+
+    /// Waits until the current slot is free again, then returns what the next submission has to
+    /// follow: the previous frame, so frames still reach the queue in order
+    pub fn begin_frame(&mut self) -> Result<Box<dyn GpuFuture + Send + Sync>, FlushError> {
+        if let Some(fence) = self.fences[self.current].take() {
+            fence.wait(None)?;
+        }
+
+        let previous = (self.current + MAX_FRAMES_IN_FLIGHT - 1) % MAX_FRAMES_IN_FLIGHT;
+        Ok(match self.fences[previous].clone() {
+            Some(fence) => Box::new(fence),
+            None => Box::new(sync::now(self.device.clone())),
+        })
+    }
+
+    /// Index of the slot the frame being recorded uses
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Stores the fence of the frame just submitted, `None` if it never reached the queue, and
+    /// moves on to the next slot
+    pub fn end_frame(&mut self, fence: Option<FrameFence>) {
+        self.fences[self.current] = fence;
+        self.current = (self.current + 1) % MAX_FRAMES_IN_FLIGHT;
+    }
+
+    /// Waits for every submitted frame, before the resources they use are destroyed
+    pub fn wait_idle(&mut self) -> Result<(), FlushError> {
+        for fence in self.fences.iter_mut().filter_map(Option::take) {
+            fence.wait(None)?;
+        }
+        Ok(())
+    }
+}
+
+/// The scene pipeline variants share `cache`, so switching debug views or culling only compiles
+/// what differs from pipelines built before. The build time is logged to show the difference.
+/// Shaders edited while the application runs are taken from `shaders`.
+pub fn create_graphics_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+    debug_view: DebugView,
+    double_sided: bool,
+    cache: &Arc<PipelineCache>,
+    shaders: &ReloadedShaders,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let start = Instant::now();
+    let shader_module = |name| move |source| Error::ShaderModule { name, source };
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(shader_module("vertex"))?;
+
+    // the modules have different types, so only the selected one is loaded and kept alive here
+    let shaded_module;
+    let lit_module;
+    let uv_module;
+    let mip_level_module;
+    let depth_module;
+    let frag_entry_point = match debug_view {
+        DebugView::Shaded => {
+            shaded_module = fragment_shader::Shader::load(device.clone()).map_err(shader_module("fragment"))?;
+            shaded_module.main_entry_point()
+        }
+        DebugView::Lit => {
+            lit_module = debug_view::lit_shader::Shader::load(device.clone()).map_err(shader_module("lit"))?;
+            lit_module.main_entry_point()
+        }
+        DebugView::Uv => {
+            uv_module = debug_view::uv_shader::Shader::load(device.clone()).map_err(shader_module("UV debug"))?;
+            uv_module.main_entry_point()
+        }
+        DebugView::MipLevel => {
+            mip_level_module = debug_view::mip_level_shader::Shader::load(device.clone())
+                .map_err(shader_module("mip level debug"))?;
+            mip_level_module.main_entry_point()
+        }
+        DebugView::Depth => {
+            depth_module =
+                debug_view::depth_shader::Shader::load(device.clone()).map_err(shader_module("depth debug"))?;
+            depth_module.main_entry_point()
+        }
+    };
+    let frag_entry_point = shaders.entry_point(debug_view.shader_file(), frag_entry_point);
+    let vert_entry_point = shaders.entry_point(VERTEX_SHADER_FILE, vert_shader_module.main_entry_point());
+
+    let builder = GraphicsPipeline::start()
+        .vertex_input_single_buffer::<vertex::Vertex>()
+        .vertex_shader(vert_entry_point, ())
+        .triangle_list()
+        .primitive_restart(false)
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(frag_entry_point, ())
+        .depth_clamp(false)
+        .polygon_mode_fill()
+        .line_width(1.0);
+
+    // a section plane opens meshes up, the back faces inside are what the cap is drawn with
+    let builder = if double_sided {
+        builder.cull_mode_disabled()
+    } else {
+        builder.cull_mode_back()
+    };
+
+    let pipeline = builder
+        .front_face_counter_clockwise()
+        .depth_stencil_simple_depth()
+        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        .blend_pass_through()
+        .build_with_cache(cache.clone())
+        .build(device.clone())?;
+
+    info!(
+        "Built the {}{} scene pipeline in {:.2} ms",
+        debug_view,
+        if double_sided { " double sided" } else { "" },
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    Ok(Arc::new(pipeline))
+}
+
+/// Every scene pipeline variant built so far, by debug view and whether it is double sided.
+/// Switching to a variant that was built before takes no time, `warm_up` builds all of them
+/// up front so switching never stalls a frame.
+pub struct ScenePipelines {
+    device: Arc<Device>,
+    render_pass: Arc<RenderPass>,
+    cache: Arc<PipelineCache>,
+    built: HashMap<(DebugView, bool), Arc<dyn GraphicsPipelineAbstract + Send + Sync>>,
+}
+
+impl ScenePipelines {
+    /// `cache` may hold pipelines from earlier runs, see `pipeline_cache::load`
+    pub fn new(device: &Arc<Device>, render_pass: &Arc<RenderPass>, cache: Arc<PipelineCache>) -> Self {
+        Self {
+            device: device.clone(),
+            render_pass: render_pass.clone(),
+            cache,
+            built: HashMap::new(),
+        }
+    }
+
+    /// Builds the variant unless it was built before
+    pub fn get(
+        &mut self,
+        debug_view: DebugView,
+        double_sided: bool,
+        shaders: &ReloadedShaders,
+    ) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+        if let Some(pipeline) = self.built.get(&(debug_view, double_sided)) {
+            return Ok(pipeline.clone());
+        }
+
+        let pipeline =
+            create_graphics_pipeline(&self.device, &self.render_pass, debug_view, double_sided, &self.cache, shaders)?;
+        self.built.insert((debug_view, double_sided), pipeline.clone());
+        Ok(pipeline)
+    }
+
+    /// Builds every variant that wasn't built yet and logs how many and how long it took
+    pub fn warm_up(&mut self, shaders: &ReloadedShaders) -> Result<(), Error> {
+        let start = Instant::now();
+        let before = self.built.len();
+        for debug_view in DebugView::ALL {
+            for double_sided in [false, true] {
+                self.get(debug_view, double_sided, shaders)?;
+            }
+        }
+
+        info!(
+            "Warmed up {} scene pipelines in {:.2} ms",
+            self.built.len() - before,
+            start.elapsed().as_secs_f64() * 1000.0
+        );
+        Ok(())
+    }
+
+    /// Shared by every variant, saved on exit
+    pub fn cache(&self) -> &Arc<PipelineCache> {
+        &self.cache
+    }
+
+    /// The variants are built again for the new render pass
+    pub fn set_render_pass(&mut self, render_pass: &Arc<RenderPass>) {
+        self.render_pass = render_pass.clone();
+        self.built.clear();
+    }
+
+    /// Drops the variants built so far, after their shaders changed
+    pub fn clear(&mut self) {
+        self.built.clear();
+    }
+}
+
+/// Viewport of the scene pass pipelines, which is set when drawing so resizing the window
+/// doesn't rebuild them
+pub fn dynamic_state(swap_chain_extent: [u32; 2]) -> DynamicState {
+    DynamicState {
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [swap_chain_extent[0] as f32, swap_chain_extent[1] as f32],
+            depth_range: 0.0..1.0,
+        }]),
+        ..DynamicState::none()
+    }
+}
+
+/// The sample count from the settings, lowered to one the device supports and whose render
+/// targets at `dimensions` fit the memory budget
+pub fn sample_count(device: &Arc<Device>, requested: u32, dimensions: [u32; 2]) -> u32 {
+    let samples = msaa::choose_sample_count(device.physical_device(), requested);
+    if samples != requested {
+        info!("{}x multisampling is not supported, using {}x", requested, samples);
+    }
+    memory_budget::fit_sample_count(device.physical_device(), dimensions, samples)
+}
+
+/// Size the scene is drawn at, `extent` scaled by `render_scale` and at least one pixel each way
+pub fn scene_size(extent: [u32; 2], render_scale: f32) -> [u32; 2] {
+    extent.map(|length| ((length as f32 * render_scale).round() as u32).max(1))
+}
+
+/// With more than one sample the scene is drawn into multisampled color and depth images and
+/// the color is resolved into the swap chain image at the end of the pass
+pub fn create_render_pass(device: &Arc<Device>, color_format: Format, samples: u32) -> Arc<RenderPass> {
+    if samples > 1 {
+        return Arc::new(
+            vulkano::single_pass_renderpass!(
+                device.clone(),
+                attachments: {
+                    multisampled_color: {
+                        load: Clear,
+                        store: DontCare,
+                        format: color_format,
+                        samples: samples,
+                    },
+                    depth: {
+                        load: Clear,
+                        store: Store,
+                        format: DEPTH_FORMAT,
+                        samples: samples,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: color_format,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [multisampled_color],
+                    depth_stencil: {depth},
+                    resolve: [color]
+                }
+            )
+            .unwrap(),
+        );
+    }
+
+    Arc::new(
+        vulkano::single_pass_renderpass!(
+            device.clone(),
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: color_format,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: Store,
+                    format: DEPTH_FORMAT,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth}
+            }
+        )
+        .unwrap(),
+    )
+}
+
+/// Sampled as well as attached, the lens flare reads it after the scene pass, and an input
+/// attachment for the lighting subpass of `deferred`
+pub fn create_depth_buffer(device: &Arc<Device>, dimensions: [u32; 2]) -> Arc<ImageView<Arc<AttachmentImage>>> {
+    let depth_buffer = AttachmentImage::sampled_input_attachment(device.clone(), dimensions, DEPTH_FORMAT).unwrap();
+    ImageView::new(depth_buffer).unwrap()
+}
+
+/// The scene is always drawn into the post pass's target, which copies it into whichever swap
+/// chain image was acquired, so one framebuffer does for all of them
+pub fn create_framebuffer(
+    render_pass: &Arc<RenderPass>,
+    color_target: &Arc<ImageView<Arc<AttachmentImage>>>,
+    depth_buffer: &Arc<ImageView<Arc<AttachmentImage>>>,
+    multisampling: Option<&Multisampling>,
+) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+    // attachments in the order create_render_pass declares them
+    if let Some(multisampling) = multisampling {
+        return Arc::new(
+            Framebuffer::start(render_pass.clone())
+                .add(multisampling.color.clone())
+                .unwrap()
+                .add(multisampling.depth.clone())
+                .unwrap()
+                .add(color_target.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+    }
+
+    Arc::new(
+        Framebuffer::start(render_pass.clone())
+            .add(color_target.clone())
+            .unwrap()
+            .add(depth_buffer.clone())
+            .unwrap()
+            .build()
+            .unwrap(),
+    )
+}
@@ -0,0 +1,104 @@
+//! What the scene pipeline draws in a frame: renderables that each own a handle to their mesh,
+//! a transform and a material. The application gathers them from the demo scene or loaded mesh
+//! and the spawned objects, command buffer recording then draws them in order.
+
+use std::{fmt, sync::Arc};
+
+use cgmath::Matrix4;
+use vulkano::buffer::{BufferAccess, TypedBufferAccess};
+
+use crate::{motion_blur::DrawId, scenes::Animation, texture::Texture};
+
+/// Vertex and index buffers on the GPU, shared by every renderable drawn with them
+#[derive(Clone)]
+pub struct MeshHandle {
+    pub vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+    pub index_buffer: Arc<dyn TypedBufferAccess<Content = [u32]> + Send + Sync>,
+}
+
+impl MeshHandle {
+    pub fn new(
+        vertex_buffer: Arc<dyn BufferAccess + Send + Sync>,
+        index_buffer: Arc<dyn TypedBufferAccess<Content = [u32]> + Send + Sync>,
+    ) -> Self {
+        Self {
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    pub fn triangles(&self) -> u64 {
+        self.index_buffer.len() as u64 / 3
+    }
+}
+
+/// Light a surface gives off by itself, added after it is shaded. Above an intensity of about
+/// 1 it is brighter than anything the light makes and the bloom's bright pass picks it up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emission {
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Default for Emission {
+    fn default() -> Self {
+        Self {
+            color: [0.0; 3],
+            intensity: 1.0,
+        }
+    }
+}
+
+impl fmt::Display for Emission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "color ({:.2}, {:.2}, {:.2}), intensity {:.2}",
+            self.color[0], self.color[1], self.color[2], self.intensity
+        )
+    }
+}
+
+/// How the scene pipeline shades a renderable
+#[derive(Clone)]
+pub struct Material {
+    pub texture: Arc<Texture>,
+    pub emission: Emission,
+    /// Multiplies the emission per texel, white to glow evenly
+    pub emissive_map: Arc<Texture>,
+    pub animation: Animation,
+}
+
+#[derive(Clone)]
+pub struct Renderable {
+    /// Finds the renderable's transform of the previous frame for motion blur
+    pub id: DrawId,
+    pub mesh: MeshHandle,
+    /// Model matrix, animations included
+    pub transform: Matrix4<f32>,
+    pub material: Material,
+}
+
+/// The renderables of one frame, in drawing order
+#[derive(Default)]
+pub struct Scene {
+    renderables: Vec<Renderable>,
+}
+
+impl Scene {
+    pub fn add(&mut self, renderable: Renderable) {
+        self.renderables.push(renderable);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Renderable> {
+        self.renderables.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.renderables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renderables.is_empty()
+    }
+}
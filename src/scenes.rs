@@ -0,0 +1,240 @@
+use std::{fmt, str::FromStr};
+
+use cgmath::{Deg, Matrix4, SquareMatrix};
+
+use crate::{
+    calibration,
+    debug_draw::DebugLines,
+    primitives,
+    vertex::{indices, vertecies, Vertex},
+};
+
+/// How a scene is animated, mirrors the `animation` push constant
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Animation {
+    None = 0,
+    /// Rotates the whole mesh around the screen center, through its model matrix
+    Rotate = 1,
+    /// Pulses the vertex colors with a wave travelling across the mesh
+    ColorWave = 2,
+}
+
+/// A subsystem a scene shows off besides its mesh, switched on while the scene is selected
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Showcase {
+    /// The Blinn-Phong lighting of `view lit`
+    Lighting,
+    /// The particle fountain moved by a compute shader
+    Particles,
+    /// The streamed world with its splatted terrain and grass
+    Terrain,
+}
+
+/// A built-in scene that can be shown without any external assets
+pub struct DemoScene {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub animation: Animation,
+    /// Drawn over the whole window in place of the mesh, given the window size in pixels
+    pub pattern: Option<fn(&mut DebugLines, [u32; 2])>,
+    pub showcase: Option<Showcase>,
+    mesh: fn() -> (Vec<Vertex>, Vec<u32>),
+    model: fn() -> Matrix4<f32>,
+}
+
+impl DemoScene {
+    pub fn mesh(&self) -> (Vec<Vertex>, Vec<u32>) {
+        (self.mesh)()
+    }
+
+    /// Places the flat mesh in the world, which is viewed from `CAMERA_DISTANCE` along +Z
+    pub fn model(&self) -> Matrix4<f32> {
+        (self.model)()
+    }
+}
+
+/// Distance of the default camera from the world origin, looking down -Z
+pub const CAMERA_DISTANCE: f32 = 2.0;
+
+impl fmt::Display for DemoScene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {}", self.name, self.description)
+    }
+}
+
+/// All demo scenes, in the order they are bound to the number keys
+pub const DEMO_SCENES: &[DemoScene] = &[
+    DemoScene {
+        name: "quad",
+        description: "the indexed quad from the vertex buffer chapter",
+        animation: Animation::None,
+        pattern: None,
+        showcase: None,
+        mesh: quad,
+        model: Matrix4::identity,
+    },
+    DemoScene {
+        name: "rotating-quad",
+        description: "the same quad rotated through its model matrix",
+        animation: Animation::Rotate,
+        pattern: None,
+        showcase: None,
+        mesh: quad,
+        model: Matrix4::identity,
+    },
+    DemoScene {
+        name: "color-wave",
+        description: "a subdivided grid with vertex colors animated in the vertex shader",
+        animation: Animation::ColorWave,
+        pattern: None,
+        showcase: None,
+        mesh: color_grid,
+        model: Matrix4::identity,
+    },
+    DemoScene {
+        name: "depth-plane",
+        description: "a large plane stretching away from the camera, try it with 'view depth'",
+        animation: Animation::None,
+        pattern: None,
+        showcase: None,
+        mesh: color_grid,
+        model: ground_plane,
+    },
+    DemoScene {
+        name: "calibration",
+        description: "gray ramps, gamma stripes and near black and white checkerboards for tuning the display settings",
+        animation: Animation::None,
+        pattern: Some(calibration::draw),
+        showcase: None,
+        mesh: quad,
+        model: Matrix4::identity,
+    },
+    DemoScene {
+        name: "textured-cube",
+        description: "a cube with a normal, color and UVs per face, try it with 'texture <file>'",
+        animation: Animation::Rotate,
+        pattern: None,
+        showcase: None,
+        mesh: cube,
+        model: tilted,
+    },
+    DemoScene {
+        name: "lit-model",
+        description: "a torus shaded with the Blinn-Phong light, which the light command moves",
+        animation: Animation::Rotate,
+        pattern: None,
+        showcase: Some(Showcase::Lighting),
+        mesh: torus,
+        model: tilted,
+    },
+    DemoScene {
+        name: "particles",
+        description: "the particle fountain a compute shader moves every frame, over a ground plane",
+        animation: Animation::None,
+        pattern: None,
+        showcase: Some(Showcase::Particles),
+        mesh: color_grid,
+        model: ground_plane,
+    },
+    DemoScene {
+        name: "terrain",
+        description: "chunks of splatted terrain with grass streamed around the camera, the cube marks the start",
+        animation: Animation::Rotate,
+        pattern: None,
+        showcase: Some(Showcase::Terrain),
+        mesh: cube,
+        model: small,
+    },
+];
+
+/// Index into `DEMO_SCENES`, parsed either from a scene name or its 1-based number
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SceneId(pub usize);
+
+impl SceneId {
+    pub fn scene(self) -> &'static DemoScene {
+        &DEMO_SCENES[self.0]
+    }
+}
+
+impl FromStr for SceneId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let by_number = s
+            .parse::<usize>()
+            .ok()
+            .filter(|number| (1..=DEMO_SCENES.len()).contains(number))
+            .map(|number| number - 1);
+        let by_name = || DEMO_SCENES.iter().position(|scene| scene.name == s);
+
+        by_number
+            .or_else(by_name)
+            .map(SceneId)
+            .ok_or_else(|| format!("unknown scene '{}', type 'scenes' for a list", s))
+    }
+}
+
+fn ground_plane() -> Matrix4<f32> {
+    Matrix4::from_translation([0.0, -0.5, -10.0].into())
+        * Matrix4::from_angle_x(Deg(-90.0))
+        * Matrix4::from_scale(25.0)
+}
+
+/// Tipped towards the camera so three faces of the cube show while it turns
+fn tilted() -> Matrix4<f32> {
+    Matrix4::from_angle_x(Deg(-30.0)) * Matrix4::from_angle_y(Deg(30.0))
+}
+
+/// A landmark that doesn't hide the world around it
+fn small() -> Matrix4<f32> {
+    Matrix4::from_scale(0.25)
+}
+
+fn cube() -> (Vec<Vertex>, Vec<u32>) {
+    primitives::cube(1.0)
+}
+
+fn torus() -> (Vec<Vertex>, Vec<u32>) {
+    primitives::torus(0.5, 0.2, 48, 24)
+}
+
+fn quad() -> (Vec<Vertex>, Vec<u32>) {
+    (vertecies().to_vec(), indices().to_vec())
+}
+
+fn color_grid() -> (Vec<Vertex>, Vec<u32>) {
+    const CELLS: u32 = 16;
+    const SIZE: f32 = 1.6;
+
+    let mut vertices = Vec::new();
+    for y in 0..=CELLS {
+        for x in 0..=CELLS {
+            let u = x as f32 / CELLS as f32;
+            let v = y as f32 / CELLS as f32;
+            vertices.push(Vertex::new(
+                [(u - 0.5) * SIZE, (v - 0.5) * SIZE, 0.0],
+                [u, v, 1.0 - u],
+                [u, v],
+            ));
+        }
+    }
+
+    let mut indices = Vec::new();
+    for y in 0..CELLS {
+        for x in 0..CELLS {
+            let top_left = y * (CELLS + 1) + x;
+            let bottom_left = top_left + CELLS + 1;
+            indices.extend_from_slice(&[
+                top_left,
+                top_left + 1,
+                bottom_left + 1,
+                bottom_left + 1,
+                bottom_left,
+                top_left,
+            ]);
+        }
+    }
+
+    (vertices, indices)
+}
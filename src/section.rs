@@ -0,0 +1,102 @@
+use std::{fmt, str::FromStr};
+
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3};
+
+use crate::ray::Plane;
+
+/// How far one key press moves the section plane along its normal
+pub const SECTION_STEP: f32 = 0.05;
+
+/// A plane that cuts the scene open to look inside, everything behind it (against the normal)
+/// is discarded. Mirrors the `section` push constant.
+#[derive(Copy, Clone, Debug)]
+pub struct Section {
+    pub plane: Plane,
+    /// Fill the cut with a flat color instead of showing the insides
+    pub cap: bool,
+}
+
+impl Section {
+    pub fn new(normal: Vector3<f32>, distance: f32) -> Self {
+        Self {
+            plane: Plane {
+                normal: normal.normalize(),
+                distance,
+            },
+            cap: true,
+        }
+    }
+
+    pub fn translate(&mut self, offset: f32) {
+        self.plane.distance += offset;
+    }
+
+    pub fn flip(&mut self) {
+        self.plane.normal = -self.plane.normal;
+        self.plane.distance = -self.plane.distance;
+    }
+
+    /// The plane as `(normal, -distance)` in the space of a mesh drawn with `model`, so the
+    /// vertex shader gets the signed distance with one dot product
+    pub fn object_space_plane(&self, model: Matrix4<f32>) -> [f32; 4] {
+        let world = self.plane.normal.extend(-self.plane.distance);
+        (model.transpose() * world).into()
+    }
+
+    /// Value of the `section` push constant
+    pub fn mode(section: Option<&Section>) -> u32 {
+        match section {
+            None => 0,
+            Some(section) if !section.cap => 1,
+            Some(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let normal = self.plane.normal;
+        write!(
+            f,
+            "normal ({:.2}, {:.2}, {:.2}), distance {:.2}, cap {}",
+            normal.x,
+            normal.y,
+            normal.z,
+            self.plane.distance,
+            if self.cap { "on" } else { "off" }
+        )
+    }
+}
+
+/// Normal of a section plane, either an axis name or three numbers
+#[derive(Copy, Clone, Debug)]
+pub struct SectionNormal(pub Vector3<f32>);
+
+impl FromStr for SectionNormal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normal = match s {
+            "x" => Vector3::unit_x(),
+            "y" => Vector3::unit_y(),
+            "z" => Vector3::unit_z(),
+            _ => {
+                let values = s
+                    .split(',')
+                    .map(|value| value.trim().parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| format!("expected x, y, z or nx,ny,nz, found '{}'", s))?;
+                match values.as_slice() {
+                    [x, y, z] => Vector3::new(*x, *y, *z),
+                    _ => return Err(format!("expected x, y, z or nx,ny,nz, found '{}'", s)),
+                }
+            }
+        };
+
+        if normal.magnitude2() == 0.0 {
+            return Err("the section normal can't be zero".to_string());
+        }
+
+        Ok(SectionNormal(normal))
+    }
+}
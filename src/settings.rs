@@ -0,0 +1,464 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
+};
+
+use cgmath::Deg;
+use vulkano::{image::ImageUsage, instance::debug::MessageType, swapchain::PresentMode};
+
+use crate::{
+    fullscreen::FullscreenMode,
+    pipeline_cache,
+    swapchain::{parse_image_usage, parse_present_mode},
+    validation::parse_message_types,
+};
+
+/// File read when `--settings` is not given, it is fine for it not to exist
+pub const DEFAULT_PATH: &str = "settings.cfg";
+/// How often the settings file's modification time is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Share of the window's width and height the scene is drawn at by the low power preset
+const LOW_POWER_RENDER_SCALE: f32 = 0.5;
+
+/// How often something happens per second, or as often as the monitor refreshes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rate {
+    Refresh,
+    Hz(u32),
+}
+
+impl Rate {
+    /// In Hz, on a monitor refreshing at `refresh_rate`
+    pub fn hz(self, refresh_rate: u32) -> u32 {
+        match self {
+            Rate::Refresh => refresh_rate,
+            Rate::Hz(hz) => hz,
+        }
+    }
+}
+
+/// Values read from the settings file, one `key = value` per line, `#` starts a comment.
+/// Keys missing from the file keep their defaults.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Inner size of the window when it opens, in logical pixels
+    pub window_size: [u32; 2],
+    /// Toggled with Alt+Enter while running, which goes back to this mode
+    pub fullscreen: FullscreenMode,
+    /// Index or part of the name of the monitor to go fullscreen on, `None` for the window's
+    pub monitor: Option<String>,
+    pub clear_color: [f32; 3],
+    /// Waits for vertical blank when presenting, applied by recreating the swap chain
+    pub vsync: bool,
+    /// Used in place of the mode `vsync` picks whenever the surface supports it
+    pub present_mode: Option<PresentMode>,
+    /// Samples per pixel, lowered to what the device supports, applied with the swap chain
+    pub msaa: u32,
+    /// Share of the window's width and height the scene is drawn at before the post pass scales
+    /// it up, applied with the swap chain
+    pub render_scale: f32,
+    /// Usages the swap chain images need besides being rendered to and copied for screenshots,
+    /// like `storage` for compute output
+    pub swapchain_usage: ImageUsage,
+    pub fov_y: Deg<f32>,
+    pub near: f32,
+    pub far: f32,
+    pub sky: bool,
+    pub day_length: f32,
+    pub turbidity: f32,
+    /// Display calibration, applied by the post pass
+    pub gamma: f32,
+    pub brightness: f32,
+    pub contrast: f32,
+    /// Validation layers can only be chosen when the Vulkan instance is created
+    pub validation: bool,
+    /// Types of validation layer messages logged, also only read at startup
+    pub validation_messages: MessageType,
+    /// Index or part of the name of the GPU to use, `None` picks the best scoring one
+    pub gpu: Option<String>,
+    /// Prefers the integrated GPU at startup and redraws only when something happens or the
+    /// idle interval passed. The `low_power` key also sets the preset's other values.
+    pub low_power: bool,
+    /// Most frames drawn per second, `None` draws as fast as presentation allows
+    pub fps_cap: Option<Rate>,
+    /// Fixed steps per second the particle simulation moves in
+    pub simulation_rate: Rate,
+    /// Where compiled pipelines are kept between runs, `None` compiles them every run
+    pub pipeline_cache: Option<PathBuf>,
+    /// Draws the scene into a G-buffer and lights it in a second subpass, only read at startup
+    pub deferred: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_size: [1024, 768],
+            fullscreen: FullscreenMode::Windowed,
+            monitor: None,
+            clear_color: [0.0, 0.0, 0.0],
+            vsync: false,
+            present_mode: None,
+            msaa: 1,
+            render_scale: 1.0,
+            swapchain_usage: ImageUsage::none(),
+            fov_y: Deg(45.0),
+            near: 0.1,
+            far: 100.0,
+            sky: false,
+            day_length: 120.0,
+            turbidity: 2.5,
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+            validation: cfg!(debug_assertions),
+            validation_messages: MessageType::all(),
+            gpu: None,
+            low_power: false,
+            fps_cap: Some(Rate::Refresh),
+            simulation_rate: Rate::Refresh,
+            pipeline_cache: Some(PathBuf::from(pipeline_cache::DEFAULT_PATH)),
+            deferred: false,
+        }
+    }
+}
+
+/// Keys that are only read at startup
+pub const RESTART_KEYS: &[&str] =
+    &["window_size", "validation", "validation_messages", "gpu", "pipeline_cache", "deferred"];
+
+impl Settings {
+    /// Keys whose values differ between `self` and `other`
+    pub fn changed_keys(&self, other: &Settings) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        let mut compare = |key, differs: bool| {
+            if differs {
+                changed.push(key);
+            }
+        };
+
+        compare("window_size", self.window_size != other.window_size);
+        compare("fullscreen", self.fullscreen != other.fullscreen);
+        compare("monitor", self.monitor != other.monitor);
+        compare("clear_color", self.clear_color != other.clear_color);
+        compare("vsync", self.vsync != other.vsync);
+        compare("present_mode", self.present_mode != other.present_mode);
+        compare("msaa", self.msaa != other.msaa);
+        compare("render_scale", self.render_scale != other.render_scale);
+        compare("swapchain_usage", self.swapchain_usage != other.swapchain_usage);
+        compare("fov", self.fov_y != other.fov_y);
+        compare("near", self.near != other.near);
+        compare("far", self.far != other.far);
+        compare("sky", self.sky != other.sky);
+        compare("day_length", self.day_length != other.day_length);
+        compare("turbidity", self.turbidity != other.turbidity);
+        compare("gamma", self.gamma != other.gamma);
+        compare("brightness", self.brightness != other.brightness);
+        compare("contrast", self.contrast != other.contrast);
+        compare("validation", self.validation != other.validation);
+        compare("validation_messages", self.validation_messages != other.validation_messages);
+        compare("gpu", self.gpu != other.gpu);
+        compare("low_power", self.low_power != other.low_power);
+        compare("fps_cap", self.fps_cap != other.fps_cap);
+        compare("simulation_rate", self.simulation_rate != other.simulation_rate);
+        compare("pipeline_cache", self.pipeline_cache != other.pipeline_cache);
+        compare("deferred", self.deferred != other.deferred);
+        changed
+    }
+
+    /// Takes the values of the `RESTART_KEYS` from `running`, the settings the app started with,
+    /// so a reloaded file can't change what was only read at startup
+    pub fn keep_restart_values(&mut self, running: &Settings) {
+        self.window_size = running.window_size;
+        self.validation = running.validation;
+        self.validation_messages = running.validation_messages;
+        self.gpu = running.gpu.clone();
+        self.pipeline_cache = running.pipeline_cache.clone();
+        self.deferred = running.deferred;
+    }
+
+    /// Sets the setting `key` from its text in the settings file
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "window_size" => parse_window_size(value).map(|size| self.window_size = size),
+            "fullscreen" => value.parse().map(|mode| self.fullscreen = mode),
+            "monitor" => {
+                self.monitor = Some(value.to_string()).filter(|monitor| monitor != "auto");
+                Ok(())
+            }
+            "clear_color" => parse_color(value).map(|color| self.clear_color = color),
+            "vsync" => parse_bool(value).map(|vsync| self.vsync = vsync),
+            "present_mode" => parse_present_mode(value).map(|mode| self.present_mode = mode),
+            "msaa" => parse_sample_count(value).map(|samples| self.msaa = samples),
+            "render_scale" => parse_render_scale(value).map(|scale| self.render_scale = scale),
+            "swapchain_usage" => parse_image_usage(value).map(|usage| self.swapchain_usage = usage),
+            "fov" => parse_number(value).map(|fov_y| self.fov_y = Deg(fov_y)),
+            "near" => parse_number(value).map(|near| self.near = near),
+            "far" => parse_number(value).map(|far| self.far = far),
+            "sky" => parse_bool(value).map(|sky| self.sky = sky),
+            "day_length" => parse_number(value).map(|day_length| self.day_length = day_length),
+            "turbidity" => parse_number(value).map(|turbidity| self.turbidity = turbidity),
+            "gamma" => parse_positive(value).map(|gamma| self.gamma = gamma),
+            "brightness" => parse_number(value).map(|brightness| self.brightness = brightness),
+            "contrast" => parse_number(value).map(|contrast| self.contrast = contrast),
+            "validation" => parse_bool(value).map(|validation| self.validation = validation),
+            "validation_messages" => parse_message_types(value).map(|types| self.validation_messages = types),
+            "gpu" => {
+                self.gpu = Some(value.to_string()).filter(|gpu| gpu != "auto");
+                Ok(())
+            }
+            "low_power" => parse_bool(value).map(|low_power| {
+                if low_power {
+                    self.use_low_power_preset();
+                }
+                self.low_power = low_power;
+            }),
+            "fps_cap" => match value {
+                "off" => {
+                    self.fps_cap = None;
+                    Ok(())
+                }
+                value => parse_rate(value).map(|rate| self.fps_cap = Some(rate)),
+            },
+            "simulation_rate" => parse_rate(value).map(|rate| self.simulation_rate = rate),
+            "pipeline_cache" => {
+                self.pipeline_cache = Some(PathBuf::from(value)).filter(|_| value != "off");
+                Ok(())
+            }
+            "deferred" => parse_bool(value).map(|deferred| self.deferred = deferred),
+            other => Err(format!("unknown setting '{}'", other)),
+        }
+    }
+
+    /// Settings for laptops on battery: FIFO presentation, no MSAA and the scene drawn at a
+    /// lower resolution. Keys after `low_power` in the file, or given on the command line after
+    /// `--low-power`, still change them.
+    fn use_low_power_preset(&mut self) {
+        self.vsync = true;
+        self.present_mode = Some(PresentMode::Fifo);
+        self.msaa = 1;
+        self.render_scale = LOW_POWER_RENDER_SCALE;
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        other => Err(format!("expected on or off, got '{}'", other)),
+    }
+}
+
+/// A finite number, `nan` and `inf` parse as floats but mean nothing here
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|number: &f32| number.is_finite())
+        .ok_or_else(|| format!("expected a number, got '{}'", value))
+}
+
+fn parse_positive(value: &str) -> Result<f32, String> {
+    match parse_number(value)? {
+        number if number > 0.0 => Ok(number),
+        _ => Err(format!("expected a number above 0, got '{}'", value)),
+    }
+}
+
+fn parse_sample_count(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(samples) if samples.is_power_of_two() && samples <= 64 => Ok(samples),
+        _ => Err(format!("expected 1, 2, 4, 8, 16, 32 or 64, got '{}'", value)),
+    }
+}
+
+fn parse_render_scale(value: &str) -> Result<f32, String> {
+    match parse_number(value)? {
+        scale if (0.25..=1.0).contains(&scale) => Ok(scale),
+        _ => Err(format!("expected a number from 0.25 to 1, got '{}'", value)),
+    }
+}
+
+/// `auto` for the monitor's refresh rate, or a rate in Hz
+fn parse_rate(value: &str) -> Result<Rate, String> {
+    match value {
+        "auto" => Ok(Rate::Refresh),
+        value => match value.parse::<u32>() {
+            Ok(hz) if hz > 0 => Ok(Rate::Hz(hz)),
+            _ => Err(format!("expected auto or a rate in Hz, got '{}'", value)),
+        },
+    }
+}
+
+/// `1280x720` or `1280 720`
+fn parse_window_size(value: &str) -> Result<[u32; 2], String> {
+    let mut numbers = value.split(|c: char| c == 'x' || c.is_whitespace()).filter(|part| !part.is_empty());
+    match (numbers.next().map(str::parse), numbers.next().map(str::parse), numbers.next()) {
+        (Some(Ok(width)), Some(Ok(height)), None) if width > 0 && height > 0 => Ok([width, height]),
+        _ => Err(format!("expected a size like 1280x720, got '{}'", value)),
+    }
+}
+
+fn parse_color(value: &str) -> Result<[f32; 3], String> {
+    let channels = value.split_whitespace().map(parse_number).collect::<Result<Vec<_>, _>>()?;
+    match channels.as_slice() {
+        &[r, g, b] => Ok([r, g, b]),
+        _ => Err("expected three numbers: red green blue".to_string()),
+    }
+}
+
+impl FromStr for Settings {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut settings = Settings::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(format!("line {}: expected 'key = value'", number + 1)),
+            };
+
+            settings.set(key, value).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+
+        Ok(settings)
+    }
+}
+
+/// A settings file that is read again whenever it changes on disk. Settings given on the command
+/// line are applied over every read.
+pub struct SettingsFile {
+    pub path: PathBuf,
+    /// `(key, value)` pairs that win over the file, already checked by `Settings::set`
+    overrides: Vec<(String, String)>,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl SettingsFile {
+    pub fn new(path: &Path, overrides: Vec<(String, String)>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            overrides,
+            modified: None,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Reads the file, `Ok(None)` if it doesn't exist and nothing was given on the command line
+    pub fn load(&mut self) -> Result<Option<Settings>, String> {
+        self.last_check = Instant::now();
+        self.modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+
+        let settings = match fs::read_to_string(&self.path) {
+            Ok(text) => text.parse().map_err(|e| format!("{}: {}", self.path.display(), e))?,
+            Err(_) if !self.path.exists() && self.overrides.is_empty() => return Ok(None),
+            Err(_) if !self.path.exists() => Settings::default(),
+            Err(e) => return Err(format!("{}: {}", self.path.display(), e)),
+        };
+        self.apply_overrides(settings).map(Some)
+    }
+
+    /// Whether `key` was given on the command line
+    pub fn is_overridden(&self, key: &str) -> bool {
+        self.overrides.iter().any(|(overridden, _)| overridden == key)
+    }
+
+    fn apply_overrides(&self, mut settings: Settings) -> Result<Settings, String> {
+        for (key, value) in &self.overrides {
+            settings.set(key, value).map_err(|e| format!("{} from the command line: {}", key, e))?;
+        }
+        Ok(settings)
+    }
+
+    /// Reads the file again if its modification time changed since the last read, checking at
+    /// most every `POLL_INTERVAL`
+    pub fn poll(&mut self) -> Option<Result<Option<Settings>, String>> {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+
+        self.last_check = Instant::now();
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        if modified == self.modified {
+            return None;
+        }
+
+        Some(self.load())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_and_missing_keys() {
+        let settings: Settings = "# comment\n\nwindow_size = 1280x720 # inner\nsky = on\nfps_cap = off\n"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            settings,
+            Settings {
+                window_size: [1280, 720],
+                sky: true,
+                fps_cap: None,
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_keys() {
+        assert_eq!("zoom = 2".parse::<Settings>(), Err("line 1: unknown setting 'zoom'".to_string()));
+        assert!("sky = on\nSky = on".parse::<Settings>().unwrap_err().starts_with("line 2:"));
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!("vsync".parse::<Settings>().is_err());
+        assert!("vsync = maybe".parse::<Settings>().is_err());
+        assert!("window_size = 800".parse::<Settings>().is_err());
+        assert!("window_size = 0x600".parse::<Settings>().is_err());
+        assert!("msaa = 3".parse::<Settings>().is_err());
+        assert!("render_scale = 2".parse::<Settings>().is_err());
+        assert!("gamma = 0".parse::<Settings>().is_err());
+        assert!("fov = nan".parse::<Settings>().is_err());
+        assert!("clear_color = 1 0".parse::<Settings>().is_err());
+        assert!("fps_cap = 0".parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn low_power_preset() {
+        let settings: Settings = "low_power = on\nmsaa = 4".parse().unwrap();
+        assert!(settings.low_power && settings.vsync);
+        assert_eq!(settings.render_scale, LOW_POWER_RENDER_SCALE);
+        // keys after low_power still win
+        assert_eq!(settings.msaa, 4);
+    }
+
+    #[test]
+    fn changed_keys_report_restart_keys() {
+        let running = Settings::default();
+        let reloaded: Settings = "deferred = on\ngpu = 1\nsky = on".parse().unwrap();
+        let changed = running.changed_keys(&reloaded);
+        assert_eq!(changed, ["sky", "gpu", "deferred"]);
+        let restart: Vec<_> = changed.into_iter().filter(|key| RESTART_KEYS.contains(key)).collect();
+        assert_eq!(restart, ["gpu", "deferred"]);
+    }
+
+    #[test]
+    fn restart_values_are_kept() {
+        let running = Settings::default();
+        let mut reloaded: Settings = "deferred = on\npipeline_cache = off\nsky = on".parse().unwrap();
+        reloaded.keep_restart_values(&running);
+        assert_eq!(running.changed_keys(&reloaded), ["sky"]);
+    }
+}
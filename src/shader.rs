@@ -0,0 +1,89 @@
+use std::{fmt, fs, path::Path, sync::Arc};
+
+use vulkano::{device::Device, pipeline::shader::ShaderModule};
+
+/// First word of every SPIR-V module
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Shader stages that can be loaded at runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    /// From the extensions the shaders in `src/shaders` use
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "vert" => Some(ShaderStage::Vertex),
+            "frag" => Some(ShaderStage::Fragment),
+            "comp" => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> shaderc::ShaderKind {
+        match self {
+            ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+            ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+            ShaderStage::Compute => shaderc::ShaderKind::Compute,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Compute => "compute",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Loads a shader module from a file at runtime, in place of one compiled into the binary by
+/// `vulkano_shaders::shader!`. Files ending in `.spv` are taken as SPIR-V, anything else is
+/// compiled from GLSL as `stage` with shaderc.
+pub fn load(device: &Arc<Device>, path: &Path, stage: ShaderStage) -> Result<Arc<ShaderModule>, String> {
+    let words = if path.extension().is_some_and(|extension| extension == "spv") {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        spirv_words(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?
+    } else {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        compile_glsl(&source, &name, stage)?
+    };
+
+    unsafe { ShaderModule::from_words(device.clone(), &words) }
+        .map_err(|e| format!("failed to create the shader module: {}", e))
+}
+
+/// Compiles GLSL `source` to SPIR-V, `name` is what errors refer to the source as
+pub fn compile_glsl(source: &str, name: &str, stage: ShaderStage) -> Result<Vec<u32>, String> {
+    let mut compiler = shaderc::Compiler::new().ok_or("failed to initialize shaderc")?;
+    let artifact = compiler
+        .compile_into_spirv(source, stage.kind(), name, "main", None)
+        .map_err(|e| e.to_string())?;
+    Ok(artifact.as_binary().to_vec())
+}
+
+/// The words of a SPIR-V binary, in the byte order its magic number was written in
+fn spirv_words(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    if !bytes.len().is_multiple_of(4) || bytes.len() < 4 {
+        return Err("not a SPIR-V module, the size is not a whole number of words".to_string());
+    }
+
+    let words: Vec<_> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    match words[0] {
+        SPIRV_MAGIC => Ok(words),
+        magic if magic.swap_bytes() == SPIRV_MAGIC => Ok(words.into_iter().map(u32::swap_bytes).collect()),
+        _ => Err("not a SPIR-V module, the magic number is missing".to_string()),
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use vulkano::{
+    device::Device,
+    pipeline::shader::{EntryPointAbstract, GraphicsEntryPoint, ShaderModule},
+};
+
+use crate::shader::{self, ShaderStage};
+
+/// Where the shaders are compiled from at build time, watched when it exists
+pub const SHADER_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+/// How often the shaders' modification times are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls the shader sources for changes, like `SettingsFile` polls the settings
+pub struct ShaderWatcher {
+    directory: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_check: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new(directory: &Path) -> Self {
+        let mut watcher = Self {
+            directory: directory.to_path_buf(),
+            modified: HashMap::new(),
+            last_check: Instant::now(),
+        };
+        watcher.modified = watcher.scan();
+        watcher
+    }
+
+    /// Vertex and fragment shaders saved since the last call, checking at most every
+    /// `POLL_INTERVAL`
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+
+        self.last_check = Instant::now();
+        let modified = self.scan();
+        let mut changed: Vec<_> = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+        self.modified = modified;
+        changed
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(_) => return HashMap::new(),
+        };
+
+        entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| graphics_stage(path).is_some())
+            .filter_map(|path| {
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+                Some((path, modified))
+            })
+            .collect()
+    }
+}
+
+fn graphics_stage(path: &Path) -> Option<ShaderStage> {
+    ShaderStage::from_extension(path).filter(|stage| *stage != ShaderStage::Compute)
+}
+
+/// Shader modules compiled at runtime from edited sources or loaded with the `shader` command, by
+/// the name of the source file they replace. Pipelines built afterwards use them in place of the
+/// modules compiled into the binary.
+pub struct ReloadedShaders {
+    device: Arc<Device>,
+    modules: HashMap<String, Arc<ShaderModule>>,
+}
+
+impl ReloadedShaders {
+    pub fn new(device: &Arc<Device>) -> Self {
+        Self {
+            device: device.clone(),
+            modules: HashMap::new(),
+        }
+    }
+
+    /// Compiles `path` to SPIR-V with shaderc and keeps the module. A shader that fails to compile
+    /// leaves the last working module in use.
+    pub fn reload(&mut self, path: &Path) -> Result<(), String> {
+        let stage =
+            graphics_stage(path).ok_or_else(|| format!("{} is not a vertex or fragment shader", path.display()))?;
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+        self.replace(&file_name, path, stage)
+    }
+
+    /// Uses the shader in `path`, GLSL or SPIR-V, in place of the source file `file_name`
+    pub fn replace(&mut self, file_name: &str, path: &Path, stage: ShaderStage) -> Result<(), String> {
+        let module = shader::load(&self.device, path, stage)?;
+        self.modules.insert(file_name.to_string(), module);
+        Ok(())
+    }
+
+    /// Goes back to the shaders compiled into the binary
+    pub fn reset(&mut self) {
+        self.modules.clear();
+    }
+
+    /// `compiled` or, once `file_name` was reloaded, the same entry point in the reloaded module.
+    /// The interface and layout are taken from `compiled`, edits that change what the shader
+    /// takes in or puts out need a rebuild.
+    pub fn entry_point<'a>(&'a self, file_name: &str, compiled: GraphicsEntryPoint<'a>) -> GraphicsEntryPoint<'a> {
+        let module = match self.modules.get(file_name) {
+            Some(module) => module,
+            None => return compiled,
+        };
+
+        let name = CStr::from_bytes_with_nul(b"main\0").unwrap();
+        // the scene shaders have no specialization constants
+        unsafe {
+            module.graphics_entry_point(
+                name,
+                compiled.layout_desc().clone(),
+                &[],
+                compiled.input().clone(),
+                compiled.output().clone(),
+                compiled.ty(),
+            )
+        }
+    }
+}
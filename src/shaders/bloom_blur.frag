@@ -0,0 +1,26 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PushConstants {
+    vec2 inverseSize;
+    // (1, 0) blurs along rows, (0, 1) along columns
+    vec2 direction;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+// a 9 tap Gaussian, the taps on each side paired into linearly filtered fetches between them
+const float OFFSETS[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float WEIGHTS[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec2 uv = gl_FragCoord.xy * pc.inverseSize;
+    vec3 sum = texture(source, uv).rgb * WEIGHTS[0];
+    for (int i = 1; i < 3; i++) {
+        vec2 offset = pc.direction * OFFSETS[i] * pc.inverseSize;
+        sum += (texture(source, uv + offset).rgb + texture(source, uv - offset).rgb) * WEIGHTS[i];
+    }
+    outColor = vec4(sum, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+
+layout(push_constant) uniform PushConstants {
+    // 1 / size of the half resolution target
+    vec2 inverseSize;
+    float threshold;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // linear filtering averages the 2x2 scene pixels under each texel
+    vec3 color = texture(scene, gl_FragCoord.xy * pc.inverseSize).rgb;
+
+    // only what is brighter than the threshold glows, a soft knee below it fades the glow in
+    // instead of switching it on at one brightness
+    float brightness = max(color.r, max(color.g, color.b));
+    float knee = pc.threshold * 0.5;
+    float soft = clamp(brightness - pc.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    float contribution = max(soft, brightness - pc.threshold) / max(brightness, 1e-4);
+    outColor = vec4(color * contribution, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D bloom;
+
+layout(push_constant) uniform PushConstants {
+    // 1 / size of the scene the glow is added to
+    vec2 inverseSize;
+    float intensity;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // drawn at half resolution and filtered up, added to the scene by blending
+    outColor = vec4(texture(bloom, gl_FragCoord.xy * pc.inverseSize).rgb * pc.intensity, 0.0);
+}
@@ -0,0 +1,15 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    uint parity;
+} pc;
+
+void main() {
+    // pixels shaded this frame are left alone, the others get the nearest depth so every draw
+    // after this fails the depth test there
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    if (uint(texel.x + texel.y) % 2 == pc.parity) {
+        discard;
+    }
+}
@@ -0,0 +1,66 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D current;
+layout(set = 0, binding = 1) uniform sampler2D currentDepth;
+layout(set = 0, binding = 2) uniform sampler2D history;
+
+layout(push_constant) uniform PushConstants {
+    // from this frame's normalized device coordinates to last frame's clip space
+    mat4 reprojection;
+    uint parity;
+    uint history_valid;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+const ivec2 NEIGHBOURS[4] = ivec2[](ivec2(-1, 0), ivec2(1, 0), ivec2(0, -1), ivec2(0, 1));
+
+void main() {
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    ivec2 size = textureSize(current, 0);
+
+    if (uint(texel.x + texel.y) % 2 == pc.parity) {
+        outColor = texelFetch(current, texel, 0);
+        gl_FragDepth = texelFetch(currentDepth, texel, 0).r;
+        return;
+    }
+
+    // the four direct neighbours were shaded this frame, at the edges the opposite one stands in
+    vec4 low = vec4(1e9);
+    vec4 high = vec4(-1e9);
+    vec4 sum = vec4(0.0);
+    float depth = 1.0;
+    for (int i = 0; i < 4; i++) {
+        ivec2 neighbour = texel + NEIGHBOURS[i];
+        if (any(lessThan(neighbour, ivec2(0))) || any(greaterThanEqual(neighbour, size))) {
+            neighbour = texel - NEIGHBOURS[i];
+        }
+        vec4 color = texelFetch(current, neighbour, 0);
+        low = min(low, color);
+        high = max(high, color);
+        sum += color;
+        // the nearest surface, so edges of objects in front are followed
+        depth = min(depth, texelFetch(currentDepth, neighbour, 0).r);
+    }
+    vec4 average = sum / 4.0;
+    gl_FragDepth = depth;
+
+    if (pc.history_valid == 0) {
+        outColor = average;
+        return;
+    }
+
+    // where this pixel was last frame, the motion vector comes from the camera's movement
+    vec2 ndc = (vec2(texel) + 0.5) / vec2(size) * 2.0 - 1.0;
+    vec4 previous = pc.reprojection * vec4(ndc, depth, 1.0);
+    vec2 previousUv = previous.xy / previous.w * 0.5 + 0.5;
+    if (previous.w <= 0.0 || any(lessThan(previousUv, vec2(0.0))) || any(greaterThan(previousUv, vec2(1.0)))) {
+        outColor = average;
+        return;
+    }
+
+    // things that moved on their own land somewhere else in the history, keeping the result
+    // within the range of the neighbours hides most of it
+    outColor = clamp(texture(history, previousUv), low, high);
+}
@@ -0,0 +1,29 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// written by the post pass in the previous subpass, only this pixel can be read
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput calibrated;
+
+layout(push_constant) uniform PushConstants {
+    vec2 inverseOutputSize;
+    float saturation;
+    float temperature;
+    float vignette;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 color = subpassLoad(calibrated).rgb;
+
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(vec3(luminance), color, pc.saturation);
+    // warmer towards red, cooler towards blue
+    color *= vec3(1.0 + pc.temperature, 1.0, 1.0 - pc.temperature);
+
+    // darkens towards the corners, where the squared distance from the center is 0.5
+    vec2 centered = gl_FragCoord.xy * pc.inverseOutputSize - 0.5;
+    color *= 1.0 - pc.vignette * 2.0 * dot(centered, centered);
+
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,37 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 2) in float fragClipDistance;
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    vec4 clip_plane;
+    vec2 depth_range;
+    float time;
+    uint animation;
+    uint frame;
+    uint section;
+    float interpolation;
+} push;
+
+void main() {
+    float near = push.depth_range.x;
+    float far = push.depth_range.y;
+
+    // undo the perspective divide to get the distance from the camera
+    float depth = gl_FragCoord.z;
+    float distance = near * far / (far - depth * (far - near));
+
+    // lines where the stored depth crosses multiples of 1/256: far away they spread out, which is
+    // where a depth buffer runs out of precision, moving the near plane closer makes it worse
+    float band = fract(depth * 256.0);
+    float line = 1.0 - smoothstep(0.0, fwidth(depth * 256.0) * 1.5, min(band, 1.0 - band));
+
+    vec3 color = vec3(1.0 - distance / far);
+    outColor = vec4(mix(color, vec3(1.0, 0.2, 0.1), line), 1.0);
+
+    // after the derivatives, which need the whole pixel quad
+    if (fragClipDistance < 0.0) {
+        discard;
+    }
+}
@@ -0,0 +1,31 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 1) in vec2 fragUv;
+layout(location = 2) in float fragClipDistance;
+layout(location = 0) out vec4 outColor;
+
+// the level is the one picked for this texture, always 0 for the white texel used without one
+layout(set = 1, binding = 0) uniform sampler2D texSampler;
+
+const vec3 LEVEL_COLORS[6] = vec3[](
+    vec3(0.0, 0.0, 1.0),
+    vec3(0.0, 1.0, 1.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(1.0, 1.0, 0.0),
+    vec3(1.0, 0.5, 0.0),
+    vec3(1.0, 0.0, 0.0)
+);
+
+void main() {
+    float level = clamp(textureQueryLod(texSampler, fragUv).x, 0.0, 5.0);
+
+    int lower = int(floor(level));
+    int upper = min(lower + 1, 5);
+    outColor = vec4(mix(LEVEL_COLORS[lower], LEVEL_COLORS[upper], fract(level)), 1.0);
+
+    // after the derivatives, which need the whole pixel quad
+    if (fragClipDistance < 0.0) {
+        discard;
+    }
+}
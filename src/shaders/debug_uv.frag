@@ -0,0 +1,19 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 1) in vec2 fragUv;
+layout(location = 2) in float fragClipDistance;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // red/green are the wrapped UV coordinates, blue is how fast they change per pixel
+    vec2 derivatives = fwidth(fragUv);
+    float rate = clamp(max(derivatives.x, derivatives.y) * 64.0, 0.0, 1.0);
+
+    outColor = vec4(fract(fragUv), rate, 1.0);
+
+    // after the derivatives, which need the whole pixel quad
+    if (fragClipDistance < 0.0) {
+        discard;
+    }
+}
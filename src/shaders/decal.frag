@@ -0,0 +1,37 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 fragClipPosition;
+layout(location = 1) flat in mat4 fragInverseMvp;
+layout(location = 0) out vec4 outColor;
+
+// depth of the scene drawn this frame
+layout(set = 0, binding = 0) uniform sampler2D depth;
+// the decal kinds side by side, with alpha
+layout(set = 0, binding = 1) uniform sampler2D atlas;
+
+layout(push_constant) uniform PushConstants {
+    mat4 mvp;
+    float opacity;
+    uint kind;
+} push;
+
+// number of tiles in the atlas
+const float KINDS = 2.0;
+
+void main() {
+    vec2 ndc = fragClipPosition.xy / fragClipPosition.w;
+    float sceneDepth = texture(depth, ndc * 0.5 + 0.5).r;
+
+    // the scene surface seen through this pixel, in the decal's unit box
+    vec4 local = fragInverseMvp * vec4(ndc, sceneDepth, 1.0);
+    vec3 position = local.xyz / local.w;
+    if (any(greaterThan(abs(position), vec3(0.5)))) {
+        discard;
+    }
+
+    // projected along the box's Z axis, which points away from the surface
+    vec2 uv = vec2((position.x + 0.5 + float(push.kind)) / KINDS, 0.5 - position.y);
+    vec4 color = texture(atlas, uv);
+    outColor = vec4(color.rgb, color.a * push.opacity);
+}
@@ -0,0 +1,39 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// clip space position, interpolated so the fragment shader knows where it is on screen
+layout(location = 0) out vec4 fragClipPosition;
+// takes clip space back into the decal's unit box, the same for every vertex
+layout(location = 1) flat out mat4 fragInverseMvp;
+
+layout(push_constant) uniform PushConstants {
+    // places the unit box, centered on the origin, over the surface
+    mat4 mvp;
+    // 1.0 until the last second of the decal's lifetime
+    float opacity;
+    // tile of the decal atlas, see decals::DecalKind
+    uint kind;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// corners of the box as bits of the index: x is bit 0, y bit 1 and z bit 2
+const int INDICES[36] = int[](
+    0, 2, 1, 1, 2, 3,
+    4, 5, 6, 5, 7, 6,
+    0, 1, 4, 1, 5, 4,
+    2, 6, 3, 3, 6, 7,
+    0, 4, 2, 2, 4, 6,
+    1, 3, 5, 3, 7, 5
+);
+
+void main() {
+    int corner = INDICES[gl_VertexIndex];
+    vec3 position = vec3(corner & 1, (corner >> 1) & 1, (corner >> 2) & 1) - 0.5;
+
+    gl_Position = push.mvp * vec4(position, 1.0);
+    fragClipPosition = gl_Position;
+    fragInverseMvp = inverse(push.mvp);
+}
@@ -0,0 +1,50 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// written by gbuffer.frag in the previous subpass, only this pixel can be read
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput albedo;
+layout(input_attachment_index = 1, set = 0, binding = 1) uniform subpassInput normal;
+layout(input_attachment_index = 2, set = 0, binding = 2) uniform subpassInput depth;
+
+layout(set = 0, binding = 3) uniform Params {
+    mat4 inverseViewProjection;
+    vec3 lightPosition;
+    float ambient;
+    vec3 lightColor;
+    float shininess;
+    vec3 cameraPosition;
+} params;
+
+layout(push_constant) uniform PushConstants {
+    vec2 inverseSize;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    float fragDepth = subpassLoad(depth).r;
+    // nothing was drawn here, the clear color stays
+    if (fragDepth >= 1.0) {
+        discard;
+    }
+
+    vec3 color = subpassLoad(albedo).rgb;
+    vec4 packedNormal = subpassLoad(normal);
+    if (packedNormal.a == 0.0) {
+        outColor = vec4(color, 1.0);
+        return;
+    }
+
+    // back from the pixel's position and depth to world space, the projection flips Y already
+    vec2 ndc = gl_FragCoord.xy * pc.inverseSize * 2.0 - 1.0;
+    vec4 world = params.inverseViewProjection * vec4(ndc, fragDepth, 1.0);
+    vec3 worldPos = world.xyz / world.w;
+
+    // the same Blinn-Phong as lit.frag
+    vec3 n = packedNormal.xyz;
+    vec3 toLight = normalize(params.lightPosition - worldPos);
+    vec3 toCamera = normalize(params.cameraPosition - worldPos);
+    float diffuse = max(dot(n, toLight), 0.0);
+    float specular = diffuse > 0.0 ? pow(max(dot(n, normalize(toLight + toCamera)), 0.0), params.shininess) : 0.0;
+    outColor = vec4(color * params.ambient + (color * diffuse + specular) * params.lightColor, 1.0);
+}
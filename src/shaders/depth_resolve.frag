@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2DMS multisampledDepth;
+
+void main() {
+    // the nearest sample, so anything covering part of the pixel counts as in front
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    float depth = 1.0;
+    for (int i = 0; i < textureSamples(multisampledDepth); i++) {
+        depth = min(depth, texelFetch(multisampledDepth, texel, i).r);
+    }
+    gl_FragDepth = depth;
+}
@@ -0,0 +1,12 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 fragOffset;
+layout(location = 1) in vec3 fragColor;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // soft disc, blended additively
+    float falloff = 1.0 - smoothstep(0.0, 1.0, length(fragOffset));
+    outColor = vec4(fragColor * falloff * falloff, 1.0);
+}
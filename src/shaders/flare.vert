@@ -0,0 +1,67 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) out vec2 fragOffset;
+layout(location = 1) out vec3 fragColor;
+
+// depth of the scene drawn this frame, 1.0 where only the sky is visible
+layout(set = 0, binding = 0) uniform sampler2D depth;
+
+layout(push_constant) uniform PushConstants {
+    // sun position in normalized device coordinates
+    vec2 sun;
+    // width / height of the screen, so sprites stay round
+    float aspect;
+    // fades the flare out at dusk and near the screen edges
+    float intensity;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+const int SPRITES = 7;
+// position along the line from the sun through the screen center, 0 is the sun and 2 its mirror image
+const float POSITIONS[SPRITES] = float[](0.0, 0.35, 0.6, 0.9, 1.2, 1.55, 2.0);
+// radius in NDC units
+const float SIZES[SPRITES] = float[](0.5, 0.05, 0.1, 0.04, 0.14, 0.07, 0.22);
+const vec3 COLORS[SPRITES] = vec3[](
+    vec3(1.0, 0.85, 0.6),
+    vec3(0.3, 0.6, 1.0),
+    vec3(0.3, 1.0, 0.5),
+    vec3(1.0, 0.5, 0.2),
+    vec3(0.5, 0.3, 1.0),
+    vec3(1.0, 1.0, 0.5),
+    vec3(0.2, 0.4, 1.0)
+);
+const vec2 CORNERS[6] = vec2[](vec2(-1, -1), vec2(1, -1), vec2(1, 1), vec2(1, 1), vec2(-1, 1), vec2(-1, -1));
+
+// share of a small grid of depth samples around the sun that see the sky
+float sunVisibility() {
+    const int TAPS = 4;
+    const float SPREAD = 0.02;
+    vec2 center = push.sun * 0.5 + 0.5;
+    float visible = 0.0;
+
+    for (int y = 0; y < TAPS; y++) {
+        for (int x = 0; x < TAPS; x++) {
+            vec2 offset = (vec2(x, y) / float(TAPS - 1) - 0.5) * SPREAD * vec2(1.0, push.aspect);
+            vec2 uv = center + offset;
+            bool onScreen = all(greaterThanEqual(uv, vec2(0.0))) && all(lessThanEqual(uv, vec2(1.0)));
+            visible += onScreen && textureLod(depth, uv, 0.0).r >= 1.0 ? 1.0 : 0.0;
+        }
+    }
+
+    return visible / float(TAPS * TAPS);
+}
+
+void main() {
+    int sprite = gl_VertexIndex / 6;
+    vec2 corner = CORNERS[gl_VertexIndex % 6];
+
+    vec2 center = mix(push.sun, -push.sun, POSITIONS[sprite] * 0.5);
+    gl_Position = vec4(center + corner * SIZES[sprite] * vec2(1.0, push.aspect), 0.0, 1.0);
+    fragOffset = corner;
+    // the glare around the sun is brighter than the ghosts
+    fragColor = COLORS[sprite] * push.intensity * sunVisibility() * (sprite == 0 ? 0.6 : 0.25);
+}
@@ -0,0 +1,42 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragUv;
+layout(location = 2) in float fragClipDistance;
+layout(location = 3) in vec3 fragWorldPos;
+layout(location = 4) in vec3 fragNormal;
+// the attachments of the G-buffer, read back by deferred_light.frag in the next subpass
+layout(location = 0) out vec4 outAlbedo;
+// alpha 0 leaves the pixel unlit, for the cap of a cross section
+layout(location = 1) out vec4 outNormal;
+
+// the texture loaded with the `texture` command, a white texel otherwise
+layout(set = 1, binding = 0) uniform sampler2D texSampler;
+
+layout(push_constant) uniform PushConstants {
+    vec4 clip_plane;
+    vec2 depth_range;
+    float time;
+    uint animation;
+    uint frame;
+    uint section;
+    float interpolation;
+} push;
+
+const vec3 CAP_COLOR = vec3(0.9, 0.35, 0.2);
+
+void main() {
+    if (fragClipDistance < 0.0) {
+        discard;
+    }
+    if (push.section == 2 && !gl_FrontFacing) {
+        outAlbedo = vec4(CAP_COLOR, 1.0);
+        outNormal = vec4(0.0);
+        return;
+    }
+
+    outAlbedo = vec4(fragColor * texture(texSampler, fragUv).rgb, 1.0);
+    // interpolation shortens the normals, and back faces of double sided meshes face away
+    outNormal = vec4(normalize(fragNormal) * (gl_FrontFacing ? 1.0 : -1.0), 1.0);
+}
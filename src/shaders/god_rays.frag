@@ -0,0 +1,39 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+// 1.0 where only the sky is visible
+layout(set = 0, binding = 1) uniform sampler2D depth;
+
+layout(push_constant) uniform PushConstants {
+    // sun position in texture coordinates, outside 0..1 when it is off screen
+    vec2 sun;
+    // 1 / size of the image the rays are drawn into
+    vec2 inverseSize;
+    // share of the way towards the sun the samples cover
+    float density;
+    // how much each sample counts less than the one before it
+    float decay;
+    float intensity;
+    uint steps;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+// blurs the visible sky along the line towards the sun, so it streaks past whatever covers it
+void main() {
+    vec2 uv = gl_FragCoord.xy * pc.inverseSize;
+    vec2 step = (pc.sun - uv) * pc.density / float(pc.steps);
+    float weight = 1.0;
+    vec3 sum = vec3(0.0);
+
+    for (uint i = 0; i < pc.steps; i++) {
+        uv += step;
+        if (texture(depth, uv).r >= 1.0) {
+            sum += texture(scene, uv).rgb * weight;
+        }
+        weight *= pc.decay;
+    }
+
+    outColor = vec4(sum * pc.intensity / float(pc.steps), 1.0);
+}
@@ -0,0 +1,16 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D rays;
+
+layout(push_constant) uniform PushConstants {
+    // 1 / size of the scene the rays are added to
+    vec2 inverseSize;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // the rays are drawn at half resolution and filtered up, added to the scene by blending
+    outColor = vec4(texture(rays, gl_FragCoord.xy * pc.inverseSize).rgb, 0.0);
+}
@@ -0,0 +1,18 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in float fragFade;
+layout(location = 0) out vec4 outColor;
+
+// thresholds of a 4x4 ordered dither, the blades fade by dropping more and more of their pixels
+// so they don't need blending and sorting
+const float BAYER[16] = float[](0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+
+void main() {
+    ivec2 cell = ivec2(gl_FragCoord.xy) % 4;
+    if (fragFade * 16.0 <= BAYER[cell.y * 4 + cell.x] + 0.5) {
+        discard;
+    }
+    outColor = vec4(fragColor, 1.0);
+}
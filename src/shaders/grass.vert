@@ -0,0 +1,56 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// per vertex, from the blade mesh with its root at the origin and its tip at y = 1
+layout(location = 0) in vec3 pos;
+layout(location = 1) in vec3 color;
+
+// per instance, see world::GrassBlade
+layout(location = 2) in vec3 root;
+layout(location = 3) in float height;
+layout(location = 4) in vec3 tint;
+layout(location = 5) in float phase;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out float fragFade;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 sun_direction;
+    vec3 camera_position;
+    float time;
+    // distances where the blades start to fade out and are gone
+    vec2 fade;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// the same as the terrain's, so the blades are as bright as the ground under them
+const float AMBIENT = 0.35;
+const vec2 WIND_DIRECTION = vec2(0.928, 0.371);
+// how far the tip of a blade one unit high leans with the wind
+const float WIND_STRENGTH = 0.35;
+// radians per second
+const float WIND_SPEED = 1.7;
+// gusts travel over the ground in waves this many units apart
+const float GUST_LENGTH = 6.0;
+
+void main() {
+    // turned around the root so neighbouring blades don't line up
+    float c = cos(phase);
+    float s = sin(phase);
+    vec3 position = root + vec3(c * pos.x - s * pos.z, pos.y * height, s * pos.x + c * pos.z);
+
+    // the root stays put and the tip bends the furthest, a little out of step from blade to blade
+    float wave = sin(push.time * WIND_SPEED - dot(root.xz, WIND_DIRECTION) * 6.2831853 / GUST_LENGTH + phase * 0.3);
+    float bend = WIND_STRENGTH * height * pos.y * pos.y * (0.6 + 0.4 * wave);
+    position.xz += WIND_DIRECTION * bend;
+
+    gl_Position = push.view_projection * vec4(position, 1.0);
+    fragFade = 1.0 - smoothstep(push.fade.x, push.fade.y, distance(position, push.camera_position));
+    // lit from above, the blades face every way
+    float diffuse = max(push.sun_direction.y, 0.0);
+    fragColor = color * tint * (AMBIENT + (1.0 - AMBIENT) * diffuse);
+}
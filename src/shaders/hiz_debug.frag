@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D level;
+
+layout(push_constant) uniform PushConstants {
+    // 1 / size of the scene target the level is stretched over
+    vec2 inverseSize;
+    // near and far plane
+    vec2 depthRange;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+float brightness(float depth) {
+    // shaded like the depth debug view, by the distance from the camera
+    float near = pc.depthRange.x;
+    float far = pc.depthRange.y;
+    float distance = near * far / (far - depth * (far - near));
+    return 1.0 - distance / far;
+}
+
+void main() {
+    vec2 bounds = texture(level, gl_FragCoord.xy * pc.inverseSize).rg;
+    // gray where the texel covers one surface, tinted red by how far its nearest and farthest
+    // depths are apart, which is where the level is least tight for culling
+    float farthest = brightness(bounds.x);
+    float nearest = brightness(bounds.y);
+    outColor = vec4(nearest, farthest, farthest, 1.0);
+}
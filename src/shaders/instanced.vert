@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// per vertex, from the shared quad
+layout(location = 0) in vec3 pos;
+layout(location = 1) in vec3 color;
+
+// per instance, see instancing::InstanceData
+layout(location = 2) in vec3 offset;
+layout(location = 3) in float scale;
+layout(location = 4) in vec3 tint;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    float time;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    // every quad spins, a little out of step with the one before it
+    float angle = push.time + float(gl_InstanceIndex) * 0.05;
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+    vec3 position = vec3(rotation * pos.xy, pos.z) * scale + offset;
+
+    gl_Position = push.view_projection * vec4(position, 1.0);
+    fragColor = color * tint;
+}
@@ -0,0 +1,21 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 color;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(push_constant) uniform PushConstants {
+    // lines are built in world space
+    mat4 view_projection;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    gl_Position = push.view_projection * vec4(position, 1.0);
+    fragColor = color;
+}
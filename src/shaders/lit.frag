@@ -0,0 +1,139 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragUv;
+layout(location = 2) in float fragClipDistance;
+layout(location = 3) in vec3 fragWorldPos;
+layout(location = 4) in vec3 fragNormal;
+layout(location = 0) out vec4 outColor;
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec4 audio[2];
+    vec3 light_position;
+    float ambient;
+    vec3 light_color;
+    float shininess;
+    vec3 camera_position;
+    float emissive_intensity;
+    vec3 emissive_color;
+    uint frame;
+} ubo;
+
+// the texture loaded with the `texture` command, a white texel otherwise
+layout(set = 1, binding = 0) uniform sampler2D texSampler;
+// the `emissive texture` command's image, a white texel otherwise
+layout(set = 1, binding = 1) uniform sampler2D emissiveSampler;
+
+// the froxel grid of clusters.rs, the same as in cluster_lights.comp
+const uvec3 GRID = uvec3(16, 9, 24);
+const uint MAX_LIGHTS_PER_CLUSTER = 64;
+
+struct PointLight {
+    vec4 positionRadius;
+    vec4 color;
+};
+
+layout(set = 2, binding = 0) uniform ClusterParams {
+    mat4 inverseProjection;
+    mat4 view;
+    vec2 screenSize;
+    float near;
+    float far;
+    uint lightCount;
+    uint heatmap;
+} clusters;
+
+layout(set = 2, binding = 1) readonly buffer Lights {
+    PointLight lights[];
+};
+
+layout(set = 2, binding = 2) readonly buffer Counts {
+    uint counts[];
+};
+
+layout(set = 2, binding = 3) readonly buffer Indices {
+    uint indices[];
+};
+
+layout(push_constant) uniform PushConstants {
+    vec4 clip_plane;
+    vec2 depth_range;
+    float time;
+    uint animation;
+    uint frame;
+    uint section;
+    float interpolation;
+} push;
+
+const vec3 CAP_COLOR = vec3(0.9, 0.35, 0.2);
+
+// diffuse and Blinn-Phong specular light from the direction `toLight`
+vec3 blinnPhong(vec3 albedo, vec3 normal, vec3 toLight, vec3 toCamera, vec3 lightColor) {
+    float diffuse = max(dot(normal, toLight), 0.0);
+    // no highlight on the side facing away from the light
+    float specular = diffuse > 0.0 ? pow(max(dot(normal, normalize(toLight + toCamera)), 0.0), ubo.shininess) : 0.0;
+    return (albedo * diffuse + specular) * lightColor;
+}
+
+// black for no lights, then blue, green, yellow and red at MAX_LIGHTS_PER_CLUSTER
+vec3 heat(uint count) {
+    if (count == 0) {
+        return vec3(0.0);
+    }
+    float t = float(count) / float(MAX_LIGHTS_PER_CLUSTER);
+    vec3 cold = mix(vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 0.0), clamp(t * 3.0, 0.0, 1.0));
+    vec3 warm = mix(vec3(1.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), clamp(t * 3.0 - 2.0, 0.0, 1.0));
+    return t < 1.0 / 3.0 ? cold : t < 2.0 / 3.0 ? mix(cold, warm, t * 3.0 - 1.0) : warm;
+}
+
+// index of the froxel the fragment is in, see cluster_lights.comp
+uint clusterIndex() {
+    uvec2 tile = min(uvec2(gl_FragCoord.xy / clusters.screenSize * vec2(GRID.xy)), GRID.xy - 1);
+    float depth = -(clusters.view * vec4(fragWorldPos, 1.0)).z;
+    float slice = log(max(depth, clusters.near) / clusters.near) / log(clusters.far / clusters.near);
+    uint z = min(uint(slice * float(GRID.z)), GRID.z - 1);
+    return (z * GRID.y + tile.y) * GRID.x + tile.x;
+}
+
+void main() {
+    if (fragClipDistance < 0.0) {
+        discard;
+    }
+    if (push.section == 2 && !gl_FrontFacing) {
+        outColor = vec4(CAP_COLOR, 1.0);
+        return;
+    }
+
+    // interpolation shortens the normals, and back faces of double sided meshes face away
+    vec3 normal = normalize(fragNormal) * (gl_FrontFacing ? 1.0 : -1.0);
+    vec3 toCamera = normalize(ubo.camera_position - fragWorldPos);
+    vec3 albedo = fragColor * texture(texSampler, fragUv).rgb;
+    vec3 color = albedo * ubo.ambient
+        + blinnPhong(albedo, normal, normalize(ubo.light_position - fragWorldPos), toCamera, ubo.light_color);
+    color += ubo.emissive_color * ubo.emissive_intensity * texture(emissiveSampler, fragUv).rgb;
+
+    if (clusters.lightCount > 0) {
+        uint cluster = clusterIndex();
+        for (uint i = 0; i < counts[cluster]; i++) {
+            PointLight light = lights[indices[cluster * MAX_LIGHTS_PER_CLUSTER + i]];
+            vec3 toLight = light.positionRadius.xyz - fragWorldPos;
+            float distance = length(toLight);
+            // falls off with the square of the distance and reaches zero at the radius
+            float window = clamp(1.0 - pow(distance / light.positionRadius.w, 4.0), 0.0, 1.0);
+            float attenuation = window * window / (1.0 + 25.0 * distance * distance);
+            color += blinnPhong(albedo, normal, toLight / max(distance, 1e-4), toCamera, light.color.rgb * attenuation);
+        }
+        if (clusters.heatmap != 0) {
+            // the tile edges show where one froxel ends, the colors change along depth too
+            vec2 inTile = fract(gl_FragCoord.xy / clusters.screenSize * vec2(GRID.xy));
+            vec2 edge = min(inTile, 1.0 - inTile) * clusters.screenSize / vec2(GRID.xy);
+            float line = min(edge.x, edge.y) < 1.0 ? 0.5 : 0.0;
+            color = mix(mix(color, heat(counts[cluster]), 0.6), vec3(1.0), line);
+        }
+    }
+    outColor = vec4(color, 1.0);
+}
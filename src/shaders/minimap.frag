@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D overhead;
+
+layout(push_constant) uniform PushConstants {
+    // top left corner of the viewport in window pixels
+    vec2 origin;
+    vec2 inverseSize;
+    float gamma;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec2 uv = (gl_FragCoord.xy - pc.origin) * pc.inverseSize;
+    vec3 color = pow(max(texture(overhead, uv).rgb, 0.0), vec3(1.0 / pc.gamma));
+
+    // a thin frame separates the view from the scene behind it
+    vec2 pixels = gl_FragCoord.xy - pc.origin;
+    vec2 size = 1.0 / pc.inverseSize;
+    if (min(min(pixels.x, pixels.y), min(size.x - pixels.x, size.y - pixels.y)) < 2.0) {
+        color = vec3(0.8);
+    }
+
+    // a dot where the main camera looks, the center of the view
+    if (length(uv - 0.5) * size.x < 3.0) {
+        color = vec3(1.0, 0.2, 0.2);
+    }
+
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+layout(set = 0, binding = 1) uniform sampler2D velocity;
+
+layout(push_constant) uniform PushConstants {
+    // 1 / size of the scene
+    vec2 inverseSize;
+    // share of the motion since the previous frame the shutter was open for
+    float shutter;
+    uint samples;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    vec2 uv = gl_FragCoord.xy * pc.inverseSize;
+    vec2 motion = texelFetch(velocity, texel, 0).rg * pc.shutter;
+
+    // samples spread evenly over the motion, centered on the pixel
+    vec3 sum = vec3(0.0);
+    for (uint i = 0; i < pc.samples; i++) {
+        float t = pc.samples > 1 ? float(i) / float(pc.samples - 1) - 0.5 : 0.0;
+        sum += texture(scene, uv - motion * t).rgb;
+    }
+
+    outColor = vec4(sum / float(pc.samples), texelFetch(scene, texel, 0).a);
+}
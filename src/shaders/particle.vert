@@ -0,0 +1,41 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 position;
+layout(location = 1) in vec4 velocity;
+layout(location = 2) in vec4 previous;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    // size in pixels of a particle one unit away from the camera
+    float point_size;
+    // largest size the device supports, 1.0 without the largePoints feature
+    float max_point_size;
+    // how far the frame is from previous to position, the simulation moves in fixed steps
+    float interpolation;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+    float gl_PointSize;
+};
+
+const vec3 YOUNG_COLOR = vec3(1.0, 0.9, 0.5);
+const vec3 OLD_COLOR = vec3(0.9, 0.2, 0.1);
+
+void main() {
+    // particles waiting for their first spawn have no lifetime yet, they are clipped away
+    if (velocity.w <= 0.0) {
+        gl_Position = vec4(0.0, 0.0, 2.0, 1.0);
+        gl_PointSize = 1.0;
+        fragColor = vec3(0.0);
+        return;
+    }
+
+    vec3 drawn = mix(previous.xyz, position.xyz, push.interpolation);
+    gl_Position = push.view_projection * vec4(drawn, 1.0);
+    gl_PointSize = clamp(push.point_size / max(gl_Position.w, 0.001), 1.0, push.max_point_size);
+    fragColor = mix(OLD_COLOR, YOUNG_COLOR, clamp(position.w / velocity.w, 0.0, 1.0));
+}
@@ -0,0 +1,27 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 color;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(push_constant) uniform PushConstants {
+    mat4 mvp;
+    // size in pixels of a point one unit away from the camera
+    float point_size;
+    // largest size the device supports, 1.0 without the largePoints feature
+    float max_point_size;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+    float gl_PointSize;
+};
+
+void main() {
+    gl_Position = push.mvp * vec4(position, 1.0);
+    // w is the view space distance, so points shrink with distance like real geometry
+    gl_PointSize = clamp(push.point_size / max(gl_Position.w, 0.001), 1.0, push.max_point_size);
+    fragColor = color;
+}
@@ -0,0 +1,19 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// the scene drawn from the camera behind the surface, as large as the pass drawing it
+layout(set = 0, binding = 0) uniform sampler2D view;
+
+layout(push_constant) uniform PushConstants {
+    mat4 mvp;
+    vec2 inverseSize;
+    float reflectance;
+} push;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // the other camera sees the same pixels this one does, so the view is sampled in screen space
+    vec3 color = texture(view, gl_FragCoord.xy * push.inverseSize).rgb;
+    outColor = vec4(color * push.reflectance, 1.0);
+}
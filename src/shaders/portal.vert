@@ -0,0 +1,24 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+layout(push_constant) uniform PushConstants {
+    // places the unit quad in the XY plane, facing +Z, over the portal surface
+    mat4 mvp;
+    // size of a pixel of the pass drawing the surface, in texture coordinates
+    vec2 inverseSize;
+    // scales the color, mirrors darken what they reflect a little
+    float reflectance;
+} push;
+
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(1.0, 1.0), vec2(-1.0, 1.0), vec2(-1.0, -1.0)
+);
+
+void main() {
+    gl_Position = push.mvp * vec4(CORNERS[gl_VertexIndex], 0.0, 1.0);
+}
@@ -0,0 +1,39 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+layout(set = 0, binding = 1) uniform sampler2D blueNoise;
+
+// ordered so nothing needs padding, the tint's last component shares a 16 byte row with gamma
+layout(push_constant) uniform PushConstants {
+    vec3 tint;
+    float gamma;
+    float brightness;
+    float contrast;
+    vec2 inverseOutputSize;
+    uint dither;
+} pc;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    ivec2 texel = ivec2(gl_FragCoord.xy);
+    // the scene may be drawn at a lower resolution than the swap chain
+    vec4 color = texture(scene, gl_FragCoord.xy * pc.inverseOutputSize);
+
+    // the tint is a look applied to the scene, the calibration after it is for the display
+    vec3 tinted = color.rgb * pc.tint;
+
+    // contrast pivots around middle gray, then brightness shifts everything
+    vec3 calibrated = (tinted - 0.5) * pc.contrast + 0.5 + pc.brightness;
+    calibrated = pow(max(calibrated, 0.0), vec3(1.0 / pc.gamma));
+
+    if (pc.dither != 0) {
+        // up to half a step of the 8 bit swap chain either way, so neighbouring pixels of a
+        // smooth gradient round to different values instead of forming bands
+        float noise = texelFetch(blueNoise, texel % textureSize(blueNoise, 0), 0).r;
+        calibrated += (noise - 0.5) / 255.0;
+    }
+
+    outColor = vec4(calibrated, color.a);
+}
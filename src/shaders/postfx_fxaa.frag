@@ -0,0 +1,57 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) out vec4 outColor;
+
+// contrast below which a pixel isn't on an edge, relative to its brightest neighbour and at least
+// the absolute minimum so dark noise isn't blurred
+const float EDGE_THRESHOLD = 1.0 / 8.0;
+const float EDGE_THRESHOLD_MIN = 1.0 / 32.0;
+// keep the direction from growing without bound where the gradient is tiny
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+// furthest the blur reaches along an edge, in pixels
+const float SPAN_MAX = 8.0;
+
+// edges are found in perceived brightness, the scene is linear and may go above 1
+float luma(vec3 color) {
+    return sqrt(dot(clamp(color, 0.0, 1.0), vec3(0.299, 0.587, 0.114)));
+}
+
+void main() {
+    vec2 inverseSize = 1.0 / vec2(textureSize(source, 0));
+    vec2 uv = gl_FragCoord.xy * inverseSize;
+
+    vec4 center = texture(source, uv);
+    float lumaM = luma(center.rgb);
+    float lumaNW = luma(textureOffset(source, uv, ivec2(-1, -1)).rgb);
+    float lumaNE = luma(textureOffset(source, uv, ivec2(1, -1)).rgb);
+    float lumaSW = luma(textureOffset(source, uv, ivec2(-1, 1)).rgb);
+    float lumaSE = luma(textureOffset(source, uv, ivec2(1, 1)).rgb);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+    if (lumaMax - lumaMin < max(EDGE_THRESHOLD_MIN, lumaMax * EDGE_THRESHOLD)) {
+        outColor = center;
+        return;
+    }
+
+    // along the edge, at a right angle to the gradient between the rows and columns of corners
+    vec2 direction = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float reduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, -SPAN_MAX, SPAN_MAX) * inverseSize;
+
+    // two samples close to the pixel, and those averaged with two further out
+    vec3 near = 0.5 * (texture(source, uv + direction * (1.0 / 3.0 - 0.5)).rgb
+        + texture(source, uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 far = 0.5 * near + 0.25 * (texture(source, uv - 0.5 * direction).rgb
+        + texture(source, uv + 0.5 * direction).rgb);
+
+    // the further samples crossed another edge when they leave the neighbourhood's range
+    float lumaFar = luma(far);
+    vec3 color = (lumaFar < lumaMin || lumaFar > lumaMax) ? near : far;
+    outColor = vec4(color, center.a);
+}
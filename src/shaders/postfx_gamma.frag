@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) out vec4 outColor;
+
+// the swap chain is UNORM, the display reads its values as gamma encoded
+const float GAMMA = 2.2;
+
+void main() {
+    vec4 color = texelFetch(source, ivec2(gl_FragCoord.xy), 0);
+    outColor = vec4(pow(max(color.rgb, 0.0), vec3(1.0 / GAMMA)), color.a);
+}
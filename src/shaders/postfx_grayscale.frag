@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 color = texelFetch(source, ivec2(gl_FragCoord.xy), 0);
+    // Rec. 709 luminance, the scene is still linear here
+    float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+    outColor = vec4(vec3(luminance), color.a);
+}
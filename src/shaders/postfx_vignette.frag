@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(location = 0) out vec4 outColor;
+
+// distances from the center where the darkening starts and where it would be full, the corners
+// are at 1
+const float INNER = 0.5;
+const float OUTER = 1.2;
+// how dark it gets at full
+const float STRENGTH = 0.7;
+
+void main() {
+    vec2 size = vec2(textureSize(source, 0));
+    vec4 color = texelFetch(source, ivec2(gl_FragCoord.xy), 0);
+
+    // divided by half the diagonal instead of the size, so the vignette is round on wide screens
+    float radius = length(gl_FragCoord.xy - 0.5 * size) / (0.5 * length(size));
+    float darkening = STRENGTH * smoothstep(INNER, OUTER, radius);
+    outColor = vec4(color.rgb * (1.0 - darkening), color.a);
+}
@@ -0,0 +1,85 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 fragNdc;
+layout(location = 0) out vec4 outColor;
+
+layout(push_constant) uniform PushConstants {
+    mat4 inverse_view_projection;
+    // unit vector towards the sun, w unused
+    vec4 sun_direction;
+    // haziness of the atmosphere, 2 is a clear day and 10 is hazy
+    float turbidity;
+} push;
+
+const float PI = 3.14159265;
+// maps the sky luminance (kcd/m²) into the displayable range
+const float EXPOSURE = 0.12;
+const vec3 NIGHT_COLOR = vec3(0.01, 0.015, 0.04);
+const vec3 GROUND_COLOR = vec3(0.12, 0.11, 0.1);
+// angular radius of the drawn sun disc
+const float SUN_RADIUS = 0.0093;
+
+// Perez et al. luminance distribution, theta from the zenith and gamma from the sun
+vec3 perez(float cosTheta, float gamma, float cosGamma, vec3 A, vec3 B, vec3 C, vec3 D, vec3 E) {
+    return (1.0 + A * exp(B / max(cosTheta, 0.01))) * (1.0 + C * exp(D * gamma) + E * cosGamma * cosGamma);
+}
+
+// A Practical Analytic Model for Daylight, Preetham, Shirley and Smits 1999
+vec3 preetham(vec3 direction, vec3 sun) {
+    float T = push.turbidity;
+    vec3 A = vec3(0.1787 * T - 1.4630, -0.0193 * T - 0.2592, -0.0167 * T - 0.2608);
+    vec3 B = vec3(-0.3554 * T + 0.4275, -0.0665 * T + 0.0008, -0.0950 * T + 0.0092);
+    vec3 C = vec3(-0.0227 * T + 5.3251, -0.0004 * T + 0.2125, -0.0079 * T + 0.2102);
+    vec3 D = vec3(0.1206 * T - 2.5771, -0.0641 * T - 0.8989, -0.0441 * T - 1.6537);
+    vec3 E = vec3(-0.0670 * T + 0.3703, -0.0033 * T + 0.0452, -0.0109 * T + 0.0529);
+
+    float thetaSun = acos(clamp(sun.y, 0.0, 1.0));
+    float theta2 = thetaSun * thetaSun;
+    float theta3 = theta2 * thetaSun;
+
+    float chi = (4.0 / 9.0 - T / 120.0) * (PI - 2.0 * thetaSun);
+    float zenithY = (4.0453 * T - 4.9710) * tan(chi) - 0.2155 * T + 2.4192;
+    float zenithX = T * T * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * thetaSun)
+        + T * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * thetaSun + 0.00394)
+        + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * thetaSun + 0.25886);
+    float zenithY2 = T * T * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * thetaSun)
+        + T * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * thetaSun + 0.00516)
+        + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * thetaSun + 0.26688);
+    vec3 zenith = vec3(zenithY, zenithX, zenithY2);
+
+    float cosGamma = clamp(dot(direction, sun), -1.0, 1.0);
+    float gamma = acos(cosGamma);
+    vec3 Yxy = zenith * perez(direction.y, gamma, cosGamma, A, B, C, D, E)
+        / perez(1.0, thetaSun, sun.y, A, B, C, D, E);
+
+    // xyY to XYZ to linear sRGB
+    float Y = Yxy.x;
+    float X = Yxy.y / Yxy.z * Y;
+    float Z = (1.0 - Yxy.y - Yxy.z) / Yxy.z * Y;
+    vec3 rgb = mat3(3.2406, -0.9689, 0.0557, -1.5372, 1.8758, -0.2040, -0.4986, 0.0415, 1.0570) * vec3(X, Y, Z);
+
+    return vec3(1.0) - exp(-max(rgb, vec3(0.0)) * EXPOSURE);
+}
+
+void main() {
+    vec4 near = push.inverse_view_projection * vec4(fragNdc, 0.0, 1.0);
+    vec4 far = push.inverse_view_projection * vec4(fragNdc, 1.0, 1.0);
+    vec3 direction = normalize(far.xyz / far.w - near.xyz / near.w);
+    vec3 sun = push.sun_direction.xyz;
+
+    // the model only holds while the sun is up, below that the sky fades to night
+    float daylight = smoothstep(-0.1, 0.05, sun.y);
+    vec3 sky = direction.y > 0.0 ? preetham(direction, sun) : vec3(0.0);
+    vec3 color = mix(NIGHT_COLOR, sky, daylight);
+
+    if (dot(direction, sun) > cos(SUN_RADIUS) && sun.y > -0.02) {
+        color = vec3(1.0, 0.95, 0.85);
+    }
+    if (direction.y < 0.0) {
+        color = GROUND_COLOR * max(daylight, 0.1);
+    }
+
+    // the swap chain is not sRGB, so encode here
+    outColor = vec4(pow(color, vec3(1.0 / 2.2)), 1.0);
+}
@@ -0,0 +1,16 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// position on the far plane in normalized device coordinates, unprojected per pixel
+layout(location = 0) out vec2 fragNdc;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    // one triangle covering the whole screen, no vertex buffer needed
+    vec2 ndc = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2)) * 2.0 - 1.0;
+    gl_Position = vec4(ndc, 1.0, 1.0);
+    fragNdc = ndc;
+}
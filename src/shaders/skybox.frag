@@ -0,0 +1,11 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(set = 0, binding = 0) uniform samplerCube environment;
+
+layout(location = 0) in vec3 fragDirection;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(texture(environment, fragDirection).rgb, 1.0);
+}
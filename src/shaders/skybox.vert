@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 position;
+
+layout(push_constant) uniform PushConstants {
+    // the camera's rotation without its position, so the box stays centered on the camera
+    mat4 view_projection;
+} push;
+
+// the cube's corners double as the directions the cubemap is sampled in
+layout(location = 0) out vec3 fragDirection;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    fragDirection = position;
+    // z = w puts every pixel on the far plane, where the depth test only lets it through
+    // where nothing was drawn
+    gl_Position = (push.view_projection * vec4(position, 1.0)).xyww;
+}
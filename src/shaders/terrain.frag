@@ -0,0 +1,54 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 fragPosition;
+layout(location = 1) in vec3 fragNormal;
+layout(location = 2) in vec4 fragWeights;
+layout(location = 0) out vec4 outColor;
+
+// grass, dirt, rock and snow, in the order of the splat weights
+layout(set = 0, binding = 0) uniform sampler2DArray details;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 sun_direction;
+    float tiling;
+    uint triplanar;
+} push;
+
+// light the ground gets with the sun behind it, so the far sides of the hills aren't black
+const float AMBIENT = 0.35;
+// where the ground is steep enough to project the textures from the sides, in the normal's
+// height, fully from the sides at the first and not at all at the second
+const vec2 STEEP = vec2(0.6, 0.8);
+
+// projected straight down, stretched on steep slopes
+vec3 planar(float layer) {
+    return texture(details, vec3(fragPosition.xz * push.tiling, layer)).rgb;
+}
+
+// projected along each axis, blended by how much the surface faces it
+vec3 triplanar(float layer, vec3 normal) {
+    vec3 blend = pow(abs(normal), vec3(4.0));
+    blend /= blend.x + blend.y + blend.z;
+    vec3 uvw = fragPosition * push.tiling;
+    return texture(details, vec3(uvw.zy, layer)).rgb * blend.x
+        + texture(details, vec3(uvw.xz, layer)).rgb * blend.y
+        + texture(details, vec3(uvw.xy, layer)).rgb * blend.z;
+}
+
+void main() {
+    vec3 normal = normalize(fragNormal);
+    float steepness = push.triplanar != 0u ? 1.0 - smoothstep(STEEP.x, STEEP.y, normal.y) : 0.0;
+
+    // every layer is sampled everywhere, implicit derivatives are undefined when neighbouring
+    // pixels skip different layers
+    vec3 color = vec3(0.0);
+    for (int layer = 0; layer < 4; layer++) {
+        vec3 detail = mix(planar(float(layer)), triplanar(float(layer), normal), steepness);
+        color += detail * fragWeights[layer];
+    }
+
+    float diffuse = max(dot(normal, push.sun_direction.xyz), 0.0);
+    outColor = vec4(color * (AMBIENT + (1.0 - AMBIENT) * diffuse), 1.0);
+}
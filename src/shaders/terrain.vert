@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// from the chunk's vertices, already in world space
+layout(location = 0) in vec3 pos;
+layout(location = 1) in vec3 normal;
+// from the chunk's splat buffer, see world::Splat
+layout(location = 2) in vec4 weights;
+
+layout(location = 0) out vec3 fragPosition;
+layout(location = 1) out vec3 fragNormal;
+layout(location = 2) out vec4 fragWeights;
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 sun_direction;
+    float tiling;
+    uint triplanar;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    gl_Position = push.view_projection * vec4(pos, 1.0);
+    fragPosition = pos;
+    fragNormal = normal;
+    fragWeights = weights;
+}
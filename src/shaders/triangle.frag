@@ -2,8 +2,50 @@
 #extension GL_ARB_separate_shader_objects : enable
 
 layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec2 fragUv;
+layout(location = 2) in float fragClipDistance;
 layout(location = 0) out vec4 outColor;
 
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec4 audio[2];
+    vec3 light_position;
+    float ambient;
+    vec3 light_color;
+    float shininess;
+    vec3 camera_position;
+    float emissive_intensity;
+    vec3 emissive_color;
+    uint frame;
+} ubo;
+
+// the texture loaded with the `texture` command, a white texel otherwise
+layout(set = 1, binding = 0) uniform sampler2D texSampler;
+// the `emissive texture` command's image, a white texel otherwise
+layout(set = 1, binding = 1) uniform sampler2D emissiveSampler;
+
+layout(push_constant) uniform PushConstants {
+    vec4 clip_plane;
+    vec2 depth_range;
+    float time;
+    uint animation;
+    uint frame;
+    uint section;
+    float interpolation;
+} push;
+
+// color of the cross section, back faces seen through the cut are drawn with it
+const vec3 CAP_COLOR = vec3(0.9, 0.35, 0.2);
+
 void main() {
-    outColor = vec4(fragColor , 1.0);
+    if (fragClipDistance < 0.0) {
+        discard;
+    }
+
+    // the emission isn't shaded, it can make the color brighter than 1 for the bloom
+    vec3 color = fragColor * texture(texSampler, fragUv).rgb
+        + ubo.emissive_color * ubo.emissive_intensity * texture(emissiveSampler, fragUv).rgb;
+    outColor = vec4(push.section == 2 && !gl_FrontFacing ? CAP_COLOR : color, 1.0);
 }
\ No newline at end of file
@@ -2,16 +2,75 @@
 #extension GL_ARB_separate_shader_objects : enable
 
 
-layout(location = 0) in vec2 pos;
+layout(location = 0) in vec3 pos;
 layout(location = 1) in vec3 color;
+layout(location = 2) in vec2 uv;
+// in the mesh's own space
+layout(location = 3) in vec3 normal;
 
 layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragUv;
+// signed distance to the section plane, negative fragments are discarded
+layout(location = 2) out float fragClipDistance;
+// in world space, for lit.frag
+layout(location = 3) out vec3 fragWorldPos;
+layout(location = 4) out vec3 fragNormal;
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    // levels of the audio input's bands from low to high frequencies, see audio::BANDS
+    vec4 audio[2];
+    // the point light and the eye in world space, see lighting::Light
+    vec3 light_position;
+    float ambient;
+    vec3 light_color;
+    float shininess;
+    vec3 camera_position;
+    // the material's own light, see scene::Emission
+    float emissive_intensity;
+    vec3 emissive_color;
+    // index of the frame being rendered, like the push constant
+    uint frame;
+} ubo;
+
+layout(push_constant) uniform PushConstants {
+    // section plane in the mesh's own space as (normal, -distance), see section::Section
+    vec4 clip_plane;
+    // near and far clip planes of the projection
+    vec2 depth_range;
+    float time;
+    // 0 - none, 1 - rotate (already in the model matrix), 2 - color wave, see scenes::Animation
+    uint animation;
+    // index of the frame being rendered, matches the one in log lines
+    uint frame;
+    // 0 - no section, 1 - cut away, 2 - cut away and cap the cross section
+    uint section;
+    // how far the frame is between the simulation's last two fixed steps, 0 to 1, see
+    // compute::ParticleSystem::interpolation
+    float interpolation;
+} push;
 
 out gl_PerVertex {
     vec4 gl_Position;
 };
 
 void main() {
-    gl_Position = vec4(pos, 0.0, 1.0);
-    fragColor = color;
+    vec3 vertexColor = color;
+
+    if (push.animation == 2) {
+        float wave = 0.5 + 0.5 * sin(push.time * 2.0 - length(pos.xy) * 8.0);
+        vertexColor = mix(color, color.bgr, wave);
+    }
+
+    vec4 worldPos = ubo.model * vec4(pos, 1.0);
+    gl_Position = ubo.proj * ubo.view * worldPos;
+    // the colors brighten with the bass of the audio input, if there is one
+    fragColor = vertexColor * (1.0 + 0.5 * ubo.audio[0].x);
+    fragUv = uv;
+    fragWorldPos = worldPos.xyz;
+    // the inverse transpose keeps normals perpendicular under non-uniform scaling
+    fragNormal = transpose(inverse(mat3(ubo.model))) * normal;
+    fragClipDistance = push.section == 0 ? 1.0 : dot(vec4(pos, 1.0), push.clip_plane);
 }
\ No newline at end of file
@@ -0,0 +1,14 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+// white with the glyph coverage in alpha, premultiplied
+layout(set = 0, binding = 0) uniform sampler2D font;
+
+void main() {
+    outColor = fragColor * texture(font, fragUv);
+}
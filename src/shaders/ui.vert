@@ -0,0 +1,26 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// egui's logical pixels, the origin is the top left corner
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+// premultiplied alpha
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragColor;
+
+layout(push_constant) uniform PushConstants {
+    // window size in egui's logical pixels
+    vec2 screen_size;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    gl_Position = vec4(2.0 * position / push.screen_size - 1.0, 0.0, 1.0);
+    fragUv = uv;
+    fragColor = color;
+}
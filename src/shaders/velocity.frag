@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 fragClip;
+layout(location = 1) in vec4 fragPreviousClip;
+
+// how far the surface moved on screen since the previous frame, in texture coordinates
+layout(location = 0) out vec2 outVelocity;
+
+void main() {
+    // perspective divide per pixel, interpolating the divided positions would bend the motion
+    outVelocity = (fragClip.xy / fragClip.w - fragPreviousClip.xy / fragPreviousClip.w) * 0.5;
+}
@@ -0,0 +1,23 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec3 pos;
+
+layout(location = 0) out vec4 fragClip;
+layout(location = 1) out vec4 fragPreviousClip;
+
+layout(push_constant) uniform PushConstants {
+    mat4 mvp;
+    // the object's model matrix and the camera of the previous frame
+    mat4 previous_mvp;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    gl_Position = push.mvp * vec4(pos, 1.0);
+    fragClip = gl_Position;
+    fragPreviousClip = push.previous_mvp * vec4(pos, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    // from this frame's normalized device coordinates to last frame's clip space
+    mat4 reprojection;
+    // 1 / size of the velocity buffer
+    vec2 inverseSize;
+} pc;
+
+layout(location = 0) out vec2 outVelocity;
+
+// camera motion of the far plane, the depth test keeps it to pixels nothing was drawn on
+void main() {
+    vec2 ndc = gl_FragCoord.xy * pc.inverseSize * 2.0 - 1.0;
+    vec4 previous = pc.reprojection * vec4(ndc, 1.0, 1.0);
+    outVelocity = previous.w > 0.0 ? (ndc - previous.xy / previous.w) * 0.5 : vec2(0.0);
+    gl_FragDepth = 1.0;
+}
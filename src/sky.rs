@@ -0,0 +1,126 @@
+use std::{sync::Arc, time::Instant};
+
+use cgmath::{Deg, InnerSpace, Matrix4, Rad, SquareMatrix, Vector3};
+use vulkano::{
+    device::Device,
+    pipeline::{vertex::BufferlessDefinition, GraphicsPipeline},
+    render_pass::{RenderPass, Subpass},
+};
+
+use crate::error::Error;
+
+/// Tilt of the sun's path away from straight overhead, so the noon sun is in front of the camera
+const SUN_PATH_TILT: Deg<f32> = Deg(35.0);
+/// Seconds one simulated day takes by default
+const DEFAULT_DAY_LENGTH: f32 = 120.0;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/sky.vert"
+    }
+}
+
+pub mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/sky.frag"
+    }
+}
+
+/// An analytic daylight sky drawn behind the scene, with the sun moving through a day
+pub struct Sky {
+    pub enabled: bool,
+    /// Seconds per simulated day, 0 stops the clock
+    pub day_length: f32,
+    /// Haziness of the atmosphere, 2 is a clear day and 10 is hazy
+    pub turbidity: f32,
+    start_hour: f32,
+    start: Instant,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            day_length: DEFAULT_DAY_LENGTH,
+            turbidity: 2.5,
+            start_hour: 9.0,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Sky {
+    /// Time of day in hours, 0 to 24
+    pub fn hour(&self) -> f32 {
+        if self.day_length <= 0.0 {
+            return self.start_hour;
+        }
+
+        let elapsed_hours = self.start.elapsed().as_secs_f32() / self.day_length * 24.0;
+        (self.start_hour + elapsed_hours).rem_euclid(24.0)
+    }
+
+    pub fn set_hour(&mut self, hour: f32) {
+        self.start_hour = hour.rem_euclid(24.0);
+        self.start = Instant::now();
+    }
+
+    /// Keeps the current time of day while changing how fast it passes
+    pub fn set_day_length(&mut self, day_length: f32) {
+        self.set_hour(self.hour());
+        self.day_length = day_length.max(0.0);
+    }
+
+    /// Unit vector towards the sun in world space. It rises in +X at 6:00, is highest at
+    /// noon and sets in -X at 18:00.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = Rad::from(Deg((self.hour() - 6.0) / 24.0 * 360.0));
+        let tilt = Rad::from(SUN_PATH_TILT);
+
+        Vector3::new(
+            angle.0.cos(),
+            angle.0.sin() * tilt.0.cos(),
+            -angle.0.sin() * tilt.0.sin(),
+        )
+        .normalize()
+    }
+
+    pub fn push_constants(&self, view_projection: Matrix4<f32>) -> fragment_shader::ty::PushConstants {
+        let inverse = view_projection.invert().unwrap_or_else(Matrix4::identity);
+
+        fragment_shader::ty::PushConstants {
+            inverse_view_projection: inverse.into(),
+            sun_direction: self.sun_direction().extend(0.0).into(),
+            turbidity: self.turbidity,
+        }
+    }
+}
+
+/// Full screen pass without depth testing, drawn before the scene. The concrete type is kept
+/// because only it accepts `BufferlessVertices` in draw calls.
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<GraphicsPipeline<BufferlessDefinition>>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "sky vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "sky fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(BufferlessDefinition {})
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
@@ -0,0 +1,382 @@
+//! A cubemap drawn behind the scene, loaded from six face images or converted from one
+//! equirectangular Radiance HDR panorama. It is drawn on a cube around the camera after the
+//! renderables, on the far plane, so it only fills the pixels nothing else covered.
+
+use std::{
+    f32::consts::PI,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use cgmath::{InnerSpace, Matrix4, Vector3, Vector4};
+use vulkano::{
+    buffer::{BufferUsage, ImmutableBuffer},
+    command_buffer::{AutoCommandBufferBuilder, DynamicState},
+    device::{Device, Queue},
+    format::Format,
+    image::{view::ImageView, ImmutableImage},
+    impl_vertex,
+    pipeline::{depth_stencil::Compare, depth_stencil::DepthStencil, GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{RenderPass, Subpass},
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::{
+    descriptors::{DescriptorCache, Resource},
+    error::Error,
+    post,
+    staging::Staging,
+};
+
+/// Float so HDR panoramas keep their range, 8 bit faces are decoded to linear into it too
+const CUBEMAP_FORMAT: Format = Format::R32G32B32A32Sfloat;
+/// Largest face made from a panorama, a quarter of its width otherwise. Six faces of 512 float
+/// texels take 24 MB.
+const MAX_FACE_SIZE: u32 = 512;
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/skybox.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/skybox.frag"
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct SkyboxVertex {
+    position: [f32; 3],
+}
+
+impl_vertex!(SkyboxVertex, position);
+
+/// The cubemap and the cube it is drawn on
+pub struct Skybox {
+    /// What it was loaded from, for the console
+    pub description: String,
+    view: Arc<ImageView<Arc<ImmutableImage>>>,
+    sampler: Arc<Sampler>,
+    vertex_buffer: Arc<ImmutableBuffer<[SkyboxVertex]>>,
+    index_buffer: Arc<ImmutableBuffer<[u32]>>,
+}
+
+impl Skybox {
+    /// Loads six PNG or JPEG faces in the order +X, -X, +Y, -Y, +Z, -Z, or one `.hdr` panorama
+    pub fn load(queue: &Arc<Queue>, paths: &[PathBuf]) -> Result<Self, String> {
+        let (pixels, size) = match paths {
+            [panorama] => {
+                let (width, height, texels) = read_hdr(panorama)?;
+                equirectangular_to_cube(width, height, &texels)
+            }
+            [_, _, _, _, _, _] => read_faces(paths)?,
+            _ => return Err("expected six face images or one .hdr panorama".to_string()),
+        };
+
+        let mut staging = Staging::new(queue);
+        let image = staging
+            .cubemap(pixels.into_iter(), size, CUBEMAP_FORMAT)
+            .map_err(|e| e.to_string())?;
+        let (vertices, indices) = cube();
+        let vertex_buffer = staging
+            .buffer(vertices.into_iter(), BufferUsage::vertex_buffer())
+            .map_err(|e| e.to_string())?;
+        let index_buffer = staging
+            .buffer(indices.into_iter(), BufferUsage::index_buffer())
+            .map_err(|e| e.to_string())?;
+        staging.submit_and_wait().map_err(|e| e.to_string())?;
+
+        let sampler = Sampler::new(
+            queue.device().clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .map_err(|e| format!("failed to create the skybox sampler: {}", e))?;
+
+        let names: Vec<_> = paths.iter().map(|path| path.display().to_string()).collect();
+        Ok(Self {
+            description: format!("{} ({}x{} faces)", names.join(", "), size, size),
+            view: ImageView::new(image).map_err(|e| format!("failed to create the cubemap view: {}", e))?,
+            sampler,
+            vertex_buffer,
+            index_buffer,
+        })
+    }
+
+    /// Records the cube into the scene pass, after the renderables
+    pub fn draw<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+        cache: &DescriptorCache,
+        view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+    ) {
+        let mut rotation = view;
+        rotation.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let push_constants = vertex_shader::ty::PushConstants {
+            view_projection: (projection * rotation).into(),
+        };
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap();
+        let set = cache.get(layout, &[Resource::SampledImage(self.view.clone(), self.sampler.clone())]);
+
+        builder
+            .draw_indexed(
+                pipeline.clone(),
+                dynamic_state,
+                vec![self.vertex_buffer.clone()],
+                self.index_buffer.clone(),
+                set,
+                push_constants,
+                vec![],
+            )
+            .unwrap();
+    }
+}
+
+/// Tests depth without writing it, `LessOrEqual` so the far plane passes where the depth buffer
+/// is still cleared. Not culled, the camera is inside the cube.
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "skybox vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "skybox fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input_single_buffer::<SkyboxVertex>()
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .depth_stencil(DepthStencil {
+                depth_compare: Compare::LessOrEqual,
+                depth_write: false,
+                ..DepthStencil::disabled()
+            })
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
+
+/// The unit cube's corners and its twelve triangles
+fn cube() -> (Vec<SkyboxVertex>, Vec<u32>) {
+    let vertices = (0..8)
+        .map(|corner| {
+            let axis = |bit: u32| if corner & bit == 0 { -1.0 } else { 1.0 };
+            SkyboxVertex {
+                position: [axis(1), axis(2), axis(4)],
+            }
+        })
+        .collect();
+    // corners by bit: 1 is +X, 2 is +Y, 4 is +Z
+    let indices = vec![
+        1, 3, 7, 7, 5, 1, // +X
+        0, 4, 6, 6, 2, 0, // -X
+        2, 6, 7, 7, 3, 2, // +Y
+        0, 1, 5, 5, 4, 0, // -Y
+        4, 5, 7, 7, 6, 4, // +Z
+        0, 2, 3, 3, 1, 0, // -Z
+    ];
+    (vertices, indices)
+}
+
+/// Six square faces of the same size, their 8 bit sRGB texels decoded to linear
+fn read_faces(paths: &[PathBuf]) -> Result<(Vec<[f32; 4]>, u32), String> {
+    let mut pixels = Vec::new();
+    let mut face_size = None;
+    for path in paths {
+        let image = image::open(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+            .to_rgba8();
+        if image.width() != image.height() || face_size.is_some_and(|size| size != image.width()) {
+            return Err(format!(
+                "{} is {}x{}, the faces have to be square and all the same size",
+                path.display(),
+                image.width(),
+                image.height()
+            ));
+        }
+        face_size = Some(image.width());
+
+        pixels.extend(image.pixels().map(|pixel| {
+            let [r, g, b, _] = pixel.0;
+            let [r, g, b] = [r, g, b].map(|channel| post::decode_srgb(channel as f32 / 255.0));
+            [r, g, b, 1.0]
+        }));
+    }
+    Ok((pixels, face_size.unwrap_or(0)))
+}
+
+/// Direction through texel `x`, `y` of cube face `face`, with the faces laid out the way Vulkan
+/// samples them
+fn face_direction(face: usize, x: u32, y: u32, size: u32) -> Vector3<f32> {
+    let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+    let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+    let direction = match face {
+        0 => Vector3::new(1.0, -t, -s),
+        1 => Vector3::new(-1.0, -t, s),
+        2 => Vector3::new(s, 1.0, t),
+        3 => Vector3::new(s, -1.0, -t),
+        4 => Vector3::new(s, -t, 1.0),
+        _ => Vector3::new(-s, -t, -1.0),
+    };
+    direction.normalize()
+}
+
+/// Resamples a panorama onto six faces. Its center looks along -Z, where the camera starts
+/// looking, its top row is straight up.
+fn equirectangular_to_cube(width: u32, height: u32, texels: &[[f32; 3]]) -> (Vec<[f32; 4]>, u32) {
+    let size = (width / 4).clamp(1, MAX_FACE_SIZE);
+    let sample = |u: f32, v: f32| {
+        // bilinear, wrapping around horizontally and clamped at the poles
+        let x = u * width as f32 - 0.5;
+        let y = (v * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |x: f32, y: f32| {
+            let x = (x as i64).rem_euclid(width as i64) as usize;
+            let y = (y as usize).min(height as usize - 1);
+            Vector3::from(texels[y * width as usize + x])
+        };
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1.0, y0) * fx;
+        let bottom = texel(x0, y0 + 1.0) * (1.0 - fx) + texel(x0 + 1.0, y0 + 1.0) * fx;
+        top * (1.0 - fy) + bottom * fy
+    };
+
+    let mut pixels = Vec::with_capacity(6 * size as usize * size as usize);
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let direction = face_direction(face, x, y, size);
+                let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+                let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+                pixels.push(sample(u, v).extend(1.0).into());
+            }
+        }
+    }
+    (pixels, size)
+}
+
+/// Reads a Radiance RGBE file with the usual `-Y height +X width` orientation, flat or with the
+/// run length encoded scanlines most tools write
+fn read_hdr(path: &Path) -> Result<(u32, u32, Vec<[f32; 3]>), String> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let error = |message: &str| format!("{}: {}", path.display(), message);
+
+    let mut position = 0;
+    let mut next_line = || {
+        let end = bytes[position..].iter().position(|&byte| byte == b'\n')? + position;
+        let line = String::from_utf8_lossy(&bytes[position..end]).into_owned();
+        position = end + 1;
+        Some(line)
+    };
+
+    let magic = next_line().ok_or_else(|| error("empty file"))?;
+    if !magic.starts_with("#?") {
+        return Err(error("not a Radiance HDR file"));
+    }
+    // header lines up to an empty one
+    loop {
+        let line = next_line().ok_or_else(|| error("the header doesn't end"))?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(error(&format!("unsupported format {}", format)));
+            }
+        }
+    }
+    let resolution = next_line().ok_or_else(|| error("no resolution"))?;
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["-Y", height, "+X", width] => (
+            width.parse::<usize>().map_err(|_| error("bad width"))?,
+            height.parse::<usize>().map_err(|_| error("bad height"))?,
+        ),
+        _ => return Err(error(&format!("unsupported orientation '{}'", resolution))),
+    };
+    if width == 0 || height == 0 {
+        return Err(error("the image is empty"));
+    }
+
+    let data = &bytes[position..];
+    let mut offset = 0;
+    let mut byte = || {
+        let value = *data.get(offset).ok_or_else(|| error("the pixels end early"))?;
+        offset += 1;
+        Ok::<u8, String>(value)
+    };
+
+    let mut rgbe = vec![[0u8; 4]; width * height];
+    for row in rgbe.chunks_mut(width) {
+        let start = [byte()?, byte()?, byte()?, byte()?];
+        let encoded = (8..0x8000).contains(&width)
+            && start[0] == 2
+            && start[1] == 2
+            && ((start[2] as usize) << 8 | start[3] as usize) == width;
+        if !encoded {
+            if start[0] == 1 && start[1] == 1 && start[2] == 1 {
+                return Err(error("old style run length encoding isn't supported"));
+            }
+            row[0] = start;
+            for texel in &mut row[1..] {
+                *texel = [byte()?, byte()?, byte()?, byte()?];
+            }
+            continue;
+        }
+
+        // each channel of the row on its own, in runs of one value or of literal values
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = byte()? as usize;
+                let (count, run) = if count > 128 { (count - 128, true) } else { (count, false) };
+                if count == 0 || x + count > width {
+                    return Err(error("bad run length"));
+                }
+                let value = if run { Some(byte()?) } else { None };
+                for texel in &mut row[x..x + count] {
+                    texel[channel] = match value {
+                        Some(value) => value,
+                        None => byte()?,
+                    };
+                }
+                x += count;
+            }
+        }
+    }
+
+    let texels = rgbe
+        .into_iter()
+        .map(|[r, g, b, exponent]| {
+            if exponent == 0 {
+                return [0.0; 3];
+            }
+            let scale = 2f32.powi(exponent as i32 - 136);
+            [r, g, b].map(|channel| (channel as f32 + 0.5) * scale)
+        })
+        .collect();
+    Ok((width as u32, height as u32, texels))
+}
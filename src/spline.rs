@@ -0,0 +1,279 @@
+//! Spline paths the camera or a spawned object follows at a constant speed. A path file lists
+//! the control points and how the path is followed, `#` starts a comment:
+//!
+//! ```text
+//! curve catmull-rom   # through every point, or bezier: point, two handles, point, ...
+//! point -3 1 3
+//! point 3 1 3
+//! point 3 1 -3
+//! point -3 1 -3
+//! speed 1.5           # units per second
+//! look 0 0 0          # look-at target, without it the camera looks along the path
+//! loop                # closes the path and follows it again and again
+//! ```
+
+use std::{fmt, fs, path::Path, path::PathBuf};
+
+use cgmath::{EuclideanSpace, InnerSpace, MetricSpace, Point3, Vector3};
+
+use crate::debug_draw::DebugLines;
+
+/// Straight pieces each curve segment is measured and drawn with
+const SAMPLES_PER_SEGMENT: usize = 32;
+const DEFAULT_SPEED: f32 = 1.0;
+const PATH_COLOR: [f32; 3] = [0.2, 0.8, 1.0];
+const CONTROL_POINT_COLOR: [f32; 3] = [1.0, 0.4, 0.2];
+const MARKER_SIZE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Curve {
+    /// Passes through every control point
+    CatmullRom,
+    /// Cubic segments, each passing through its first and last point and pulled towards the two
+    /// handles in between
+    Bezier,
+}
+
+/// What moves along the path
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathFollower {
+    Camera,
+    /// The spawned object with this id
+    Object(u32),
+}
+
+impl fmt::Display for PathFollower {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathFollower::Camera => write!(f, "camera"),
+            PathFollower::Object(id) => write!(f, "object #{}", id),
+        }
+    }
+}
+
+/// A path file measured into short straight pieces, and how far along it the follower is
+pub struct SplinePath {
+    pub file: PathBuf,
+    pub follower: PathFollower,
+    /// Units per second along the path
+    pub speed: f32,
+    pub look_at: Option<Point3<f32>>,
+    looping: bool,
+    control_points: Vec<Point3<f32>>,
+    /// Points along the curve and their distance from its start
+    samples: Vec<(f32, Point3<f32>)>,
+    travelled: f32,
+}
+
+impl SplinePath {
+    pub fn load(file: &Path, follower: PathFollower) -> Result<Self, String> {
+        let text = fs::read_to_string(file).map_err(|e| format!("failed to read file: {}", e))?;
+        let mut curve = Curve::CatmullRom;
+        let mut control_points = Vec::new();
+        let mut speed = DEFAULT_SPEED;
+        let mut look_at = None;
+        let mut looping = false;
+
+        for (number, line) in text.lines().enumerate() {
+            let words: Vec<_> = line.split('#').next().unwrap().split_whitespace().collect();
+            let error = |e: String| format!("line {}: {}", number + 1, e);
+            match words.as_slice() {
+                [] => (),
+                ["curve", "catmull-rom"] => curve = Curve::CatmullRom,
+                ["curve", "bezier"] => curve = Curve::Bezier,
+                ["point", x, y, z] => control_points.push(parse_point(x, y, z).map_err(error)?),
+                ["look", x, y, z] => look_at = Some(parse_point(x, y, z).map_err(error)?),
+                ["speed", value] => speed = parse_number(value).map_err(error)?,
+                ["loop"] => looping = true,
+                _ => return Err(error(format!("unexpected '{}'", words.join(" ")))),
+            }
+        }
+
+        let segments = segments(curve, &control_points, looping)?;
+        let mut samples = vec![(0.0, segments[0][0])];
+        for segment in &segments {
+            for i in 1..=SAMPLES_PER_SEGMENT {
+                let point = evaluate(curve, segment, i as f32 / SAMPLES_PER_SEGMENT as f32);
+                let (length, last) = *samples.last().unwrap();
+                samples.push((length + last.distance(point), point));
+            }
+        }
+
+        Ok(Self {
+            file: file.to_path_buf(),
+            follower,
+            speed,
+            look_at,
+            looping,
+            control_points,
+            samples,
+            travelled: 0.0,
+        })
+    }
+
+    pub fn length(&self) -> f32 {
+        self.samples.last().unwrap().0
+    }
+
+    /// Moves the follower on, stopping at the end unless the path loops
+    pub fn advance(&mut self, seconds: f32) {
+        let length = self.length();
+        self.travelled += self.speed * seconds;
+        self.travelled = if self.looping && length > 0.0 {
+            self.travelled.rem_euclid(length)
+        } else {
+            self.travelled.clamp(0.0, length)
+        };
+    }
+
+    /// Where the follower is
+    pub fn position(&self) -> Point3<f32> {
+        self.point_at(self.travelled)
+    }
+
+    /// Where the camera looks from the current position, the look-at target or a little further
+    /// along the path
+    pub fn view_target(&self) -> Point3<f32> {
+        if let Some(look_at) = self.look_at {
+            return look_at;
+        }
+
+        let position = self.position();
+        let ahead = self.point_at(self.travelled + 0.1);
+        let behind = self.point_at(self.travelled - 0.1);
+        let direction = ahead - behind;
+        if direction.magnitude2() > 0.0 {
+            position + direction.normalize()
+        } else {
+            // a path with no length has no direction to look along
+            position - Vector3::unit_z()
+        }
+    }
+
+    /// The curve, its control points and the follower, drawn while the path is loaded
+    pub fn draw(&self, lines: &mut DebugLines) {
+        for pair in self.samples.windows(2) {
+            lines.line(pair[0].1, pair[1].1, PATH_COLOR);
+        }
+        for point in &self.control_points {
+            lines.cross(*point, MARKER_SIZE, CONTROL_POINT_COLOR);
+        }
+        if self.follower != PathFollower::Camera {
+            lines.cross(self.position(), MARKER_SIZE * 2.0, PATH_COLOR);
+        }
+        if let Some(look_at) = self.look_at {
+            lines.cross(look_at, MARKER_SIZE, PATH_COLOR);
+        }
+    }
+
+    /// The point `distance` along the curve, wrapping around on loops
+    fn point_at(&self, distance: f32) -> Point3<f32> {
+        let length = self.length();
+        let distance = if self.looping && length > 0.0 {
+            distance.rem_euclid(length)
+        } else {
+            distance.clamp(0.0, length)
+        };
+
+        let next = self.samples.partition_point(|(length, _)| *length < distance).max(1);
+        if next == self.samples.len() {
+            return self.samples[next - 1].1;
+        }
+        let ((from_length, from), (to_length, to)) = (self.samples[next - 1], self.samples[next]);
+        let t = if to_length > from_length {
+            (distance - from_length) / (to_length - from_length)
+        } else {
+            0.0
+        };
+        from + (to - from) * t
+    }
+}
+
+impl fmt::Display for SplinePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {} at {:.2} of {:.2} units, {:.2} units/s{}",
+            self.file.display(),
+            self.follower,
+            self.travelled,
+            self.length(),
+            self.speed,
+            if self.looping { ", looping" } else { "" }
+        )
+    }
+}
+
+/// The four control points of each cubic segment
+fn segments(curve: Curve, points: &[Point3<f32>], looping: bool) -> Result<Vec<[Point3<f32>; 4]>, String> {
+    match curve {
+        Curve::CatmullRom => {
+            if points.len() < 2 {
+                return Err("a Catmull-Rom path needs at least 2 points".to_string());
+            }
+
+            let count = points.len();
+            let point = |i: isize| {
+                if looping {
+                    points[i.rem_euclid(count as isize) as usize]
+                } else {
+                    // the ends are repeated, so the curve starts and stops on them
+                    points[i.clamp(0, count as isize - 1) as usize]
+                }
+            };
+            let segment_count = if looping { count } else { count - 1 };
+            Ok((0..segment_count as isize)
+                .map(|i| [point(i - 1), point(i), point(i + 1), point(i + 2)])
+                .collect())
+        }
+        Curve::Bezier => {
+            // a loop's last segment ends on the first point again
+            let mut points = points.to_vec();
+            if looping && !points.is_empty() {
+                points.push(points[0]);
+            }
+            if points.len() < 4 || !(points.len() - 1).is_multiple_of(3) {
+                return Err(format!(
+                    "a Bezier path needs 4, 7, 10, ... points{}, got {}",
+                    if looping { " counting the first one again" } else { "" },
+                    points.len()
+                ));
+            }
+
+            Ok(points.windows(4).step_by(3).map(|window| [window[0], window[1], window[2], window[3]]).collect())
+        }
+    }
+}
+
+/// The point at `t` from 0 to 1 along one segment
+fn evaluate(curve: Curve, [p0, p1, p2, p3]: &[Point3<f32>; 4], t: f32) -> Point3<f32> {
+    let (p0, p1, p2, p3) = (p0.to_vec(), p1.to_vec(), p2.to_vec(), p3.to_vec());
+    let (t2, t3) = (t * t, t * t * t);
+    let point = match curve {
+        // uniform Catmull-Rom between p1 and p2
+        Curve::CatmullRom => {
+            (p1 * 2.0
+                + (p2 - p0) * t
+                + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+                + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+                * 0.5
+        }
+        Curve::Bezier => {
+            let u = 1.0 - t;
+            p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t2) + p3 * t3
+        }
+    };
+    Point3::from_vec(point)
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .ok()
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| format!("expected a number, got '{}'", value))
+}
+
+fn parse_point(x: &str, y: &str, z: &str) -> Result<Point3<f32>, String> {
+    Ok(Point3::new(parse_number(x)?, parse_number(y)?, parse_number(z)?))
+}
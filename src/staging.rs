@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use vulkano::{
+    buffer::{BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer, ImmutableBuffer},
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBuffer},
+    device::Queue,
+    format::{Format, Pixel},
+    image::{
+        immutable::SubImage, ImageCreateFlags, ImageDimensions, ImageLayout, ImageUsage, ImmutableImage,
+        MipmapsCount,
+    },
+    sync::{self, GpuFuture},
+};
+
+use crate::error::Error;
+
+/// Collects uploads of data that never changes into one command buffer, submitted at once by
+/// `submit`. The buffers and images it returns can only be used by submissions that wait for the
+/// upload. Given the transfer only queue, uploads made while rendering don't wait behind the
+/// frames queued on the graphics queue.
+pub struct Staging {
+    queue: Arc<Queue>,
+    builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    uploads: usize,
+}
+
+impl Staging {
+    pub fn new(queue: &Arc<Queue>) -> Self {
+        let builder =
+            AutoCommandBufferBuilder::primary(queue.device().clone(), queue.family(), CommandBufferUsage::OneTimeSubmit)
+                .unwrap();
+        Self {
+            queue: queue.clone(),
+            builder,
+            uploads: 0,
+        }
+    }
+
+    pub fn queue(&self) -> &Arc<Queue> {
+        &self.queue
+    }
+
+    /// A buffer for `usage`, filled with `data`
+    pub fn buffer<T, I>(&mut self, data: I, usage: BufferUsage) -> Result<Arc<ImmutableBuffer<[T]>>, Error>
+    where
+        I: ExactSizeIterator<Item = T>,
+        T: Send + Sync + 'static,
+    {
+        let device = self.queue.device();
+        let len = data.len();
+        let source = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false, data)?;
+        let usage = BufferUsage {
+            transfer_destination: true,
+            ..usage
+        };
+        // filled by the copy before anything can read it, the buffer is shared with every queue
+        // the device was created with
+        let (buffer, initialization) =
+            unsafe { ImmutableBuffer::uninitialized_array(device.clone(), len, usage)? };
+
+        self.builder.copy_buffer(source, initialization)?;
+        self.uploads += 1;
+        Ok(buffer)
+    }
+
+    /// A buffer for `usage` that the GPU keeps writing to, like a compute shader's storage buffer,
+    /// filled with `data` to start with
+    pub fn device_local_buffer<T, I>(&mut self, data: I, usage: BufferUsage) -> Result<Arc<DeviceLocalBuffer<[T]>>, Error>
+    where
+        I: ExactSizeIterator<Item = T>,
+        T: Send + Sync + 'static,
+    {
+        let device = self.queue.device();
+        let len = data.len();
+        let source = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false, data)?;
+        let usage = BufferUsage {
+            transfer_destination: true,
+            ..usage
+        };
+        let buffer = DeviceLocalBuffer::array(device.clone(), len, usage, device.active_queue_families())?;
+
+        self.builder.copy_buffer(source, buffer.clone())?;
+        self.uploads += 1;
+        Ok(buffer)
+    }
+
+    /// A sampled 2D image without mip levels, filled row by row with `pixels`
+    pub fn image<Px, I>(
+        &mut self,
+        pixels: I,
+        dimensions: [u32; 2],
+        format: Format,
+    ) -> Result<Arc<ImmutableImage>, Error>
+    where
+        I: ExactSizeIterator<Item = Px>,
+        Px: Pixel + Send + Sync + 'static,
+    {
+        self.image_array(pixels, dimensions, 1, format)
+    }
+
+    /// A sampled 2D array image without mip levels, `pixels` holds its `layers` one after the
+    /// other, each row by row
+    pub fn image_array<Px, I>(
+        &mut self,
+        pixels: I,
+        dimensions: [u32; 2],
+        layers: u32,
+        format: Format,
+    ) -> Result<Arc<ImmutableImage>, Error>
+    where
+        I: ExactSizeIterator<Item = Px>,
+        Px: Pixel + Send + Sync + 'static,
+    {
+        let device = self.queue.device();
+        let source = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false, pixels)?;
+        let usage = ImageUsage {
+            transfer_destination: true,
+            sampled: true,
+            ..ImageUsage::none()
+        };
+        let (image, initialization) = ImmutableImage::uninitialized(
+            device.clone(),
+            ImageDimensions::Dim2d {
+                width: dimensions[0],
+                height: dimensions[1],
+                array_layers: layers,
+            },
+            format,
+            MipmapsCount::One,
+            usage,
+            ImageCreateFlags::none(),
+            ImageLayout::ShaderReadOnlyOptimal,
+            device.active_queue_families(),
+        )?;
+
+        // written through a view of the level like `ImmutableImage::from_iter` does
+        let level = SubImage::new(Arc::new(initialization), 0, 1, 0, layers, ImageLayout::ShaderReadOnlyOptimal);
+        self.builder.copy_buffer_to_image_dimensions(
+            source,
+            level,
+            [0, 0, 0],
+            [dimensions[0], dimensions[1], 1],
+            0,
+            layers,
+            0,
+        )?;
+        self.uploads += 1;
+        Ok(image)
+    }
+
+    /// A sampled cube image without mip levels, `pixels` holds its six `size` square faces one
+    /// after the other in the order +X, -X, +Y, -Y, +Z, -Z, each row by row
+    pub fn cubemap<Px, I>(&mut self, pixels: I, size: u32, format: Format) -> Result<Arc<ImmutableImage>, Error>
+    where
+        I: ExactSizeIterator<Item = Px>,
+        Px: Pixel + Send + Sync + 'static,
+    {
+        let device = self.queue.device();
+        let source = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::transfer_source(), false, pixels)?;
+        let usage = ImageUsage {
+            transfer_destination: true,
+            sampled: true,
+            ..ImageUsage::none()
+        };
+        let flags = ImageCreateFlags {
+            cube_compatible: true,
+            ..ImageCreateFlags::none()
+        };
+        let (image, initialization) = ImmutableImage::uninitialized(
+            device.clone(),
+            ImageDimensions::Dim2d {
+                width: size,
+                height: size,
+                array_layers: 6,
+            },
+            format,
+            MipmapsCount::One,
+            usage,
+            flags,
+            ImageLayout::ShaderReadOnlyOptimal,
+            device.active_queue_families(),
+        )?;
+
+        let level = SubImage::new(Arc::new(initialization), 0, 1, 0, 6, ImageLayout::ShaderReadOnlyOptimal);
+        // the faces follow each other in the buffer like the layers do in the image
+        self.builder
+            .copy_buffer_to_image_dimensions(source, level, [0, 0, 0], [size, size, 1], 0, 6, 0)?;
+        self.uploads += 1;
+        Ok(image)
+    }
+
+    /// Submits everything recorded without waiting for it. The copies signal a semaphore, which
+    /// the submission the upload is joined into waits for on the GPU.
+    pub fn submit(self) -> Result<Upload, Error> {
+        if self.uploads == 0 {
+            return Ok(Box::new(sync::now(self.queue.device().clone())));
+        }
+
+        let copies = self.builder.build()?.execute(self.queue)?;
+        Ok(Box::new(copies.then_signal_semaphore_and_flush()?))
+    }
+
+    /// Submits everything recorded and waits until it is on the GPU, for loading
+    pub fn submit_and_wait(self) -> Result<(), Error> {
+        if self.uploads == 0 {
+            return Ok(());
+        }
+
+        self.builder
+            .build()?
+            .execute(self.queue)?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+        Ok(())
+    }
+}
+
+/// The copies `Staging::submit` sent to the GPU, joined into the future of the first submission
+/// that uses what they fill
+pub type Upload = Box<dyn GpuFuture + Send + Sync>;
@@ -0,0 +1,194 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use crate::{
+    debug_view::DebugView,
+    scenes::{SceneId, CAMERA_DISTANCE},
+};
+
+/// Written on exit into the working directory, next to the settings file
+pub const DEFAULT_PATH: &str = "state.cfg";
+
+/// Where the window was and what was being looked at when the application last closed, so the
+/// next run picks up from there. Unlike the settings it is written by the application, in the
+/// same `key = value` format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppState {
+    /// Inner size in physical pixels, `None` leaves it to the window system
+    pub window_size: Option<[u32; 2]>,
+    pub window_position: Option<[i32; 2]>,
+    pub scene: SceneId,
+    pub debug_view: DebugView,
+    pub plot: bool,
+    pub mipmaps: bool,
+    pub dither: bool,
+    /// The point the camera orbits, how far from it and at which angles in degrees, see
+    /// `camera::Camera`
+    pub camera_target: [f32; 3],
+    pub camera_distance: f32,
+    pub camera_yaw: f32,
+    pub camera_pitch: f32,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            window_size: None,
+            window_position: None,
+            scene: SceneId::default(),
+            debug_view: DebugView::default(),
+            plot: false,
+            mipmaps: true,
+            dither: true,
+            camera_target: [0.0; 3],
+            camera_distance: CAMERA_DISTANCE,
+            camera_yaw: 0.0,
+            camera_pitch: 0.0,
+        }
+    }
+}
+
+impl AppState {
+    /// `Ok(None)` if the file doesn't exist, like on the first run
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => text.parse().map(Some).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(_) if !path.exists() => Ok(None),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+fn parse_pair<T: FromStr>(value: &str) -> Result<[T; 2], String> {
+    let mut numbers = value.split_whitespace().map(str::parse::<T>);
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Ok([x, y]),
+        _ => Err(format!("expected two whole numbers, got '{}'", value)),
+    }
+}
+
+fn parse_vector(value: &str) -> Result<[f32; 3], String> {
+    let mut numbers = value.split_whitespace().map(str::parse::<f32>);
+    match (numbers.next(), numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Ok(x)), Some(Ok(y)), Some(Ok(z)), None) => Ok([x, y, z]),
+        _ => Err(format!("expected three numbers, got '{}'", value)),
+    }
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("expected a number, got '{}'", value))
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        other => Err(format!("expected on or off, got '{}'", other)),
+    }
+}
+
+impl FromStr for AppState {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut state = AppState::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(format!("line {}: expected 'key = value'", number + 1)),
+            };
+
+            let result = match key {
+                "window_size" => parse_pair(value).map(|size| state.window_size = Some(size)),
+                "window_position" => parse_pair(value).map(|position| state.window_position = Some(position)),
+                "scene" => value.parse().map(|scene| state.scene = scene),
+                "view" => value.parse().map(|debug_view| state.debug_view = debug_view),
+                "plot" => parse_bool(value).map(|plot| state.plot = plot),
+                "mipmaps" => parse_bool(value).map(|mipmaps| state.mipmaps = mipmaps),
+                "dither" => parse_bool(value).map(|dither| state.dither = dither),
+                "camera_target" => parse_vector(value).map(|target| state.camera_target = target),
+                "camera_distance" => parse_number(value).map(|distance| state.camera_distance = distance),
+                "camera_yaw" => parse_number(value).map(|yaw| state.camera_yaw = yaw),
+                "camera_pitch" => parse_number(value).map(|pitch| state.camera_pitch = pitch),
+                other => Err(format!("unknown key '{}'", other)),
+            };
+            result.map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+
+        Ok(state)
+    }
+}
+
+impl fmt::Display for AppState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+
+        writeln!(f, "# written on exit, restored at startup")?;
+        if let Some([width, height]) = self.window_size {
+            writeln!(f, "window_size = {} {}", width, height)?;
+        }
+        if let Some([x, y]) = self.window_position {
+            writeln!(f, "window_position = {} {}", x, y)?;
+        }
+        writeln!(f, "scene = {}", self.scene.scene().name)?;
+        writeln!(f, "view = {}", self.debug_view)?;
+        writeln!(f, "plot = {}", on_off(self.plot))?;
+        writeln!(f, "mipmaps = {}", on_off(self.mipmaps))?;
+        writeln!(f, "dither = {}", on_off(self.dither))?;
+        let [x, y, z] = self.camera_target;
+        writeln!(f, "camera_target = {} {} {}", x, y, z)?;
+        writeln!(f, "camera_distance = {}", self.camera_distance)?;
+        writeln!(f, "camera_yaw = {}", self.camera_yaw)?;
+        writeln!(f, "camera_pitch = {}", self.camera_pitch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let state = AppState {
+            window_size: Some([1280, 720]),
+            window_position: Some([-10, 40]),
+            scene: "2".parse().unwrap(),
+            debug_view: "uv".parse().unwrap(),
+            plot: true,
+            mipmaps: false,
+            dither: true,
+            camera_target: [0.5, -1.25, 3.0],
+            camera_distance: 7.5,
+            camera_yaw: -135.0,
+            camera_pitch: 20.0,
+        };
+        assert_eq!(state.to_string().parse(), Ok(state));
+    }
+
+    #[test]
+    fn comments_and_missing_keys() {
+        let state: AppState = "# comment\n\nplot = on # shown\n".parse().unwrap();
+        assert_eq!(state, AppState { plot: true, ..AppState::default() });
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert!("plot on".parse::<AppState>().is_err());
+        assert!("zoom = 2".parse::<AppState>().is_err());
+        assert!("window_size = 800".parse::<AppState>().is_err());
+        assert!("mipmaps = yes".parse::<AppState>().is_err());
+        assert!("camera_target = 1 2".parse::<AppState>().is_err());
+        assert!("camera_yaw = left".parse::<AppState>().is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::gpu_timer::GpuTimes;
+
+/// Frames the statistics are taken over, a few seconds at common refresh rates
+const HISTORY: usize = 600;
+/// How often the statistics are logged or written to the window title
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where the `stats` command shows the frame statistics every `REPORT_INTERVAL`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatsDisplay {
+    Off,
+    /// Logged with the other messages
+    #[default]
+    Log,
+    /// Appended to the window title
+    Title,
+}
+
+impl fmt::Display for StatsDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StatsDisplay::Off => "off",
+            StatsDisplay::Log => "log",
+            StatsDisplay::Title => "title",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Times of the most recent frames, kept whether or not they are shown
+pub struct FrameStats {
+    /// Time between the starts of consecutive frames
+    intervals: VecDeque<Duration>,
+    /// Time the CPU spent preparing and submitting each frame
+    cpu_times: VecDeque<Duration>,
+    /// GPU times of the frames whose timestamps could be read, they arrive a few frames late
+    gpu_times: VecDeque<GpuTimes>,
+    last_report: Instant,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            intervals: VecDeque::with_capacity(HISTORY),
+            cpu_times: VecDeque::with_capacity(HISTORY),
+            gpu_times: VecDeque::with_capacity(HISTORY),
+            last_report: Instant::now(),
+        }
+    }
+}
+
+impl FrameStats {
+    pub fn push(&mut self, interval: Duration, cpu_time: Duration) {
+        if self.intervals.len() == HISTORY {
+            self.intervals.pop_front();
+            self.cpu_times.pop_front();
+        }
+        self.intervals.push_back(interval);
+        self.cpu_times.push_back(cpu_time);
+    }
+
+    /// Called once the GPU times of a finished frame are read
+    pub fn push_gpu(&mut self, times: GpuTimes) {
+        if self.gpu_times.len() == HISTORY {
+            self.gpu_times.pop_front();
+        }
+        self.gpu_times.push_back(times);
+    }
+
+    /// The statistics over the kept frames, `None` before the first frame
+    pub fn summary(&self) -> Option<StatsSummary> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+
+        let mut intervals: Vec<_> = self.intervals.iter().copied().collect();
+        intervals.sort();
+        let total: Duration = intervals.iter().sum();
+        let cpu_total: Duration = self.cpu_times.iter().sum();
+        let frames = intervals.len() as u32;
+        let gpu = (!self.gpu_times.is_empty()).then(|| {
+            let count = self.gpu_times.len() as u32;
+            let average = |part: fn(&GpuTimes) -> Duration| self.gpu_times.iter().map(part).sum::<Duration>() / count;
+            GpuTimes {
+                scene: average(|times| times.scene),
+                effects: average(|times| times.effects),
+                post: average(|times| times.post),
+                overlays: average(|times| times.overlays),
+            }
+        });
+
+        Some(StatsSummary {
+            fps: frames as f64 / total.as_secs_f64().max(f64::EPSILON),
+            average: total / frames,
+            cpu: cpu_total / frames,
+            p95: percentile(&intervals, 95),
+            p99: percentile(&intervals, 99),
+            gpu,
+        })
+    }
+
+    /// The summary once `REPORT_INTERVAL` has passed since the last one, for periodic reports
+    pub fn poll_report(&mut self) -> Option<StatsSummary> {
+        if self.last_report.elapsed() < REPORT_INTERVAL {
+            return None;
+        }
+
+        self.last_report = Instant::now();
+        self.summary()
+    }
+}
+
+/// The nearest rank `percent`th percentile of `sorted`, which must not be empty
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100);
+    sorted[rank.saturating_sub(1)]
+}
+
+/// Frame rate and frame times over the last `HISTORY` frames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StatsSummary {
+    pub fps: f64,
+    pub average: Duration,
+    /// Average CPU time per frame
+    pub cpu: Duration,
+    /// Frame time 95% of the frames were at or below
+    pub p95: Duration,
+    pub p99: Duration,
+    /// Average GPU time per frame and part of the frame, `None` where the graphics queue can't
+    /// write timestamps
+    pub gpu: Option<GpuTimes>,
+}
+
+impl fmt::Display for StatsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "{:.1} fps, frame {:.2} ms (95% {:.2}, 99% {:.2}), cpu {:.2} ms",
+            self.fps,
+            ms(self.average),
+            ms(self.p95),
+            ms(self.p99),
+            ms(self.cpu)
+        )?;
+        if let Some(gpu) = &self.gpu {
+            write!(f, ", gpu {}", gpu)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! Reader for STL files, both binary and ascii, as exported by CAD tools and slicers
+
+use std::{convert::TryInto, fs, path::Path};
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::mesh::ImportedMesh;
+
+/// STL files have no colors, so every face gets this one and the `lit` view shades it
+const BASE_COLOR: [f32; 3] = [0.75, 0.78, 0.82];
+
+/// Size of the binary header and of one facet record
+const BINARY_HEADER_SIZE: usize = 84;
+const BINARY_FACET_SIZE: usize = 50;
+
+struct Facet {
+    normal: Vector3<f32>,
+    corners: [Vector3<f32>; 3],
+}
+
+pub fn load(path: &Path) -> Result<ImportedMesh, String> {
+    let data = fs::read(path).map_err(|e| format!("failed to read file: {}", e))?;
+    let facets = if is_binary(&data) {
+        parse_binary(&data)?
+    } else {
+        parse_ascii(&data)?
+    };
+
+    Ok(build_mesh(&facets))
+}
+
+/// Binary files may also start with "solid", so the size is checked against the facet count
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() < BINARY_HEADER_SIZE {
+        return false;
+    }
+
+    let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+    data.len() == BINARY_HEADER_SIZE + count * BINARY_FACET_SIZE || !data.starts_with(b"solid")
+}
+
+fn parse_binary(data: &[u8]) -> Result<Vec<Facet>, String> {
+    let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+    let records = &data[BINARY_HEADER_SIZE..];
+    if records.len() < count * BINARY_FACET_SIZE {
+        return Err(format!("expected {} facets, the file is too short", count));
+    }
+
+    let vector = |bytes: &[u8]| {
+        let value = |i: usize| f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+        Vector3::new(value(0), value(1), value(2))
+    };
+
+    Ok(records
+        .chunks_exact(BINARY_FACET_SIZE)
+        .take(count)
+        .map(|record| Facet {
+            normal: vector(&record[0..12]),
+            corners: [vector(&record[12..24]), vector(&record[24..36]), vector(&record[36..48])],
+        })
+        .collect())
+}
+
+fn parse_ascii(data: &[u8]) -> Result<Vec<Facet>, String> {
+    let text = std::str::from_utf8(data).map_err(|_| "not a binary STL and not valid text")?;
+    let mut facets = Vec::new();
+    let mut normal = Vector3::new(0.0, 0.0, 0.0);
+    let mut corners = Vec::with_capacity(3);
+
+    for (number, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let vector = |values: &[&str]| -> Result<Vector3<f32>, String> {
+            let value = |i: usize| {
+                values[i]
+                    .parse::<f32>()
+                    .map_err(|_| format!("line {}: expected a number, found '{}'", number + 1, values[i]))
+            };
+            Ok(Vector3::new(value(0)?, value(1)?, value(2)?))
+        };
+
+        match words.as_slice() {
+            ["facet", "normal", values @ ..] if values.len() == 3 => {
+                normal = vector(values)?;
+                corners.clear();
+            }
+            ["vertex", values @ ..] if values.len() == 3 => corners.push(vector(values)?),
+            ["endfacet"] => {
+                if corners.len() != 3 {
+                    return Err(format!("line {}: a facet needs 3 vertices, found {}", number + 1, corners.len()));
+                }
+                facets.push(Facet {
+                    normal,
+                    corners: [corners[0], corners[1], corners[2]],
+                });
+            }
+            [] | ["solid", ..] | ["endsolid", ..] | ["outer", "loop"] | ["endloop"] => {}
+            _ => return Err(format!("line {}: unexpected '{}'", number + 1, line.trim())),
+        }
+    }
+
+    if facets.is_empty() {
+        return Err("the file contains no facets".to_string());
+    }
+
+    Ok(facets)
+}
+
+/// Every facet gets its own three vertices with the facet's normal, so the faces stay flat shaded
+fn build_mesh(facets: &[Facet]) -> ImportedMesh {
+    let mut mesh = ImportedMesh::default();
+
+    for facet in facets {
+        let [a, b, c] = facet.corners;
+        let geometric = (b - a).cross(c - a);
+        if geometric.magnitude2() == 0.0 {
+            // degenerate facets are common in exported models and have no area to draw
+            continue;
+        }
+
+        // many exporters write zero normals, the winding is authoritative then
+        let stored = facet.normal;
+        let valid_stored = stored.magnitude2() > 0.0 && stored.x.is_finite() && stored.y.is_finite() && stored.z.is_finite();
+        let (normal, corners) = if valid_stored && stored.dot(geometric) < 0.0 {
+            // the winding disagrees with the stored normal, flip it so back-face culling matches
+            (stored.normalize(), [a, c, b])
+        } else if valid_stored {
+            (stored.normalize(), [a, b, c])
+        } else {
+            (geometric.normalize(), [a, b, c])
+        };
+
+        for corner in &corners {
+            mesh.indices.push(mesh.positions.len() as u32);
+            mesh.positions.push((*corner).into());
+            mesh.normals.push(normal.into());
+            mesh.colors.push(BASE_COLOR);
+        }
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &[u8]) -> Result<ImportedMesh, String> {
+        let facets = if is_binary(data) { parse_binary(data)? } else { parse_ascii(data)? };
+        Ok(build_mesh(&facets))
+    }
+
+    #[test]
+    fn ascii_facet() {
+        let data = b"solid part\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\n\
+            endloop\nendfacet\nendsolid part\n";
+        let mesh = parse(data).unwrap();
+        assert_eq!(mesh.positions, vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        assert_eq!(mesh.normals, vec![[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(mesh.colors, vec![BASE_COLOR; 3]);
+    }
+
+    #[test]
+    fn binary_starting_with_solid() {
+        let mut data = b"solid but binary".to_vec();
+        data.resize(80, 0);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        for value in &[0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&[0, 0]);
+
+        let mesh = parse(&data).unwrap();
+        assert_eq!(mesh.positions.len(), 3);
+        assert_eq!(mesh.normals[0], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn winding_follows_the_stored_normal() {
+        let data = b"solid\nfacet normal 0 0 -1\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendfacet\n";
+        let mesh = parse(data).unwrap();
+        assert_eq!(mesh.positions, vec![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]]);
+        assert_eq!(mesh.normals[0], [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn zero_normals_and_degenerate_facets() {
+        let data = b"solid\nfacet normal 0 0 0\nvertex 0 0 0\nvertex 0 1 0\nvertex 1 0 0\nendfacet\n\
+            facet normal 0 0 1\nvertex 0 0 0\nvertex 1 1 1\nvertex 2 2 2\nendfacet\n";
+        let mesh = parse(data).unwrap();
+        assert_eq!(mesh.positions.len(), 3);
+        assert_eq!(mesh.normals[0], [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(parse(b"solid empty\nendsolid empty\n").is_err());
+        assert!(parse(b"solid\nfacet normal 0 0 1\nvertex 0 0 0\nvertex 1 0 0\nendfacet\n").is_err());
+        assert!(parse(b"solid\nfacet normal 0 0 1\nvertex 0 zero 0\n").is_err());
+    }
+}
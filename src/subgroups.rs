@@ -0,0 +1,300 @@
+use std::{fmt, sync::Arc};
+
+use cgmath::{Matrix4, Point3, Vector3};
+use vulkano::{
+    buffer::{BufferUsage, CpuAccessibleBuffer},
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, PrimaryCommandBuffer},
+    device::{Device, Queue},
+    image::{view::ImageView, AttachmentImage, ImageAccess},
+    instance::PhysicalDevice,
+    pipeline::{ComputePipeline, ComputePipelineAbstract},
+    sampler::Sampler,
+    sync::GpuFuture,
+};
+
+use crate::{
+    descriptors::{self, Resource},
+    frustum::Frustum,
+    ray::Aabb,
+};
+
+mod luminance_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/luminance_reduce.comp"
+    }
+}
+
+mod luminance_subgroup_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/luminance_reduce_subgroup.comp",
+        vulkan_version: "1.1",
+        spirv_version: "1.3"
+    }
+}
+
+mod cull_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/cull_compact.comp"
+    }
+}
+
+mod cull_subgroup_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/cull_compact_subgroup.comp",
+        vulkan_version: "1.1",
+        spirv_version: "1.3"
+    }
+}
+
+/// Side of the tile of pixels each luminance workgroup sums
+const LUMINANCE_TILE: u32 = 16;
+/// Invocations per culling workgroup
+const CULL_WORKGROUP_SIZE: u32 = 64;
+/// Boxes per axis of the grid the culling demo tests, spread around the origin
+const CULL_GRID: u32 = 16;
+const CULL_GRID_SPACING: f32 = 1.0;
+const CULL_BOX_SIZE: f32 = 0.4;
+const STORAGE_BUFFER: BufferUsage = BufferUsage {
+    storage_buffer: true,
+    ..BufferUsage::none()
+};
+
+/// What subgroup operations compute shaders can use. Devices below Vulkan 1.1 report nothing
+/// and get the default, which makes every demo take its scalar path.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SubgroupSupport {
+    /// Invocations per subgroup, 0 if unknown
+    pub size: u32,
+    pub compute: bool,
+    pub arithmetic: bool,
+    pub ballot: bool,
+}
+
+impl SubgroupSupport {
+    pub fn query(physical_device: PhysicalDevice) -> Self {
+        let properties = physical_device.properties();
+        let operations = properties.subgroup_supported_operations;
+
+        Self {
+            size: properties.subgroup_size.unwrap_or(0),
+            compute: properties.subgroup_supported_stages.is_some_and(|stages| stages.compute),
+            arithmetic: operations.is_some_and(|operations| operations.arithmetic),
+            ballot: operations.is_some_and(|operations| operations.ballot),
+        }
+    }
+
+    /// `subgroupAdd` in compute shaders. The luminance shader keeps a sum per subgroup in
+    /// shared memory sized for subgroups of at least 4.
+    pub fn reductions(&self) -> bool {
+        self.compute && self.arithmetic && self.size >= 4
+    }
+
+    /// `subgroupBallot` and counting its bits in compute shaders
+    pub fn ballots(&self) -> bool {
+        self.compute && self.ballot
+    }
+}
+
+impl fmt::Display for SubgroupSupport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.compute {
+            return write!(f, "none in compute shaders");
+        }
+
+        let operations: Vec<_> = [(self.arithmetic, "arithmetic"), (self.ballot, "ballot")]
+            .iter()
+            .filter(|(supported, _)| *supported)
+            .map(|(_, name)| *name)
+            .collect();
+        write!(
+            f,
+            "{} invocations, {}",
+            self.size,
+            if operations.is_empty() { "no arithmetic or ballot".to_string() } else { operations.join(" ") }
+        )
+    }
+}
+
+/// Sums the luminance of every pixel of an image in a compute pass, one workgroup per tile. The
+/// tiles are added up on the CPU once the frame it was recorded into has finished.
+pub struct LuminanceReduction {
+    support: SubgroupSupport,
+    partials: Option<Arc<CpuAccessibleBuffer<[[f32; 2]]>>>,
+    pixels: u32,
+}
+
+impl LuminanceReduction {
+    /// Uses `subgroupAdd` if `support` allows, otherwise a reduction through shared memory
+    pub fn new(support: SubgroupSupport) -> Self {
+        Self {
+            support,
+            partials: None,
+            pixels: 0,
+        }
+    }
+
+    /// Records the reduction of `image` outside of a render pass
+    pub fn record(
+        &mut self,
+        device: &Arc<Device>,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: &Arc<ImageView<Arc<AttachmentImage>>>,
+    ) {
+        let [width, height] = image.image().dimensions().width_height();
+        let groups = [width.div_ceil(LUMINANCE_TILE), height.div_ceil(LUMINANCE_TILE), 1];
+        let partials = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            STORAGE_BUFFER,
+            false,
+            (0..groups[0] * groups[1]).map(|_| [0.0; 2]),
+        )
+        .unwrap();
+
+        let pipeline = if self.support.reductions() {
+            let shader = luminance_subgroup_shader::Shader::load(device.clone())
+                .expect("Failed to create luminance shader module");
+            Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None).unwrap())
+        } else {
+            let shader =
+                luminance_shader::Shader::load(device.clone()).expect("Failed to create luminance shader module");
+            Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None).unwrap())
+        };
+
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        let set = descriptors::build(
+            layout,
+            &[
+                Resource::SampledImage(image.clone(), Sampler::simple_repeat_linear_no_mipmap(device.clone())),
+                Resource::Buffer(partials.clone()),
+            ],
+        );
+        builder.dispatch(groups, pipeline, set, (), vec![]).unwrap();
+
+        self.partials = Some(partials);
+        self.pixels = width * height;
+    }
+
+    /// Average and log-average luminance, once the command buffer `record` went into has finished
+    pub fn report(&self) -> Result<String, String> {
+        let partials = match &self.partials {
+            Some(partials) => partials
+                .read()
+                .map_err(|e| format!("failed to read the luminance sums: {}", e))?,
+            None => return Ok("nothing was recorded".to_string()),
+        };
+
+        let [sum, log_sum] = partials
+            .iter()
+            .fold([0.0f64; 2], |[sum, log_sum], partial| [sum + partial[0] as f64, log_sum + partial[1] as f64]);
+        let pixels = self.pixels.max(1) as f64;
+        Ok(format!(
+            "average {:.4}, log-average {:.4} over {} pixels, summed with {}",
+            sum / pixels,
+            (log_sum / pixels).exp(),
+            self.pixels,
+            if self.support.reductions() { "subgroupAdd" } else { "shared memory" }
+        ))
+    }
+}
+
+/// Culls a grid of boxes against `view_projection` on the GPU and compacts the visible ones
+/// into a list, with ballots if `support` allows and one atomic per box otherwise. Compares the
+/// result with `Frustum::intersects_aabb`.
+pub fn cull_demo(queue: &Arc<Queue>, view_projection: Matrix4<f32>, support: SubgroupSupport) -> Result<String, String> {
+    let device = queue.device();
+    let boxes = cull_grid();
+    let box_data = boxes.iter().map(|aabb| {
+        [
+            [aabb.min.x, aabb.min.y, aabb.min.z, 0.0],
+            [aabb.max.x, aabb.max.y, aabb.max.z, 0.0],
+        ]
+    });
+    let box_buffer = CpuAccessibleBuffer::from_iter(device.clone(), STORAGE_BUFFER, false, box_data)
+        .map_err(|e| format!("failed to create the box buffer: {}", e))?;
+    // the count followed by room for every box
+    let visible_buffer = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        STORAGE_BUFFER,
+        false,
+        (0..boxes.len() + 1).map(|_| 0u32),
+    )
+    .map_err(|e| format!("failed to create the visible list: {}", e))?;
+
+    let ballots = support.ballots() && queue.family().supports_compute();
+    let pipeline = if ballots {
+        let shader = cull_subgroup_shader::Shader::load(device.clone()).expect("Failed to create culling shader module");
+        Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None).map_err(|e| e.to_string())?)
+    } else {
+        let shader = cull_shader::Shader::load(device.clone()).expect("Failed to create culling shader module");
+        Arc::new(ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None).map_err(|e| e.to_string())?)
+    };
+
+    let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+    let set = descriptors::build(layout, &[Resource::Buffer(box_buffer), Resource::Buffer(visible_buffer.clone())]);
+
+    let frustum = Frustum::from_matrix(view_projection);
+    // both shaders declare the same push constants
+    let push_constants = cull_shader::ty::PushConstants {
+        planes: frustum.planes(),
+        box_count: boxes.len() as u32,
+    };
+
+    let mut builder =
+        AutoCommandBufferBuilder::primary(device.clone(), queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    builder
+        .dispatch(
+            [(boxes.len() as u32).div_ceil(CULL_WORKGROUP_SIZE), 1, 1],
+            pipeline,
+            set,
+            push_constants,
+            vec![],
+        )
+        .unwrap();
+    builder
+        .build()
+        .unwrap()
+        .execute(queue.clone())
+        .map_err(|e| format!("failed to cull: {}", e))?
+        .then_signal_fence_and_flush()
+        .and_then(|future| future.wait(None))
+        .map_err(|e| format!("failed to cull: {}", e))?;
+
+    let visible = visible_buffer.read().unwrap();
+    let count = visible[0] as usize;
+    let mut gpu: Vec<_> = visible[1..=count].to_vec();
+    gpu.sort_unstable();
+    let cpu: Vec<_> = (0..boxes.len() as u32)
+        .filter(|&i| frustum.intersects_aabb(&boxes[i as usize]))
+        .collect();
+
+    Ok(format!(
+        "{} of {} boxes visible, compacted with {}, {} the CPU test",
+        count,
+        boxes.len(),
+        if ballots { "ballots" } else { "an atomic per box" },
+        if gpu == cpu { "matching" } else { "NOT matching" }
+    ))
+}
+
+/// `CULL_GRID` boxes along each axis, centered on the origin
+fn cull_grid() -> Vec<Aabb> {
+    let half = (CULL_GRID - 1) as f32 * CULL_GRID_SPACING / 2.0;
+    let mut boxes = Vec::with_capacity((CULL_GRID * CULL_GRID * CULL_GRID) as usize);
+    for x in 0..CULL_GRID {
+        for y in 0..CULL_GRID {
+            for z in 0..CULL_GRID {
+                let center = Point3::new(x as f32, y as f32, z as f32) * CULL_GRID_SPACING - Vector3::new(half, half, half);
+                let extent = Vector3::new(CULL_BOX_SIZE, CULL_BOX_SIZE, CULL_BOX_SIZE) / 2.0;
+                boxes.push(Aabb {
+                    min: center - extent,
+                    max: center + extent,
+                });
+            }
+        }
+    }
+    boxes
+}
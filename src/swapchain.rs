@@ -1,45 +1,183 @@
 use std::{sync::Arc, usize};
 
-use vulkano::{device::{Device, Queue}, format::Format, image::{ImageUsage, SwapchainImage}, instance::{Instance, PhysicalDevice}, swapchain::{Capabilities, ColorSpace, PresentMode, SupportedPresentModes, Surface, Swapchain, SwapchainBuilder}, sync::SharingMode};
+use log::{info, warn};
+use vulkano::{device::{Device, Queue}, format::{Format, FormatFeatures}, image::{ImageUsage, SwapchainImage}, instance::{Instance, PhysicalDevice}, swapchain::{Capabilities, ColorSpace, PresentMode, SupportedPresentModes, Surface, Swapchain, SwapchainBuilder}, sync::SharingMode};
 use winit::window::Window;
 
-fn choose_swap_surface_format(available_formats: &[(Format, ColorSpace)]) -> (Format, ColorSpace) {
-    *available_formats
+use crate::error::Error;
+
+/// Prefers 8 bit BGRA with the sRGB color space, otherwise takes the first format the surface lists
+fn choose_swap_surface_format(available_formats: &[(Format, ColorSpace)]) -> Result<(Format, ColorSpace), String> {
+    available_formats
         .iter()
         .find(|(format, color_space)| {
             *format == Format::B8G8R8A8Unorm && *color_space == ColorSpace::SrgbNonLinear
         })
-        .unwrap_or_else(|| &available_formats[0])
+        .or_else(|| available_formats.first())
+        .copied()
+        .ok_or_else(|| "the surface supports no image formats".to_string())
 }
 
-fn choose_swap_present_mode(available_present_modes: SupportedPresentModes) -> PresentMode {
-    if available_present_modes.mailbox {
-        PresentMode::Mailbox
-    } else if available_present_modes.immediate {
-        PresentMode::Immediate
-    } else {
-        PresentMode::Fifo
+/// FIFO is the only mode that waits for vertical blank. Every surface has to support it, but a
+/// broken driver that leaves it out still gets whatever mode it does list. A `requested` mode the
+/// surface doesn't support falls back to the one `vsync` picks.
+fn choose_swap_present_mode(
+    available_present_modes: SupportedPresentModes,
+    vsync: bool,
+    requested: Option<PresentMode>,
+) -> Result<PresentMode, String> {
+    if let Some(mode) = requested {
+        if available_present_modes.supports(mode) {
+            info!("Present mode {:?}, as the settings ask", mode);
+            return Ok(mode);
+        }
+        warn!("The surface doesn't support the {:?} present mode", mode);
     }
+
+    let preferred: &[PresentMode] = if vsync {
+        &[PresentMode::Fifo, PresentMode::Relaxed, PresentMode::Mailbox, PresentMode::Immediate]
+    } else {
+        &[PresentMode::Mailbox, PresentMode::Immediate, PresentMode::Fifo, PresentMode::Relaxed]
+    };
+
+    let mode = preferred
+        .iter()
+        .copied()
+        .find(|&mode| available_present_modes.supports(mode))
+        .ok_or_else(|| "the surface supports no present modes".to_string())?;
+    info!("Present mode {:?}, picked with vsync {}", mode, if vsync { "on" } else { "off" });
+    Ok(mode)
+}
+
+/// The present modes `present` can choose from on this surface
+pub fn supported_present_modes(available_present_modes: SupportedPresentModes) -> Vec<PresentMode> {
+    [PresentMode::Fifo, PresentMode::Relaxed, PresentMode::Mailbox, PresentMode::Immediate]
+        .iter()
+        .copied()
+        .filter(|&mode| available_present_modes.supports(mode))
+        .collect()
 }
 
+/// The surface's own size when it has one, otherwise the desired size within the surface's limits.
+/// Fails for a zero sized surface, which is what minimized windows report on some platforms.
 fn choose_swap_extent(
     capabilities: &Capabilities,
     desired_width: u32,
     desired_height: u32,
-) -> [u32; 2] {
-    if let Some(current_extent) = capabilities.current_extent {
-        current_extent
-    } else {
-        let mut actual_extent = [desired_width, desired_height];
-        actual_extent[0] = capabilities.min_image_extent[0]
-            .max(capabilities.max_image_extent[0].min(actual_extent[0]));
-        actual_extent[1] = capabilities.min_image_extent[1]
-            .max(capabilities.max_image_extent[1].min(actual_extent[1]));
+) -> Result<[u32; 2], String> {
+    let [min, max] = [capabilities.min_image_extent, capabilities.max_image_extent];
+    if min[0] > max[0] || min[1] > max[1] {
+        return Err(format!(
+            "the surface's minimum extent {}x{} is larger than its maximum {}x{}",
+            min[0], min[1], max[0], max[1]
+        ));
+    }
+
+    let extent = match capabilities.current_extent {
+        Some(current_extent) => current_extent,
+        None => [desired_width.clamp(min[0], max[0]), desired_height.clamp(min[1], max[1])],
+    };
+
+    if extent[0] == 0 || extent[1] == 0 {
+        return Err(format!("the surface has no area ({}x{})", extent[0], extent[1]));
+    }
+    Ok(extent)
+}
 
-        actual_extent
+/// Names of the image usages in the settings file and in errors, in `usage_flags` order
+const USAGE_NAMES: [&str; 5] = ["transfer_src", "transfer_dst", "sampled", "storage", "color_attachment"];
+
+fn usage_flags(usage: ImageUsage) -> [bool; 5] {
+    [
+        usage.transfer_source,
+        usage.transfer_destination,
+        usage.sampled,
+        usage.storage,
+        usage.color_attachment,
+    ]
+}
+
+fn usage_names(usage: ImageUsage) -> Vec<&'static str> {
+    USAGE_NAMES
+        .iter()
+        .zip(usage_flags(usage).iter())
+        .filter(|(_, &set)| set)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// `auto` or the name of a present mode, `auto` leaves the choice to `vsync`
+pub fn parse_present_mode(text: &str) -> Result<Option<PresentMode>, String> {
+    match text {
+        "auto" => Ok(None),
+        "fifo" => Ok(Some(PresentMode::Fifo)),
+        "relaxed" => Ok(Some(PresentMode::Relaxed)),
+        "mailbox" => Ok(Some(PresentMode::Mailbox)),
+        "immediate" => Ok(Some(PresentMode::Immediate)),
+        other => Err(format!("expected auto, fifo, relaxed, mailbox or immediate, got '{}'", other)),
+    }
+}
+
+/// Parses a space separated list of usages like `transfer_src storage`
+pub fn parse_image_usage(text: &str) -> Result<ImageUsage, String> {
+    let mut usage = ImageUsage::none();
+    for name in text.split_whitespace() {
+        match name {
+            "transfer_src" => usage.transfer_source = true,
+            "transfer_dst" => usage.transfer_destination = true,
+            "sampled" => usage.sampled = true,
+            "storage" => usage.storage = true,
+            "color_attachment" => usage.color_attachment = true,
+            other => return Err(format!("unknown image usage '{}', expected one of {}", other, USAGE_NAMES.join(", "))),
+        }
+    }
+    Ok(usage)
+}
+
+/// Every `required` usage and those `optional` ones the surface and the swap chain format allow.
+/// Storage and sampling also need support from the format, the surface alone doesn't promise them.
+fn choose_image_usage(
+    supported: ImageUsage,
+    format_features: FormatFeatures,
+    required: ImageUsage,
+    optional: ImageUsage,
+) -> Result<ImageUsage, String> {
+    let supported = ImageUsage {
+        sampled: supported.sampled && format_features.sampled_image,
+        storage: supported.storage && format_features.storage_image,
+        ..supported
+    };
+
+    let missing: Vec<_> = USAGE_NAMES
+        .iter()
+        .zip(usage_flags(required).iter().zip(usage_flags(supported).iter()))
+        .filter(|(_, (&required, &supported))| required && !supported)
+        .map(|(name, _)| *name)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "the swap chain images can't be used as {}, the surface supports {}",
+            missing.join(", "),
+            usage_names(supported).join(", ")
+        ));
     }
+
+    Ok(ImageUsage {
+        transfer_source: required.transfer_source || (optional.transfer_source && supported.transfer_source),
+        transfer_destination: required.transfer_destination
+            || (optional.transfer_destination && supported.transfer_destination),
+        sampled: required.sampled || (optional.sampled && supported.sampled),
+        storage: required.storage || (optional.storage && supported.storage),
+        color_attachment: required.color_attachment || (optional.color_attachment && supported.color_attachment),
+        ..ImageUsage::none()
+    })
 }
 
+pub type SwapChainWithImages = (Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>);
+
+/// `extra_usage` is required on top of rendering to the images, screenshots are enabled when the
+/// surface allows copying from them
+#[allow(clippy::too_many_arguments)]
 pub fn create_swap_chain(
     instance: &Arc<Instance>,
     surface: &Arc<Surface<Window>>,
@@ -47,21 +185,47 @@ pub fn create_swap_chain(
     device: &Arc<Device>,
     graphics_queue: &Arc<Queue>,
     presentation_queue: &Arc<Queue>,
-    old_swap_chain: Option<&Arc<Swapchain<Window>>>
-) -> (Arc<Swapchain<Window>>, Vec<Arc<SwapchainImage<Window>>>) {
+    old_swap_chain: Option<&Arc<Swapchain<Window>>>,
+    vsync: bool,
+    present_mode: Option<PresentMode>,
+    extra_usage: ImageUsage,
+) -> Result<SwapChainWithImages, Error> {
     let mut builder: Option<SwapchainBuilder<Window>> = None;
 
+    let physical_device = PhysicalDevice::from_index(instance, physical_device_index).unwrap();
+    let capabilities = surface.capabilities(physical_device)?;
+    let present_mode =
+        choose_swap_present_mode(capabilities.present_modes, vsync, present_mode).map_err(Error::UnsupportedSurface)?;
+    // surfaces without a size of their own take the window's
+    let window_size = surface.window().inner_size();
+    let extent = choose_swap_extent(&capabilities, window_size.width, window_size.height)
+        .map_err(Error::UnsupportedSurface)?;
+    let (surface_format, color_space) =
+        choose_swap_surface_format(&capabilities.supported_formats).map_err(Error::UnsupportedSurface)?;
+
+    // recreating keeps the format, the usage has to be supported for that one
+    let format = old_swap_chain.map_or(surface_format, |swap_chain| swap_chain.format());
+    let required_usage = ImageUsage {
+        color_attachment: true,
+        ..extra_usage
+    };
+    // transfer source lets screenshots copy the presented image
+    let optional_usage = ImageUsage {
+        transfer_source: true,
+        ..ImageUsage::none()
+    };
+    let image_usage = choose_image_usage(
+        capabilities.supported_usage_flags,
+        format.properties(physical_device).optimal_tiling_features,
+        required_usage,
+        optional_usage,
+    )
+    .map_err(Error::UnsupportedSurface)?;
+
     if let Some(swap_chain) = old_swap_chain {
-        builder = Some(swap_chain.recreate()); // new feature in vulkako 0.24, breaks lesson 16
+        // new feature in vulkako 0.24, breaks lesson 16
+        builder = Some(swap_chain.recreate().present_mode(present_mode).dimensions(extent).usage(image_usage));
     } else {
-        let physical_device = PhysicalDevice::from_index(instance, physical_device_index).unwrap();
-        let capabilities = surface
-            .capabilities(physical_device)
-            .expect("failed to get surface capabilities");
-
-        let (surface_format, color_space) = choose_swap_surface_format(&capabilities.supported_formats);
-        let present_mode = choose_swap_present_mode(capabilities.present_modes);
-        let extent = choose_swap_extent(&capabilities, 1024, 768);
 
         let mut image_count = capabilities.min_image_count + 1;
 
@@ -71,11 +235,6 @@ pub fn create_swap_chain(
             }
         }
 
-        let image_usage = ImageUsage {
-            color_attachment: true,
-            ..ImageUsage::none()
-        };
-
         let sharing: SharingMode =
             if graphics_queue.id_within_family() == presentation_queue.id_within_family() {
                 graphics_queue.into()
@@ -97,8 +256,92 @@ pub fn create_swap_chain(
 
     }
 
-    builder
-        .expect("Failed to create swap chain builder")
-        .build()
-        .expect("Failed to build swap chain")
+    Ok(builder.expect("Failed to create swap chain builder").build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use vulkano::swapchain::{SupportedCompositeAlpha, SupportedSurfaceTransforms, SurfaceTransform};
+
+    use super::*;
+
+    fn capabilities(current_extent: Option<[u32; 2]>, min: [u32; 2], max: [u32; 2]) -> Capabilities {
+        Capabilities {
+            min_image_count: 2,
+            max_image_count: None,
+            current_extent,
+            min_image_extent: min,
+            max_image_extent: max,
+            max_image_array_layers: 1,
+            supported_transforms: SupportedSurfaceTransforms::none(),
+            current_transform: SurfaceTransform::Identity,
+            supported_composite_alpha: SupportedCompositeAlpha::none(),
+            supported_usage_flags: ImageUsage::none(),
+            supported_formats: Vec::new(),
+            present_modes: SupportedPresentModes::none(),
+        }
+    }
+
+    #[test]
+    fn no_formats() {
+        assert!(choose_swap_surface_format(&[]).is_err());
+    }
+
+    #[test]
+    fn prefers_bgra_srgb_else_first_format() {
+        let bgra = (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear);
+        let rgba = (Format::R8G8B8A8Unorm, ColorSpace::SrgbNonLinear);
+        assert_eq!(choose_swap_surface_format(&[rgba, bgra]), Ok(bgra));
+        assert_eq!(choose_swap_surface_format(&[rgba]), Ok(rgba));
+    }
+
+    #[test]
+    fn no_present_modes() {
+        assert!(choose_swap_present_mode(SupportedPresentModes::none(), true, None).is_err());
+        assert!(choose_swap_present_mode(SupportedPresentModes::none(), false, Some(PresentMode::Fifo)).is_err());
+    }
+
+    #[test]
+    fn fifo_without_vsync_when_nothing_faster() {
+        let modes = SupportedPresentModes {
+            fifo: true,
+            ..SupportedPresentModes::none()
+        };
+        assert_eq!(choose_swap_present_mode(modes, false, None), Ok(PresentMode::Fifo));
+    }
+
+    #[test]
+    fn unsupported_request_falls_back() {
+        let modes = SupportedPresentModes {
+            fifo: true,
+            mailbox: true,
+            ..SupportedPresentModes::none()
+        };
+        assert_eq!(choose_swap_present_mode(modes, true, Some(PresentMode::Immediate)), Ok(PresentMode::Fifo));
+        assert_eq!(choose_swap_present_mode(modes, false, None), Ok(PresentMode::Mailbox));
+    }
+
+    #[test]
+    fn min_above_max() {
+        assert!(choose_swap_extent(&capabilities(None, [800, 600], [640, 480]), 700, 500).is_err());
+    }
+
+    #[test]
+    fn zero_area() {
+        assert!(choose_swap_extent(&capabilities(Some([0, 600]), [0, 0], [4096, 4096]), 800, 600).is_err());
+        assert!(choose_swap_extent(&capabilities(None, [0, 0], [4096, 4096]), 800, 0).is_err());
+    }
+
+    #[test]
+    fn desired_size_clamped_without_current_extent() {
+        let capabilities = capabilities(None, [320, 240], [1920, 1080]);
+        assert_eq!(choose_swap_extent(&capabilities, 100, 5000), Ok([320, 1080]));
+        assert_eq!(choose_swap_extent(&capabilities, 800, 600), Ok([800, 600]));
+    }
+
+    #[test]
+    fn current_extent_wins() {
+        let capabilities = capabilities(Some([1024, 768]), [1, 1], [4096, 4096]);
+        assert_eq!(choose_swap_extent(&capabilities, 800, 600), Ok([1024, 768]));
+    }
 }
@@ -0,0 +1,176 @@
+//! Detail textures for the streamed world's chunks. Grass, dirt, rock and snow are generated
+//! into the layers of one array texture, and the terrain pipeline blends them by the weights
+//! every chunk vertex carries in its splat buffer. On steep slopes they can be projected along
+//! all three axes instead of only from above.
+
+use std::{f32::consts::TAU, sync::Arc};
+
+use cgmath::{Matrix4, Vector3};
+use vulkano::{
+    buffer::BufferAccess,
+    command_buffer::{AutoCommandBufferBuilder, DynamicState},
+    device::Device,
+    pipeline::{vertex::TwoBuffersDefinition, GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{RenderPass, Subpass},
+};
+
+use crate::{
+    descriptors::DescriptorCache,
+    error::Error,
+    frame_log::FrameCounters,
+    frustum::Frustum,
+    post,
+    staging::Staging,
+    texture::TextureArray,
+    vertex::Vertex,
+    world::{Layer, Splat, World, LAYERS},
+};
+
+/// Side of a detail texture in texels
+const DETAIL_SIZE: u32 = 64;
+/// Times a detail texture repeats per world unit
+const TILING: f32 = 0.5;
+
+pub mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/terrain.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/terrain.frag"
+    }
+}
+
+/// The detail textures, shared by every chunk
+pub struct Terrain {
+    details: TextureArray,
+}
+
+impl Terrain {
+    /// Usable once `staging` was submitted
+    pub fn new(staging: &mut Staging) -> Result<Self, Error> {
+        let layers: Vec<Vec<u8>> = LAYERS.iter().map(|layer| detail_texture(*layer)).collect();
+        Ok(Self {
+            details: TextureArray::new(staging, [DETAIL_SIZE; 2], &layers)?,
+        })
+    }
+
+    /// Records the world's chunks inside `frustum` into the scene pass, every chunk a draw
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+        cache: &DescriptorCache,
+        world: &World,
+        frustum: Option<&Frustum>,
+        view_projection: Matrix4<f32>,
+        sun_direction: Vector3<f32>,
+        counters: &mut FrameCounters,
+    ) {
+        let push_constants = vertex_shader::ty::PushConstants {
+            view_projection: view_projection.into(),
+            sun_direction: sun_direction.extend(0.0).into(),
+            tiling: TILING,
+            triplanar: world.triplanar as u32,
+        };
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap();
+        let set = cache.get(layout, &[self.details.resource()]);
+
+        for (mesh, splat, bounds) in world.splatted_chunks() {
+            if frustum.is_some_and(|frustum| !frustum.intersects_aabb(&bounds)) {
+                continue;
+            }
+
+            let vertex_buffers: Vec<Arc<dyn BufferAccess + Send + Sync>> =
+                vec![mesh.vertex_buffer.clone(), splat.clone()];
+            builder
+                .draw_indexed(
+                    pipeline.clone(),
+                    dynamic_state,
+                    vertex_buffers,
+                    mesh.index_buffer.clone(),
+                    set.clone(),
+                    push_constants,
+                    vec![],
+                )
+                .unwrap();
+            counters.draw(mesh.triangles());
+        }
+    }
+}
+
+/// RGBA texels of a tiling detail texture for `layer`, varied around the layer's average color
+fn detail_texture(layer: Layer) -> Vec<u8> {
+    let base = layer.color();
+    (0..DETAIL_SIZE * DETAIL_SIZE)
+        .flat_map(|index| {
+            let u = (index % DETAIL_SIZE) as f32 / DETAIL_SIZE as f32;
+            let v = (index / DETAIL_SIZE) as f32 / DETAIL_SIZE as f32;
+            let noise = tiling_noise(u, v, 4, 1) * 0.5 + tiling_noise(u, v, 16, 2) * 0.5;
+            let brightness = match layer {
+                Layer::Grass => 0.7 + 0.6 * noise,
+                // with a few light pebbles
+                Layer::Dirt if tiling_noise(u, v, 32, 3) > 0.8 => 1.4,
+                Layer::Dirt => 0.8 + 0.4 * noise,
+                // wavy bands of strata
+                Layer::Rock => 0.75 + 0.25 * ((v * 6.0 + noise * 0.8) * TAU).sin().abs() + 0.2 * (noise - 0.5),
+                Layer::Snow => 0.95 + 0.1 * (noise - 0.5),
+            };
+            let [r, g, b] = base.map(|channel| post::encode_srgb((channel * brightness).min(1.0)) * 255.0);
+            [r.round() as u8, g.round() as u8, b.round() as u8, 255]
+        })
+        .collect()
+}
+
+/// Value noise in 0..1 on a `cells` by `cells` lattice that wraps around, so the texture tiles
+fn tiling_noise(u: f32, v: f32, cells: u32, seed: u32) -> f32 {
+    let (x, y) = (u * cells as f32, v * cells as f32);
+    let (ix, iy) = (x.floor() as u32, y.floor() as u32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sy) = (smooth(x.fract()), smooth(y.fract()));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let lattice = |x: u32, y: u32| {
+        let mut hash = (x % cells).wrapping_mul(0x8da6_b343) ^ (y % cells).wrapping_mul(0xd816_3841) ^ seed;
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0x85eb_ca6b);
+        hash ^= hash >> 16;
+        hash as f32 / u32::MAX as f32
+    };
+
+    let near = lerp(lattice(ix, iy), lattice(ix + 1, iy), sx);
+    let far = lerp(lattice(ix, iy + 1), lattice(ix + 1, iy + 1), sx);
+    lerp(near, far, sy)
+}
+
+/// Takes the chunk's vertices from the first buffer and its `Splat` weights from the second
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "terrain vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "terrain fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(TwoBuffersDefinition::<Vertex, Splat>::new())
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .depth_stencil_simple_depth()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
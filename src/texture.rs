@@ -0,0 +1,137 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use vulkano::{
+    descriptor::{descriptor_set::DescriptorSetDesc, DescriptorSet},
+    device::Queue,
+    image::{view::ImageView, ImmutableImage},
+    pipeline::GraphicsPipelineAbstract,
+    sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode},
+};
+
+use crate::{
+    descriptors::{DescriptorCache, Resource},
+    error::Error,
+    mipmaps::{self, MipGeneration},
+    staging::Staging,
+};
+
+/// An image on the GPU with a full mip chain, and the samplers the scene reads it with
+pub struct Texture {
+    view: Arc<ImageView<Arc<ImmutableImage>>>,
+    /// Blends between the two closest mip levels
+    sampler: Arc<Sampler>,
+    /// Only ever reads the full resolution level, to compare against mipmapping
+    base_level_sampler: Arc<Sampler>,
+    pub dimensions: [u32; 2],
+    pub mip_levels: u32,
+    /// How the levels below the first were made, `None` with only one level
+    pub generation: Option<MipGeneration>,
+    /// GPU time spent on the levels below the first, if the queue has timestamps
+    pub generation_time: Option<Duration>,
+}
+
+impl Texture {
+    /// Loads a PNG or JPEG file, the mip levels are generated on the GPU the way `generation` says
+    pub fn load(queue: &Arc<Queue>, path: &Path, generation: MipGeneration) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+            .to_rgba8();
+        let dimensions = [image.width(), image.height()];
+
+        let (image, generation_time) = mipmaps::generate(queue, dimensions, image.into_raw(), generation)?;
+        let mut texture = Self::from_image(queue, image, dimensions)?;
+        texture.generation = Some(generation);
+        texture.generation_time = generation_time;
+        Ok(texture)
+    }
+
+    /// A single white texel, sampling it leaves the vertex colors as they are. Usable once
+    /// `staging` was submitted.
+    pub fn white(staging: &mut Staging) -> Result<Self, Error> {
+        let image = staging.image(vec![255u8; 4].into_iter(), [1, 1], mipmaps::TEXTURE_FORMAT)?;
+        Ok(Self::from_image(staging.queue(), image, [1, 1]).unwrap())
+    }
+
+    fn from_image(queue: &Arc<Queue>, image: Arc<ImmutableImage>, dimensions: [u32; 2]) -> Result<Self, String> {
+        let mip_levels = image.mipmap_levels();
+
+        Ok(Self {
+            view: ImageView::new(image).map_err(|e| format!("failed to create the texture view: {}", e))?,
+            sampler: create_sampler(queue, mip_levels as f32)?,
+            base_level_sampler: create_sampler(queue, 0.0)?,
+            dimensions,
+            mip_levels,
+            generation: None,
+            generation_time: None,
+        })
+    }
+
+    /// Binds the texture as the combined image sampler at binding 0 of `set`, and `emissive_map`
+    /// at binding 1 if the shaders read one, reading only the full resolution level unless
+    /// `mipmaps` is set. `None` when the pipeline's shaders don't sample a texture there, like
+    /// the UV and depth debug views. The set comes from `cache`, it only changes with the
+    /// pipeline.
+    pub fn descriptor_set(
+        &self,
+        emissive_map: &Texture,
+        cache: &DescriptorCache,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        set: usize,
+        mipmaps: bool,
+    ) -> Option<Arc<dyn DescriptorSet + Send + Sync>> {
+        let layout = pipeline.layout().descriptor_set_layout(set)?;
+        let resources = [self, emissive_map].map(|texture| {
+            let sampler = if mipmaps { &texture.sampler } else { &texture.base_level_sampler };
+            Resource::SampledImage(texture.view.clone(), sampler.clone())
+        });
+        Some(cache.get(layout, &resources[..layout.num_bindings().min(resources.len())]))
+    }
+}
+
+/// Images of the same size in the layers of one 2D array image, sampled by layer index from a
+/// single binding, like the terrain's detail textures
+pub struct TextureArray {
+    view: Arc<ImageView<Arc<ImmutableImage>>>,
+    sampler: Arc<Sampler>,
+    pub dimensions: [u32; 2],
+    pub layers: u32,
+}
+
+impl TextureArray {
+    /// `layers` holds the RGBA pixels of every layer, each row by row. There are no mip levels.
+    /// Usable once `staging` was submitted.
+    pub fn new(staging: &mut Staging, dimensions: [u32; 2], layers: &[Vec<u8>]) -> Result<Self, Error> {
+        let pixels: Vec<u8> = layers.concat();
+        let image = staging.image_array(pixels.into_iter(), dimensions, layers.len() as u32, mipmaps::TEXTURE_FORMAT)?;
+
+        Ok(Self {
+            view: ImageView::new(image).unwrap(),
+            sampler: create_sampler(staging.queue(), 0.0).unwrap(),
+            dimensions,
+            layers: layers.len() as u32,
+        })
+    }
+
+    /// The whole array as one combined image sampler
+    pub fn resource(&self) -> Resource {
+        Resource::SampledImage(self.view.clone(), self.sampler.clone())
+    }
+}
+
+/// Trilinear sampler that reads mip levels up to `max_lod`
+fn create_sampler(queue: &Arc<Queue>, max_lod: f32) -> Result<Arc<Sampler>, String> {
+    Sampler::new(
+        queue.device().clone(),
+        Filter::Linear,
+        Filter::Linear,
+        MipmapMode::Linear,
+        SamplerAddressMode::Repeat,
+        SamplerAddressMode::Repeat,
+        SamplerAddressMode::Repeat,
+        0.0,
+        1.0,
+        0.0,
+        max_lod,
+    )
+    .map_err(|e| format!("failed to create the texture sampler: {}", e))
+}
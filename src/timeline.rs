@@ -0,0 +1,291 @@
+//! Keyframed demo scripts. A timeline file moves the camera, the spawned objects and the post
+//! effect parameters over time, values between keyframes are interpolated linearly.
+//!
+//! Every line is `<seconds> <track> <values>`, `#` starts a comment:
+//!
+//! ```text
+//! 0  camera 0 0 0 3 0 0      # target x y z, distance, yaw and pitch in degrees
+//! 8  camera 0 0 0 6 360 30
+//! 0  object 0 -1 0 0         # object id, position x y z
+//! 4  object 0 1 0 0
+//! 6  gamma 1.0
+//! 8  gamma 1.4
+//! ```
+
+use std::{fmt, fs, path::Path, path::PathBuf, str::FromStr};
+
+use cgmath::{Deg, Point3, Rad};
+
+use crate::camera::Camera;
+
+/// What a track animates, each has a fixed number of values per keyframe
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Track {
+    /// Target x, y, z, distance, yaw and pitch in degrees
+    Camera,
+    /// Position of the spawned object with this id
+    Object(u32),
+    Parameter(Parameter),
+}
+
+impl Track {
+    fn value_count(self) -> usize {
+        match self {
+            Track::Camera => 6,
+            Track::Object(_) => 3,
+            Track::Parameter(_) => 1,
+        }
+    }
+}
+
+/// Post effect values a track can drive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parameter {
+    Gamma,
+    Brightness,
+    Contrast,
+    GodRayDensity,
+    Shutter,
+}
+
+impl FromStr for Parameter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "gamma" => Ok(Parameter::Gamma),
+            "brightness" => Ok(Parameter::Brightness),
+            "contrast" => Ok(Parameter::Contrast),
+            "godrays" => Ok(Parameter::GodRayDensity),
+            "shutter" => Ok(Parameter::Shutter),
+            _ => Err(format!(
+                "unknown track '{}', expected camera, object, gamma, brightness, contrast, godrays or shutter",
+                name
+            )),
+        }
+    }
+}
+
+struct Keyframe {
+    time: f32,
+    values: Vec<f32>,
+}
+
+/// The keyframes of one track, sorted by time
+struct Curve {
+    track: Track,
+    keyframes: Vec<Keyframe>,
+}
+
+impl Curve {
+    /// Holds the first and last keyframes before and after them
+    fn sample(&self, time: f32) -> Vec<f32> {
+        let next = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes[0].values.clone();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].values.clone();
+        }
+
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - from.time) / (to.time - from.time);
+        from.values
+            .iter()
+            .zip(to.values.iter())
+            .map(|(from, to)| from + (to - from) * t)
+            .collect()
+    }
+}
+
+/// Where the camera is at a point of the timeline
+#[derive(Clone, Copy, Debug)]
+pub struct CameraPose {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+}
+
+impl CameraPose {
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.target = self.target;
+        camera.distance = self.distance;
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+    }
+}
+
+/// The values of every track at one point of the timeline
+#[derive(Default)]
+pub struct TimelineValues {
+    pub camera: Option<CameraPose>,
+    pub objects: Vec<(u32, Point3<f32>)>,
+    pub parameters: Vec<(Parameter, f32)>,
+}
+
+/// A timeline file and the playback position in it. It plays once and stops at the end,
+/// holding the last keyframes.
+pub struct Timeline {
+    pub path: PathBuf,
+    curves: Vec<Curve>,
+    duration: f32,
+    time: f32,
+    pub playing: bool,
+}
+
+impl Timeline {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("failed to read file: {}", e))?;
+        let curves = parse(&text)?;
+        let duration = curves
+            .iter()
+            .filter_map(|curve| curve.keyframes.last())
+            .map(|keyframe| keyframe.time)
+            .fold(0.0, f32::max);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            curves,
+            duration,
+            time: 0.0,
+            playing: true,
+        })
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Moves the playback position by `seconds` while playing, pausing at the end
+    pub fn advance(&mut self, seconds: f32) {
+        if !self.playing {
+            return;
+        }
+
+        self.time += seconds;
+        if self.time >= self.duration {
+            self.time = self.duration;
+            self.playing = false;
+        }
+    }
+
+    /// Jumps to `time`, clamped to the timeline
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration);
+    }
+
+    /// Starts playing, from the start again once the end was reached
+    pub fn play(&mut self) {
+        if self.time >= self.duration {
+            self.time = 0.0;
+        }
+        self.playing = true;
+    }
+
+    /// The values of every track at the playback position
+    pub fn values(&self) -> TimelineValues {
+        let mut values = TimelineValues::default();
+        for curve in &self.curves {
+            let sample = curve.sample(self.time);
+            match curve.track {
+                Track::Camera => {
+                    values.camera = Some(CameraPose {
+                        target: Point3::new(sample[0], sample[1], sample[2]),
+                        distance: sample[3],
+                        yaw: Deg(sample[4]).into(),
+                        pitch: Deg(sample[5]).into(),
+                    })
+                }
+                Track::Object(id) => values.objects.push((id, Point3::new(sample[0], sample[1], sample[2]))),
+                Track::Parameter(parameter) => values.parameters.push((parameter, sample[0])),
+            }
+        }
+        values
+    }
+}
+
+impl fmt::Display for Timeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {:.2} of {:.2} s, {}, {} tracks",
+            self.path.display(),
+            self.time,
+            self.duration,
+            if self.playing { "playing" } else { "paused" },
+            self.curves.len()
+        )
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<Curve>, String> {
+    let mut curves: Vec<Curve> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (time, track, values) =
+            parse_line(line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        let keyframe = Keyframe { time, values };
+        match curves.iter_mut().find(|curve| curve.track == track) {
+            Some(curve) => curve.keyframes.push(keyframe),
+            None => curves.push(Curve {
+                track,
+                keyframes: vec![keyframe],
+            }),
+        }
+    }
+
+    if curves.is_empty() {
+        return Err("the timeline has no keyframes".to_string());
+    }
+    for curve in &mut curves {
+        // keyframes may be written in any order, at equal times the later line wins
+        curve.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        curve.keyframes.dedup_by(|later, earlier| {
+            let same_time = later.time == earlier.time;
+            if same_time {
+                std::mem::swap(&mut later.values, &mut earlier.values);
+            }
+            same_time
+        });
+    }
+    Ok(curves)
+}
+
+fn parse_line(line: &str) -> Result<(f32, Track, Vec<f32>), String> {
+    let words: Vec<_> = line.split_whitespace().collect();
+    let number = |word: &str| {
+        word.parse::<f32>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("expected a number, got '{}'", word))
+    };
+
+    let time = number(words[0])?;
+    if time < 0.0 {
+        return Err("keyframe times can't be negative".to_string());
+    }
+    let (track, values) = match words.get(1..) {
+        Some(["camera", values @ ..]) => (Track::Camera, values),
+        Some(["object", id, values @ ..]) => {
+            let id = id.parse().map_err(|_| format!("expected an object id, got '{}'", id))?;
+            (Track::Object(id), values)
+        }
+        Some([name, values @ ..]) => (Track::Parameter(name.parse()?), values),
+        _ => return Err("expected a track after the time".to_string()),
+    };
+
+    if values.len() != track.value_count() {
+        return Err(format!("expected {} values, got {}", track.value_count(), values.len()));
+    }
+    let values = values.iter().map(|value| number(value)).collect::<Result<_, _>>()?;
+    Ok((time, track, values))
+}
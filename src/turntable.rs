@@ -0,0 +1,52 @@
+use std::path::PathBuf;
+
+use cgmath::{Deg, Matrix4, Point3, Rad, Vector3};
+
+/// Orbits the camera once around the origin, where scenes and loaded models are centered,
+/// saving every frame
+pub struct Turntable {
+    /// Frames per revolution, each one is saved
+    pub frames: u32,
+    pub radius: f32,
+    /// Angle of the camera above the horizon
+    pub elevation: Deg<f32>,
+    /// Directory the PNG sequence is written to
+    pub output: PathBuf,
+    frame: u32,
+}
+
+impl Turntable {
+    pub fn new(frames: u32, radius: f32, elevation: Deg<f32>, output: PathBuf) -> Self {
+        Self {
+            frames: frames.max(1),
+            radius,
+            // straight above or below, look_at has no way to tell which way is up
+            elevation: Deg(elevation.0.clamp(-89.0, 89.0)),
+            output,
+            frame: 0,
+        }
+    }
+
+    /// Starts in front of the model, where the default camera is, and turns counter-clockwise
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let azimuth = Rad(std::f32::consts::PI * 2.0 * self.frame as f32 / self.frames as f32);
+        let elevation = Rad::from(self.elevation);
+        let eye = Point3::new(
+            self.radius * elevation.0.cos() * azimuth.0.sin(),
+            self.radius * elevation.0.sin(),
+            self.radius * elevation.0.cos() * azimuth.0.cos(),
+        );
+
+        Matrix4::look_at_rh(eye, Point3::new(0.0, 0.0, 0.0), Vector3::unit_y())
+    }
+
+    pub fn frame_path(&self) -> PathBuf {
+        self.output.join(format!("frame_{:04}.png", self.frame))
+    }
+
+    /// Moves to the next frame, returns `false` once the revolution is complete
+    pub fn advance(&mut self) -> bool {
+        self.frame += 1;
+        self.frame < self.frames
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use cgmath::{EuclideanSpace, Matrix4, Point3, Rad, SquareMatrix, Transform};
+use vulkano::{
+    buffer::CpuBufferPool,
+    descriptor::DescriptorSet,
+    device::Device,
+    pipeline::GraphicsPipelineAbstract,
+};
+
+use crate::{
+    audio,
+    descriptors::{self, Resource},
+    lighting::Light,
+    scene::Emission,
+};
+
+/// What triangle.vert and lit.frag read from their uniform buffer. Three mat4s need no std140
+/// padding and the audio bands are packed into vec4s, since std140 pads float arrays to 16 bytes
+/// per element. Each vec3 of the light, the camera and the emission is followed by a 4 byte
+/// scalar, which std140 packs into the vec3's last 4 bytes.
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct UniformBufferObject {
+    pub model: [[f32; 4]; 4],
+    pub view: [[f32; 4]; 4],
+    pub proj: [[f32; 4]; 4],
+    /// Levels of the `audio` command's bands, zero without audio
+    pub audio: [[f32; 4]; audio::BANDS / 4],
+    pub light_position: [f32; 3],
+    pub ambient: f32,
+    pub light_color: [f32; 3],
+    pub shininess: f32,
+    /// Eye position in world space, for the specular term
+    pub camera_position: [f32; 3],
+    /// The material's emission, see `scene::Emission`
+    pub emissive_intensity: f32,
+    pub emissive_color: [f32; 3],
+    /// Index of the frame being rendered, for pipelines without the scene's push constants
+    pub frame: u32,
+}
+
+impl UniformBufferObject {
+    pub fn new(
+        model: Matrix4<f32>,
+        view: Matrix4<f32>,
+        proj: Matrix4<f32>,
+        audio: [f32; audio::BANDS],
+        light: &Light,
+        emission: Emission,
+        frame: u64,
+    ) -> Self {
+        let camera_position = view
+            .invert()
+            .map_or(Point3::origin(), |camera| camera.transform_point(Point3::origin()));
+        let mut packed = [[0.0; 4]; audio::BANDS / 4];
+        for (band, level) in audio.iter().enumerate() {
+            packed[band / 4][band % 4] = *level;
+        }
+
+        Self {
+            model: model.into(),
+            view: view.into(),
+            proj: proj.into(),
+            audio: packed,
+            light_position: light.position.into(),
+            ambient: light.ambient,
+            light_color: light.color,
+            shininess: light.shininess,
+            camera_position: camera_position.into(),
+            emissive_intensity: emission.intensity,
+            emissive_color: emission.color,
+            frame: frame as u32,
+        }
+    }
+}
+
+/// Turns a mesh `angle` radians around its own Z axis, for `Animation::Rotate`
+pub fn rotation(angle: f32) -> Matrix4<f32> {
+    Matrix4::from_angle_z(Rad(angle))
+}
+
+/// Hands out one uniform buffer per draw. The pool reuses a buffer once the command buffer that
+/// read it has finished, so writing the matrices every frame never races the GPU.
+pub struct UniformBuffers {
+    pool: CpuBufferPool<UniformBufferObject>,
+}
+
+impl UniformBuffers {
+    pub fn new(device: &Arc<Device>) -> Self {
+        Self {
+            pool: CpuBufferPool::uniform_buffer(device.clone()),
+        }
+    }
+
+    /// Set 0 of `pipeline`, holding a fresh buffer with `uniforms`
+    pub fn descriptor_set(
+        &self,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        uniforms: UniformBufferObject,
+    ) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let buffer = self.pool.next(uniforms).unwrap();
+        let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+        descriptors::build(layout, &[Resource::Buffer(Arc::new(buffer))])
+    }
+}
@@ -0,0 +1,181 @@
+//! Routes the validation layers' messages to the `log` crate, at the level matching their
+//! severity and under the `vulkan` target. Each record names the message ID and the objects the
+//! layer reported, by the names `debug_utils::set_object_name` gave them. The layers themselves
+//! are looked up by name when the instance is created.
+
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use log::{info, log, log_enabled, warn, Level};
+use vulkano::instance::{
+    debug::{DebugCallback, Message, MessageSeverity, MessageType},
+    layers_list, Instance,
+};
+
+/// Layer sets that validate, in order of preference. The Khronos layer replaced the LunarG meta
+/// layer, which current SDKs no longer ship.
+const LAYER_SETS: [&[&str]; 2] = [
+    &["VK_LAYER_KHRONOS_validation"],
+    &["VK_LAYER_LUNARG_standard_validation"],
+];
+
+/// Target of the log records, so a logger can filter them apart from the application's
+const TARGET: &str = "vulkan";
+const TYPE_NAMES: [&str; 3] = ["general", "validation", "performance"];
+
+/// `general`, `validation` and `performance` separated by spaces, or `all`
+pub fn parse_message_types(text: &str) -> Result<MessageType, String> {
+    let mut types = MessageType::none();
+    for name in text.split_whitespace() {
+        match name {
+            "all" => types = MessageType::all(),
+            "general" => types.general = true,
+            "validation" => types.validation = true,
+            "performance" => types.performance = true,
+            other => {
+                return Err(format!(
+                    "unknown message type '{}', expected all or any of {}",
+                    other,
+                    TYPE_NAMES.join(", ")
+                ))
+            }
+        }
+    }
+    // Vulkan needs at least one type to create the messenger
+    if types == MessageType::none() {
+        return Err("expected at least one message type".to_string());
+    }
+    Ok(types)
+}
+
+/// The first validation layer set that is installed, logging which, or none when none is
+pub fn pick_layers() -> &'static [&'static str] {
+    let installed: Vec<String> = match layers_list() {
+        Ok(layers) => layers.map(|layer| layer.name().to_owned()).collect(),
+        Err(e) => {
+            warn!("Failed to list the Vulkan layers, validation is off: {}", e);
+            return &[];
+        }
+    };
+    let available = |set: &&[&str]| set.iter().all(|name| installed.iter().any(|layer| layer == name));
+    match LAYER_SETS.iter().copied().find(available) {
+        Some(set) => {
+            info!("Validation layers: {}", set.join(", "));
+            set
+        }
+        None => {
+            warn!(
+                "None of the validation layers {} are installed, validation is off",
+                LAYER_SETS.iter().map(|set| set.join(" + ")).collect::<Vec<_>>().join(", ")
+            );
+            &[]
+        }
+    }
+}
+
+/// Logs the messages of the types in `types`, counting errors into `errors`. Verbose messages
+/// are only asked for when the log level lets trace records through.
+pub fn create_callback(
+    instance: &Arc<Instance>,
+    types: MessageType,
+    errors: &Arc<AtomicUsize>,
+) -> Option<DebugCallback> {
+    let severity = MessageSeverity {
+        error: true,
+        warning: true,
+        information: true,
+        verbose: log_enabled!(target: TARGET, Level::Trace),
+    };
+
+    let errors = errors.clone();
+    DebugCallback::new(instance, severity, types, move |message| {
+        if message.severity.error {
+            errors.fetch_add(1, Ordering::SeqCst);
+        }
+        route(message);
+    })
+    .ok()
+}
+
+fn level(severity: MessageSeverity) -> Level {
+    if severity.error {
+        Level::Error
+    } else if severity.warning {
+        Level::Warn
+    } else if severity.information {
+        Level::Info
+    } else {
+        Level::Trace
+    }
+}
+
+fn type_name(ty: MessageType) -> &'static str {
+    if ty.validation {
+        "validation"
+    } else if ty.performance {
+        "performance"
+    } else {
+        "general"
+    }
+}
+
+fn route(message: &Message) {
+    let (objects, text) = split_objects(message.description);
+    let mut line = format!(
+        "{} [{}] {}",
+        type_name(message.ty),
+        message.layer_prefix.unwrap_or("no id"),
+        text
+    );
+    if !objects.is_empty() {
+        let _ = write!(line, " (objects: {})", objects.join(", "));
+    }
+    log!(target: TARGET, level(message.severity), "{}", line);
+}
+
+/// The objects listed in a validation layer message and the rest of its text. The layers write
+/// `Object 0: handle = 0x1234, name = vertex buffer, type = VK_OBJECT_TYPE_BUFFER; | MessageID =
+/// 0x5678 | <text>`, a message in another shape is returned whole with no objects.
+fn split_objects(description: &str) -> (Vec<String>, &str) {
+    let mut objects = Vec::new();
+    let mut text = description;
+    for part in description.split(" | ") {
+        let part = part.trim();
+        if part.starts_with("MessageID") {
+            continue;
+        }
+        if !part.contains("Object ") {
+            // the message itself, which may contain the separator too
+            text = &description[description.find(part).unwrap_or(0)..];
+            break;
+        }
+        objects.extend(part.split(';').filter_map(describe_object));
+    }
+    (objects, text.trim())
+}
+
+/// `buffer 'vertex buffer'`, or `buffer 0x1234` when it has no name
+fn describe_object(object: &str) -> Option<String> {
+    // the first object follows the message's severity and VUID
+    let object = &object[object.find("Object ")?..];
+    let fields = object.split_once(": ")?.1;
+    let field = |key: &str| {
+        fields
+            .split(", ")
+            .find_map(|field| field.strip_prefix(key)?.strip_prefix(" = "))
+    };
+    let ty = field("type")?
+        .trim_start_matches("VK_OBJECT_TYPE_")
+        .to_lowercase()
+        .replace('_', " ");
+    Some(match (field("name"), field("handle")) {
+        (Some(name), _) => format!("{} '{}'", ty, name),
+        (None, Some(handle)) => format!("{} {}", ty, handle),
+        (None, None) => ty,
+    })
+}
@@ -0,0 +1,182 @@
+//! Grass on the streamed world's chunks. Every chunk carries the blades its worker scattered
+//! over it, and they are all drawn as instances of one low poly blade mesh. The blades sway in
+//! the wind with the time and fade out with the distance to the camera.
+
+use std::sync::Arc;
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3};
+use vulkano::{
+    buffer::{BufferAccess, BufferUsage, TypedBufferAccess},
+    command_buffer::{AutoCommandBufferBuilder, DynamicState},
+    device::Device,
+    pipeline::{vertex::OneVertexOneInstanceDefinition, GraphicsPipeline, GraphicsPipelineAbstract},
+    render_pass::{RenderPass, Subpass},
+};
+
+use crate::{
+    error::Error,
+    frame_log::FrameCounters,
+    frustum::Frustum,
+    ray::Aabb,
+    scene::MeshHandle,
+    staging::Staging,
+    vertex::Vertex,
+    world::{GrassBlade, World, MAX_GRASS_HEIGHT},
+};
+
+/// Width of a blade at its root
+const BLADE_WIDTH: f32 = 0.06;
+/// Distance from the camera where the blades start to fade out
+const FADE_START: f32 = 14.0;
+/// Distance from the camera where the blades are gone, chunks further away aren't drawn
+const FADE_END: f32 = 20.0;
+
+pub mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/grass.vert"
+    }
+}
+
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/grass.frag"
+    }
+}
+
+/// The blade mesh every grass blade is an instance of
+pub struct Vegetation {
+    blade: MeshHandle,
+}
+
+impl Vegetation {
+    /// Usable once `staging` was submitted
+    pub fn new(staging: &mut Staging) -> Result<Self, Error> {
+        let (vertices, indices) = blade();
+        let vertex_buffer = staging.buffer(vertices.into_iter(), BufferUsage::vertex_buffer())?;
+        let index_buffer = staging.buffer(indices.into_iter(), BufferUsage::index_buffer())?;
+        Ok(Self {
+            blade: MeshHandle::new(vertex_buffer, index_buffer),
+        })
+    }
+
+    /// Records the grass of the world's chunks close enough to be seen into the scene pass,
+    /// leaving out those outside `frustum`. A draw per chunk, with an instance per blade.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<L>(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<L>,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        dynamic_state: &DynamicState,
+        world: &World,
+        frustum: Option<&Frustum>,
+        view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+        sun_direction: Vector3<f32>,
+        time: f32,
+        counters: &mut FrameCounters,
+    ) {
+        let camera_position = view
+            .invert()
+            .map_or(Point3::origin(), |camera| camera.transform_point(Point3::origin()));
+        let push_constants = vertex_shader::ty::PushConstants {
+            view_projection: (projection * view).into(),
+            sun_direction: sun_direction.extend(0.0).into(),
+            camera_position: camera_position.into(),
+            time,
+            fade: [FADE_START, FADE_END],
+        };
+
+        for (grass, bounds) in world.grass_chunks() {
+            // the tips stand above the ground and lean with the wind
+            let bounds = Aabb {
+                min: bounds.min - Vector3::new(MAX_GRASS_HEIGHT, 0.0, MAX_GRASS_HEIGHT),
+                max: bounds.max + Vector3::new(MAX_GRASS_HEIGHT, MAX_GRASS_HEIGHT, MAX_GRASS_HEIGHT),
+            };
+            if distance_to(&bounds, camera_position) > FADE_END
+                || frustum.is_some_and(|frustum| !frustum.intersects_aabb(&bounds))
+            {
+                continue;
+            }
+
+            let instances: Arc<dyn BufferAccess + Send + Sync> = grass.clone();
+            builder
+                .draw_indexed(
+                    pipeline.clone(),
+                    dynamic_state,
+                    vec![self.blade.vertex_buffer.clone(), instances],
+                    self.blade.index_buffer.clone(),
+                    (),
+                    push_constants,
+                    vec![],
+                )
+                .unwrap();
+            counters.draw(self.blade.triangles() * grass.len() as u64);
+        }
+    }
+}
+
+/// Distance from `point` to the closest point of `bounds`, 0 inside them
+fn distance_to(bounds: &Aabb, point: Point3<f32>) -> f32 {
+    let closest = Point3::new(
+        point.x.clamp(bounds.min.x, bounds.max.x),
+        point.y.clamp(bounds.min.y, bounds.max.y),
+        point.z.clamp(bounds.min.z, bounds.max.z),
+    );
+    (point - closest).magnitude()
+}
+
+/// Two tapering quads crossed at a right angle, each narrowing to a tip, dark at the root and
+/// light at the tip. One unit high, the instances scale it.
+fn blade() -> (Vec<Vertex>, Vec<u32>) {
+    let half = BLADE_WIDTH / 2.0;
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for side in [Vector3::unit_x(), Vector3::unit_z()] {
+        let first = vertices.len() as u32;
+        let points = [
+            (-half, 0.0),
+            (half, 0.0),
+            (-half * 0.6, 0.5),
+            (half * 0.6, 0.5),
+            (0.0, 1.0),
+        ];
+        for (across, up) in points {
+            let position = side * across + Vector3::unit_y() * up;
+            let shade = 0.5 + 0.5 * up;
+            vertices.push(Vertex::new(position.into(), [shade; 3], [across / BLADE_WIDTH + 0.5, up]));
+        }
+        indices.extend([0, 1, 3, 3, 2, 0, 2, 3, 4].map(|index| first + index));
+    }
+    (vertices, indices)
+}
+
+/// Takes the blade's vertices from the first buffer and `GrassBlade` from the second. Both sides
+/// of a blade are drawn.
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>, Error> {
+    let vert_shader_module = vertex_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "grass vertex",
+        source,
+    })?;
+    let frag_shader_module = fragment_shader::Shader::load(device.clone()).map_err(|source| Error::ShaderModule {
+        name: "grass fragment",
+        source,
+    })?;
+
+    Ok(Arc::new(
+        GraphicsPipeline::start()
+            .vertex_input(OneVertexOneInstanceDefinition::<Vertex, GrassBlade>::new())
+            .vertex_shader(vert_shader_module.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1)
+            .fragment_shader(frag_shader_module.main_entry_point(), ())
+            .cull_mode_disabled()
+            .depth_stencil_simple_depth()
+            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+            .build(device.clone())?,
+    ))
+}
@@ -2,27 +2,43 @@ use vulkano::impl_vertex;
 
 #[derive(Copy, Clone, Default)]
 pub struct Vertex {
-    pos: [f32; 2],
+    pos: [f32; 3],
     color: [f32; 3],
+    normal: [f32; 3],
+    uv: [f32; 2],
 }
 
 impl Vertex {
-    fn new(pos: [f32; 2], color: [f32; 3]) -> Self {
-        Self { pos, color }
+    /// Facing +Z, like the quads in the XY plane
+    pub fn new(pos: [f32; 3], color: [f32; 3], uv: [f32; 2]) -> Self {
+        Self {
+            pos,
+            color,
+            normal: [0.0, 0.0, 1.0],
+            uv,
+        }
+    }
+
+    pub fn with_normal(self, normal: [f32; 3]) -> Self {
+        Self { normal, ..self }
+    }
+
+    pub fn pos(&self) -> [f32; 3] {
+        self.pos
     }
 }
 
-impl_vertex!(Vertex, pos, color);
+impl_vertex!(Vertex, pos, color, normal, uv);
 
 pub fn vertecies() -> [Vertex; 4] {
     [
-        Vertex::new([-0.5, -0.5], [1.0, 0.0, 0.0]),
-        Vertex::new([0.5, -0.5], [0.0, 1.0, 0.0]),
-        Vertex::new([0.5, 0.5], [0.0, 0.0, 1.0]),
-        Vertex::new([-0.5, 0.5], [1.0, 1.0, 1.0])
+        Vertex::new([-0.5, -0.5, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0]),
+        Vertex::new([0.5, -0.5, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0]),
+        Vertex::new([0.5, 0.5, 0.0], [0.0, 0.0, 1.0], [1.0, 1.0]),
+        Vertex::new([-0.5, 0.5, 0.0], [1.0, 1.0, 1.0], [0.0, 1.0])
     ]
 }
 
-pub fn indices() -> [u16; 6] {
+pub fn indices() -> [u32; 6] {
     [0, 1, 2, 2, 3, 0]
-}
\ No newline at end of file
+}
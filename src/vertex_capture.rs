@@ -0,0 +1,143 @@
+//! Vertices after the vertex stage, read back to the CPU. A compute pass applies what
+//! `triangle.vert` does to each vertex and writes the results to a buffer, which is simpler to
+//! set up than transform feedback and works on every device. Useful for checking the vertex
+//! stage and for testing against animated meshes on the CPU.
+
+use std::{fmt::Write as _, fs, mem, path::Path, sync::Arc};
+
+use cgmath::{Matrix4, Point3, Vector4};
+use vulkano::{
+    buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer},
+    command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBuffer},
+    device::Queue,
+    pipeline::{ComputePipeline, ComputePipelineAbstract},
+    sync::GpuFuture,
+};
+
+use crate::{
+    descriptors::{self, Resource},
+    ray::Aabb,
+    scenes::Animation,
+    vertex::Vertex,
+};
+
+mod shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/vertex_capture.comp"
+    }
+}
+
+/// Invocations per workgroup, one vertex each
+const WORKGROUP_SIZE: u32 = 64;
+const STORAGE_BUFFER: BufferUsage = BufferUsage {
+    storage_buffer: true,
+    ..BufferUsage::none()
+};
+
+/// One vertex as the vertex stage leaves it
+#[derive(Clone, Copy, Debug)]
+pub struct CapturedVertex {
+    pub world: Point3<f32>,
+    pub clip: Vector4<f32>,
+    /// After the color animation
+    pub color: [f32; 3],
+}
+
+/// How the mesh is drawn, the vertex stage's inputs besides the vertices
+pub struct Transform {
+    pub model: Matrix4<f32>,
+    pub view_projection: Matrix4<f32>,
+    /// Seconds since the scene started, for the animations
+    pub time: f32,
+    pub animation: Animation,
+}
+
+/// Runs the capture pass over every vertex in `vertex_buffer` and waits for it. The buffer has
+/// to hold `Vertex`es and allow storage buffer use, like the ones `renderer` creates.
+pub fn capture(
+    queue: &Arc<Queue>,
+    vertex_buffer: &Arc<dyn BufferAccess + Send + Sync>,
+    transform: &Transform,
+) -> Result<Vec<CapturedVertex>, String> {
+    let device = queue.device();
+    let vertex_count = vertex_buffer.size() / mem::size_of::<Vertex>();
+    if vertex_count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let captured_buffer = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        STORAGE_BUFFER,
+        false,
+        (0..vertex_count).map(|_| [[0.0f32; 4]; 3]),
+    )
+    .map_err(|e| format!("failed to create the capture buffer: {}", e))?;
+
+    let shader = shader::Shader::load(device.clone()).expect("Failed to create vertex capture shader module");
+    let pipeline = Arc::new(
+        ComputePipeline::new(device.clone(), &shader.main_entry_point(), &(), None).map_err(|e| e.to_string())?,
+    );
+    let layout = pipeline.layout().descriptor_set_layout(0).unwrap().clone();
+    let set = descriptors::build(
+        layout,
+        &[Resource::Buffer(vertex_buffer.clone()), Resource::Buffer(captured_buffer.clone())],
+    );
+
+    let push_constants = shader::ty::PushConstants {
+        model: transform.model.into(),
+        view_projection: transform.view_projection.into(),
+        time: transform.time,
+        animation: transform.animation as u32,
+        vertex_count: vertex_count as u32,
+    };
+
+    let mut builder =
+        AutoCommandBufferBuilder::primary(device.clone(), queue.family(), CommandBufferUsage::OneTimeSubmit).unwrap();
+    builder
+        .dispatch(
+            [(vertex_count as u32).div_ceil(WORKGROUP_SIZE), 1, 1],
+            pipeline,
+            set,
+            push_constants,
+            vec![],
+        )
+        .unwrap();
+    builder
+        .build()
+        .unwrap()
+        .execute(queue.clone())
+        .map_err(|e| format!("failed to capture the vertices: {}", e))?
+        .then_signal_fence_and_flush()
+        .and_then(|future| future.wait(None))
+        .map_err(|e| format!("failed to capture the vertices: {}", e))?;
+
+    let captured = captured_buffer.read().unwrap();
+    Ok(captured
+        .iter()
+        .map(|[world, clip, color]| CapturedVertex {
+            world: Point3::new(world[0], world[1], world[2]),
+            clip: Vector4::from(*clip),
+            color: [color[0], color[1], color[2]],
+        })
+        .collect())
+}
+
+/// World space bounds of the captured vertices
+pub fn bounds(vertices: &[CapturedVertex]) -> Option<Aabb> {
+    Aabb::from_points(vertices.iter().map(|vertex| vertex.world))
+}
+
+/// Writes one row per vertex: world position, clip position and color
+pub fn write_csv(vertices: &[CapturedVertex], path: &Path) -> Result<(), String> {
+    let mut csv = String::from("index,world_x,world_y,world_z,clip_x,clip_y,clip_z,clip_w,red,green,blue\n");
+    for (index, vertex) in vertices.iter().enumerate() {
+        let (world, clip, color) = (vertex.world, vertex.clip, vertex.color);
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            index, world.x, world.y, world.z, clip.x, clip.y, clip.z, clip.w, color[0], color[1], color[2]
+        );
+    }
+    fs::write(path, csv).map_err(|e| format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,536 @@
+//! An endless rolling landscape around the camera, split into square chunks. Worker threads
+//! generate the chunks' meshes from a noise height function, a few finished chunks are uploaded
+//! through the transfer queue every frame, and chunks that fall behind the camera are dropped,
+//! their buffers freed once the frames in flight that draw them are done. Every vertex also gets
+//! the weights of the terrain's detail textures, the chunk's splat map at vertex resolution, and
+//! grass blades are scattered over the chunk where it is grassy.
+
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::TAU,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use log::info;
+use vulkano::{
+    buffer::{BufferUsage, ImmutableBuffer, TypedBufferAccess},
+    device::Queue,
+    impl_vertex,
+};
+
+use crate::{
+    camera::Camera,
+    error::Error,
+    ray::Aabb,
+    scene::MeshHandle,
+    staging::{Staging, Upload},
+    vertex::Vertex,
+};
+
+/// Side of a chunk in world units
+const CHUNK_SIZE: f32 = 8.0;
+/// Quads along each side of a chunk
+const CHUNK_QUADS: usize = 32;
+/// Chunks up to this many chunks away from the camera's, along either axis, are loaded
+const LOAD_RADIUS: i32 = 3;
+/// Chunks are only dropped this many chunks further out, so going back and forth over a chunk
+/// border doesn't reload them
+const UNLOAD_MARGIN: i32 = 1;
+/// Threads generating chunks
+const WORKERS: usize = 2;
+/// Chunks uploaded per frame, so a row of new chunks doesn't stall rendering
+const MAX_UPLOADS_PER_FRAME: usize = 2;
+/// Height of the valleys, below the demo scene at the origin
+const GROUND_HEIGHT: f32 = -2.0;
+/// How far the hills rise above the valleys
+const HILL_HEIGHT: f32 = 4.0;
+/// Width of the largest hills
+const FEATURE_SIZE: f32 = 24.0;
+/// Noise layers added up, each half as wide and high as the one before
+const OCTAVES: usize = 4;
+/// Width of the patches of bare dirt in the grass
+const DIRT_PATCH_SIZE: f32 = 5.0;
+/// Places along each side of a chunk where a grass blade may grow, jittered off a grid
+const GRASS_PLACES: usize = 24;
+/// Width of the clumps the grass grows in, with fewer blades in between
+const GRASS_CLUMP_SIZE: f32 = 3.0;
+/// Height of the tallest grass blades
+pub const MAX_GRASS_HEIGHT: f32 = 0.4;
+/// Units per second the camera's target moves at when the world is turned on
+pub const DEFAULT_SPEED: f32 = 4.0;
+/// The detail textures in the order of the splat weights
+pub const LAYERS: [Layer; 4] = [Layer::Grass, Layer::Dirt, Layer::Rock, Layer::Snow];
+
+/// What the ground is covered with, one detail texture each
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Grass,
+    Dirt,
+    Rock,
+    Snow,
+}
+
+impl Layer {
+    /// Average color of the detail texture, what the vertex colors blend without splatting
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            Layer::Grass => [0.25, 0.45, 0.18],
+            Layer::Dirt => [0.36, 0.27, 0.18],
+            Layer::Rock => [0.42, 0.38, 0.34],
+            Layer::Snow => [0.92, 0.93, 0.96],
+        }
+    }
+}
+
+/// Second vertex input of the terrain pipeline next to `Vertex`: how much each of `LAYERS`
+/// covers the ground there, adding up to 1
+#[derive(Copy, Clone, Default)]
+pub struct Splat {
+    weights: [f32; 4],
+}
+
+impl_vertex!(Splat, weights);
+
+/// Per instance vertex input of the grass pipeline, one blade standing on the ground
+#[derive(Copy, Clone, Default)]
+pub struct GrassBlade {
+    root: [f32; 3],
+    height: f32,
+    tint: [f32; 3],
+    /// Turns the blade around its root and puts it out of step with the wind, in radians
+    phase: f32,
+}
+
+impl_vertex!(GrassBlade, root, height, tint, phase);
+
+/// Position of a chunk in the grid, the chunk at [0, 0] starts at the origin
+pub type ChunkCoord = [i32; 2];
+
+/// A chunk's mesh as a worker made it
+struct ChunkMesh {
+    coord: ChunkCoord,
+    vertices: Vec<Vertex>,
+    splat: Vec<Splat>,
+    indices: Vec<u32>,
+    bounds: Aabb,
+    grass: Vec<GrassBlade>,
+}
+
+struct GpuChunk {
+    mesh: MeshHandle,
+    splat: Arc<ImmutableBuffer<[Splat]>>,
+    bounds: Aabb,
+    /// `None` where nothing grows
+    grass: Option<Arc<ImmutableBuffer<[GrassBlade]>>>,
+    /// Of the vertex, splat, index and grass buffers
+    bytes: usize,
+}
+
+/// The chunks around the camera, loaded with `world on`
+pub struct World {
+    /// Units per second the camera's target moves forward, 0 holds it still
+    pub speed: f32,
+    /// Draws the chunks with the blended detail textures instead of their vertex colors
+    pub splatting: bool,
+    /// Projects the detail textures along all three axes on steep slopes, where projecting them
+    /// from above stretches them
+    pub triplanar: bool,
+    /// Draws the grass blades on the chunks
+    pub grass: bool,
+    chunks: HashMap<ChunkCoord, GpuChunk>,
+    /// Sent to the workers and not uploaded yet
+    requested: HashSet<ChunkCoord>,
+    /// Generated and waiting for upload
+    pending: Vec<ChunkMesh>,
+    requests: Sender<ChunkCoord>,
+    generated: Receiver<ChunkMesh>,
+    loaded: usize,
+    unloaded: usize,
+}
+
+impl World {
+    /// Starts the workers, the first chunks are requested by `update`
+    pub fn start() -> Self {
+        let (requests, worker_requests) = mpsc::channel::<ChunkCoord>();
+        let (sender, generated) = mpsc::channel();
+        let worker_requests = Arc::new(Mutex::new(worker_requests));
+
+        for index in 0..WORKERS {
+            let worker_requests = worker_requests.clone();
+            let sender = sender.clone();
+            thread::Builder::new()
+                .name(format!("world chunk generator {}", index))
+                .spawn(move || loop {
+                    // ends when the world is dropped with its request sender
+                    let coord = match worker_requests.lock().unwrap().recv() {
+                        Ok(coord) => coord,
+                        Err(_) => return,
+                    };
+                    if sender.send(generate_chunk(coord)).is_err() {
+                        return;
+                    }
+                })
+                .expect("Failed to spawn world chunk generator thread");
+        }
+
+        Self {
+            speed: DEFAULT_SPEED,
+            splatting: true,
+            triplanar: true,
+            grass: true,
+            chunks: HashMap::new(),
+            requested: HashSet::new(),
+            pending: vec![],
+            requests,
+            generated,
+            loaded: 0,
+            unloaded: 0,
+        }
+    }
+
+    /// Moves the camera's target forward over the ground, the way the camera looks
+    pub fn travel(&self, camera: &mut Camera, elapsed: Duration) {
+        let forward = camera.target - camera.eye();
+        let forward = Vector3::new(forward.x, 0.0, forward.z);
+        if forward.magnitude2() > f32::EPSILON {
+            camera.target += forward.normalize() * self.speed * elapsed.as_secs_f32();
+        }
+    }
+
+    /// Drops the chunks behind `focus`, asks the workers for the missing ones around it, nearest
+    /// first, and uploads a few of those they finished. The upload has to be joined into the next
+    /// frame, which draws the new chunks.
+    pub fn update(&mut self, queue: &Arc<Queue>, focus: Point3<f32>) -> Result<Option<Upload>, Error> {
+        let center = chunk_of(focus);
+        let before = self.chunks.len();
+        self.chunks
+            .retain(|coord, _| distance(*coord, center) <= LOAD_RADIUS + UNLOAD_MARGIN);
+        self.unloaded += before - self.chunks.len();
+        // results of dropped requests are thrown away when they arrive
+        self.requested
+            .retain(|coord| distance(*coord, center) <= LOAD_RADIUS + UNLOAD_MARGIN);
+        self.pending
+            .retain(|chunk| distance(chunk.coord, center) <= LOAD_RADIUS + UNLOAD_MARGIN);
+
+        let mut missing: Vec<ChunkCoord> = (-LOAD_RADIUS..=LOAD_RADIUS)
+            .flat_map(|z| (-LOAD_RADIUS..=LOAD_RADIUS).map(move |x| [center[0] + x, center[1] + z]))
+            .filter(|coord| !self.chunks.contains_key(coord) && !self.requested.contains(coord))
+            .collect();
+        missing.sort_by_key(|coord| distance(*coord, center));
+        for coord in missing {
+            self.requests
+                .send(coord)
+                .map_err(|_| Error::ChunkGeneratorsStopped)?;
+            self.requested.insert(coord);
+        }
+
+        for chunk in self.generated.try_iter() {
+            if self.requested.remove(&chunk.coord) {
+                self.pending.push(chunk);
+            }
+        }
+
+        let mut upload = None;
+        if !self.pending.is_empty() {
+            self.pending.sort_by_key(|chunk| distance(chunk.coord, center));
+            // this frame's chunks go up in a single submit
+            let mut staging = Staging::new(queue);
+            for chunk in self.pending.drain(..self.pending.len().min(MAX_UPLOADS_PER_FRAME)) {
+                let vertex_buffer = staging.buffer(chunk.vertices.iter().cloned(), BufferUsage::vertex_buffer())?;
+                let splat = staging.buffer(chunk.splat.iter().cloned(), BufferUsage::vertex_buffer())?;
+                let index_buffer = staging.buffer(chunk.indices.iter().cloned(), BufferUsage::index_buffer())?;
+
+                // a buffer can't be empty
+                let grass = if chunk.grass.is_empty() {
+                    None
+                } else {
+                    Some(staging.buffer(chunk.grass.iter().cloned(), BufferUsage::vertex_buffer())?)
+                };
+
+                let bytes = chunk.vertices.len() * (std::mem::size_of::<Vertex>() + std::mem::size_of::<Splat>())
+                    + chunk.indices.len() * std::mem::size_of::<u32>()
+                    + chunk.grass.len() * std::mem::size_of::<GrassBlade>();
+                self.chunks.insert(
+                    chunk.coord,
+                    GpuChunk {
+                        mesh: MeshHandle::new(vertex_buffer, index_buffer),
+                        splat,
+                        bounds: chunk.bounds,
+                        grass,
+                        bytes,
+                    },
+                );
+                self.loaded += 1;
+            }
+            upload = Some(staging.submit()?);
+
+            if self.pending.is_empty() && self.requested.is_empty() {
+                info!("World chunks around {:?} are loaded: {}", center, self.status());
+            }
+        }
+
+        Ok(upload)
+    }
+
+    /// The loaded chunks' meshes, drawn without a transform
+    pub fn chunks(&self) -> impl Iterator<Item = (ChunkCoord, &MeshHandle)> {
+        self.chunks.iter().map(|(coord, chunk)| (*coord, &chunk.mesh))
+    }
+
+    /// The loaded chunks' meshes with their splat weights and world space bounds, for the
+    /// terrain pipeline
+    pub fn splatted_chunks(&self) -> impl Iterator<Item = (&MeshHandle, &Arc<ImmutableBuffer<[Splat]>>, Aabb)> {
+        self.chunks.values().map(|chunk| (&chunk.mesh, &chunk.splat, chunk.bounds))
+    }
+
+    /// The grass blades of the loaded chunks that have any, with the chunk's world space bounds
+    pub fn grass_chunks(&self) -> impl Iterator<Item = (&Arc<ImmutableBuffer<[GrassBlade]>>, Aabb)> {
+        self.chunks
+            .values()
+            .filter_map(|chunk| chunk.grass.as_ref().map(|grass| (grass, chunk.bounds)))
+    }
+
+    /// World space bounds of a loaded chunk
+    pub fn bounds(&self, coord: ChunkCoord) -> Option<Aabb> {
+        self.chunks.get(&coord).map(|chunk| chunk.bounds)
+    }
+
+    pub fn status(&self) -> String {
+        let bytes: usize = self.chunks.values().map(|chunk| chunk.bytes).sum();
+        let blades: usize = self.grass_chunks().map(|(grass, _)| grass.len()).sum();
+        format!(
+            "{} chunks on the GPU ({:.1} MB) with {} grass blades, {} generating, {} waiting for upload, {} loaded \
+             and {} unloaded since it was turned on, moving at {} units/s, splatting {}, triplanar {}, grass {}",
+            self.chunks.len(),
+            bytes as f64 / (1024.0 * 1024.0),
+            blades,
+            self.requested.len(),
+            self.pending.len(),
+            self.loaded,
+            self.unloaded,
+            self.speed,
+            if self.splatting { "on" } else { "off" },
+            if self.triplanar { "on" } else { "off" },
+            if self.grass { "on" } else { "off" }
+        )
+    }
+}
+
+fn chunk_of(position: Point3<f32>) -> ChunkCoord {
+    [(position.x / CHUNK_SIZE).floor() as i32, (position.z / CHUNK_SIZE).floor() as i32]
+}
+
+/// Chunks between two chunks along the axis they are furthest apart on, the loaded area is a
+/// square
+fn distance(a: ChunkCoord, b: ChunkCoord) -> i32 {
+    (a[0] - b[0]).abs().max((a[1] - b[1]).abs())
+}
+
+/// A grid of `CHUNK_QUADS` quads on the height function. Vertices on a chunk's edge get the same
+/// heights and normals as the neighbour's, so there are no seams.
+fn generate_chunk(coord: ChunkCoord) -> ChunkMesh {
+    let origin = [coord[0] as f32 * CHUNK_SIZE, coord[1] as f32 * CHUNK_SIZE];
+    let step = CHUNK_SIZE / CHUNK_QUADS as f32;
+
+    let mut vertices = Vec::with_capacity((CHUNK_QUADS + 1) * (CHUNK_QUADS + 1));
+    let mut splat = Vec::with_capacity(vertices.capacity());
+    for row in 0..=CHUNK_QUADS {
+        for column in 0..=CHUNK_QUADS {
+            let (x, z) = (origin[0] + column as f32 * step, origin[1] + row as f32 * step);
+            let y = height(x, z);
+            let normal = normal(x, z, step);
+            let uv = [column as f32 / CHUNK_QUADS as f32, row as f32 / CHUNK_QUADS as f32];
+            let weights = splat_weights(x, z, y, normal.y);
+            vertices.push(Vertex::new([x, y, z], ground_color(weights), uv).with_normal(normal.into()));
+            splat.push(Splat { weights });
+        }
+    }
+
+    let columns = CHUNK_QUADS as u32 + 1;
+    let mut indices = Vec::with_capacity(CHUNK_QUADS * CHUNK_QUADS * 6);
+    for row in 0..CHUNK_QUADS as u32 {
+        for column in 0..CHUNK_QUADS as u32 {
+            let top_left = row * columns + column;
+            let bottom_left = top_left + columns;
+            indices.extend_from_slice(&[
+                top_left,
+                bottom_left,
+                bottom_left + 1,
+                bottom_left + 1,
+                top_left + 1,
+                top_left,
+            ]);
+        }
+    }
+
+    let bounds = Aabb::from_points(vertices.iter().map(|vertex| Point3::from(vertex.pos())))
+        .expect("a chunk has vertices");
+    ChunkMesh {
+        coord,
+        vertices,
+        splat,
+        indices,
+        bounds,
+        grass: scatter_grass(coord),
+    }
+}
+
+/// Grass blades at jittered places on a grid that runs on over the chunk borders, kept where a
+/// density map says so: the grass layer's splat weight, thinned out between the clumps
+fn scatter_grass(coord: ChunkCoord) -> Vec<GrassBlade> {
+    let spacing = CHUNK_SIZE / GRASS_PLACES as f32;
+    let first = [coord[0] * GRASS_PLACES as i32, coord[1] * GRASS_PLACES as i32];
+
+    let mut blades = vec![];
+    for row in 0..GRASS_PLACES as i32 {
+        for column in 0..GRASS_PLACES as i32 {
+            let (gx, gz) = (first[0] + column, first[1] + row);
+            let x = (gx as f32 + lattice(gx, gz, 1)) * spacing;
+            let z = (gz as f32 + lattice(gx, gz, 2)) * spacing;
+            let y = height(x, z);
+
+            let grass = splat_weights(x, z, y, normal(x, z, spacing).y)[0];
+            let clumps = value_noise(x / GRASS_CLUMP_SIZE + 53.0, z / GRASS_CLUMP_SIZE + 71.0);
+            let density = grass * (0.2 + 0.8 * ramp(clumps, 0.3, 0.6));
+            if lattice(gx, gz, 3) >= density {
+                continue;
+            }
+
+            let shade = 0.8 + 0.4 * lattice(gx, gz, 4);
+            blades.push(GrassBlade {
+                root: [x, y, z],
+                height: MAX_GRASS_HEIGHT * (0.5 + 0.5 * lattice(gx, gz, 5)),
+                tint: Layer::Grass.color().map(|channel| channel * shade),
+                phase: lattice(gx, gz, 6) * TAU,
+            });
+        }
+    }
+    blades
+}
+
+/// Normal of the ground at `x`, `z` from central differences `step` apart, the same on both
+/// sides of a chunk border
+fn normal(x: f32, z: f32, step: f32) -> Vector3<f32> {
+    Vector3::new(
+        height(x - step, z) - height(x + step, z),
+        2.0 * step,
+        height(x, z - step) - height(x, z + step),
+    )
+    .normalize()
+}
+
+/// Height of the ground at `x`, `z`: layers of value noise, the same for every run
+fn height(x: f32, z: f32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut frequency = 1.0 / FEATURE_SIZE;
+    for _ in 0..OCTAVES {
+        sum += amplitude * value_noise(x * frequency, z * frequency);
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    GROUND_HEIGHT + HILL_HEIGHT * sum
+}
+
+/// Random values at integer coordinates, smoothly blended in between
+fn value_noise(x: f32, z: f32) -> f32 {
+    let (ix, iz) = (x.floor() as i32, z.floor() as i32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sz) = (smooth(x - ix as f32), smooth(z - iz as f32));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+    let near = lerp(lattice(ix, iz, 0), lattice(ix + 1, iz, 0), sx);
+    let far = lerp(lattice(ix, iz + 1, 0), lattice(ix + 1, iz + 1, 0), sx);
+    lerp(near, far, sz)
+}
+
+/// A value in 0..1 hashed from a lattice point, an unrelated one for every `seed`
+fn lattice(x: i32, z: i32, seed: u32) -> f32 {
+    let mut hash =
+        (x as u32).wrapping_mul(0x8da6_b343) ^ (z as u32).wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0x9e37_79b9);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 16;
+    hash as f32 / u32::MAX as f32
+}
+
+/// Weights of `LAYERS` at `x`, `z`: grass in the valleys with patches of dirt, rock on the
+/// hillsides and wherever it is steep, snow on the tops unless it is too steep to stay
+fn splat_weights(x: f32, z: f32, height: f32, normal_y: f32) -> [f32; 4] {
+    let t = (height - GROUND_HEIGHT) / HILL_HEIGHT;
+    let slope = 1.0 - normal_y;
+
+    let snow = ramp(t, 0.65, 0.75) * (1.0 - ramp(slope, 0.3, 0.5));
+    let rock = ramp(t, 0.35, 0.55).max(ramp(slope, 0.2, 0.4)) * (1.0 - snow);
+    // offset so the patches don't follow the hills
+    let patches = value_noise(x / DIRT_PATCH_SIZE + 31.0, z / DIRT_PATCH_SIZE + 17.0);
+    let dirt = ramp(patches, 0.6, 0.75) * (1.0 - rock - snow);
+    [1.0 - dirt - rock - snow, dirt, rock, snow]
+}
+
+/// 0 up to `from`, 1 from `to` on and linear in between
+fn ramp(value: f32, from: f32, to: f32) -> f32 {
+    ((value - from) / (to - from)).clamp(0.0, 1.0)
+}
+
+/// The layers' average colors blended by their `weights`
+fn ground_color(weights: [f32; 4]) -> [f32; 3] {
+    let mut color = [0.0; 3];
+    for (layer, weight) in LAYERS.iter().zip(weights) {
+        for (channel, value) in color.iter_mut().zip(layer.color()) {
+            *channel += value * weight;
+        }
+    }
+    color
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splat_weights_add_up_to_one() {
+        for (x, z) in [(0.0, 0.0), (3.5, -12.25), (-40.0, 7.0), (100.0, 100.0)] {
+            for normal_y in [1.0, 0.8, 0.5, 0.1] {
+                for height in [GROUND_HEIGHT, GROUND_HEIGHT + HILL_HEIGHT * 0.5, GROUND_HEIGHT + HILL_HEIGHT] {
+                    let weights = splat_weights(x, z, height, normal_y);
+                    assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5, "{:?}", weights);
+                    assert!(weights.iter().all(|weight| (0.0..=1.0).contains(weight)), "{:?}", weights);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn splat_layers_follow_height_and_slope() {
+        let top = GROUND_HEIGHT + HILL_HEIGHT;
+        assert_eq!(splat_weights(0.0, 0.0, top, 1.0)[3], 1.0);
+        // too steep for snow
+        assert_eq!(splat_weights(0.0, 0.0, top, 0.3)[2], 1.0);
+        assert_eq!(splat_weights(0.0, 0.0, GROUND_HEIGHT, 0.5)[2], 1.0);
+        let valley = splat_weights(0.0, 0.0, GROUND_HEIGHT, 1.0);
+        assert_eq!(valley[2] + valley[3], 0.0);
+    }
+
+    #[test]
+    fn grass_grows_on_the_chunk_where_it_is_grassy() {
+        let coord = [-3, 2];
+        let blades = scatter_grass(coord);
+        assert!(!blades.is_empty());
+        for blade in &blades {
+            let [x, y, z] = blade.root;
+            assert_eq!(chunk_of(Point3::new(x, y, z)), coord);
+            assert_eq!(y, height(x, z));
+            assert!(splat_weights(x, z, y, normal(x, z, CHUNK_SIZE / GRASS_PLACES as f32).y)[0] > 0.0);
+            assert!(blade.height > 0.0 && blade.height <= MAX_GRASS_HEIGHT);
+        }
+        // the same for every run
+        assert_eq!(scatter_grass(coord).len(), blades.len());
+    }
+}